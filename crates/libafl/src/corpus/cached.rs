@@ -1,7 +1,13 @@
 //! The [`CachedOnDiskCorpus`] stores [`Testcase`]s to disk, keeping a subset of them in memory/cache, evicting in a FIFO manner.
+//!
+//! By default, the [`Testcase`]s are stored in an [`InMemoryOnDiskCorpus`], but any other persistent [`Corpus`],
+//! such as the `SqliteCorpus`, can be used as backing store, see [`CachedOnDiskCorpus::with_backing_corpus`].
 
 use alloc::{collections::vec_deque::VecDeque, string::String};
-use core::cell::{Ref, RefCell, RefMut};
+use core::{
+    cell::{Ref, RefCell, RefMut},
+    marker::PhantomData,
+};
 use std::path::Path;
 
 use serde::{Deserialize, Serialize};
@@ -18,15 +24,19 @@ use crate::{
 /// A corpus that keeps a maximum number of [`Testcase`]s in memory
 /// and load them from disk, when they are being used.
 /// The eviction policy is FIFO.
+///
+/// The inputs are stored in, and loaded from, the backing corpus `C`.
 #[derive(Default, Serialize, Deserialize, Clone, Debug)]
-pub struct CachedOnDiskCorpus<I> {
-    inner: InMemoryOnDiskCorpus<I>,
+pub struct CachedOnDiskCorpus<I, C = InMemoryOnDiskCorpus<I>> {
+    inner: C,
     cached_indexes: RefCell<VecDeque<CorpusId>>,
     cache_max_len: usize,
+    phantom: PhantomData<I>,
 }
 
-impl<I> CachedOnDiskCorpus<I>
+impl<I, C> CachedOnDiskCorpus<I, C>
 where
+    C: Corpus<I>,
     I: Input,
 {
     fn cache_testcase<'a>(
@@ -56,8 +66,9 @@ where
     }
 }
 
-impl<I> Corpus<I> for CachedOnDiskCorpus<I>
+impl<I, C> Corpus<I> for CachedOnDiskCorpus<I, C>
 where
+    C: Corpus<I>,
     I: Input,
 {
     /// Returns the number of all enabled entries
@@ -178,8 +189,9 @@ where
     }
 }
 
-impl<I> HasTestcase<I> for CachedOnDiskCorpus<I>
+impl<I, C> HasTestcase<I> for CachedOnDiskCorpus<I, C>
 where
+    C: Corpus<I>,
     I: Input,
 {
     fn testcase(&self, id: CorpusId) -> Result<Ref<'_, Testcase<I>>, Error> {
@@ -191,8 +203,9 @@ where
     }
 }
 
-impl<I> EnableDisableCorpus for CachedOnDiskCorpus<I>
+impl<I, C> EnableDisableCorpus for CachedOnDiskCorpus<I, C>
 where
+    C: EnableDisableCorpus,
     I: Input,
 {
     #[inline]
@@ -280,20 +293,31 @@ impl<I> CachedOnDiskCorpus<I> {
 
     /// Internal constructor `fn`
     fn _new(on_disk_corpus: InMemoryOnDiskCorpus<I>, cache_max_len: usize) -> Result<Self, Error> {
+        Self::with_backing_corpus(on_disk_corpus, cache_max_len)
+    }
+}
+
+impl<I, C> CachedOnDiskCorpus<I, C> {
+    /// Creates the [`CachedOnDiskCorpus`] on top of the given backing corpus.
+    ///
+    /// The backing corpus must be able to load the inputs of its [`Testcase`]s again
+    /// in [`Corpus::load_input_into`], as inputs are evicted from the cache.
+    pub fn with_backing_corpus(inner: C, cache_max_len: usize) -> Result<Self, Error> {
         if cache_max_len == 0 {
             return Err(Error::illegal_argument(
                 "The max cache len in CachedOnDiskCorpus cannot be 0",
             ));
         }
         Ok(Self {
-            inner: on_disk_corpus,
+            inner,
             cached_indexes: RefCell::new(VecDeque::new()),
             cache_max_len,
+            phantom: PhantomData,
         })
     }
 
     /// Fetch the inner corpus
-    pub fn inner(&self) -> &InMemoryOnDiskCorpus<I> {
+    pub fn inner(&self) -> &C {
         &self.inner
     }
 }
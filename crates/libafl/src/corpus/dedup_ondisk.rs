@@ -0,0 +1,719 @@
+//! The [`DedupOnDiskCorpus`] stores the inputs of its [`Testcase`]s by content hash,
+//! in a directory that can be shared by many fuzzer instances.
+//!
+//! When several clients of a [`crate::events::Launcher`] find, or receive, the same input,
+//! it is only stored on disk once, instead of once per client.
+//!
+//! The directory is laid out as follows:
+//! - `objects/<hash>`: the inputs, named after the hash of their content, shared by all clients.
+//!   Different inputs with the same hash are stored as `<hash>-1`, `<hash>-2`, and so on.
+//! - `client_<id>/index`: an append-only log of the corpus operations of each client, one per line:
+//!   `add <corpus_id> <hash>`, `add_disabled <corpus_id> <hash>`, `replace <corpus_id> <hash>`,
+//!   `rewrite <corpus_id> <hash>`, `remove <corpus_id>`, `disable <corpus_id>`, and `enable <corpus_id>`.
+//! - `client_<id>/<corpus_id>.metadata`: the metadata of each [`Testcase`], if a metadata format is set.
+//!
+//! Objects are never deleted, as they may still be referenced by other clients.
+//! When a client is restarted, it restores its corpus from its index.
+
+use alloc::{format, string::String};
+use core::cell::{Ref, RefCell, RefMut};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use hashbrown::HashMap;
+use libafl_bolts::{ClientId, generic_hash_std};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    EnableDisableCorpus, HasTestcase,
+    ondisk::{OnDiskMetadata, OnDiskMetadataFormat},
+};
+use crate::{
+    Error, HasMetadata,
+    corpus::{Corpus, CorpusId, InMemoryCorpus, Testcase},
+    inputs::Input,
+};
+
+/// The directory, shared by all clients, the inputs are stored in
+const OBJECTS_DIR: &str = "objects";
+/// The name of the index file in each client directory
+const INDEX_FILE: &str = "index";
+
+/// A corpus storing the inputs of its [`Testcase`]s by content hash, in a directory shared with other clients.
+/// All [`Testcase`]s are kept in memory, without their inputs.
+///
+/// Metadata is written to a `<corpus_id>.metadata` file in the directory of this client by default.
+#[derive(Default, Serialize, Deserialize, Clone, Debug)]
+pub struct DedupOnDiskCorpus<I> {
+    inner: InMemoryCorpus<I>,
+    dir_path: PathBuf,
+    client_dir_path: PathBuf,
+    meta_format: Option<OnDiskMetadataFormat>,
+    /// Objects written by [`Corpus::store_input_from`], not yet reflected in the in-memory [`Testcase`]
+    rewritten: RefCell<HashMap<CorpusId, (String, PathBuf)>>,
+    /// The entries by the name of the object of their in-memory [`Testcase`], to find them in [`Corpus::store_input_from`]
+    object_ids: RefCell<HashMap<String, Vec<CorpusId>>>,
+}
+
+impl<I> Corpus<I> for DedupOnDiskCorpus<I>
+where
+    I: Input,
+{
+    /// Returns the number of all enabled entries
+    #[inline]
+    fn count(&self) -> usize {
+        self.inner.count()
+    }
+
+    /// Returns the number of all disabled entries
+    fn count_disabled(&self) -> usize {
+        self.inner.count_disabled()
+    }
+
+    /// Returns the number of elements including disabled entries
+    #[inline]
+    fn count_all(&self) -> usize {
+        self.inner.count_all()
+    }
+
+    /// Add an enabled testcase to the corpus and return its index
+    #[inline]
+    fn add(&mut self, mut testcase: Testcase<I>) -> Result<CorpusId, Error> {
+        // Persist first, so a failed write leaves the corpus unchanged.
+        self.save_testcase(&mut testcase, self.inner.peek_free_id(), "add")?;
+        *testcase.input_mut() = None;
+        let name = testcase.filename().clone();
+        let id = self.inner.add(testcase)?;
+        self.track_object(name, id);
+        Ok(id)
+    }
+
+    /// Add a disabled testcase to the corpus and return its index
+    #[inline]
+    fn add_disabled(&mut self, mut testcase: Testcase<I>) -> Result<CorpusId, Error> {
+        self.save_testcase(&mut testcase, self.inner.peek_free_id(), "add_disabled")?;
+        *testcase.input_mut() = None;
+        let name = testcase.filename().clone();
+        let id = self.inner.add_disabled(testcase)?;
+        self.track_object(name, id);
+        Ok(id)
+    }
+
+    /// Replaces the testcase at the given idx
+    #[inline]
+    fn replace(&mut self, id: CorpusId, mut testcase: Testcase<I>) -> Result<Testcase<I>, Error> {
+        // Make sure the entry exists before logging its replacement.
+        self.inner.get(id)?;
+        self.save_testcase(&mut testcase, id, "replace")?;
+        *testcase.input_mut() = None;
+        self.rewritten.get_mut().remove(&id);
+        let name = testcase.filename().clone();
+        let old = self.inner.replace(id, testcase)?;
+        self.untrack_object(old.filename().as_ref(), id);
+        self.track_object(name, id);
+        Ok(old)
+    }
+
+    /// Removes an entry from the corpus, returning it if it was present; considers both enabled and disabled corpus
+    #[inline]
+    fn remove(&mut self, id: CorpusId) -> Result<Testcase<I>, Error> {
+        let entry = self.inner.remove(id)?;
+        self.append_to_index(&format!("remove {id}"))?;
+        self.rewritten.get_mut().remove(&id);
+        self.untrack_object(entry.filename().as_ref(), id);
+        if let Some(metadata_path) = entry.metadata_path() {
+            match fs::remove_file(metadata_path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(entry)
+    }
+
+    /// Get by id; considers only enabled testcases
+    #[inline]
+    fn get(&self, id: CorpusId) -> Result<&RefCell<Testcase<I>>, Error> {
+        self.inner.get(id)
+    }
+
+    /// Get by id; considers both enabled and disabled testcases
+    #[inline]
+    fn get_from_all(&self, id: CorpusId) -> Result<&RefCell<Testcase<I>>, Error> {
+        self.inner.get_from_all(id)
+    }
+
+    /// Current testcase scheduled
+    #[inline]
+    fn current(&self) -> &Option<CorpusId> {
+        self.inner.current()
+    }
+
+    /// Current testcase scheduled (mutable)
+    #[inline]
+    fn current_mut(&mut self) -> &mut Option<CorpusId> {
+        self.inner.current_mut()
+    }
+
+    #[inline]
+    fn next(&self, id: CorpusId) -> Option<CorpusId> {
+        self.inner.next(id)
+    }
+
+    /// Peek the next free corpus id
+    #[inline]
+    fn peek_free_id(&self) -> CorpusId {
+        self.inner.peek_free_id()
+    }
+
+    #[inline]
+    fn prev(&self, id: CorpusId) -> Option<CorpusId> {
+        self.inner.prev(id)
+    }
+
+    #[inline]
+    fn first(&self) -> Option<CorpusId> {
+        self.inner.first()
+    }
+
+    #[inline]
+    fn last(&self) -> Option<CorpusId> {
+        self.inner.last()
+    }
+
+    /// Get the nth corpus id; considers only enabled testcases
+    #[inline]
+    fn nth(&self, nth: usize) -> CorpusId {
+        self.inner.nth(nth)
+    }
+    /// Get the nth corpus id; considers both enabled and disabled testcases
+    #[inline]
+    fn nth_from_all(&self, nth: usize) -> CorpusId {
+        self.inner.nth_from_all(nth)
+    }
+
+    fn load_input_into(&self, testcase: &mut Testcase<I>) -> Result<(), Error> {
+        if let Some(id) = self.rewritten_id(testcase) {
+            let (name, object_path) = self.rewritten.borrow_mut().remove(&id).unwrap();
+            self.untrack_object(testcase.filename().as_ref(), id);
+            self.track_object(Some(name.clone()), id);
+            *testcase.filename_mut() = Some(name);
+            *testcase.file_path_mut() = Some(object_path);
+        }
+        if testcase.input_mut().is_none() {
+            let Some(file_path) = testcase.file_path().as_ref() else {
+                return Err(Error::illegal_argument(
+                    "No file path set for testcase. Could not load inputs.",
+                ));
+            };
+            let input = I::from_file(file_path)?;
+            testcase.set_input(input);
+        }
+        Ok(())
+    }
+
+    /// Stores the input of this `Testcase` as object, if no object with the same content exists yet.
+    ///
+    /// If the `Testcase` is part of this corpus and its input changed, the new object is logged to the index.
+    /// As the `Testcase` cannot be changed here, its file path is updated the next time its input is loaded.
+    fn store_input_from(&self, testcase: &Testcase<I>) -> Result<(), Error> {
+        let Some(input) = testcase.input() else {
+            return Err(Error::illegal_argument(
+                "No input available for testcase. Could not store anything.",
+            ));
+        };
+        let (name, object_path) = self.store_object(input)?;
+
+        let Some(id) = self.id_of(testcase) else {
+            return Ok(());
+        };
+        let current = match self.rewritten.borrow().get(&id) {
+            Some((current, _)) => Some(current.clone()),
+            None => testcase.filename().clone(),
+        };
+        if current.as_ref() != Some(&name) {
+            self.append_to_index(&format!("rewrite {id} {name}"))?;
+            self.rewritten.borrow_mut().insert(id, (name, object_path));
+        }
+        Ok(())
+    }
+}
+
+impl<I> EnableDisableCorpus for DedupOnDiskCorpus<I>
+where
+    I: Input,
+{
+    #[inline]
+    fn disable(&mut self, id: CorpusId) -> Result<(), Error> {
+        self.inner.disable(id)?;
+        self.append_to_index(&format!("disable {id}"))
+    }
+
+    #[inline]
+    fn enable(&mut self, id: CorpusId) -> Result<(), Error> {
+        self.inner.enable(id)?;
+        self.append_to_index(&format!("enable {id}"))
+    }
+}
+
+impl<I> HasTestcase<I> for DedupOnDiskCorpus<I>
+where
+    I: Input,
+{
+    fn testcase(&self, id: CorpusId) -> Result<Ref<'_, Testcase<I>>, Error> {
+        Ok(self.get(id)?.borrow())
+    }
+
+    fn testcase_mut(&self, id: CorpusId) -> Result<RefMut<'_, Testcase<I>>, Error> {
+        Ok(self.get(id)?.borrow_mut())
+    }
+}
+
+impl<I> DedupOnDiskCorpus<I> {
+    /// Creates a [`DedupOnDiskCorpus`] for the given `client`, in the directory at `dir_path`.
+    ///
+    /// All clients of a campaign should use the same `dir_path`, and distinct `client` ids.
+    /// If `dir_path` already holds an index for this `client`, its corpus is restored from it.
+    /// The inputs of restored [`Testcase`]s are loaded on demand, their metadata files are not read back.
+    ///
+    /// By default, it stores metadata for each [`Testcase`] as prettified json.
+    /// If you don't want metadata, use [`DedupOnDiskCorpus::no_meta`].
+    /// To pick a different metadata format, use [`DedupOnDiskCorpus::with_meta_format`].
+    ///
+    /// Will error, if [`fs::create_dir_all()`] failed for `dir_path`.
+    pub fn new<P>(dir_path: P, client: ClientId) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        Self::_new(
+            dir_path.as_ref(),
+            client,
+            Some(OnDiskMetadataFormat::JsonPretty),
+        )
+    }
+
+    /// Creates the [`DedupOnDiskCorpus`] specifying the format in which `Metadata` will be saved to disk.
+    ///
+    /// Will error, if [`fs::create_dir_all()`] failed for `dir_path`.
+    pub fn with_meta_format<P>(
+        dir_path: P,
+        client: ClientId,
+        meta_format: Option<OnDiskMetadataFormat>,
+    ) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        Self::_new(dir_path.as_ref(), client, meta_format)
+    }
+
+    /// Creates a [`DedupOnDiskCorpus`] that will not store .metadata files
+    ///
+    /// Will error, if [`fs::create_dir_all()`] failed for `dir_path`.
+    pub fn no_meta<P>(dir_path: P, client: ClientId) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        Self::_new(dir_path.as_ref(), client, None)
+    }
+
+    /// Private fn to crate a new corpus at the given (non-generic) path with the given optional `meta_format`
+    fn _new(
+        dir_path: &Path,
+        client: ClientId,
+        meta_format: Option<OnDiskMetadataFormat>,
+    ) -> Result<Self, Error> {
+        let client_dir_path = dir_path.join(format!("client_{}", client.0));
+        for path in [&dir_path.join(OBJECTS_DIR), &client_dir_path] {
+            match fs::create_dir_all(path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        let inner = Self::load_index(&client_dir_path, &dir_path.join(OBJECTS_DIR))?;
+
+        let corpus = DedupOnDiskCorpus {
+            inner,
+            dir_path: dir_path.into(),
+            client_dir_path,
+            meta_format,
+            rewritten: RefCell::default(),
+            object_ids: RefCell::default(),
+        };
+        for nth in 0..corpus.inner.count_all() {
+            let id = corpus.inner.nth_from_all(nth);
+            let name = corpus.inner.get_from_all(id)?.borrow().filename().clone();
+            corpus.track_object(name, id);
+        }
+        Ok(corpus)
+    }
+
+    /// Restores the corpus of a client by replaying its index, if it has one
+    fn load_index(client_dir_path: &Path, objects_dir: &Path) -> Result<InMemoryCorpus<I>, Error> {
+        let mut inner = InMemoryCorpus::new();
+        let index_path = client_dir_path.join(INDEX_FILE);
+        let mut index = match fs::read_to_string(&index_path) {
+            Ok(index) => index,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(inner),
+            Err(e) => return Err(e.into()),
+        };
+
+        // A client killed while appending may leave a partial last line, drop it
+        let complete_len = index.rfind('\n').map_or(0, |end| end + 1);
+        if complete_len < index.len() {
+            log::warn!(
+                "Dropping the partially written last line of {}",
+                index_path.display()
+            );
+            index.truncate(complete_len);
+            OpenOptions::new()
+                .write(true)
+                .open(&index_path)?
+                .set_len(complete_len as u64)?;
+        }
+
+        for line in index.lines() {
+            let malformed = || Error::illegal_state(format!("Malformed corpus index line: {line}"));
+            let mut parts = line.split(' ');
+            let operation = parts.next().ok_or_else(malformed)?;
+            let id = CorpusId(
+                parts
+                    .next()
+                    .and_then(|id| id.parse().ok())
+                    .ok_or_else(malformed)?,
+            );
+            match (operation, parts.next()) {
+                ("add" | "add_disabled" | "replace", Some(name)) => {
+                    let mut testcase = Testcase::default();
+                    *testcase.filename_mut() = Some(name.into());
+                    *testcase.file_path_mut() = Some(objects_dir.join(name));
+                    let metafile_path = client_dir_path.join(format!("{id}.metadata"));
+                    if metafile_path.exists() {
+                        *testcase.metadata_path_mut() = Some(metafile_path);
+                    }
+                    let new_id = match operation {
+                        "add" => inner.add(testcase)?,
+                        "add_disabled" => inner.add_disabled(testcase)?,
+                        _ => {
+                            inner.replace(id, testcase)?;
+                            id
+                        }
+                    };
+                    if new_id != id {
+                        return Err(Error::illegal_state(format!(
+                            "Corpus index expected id {id}, but got {new_id} at: {line}"
+                        )));
+                    }
+                }
+                ("rewrite", Some(name)) => {
+                    let mut testcase = inner.get_from_all(id)?.borrow_mut();
+                    *testcase.filename_mut() = Some(name.into());
+                    *testcase.file_path_mut() = Some(objects_dir.join(name));
+                }
+                ("remove", None) => {
+                    inner.remove(id)?;
+                }
+                ("disable", None) => inner.disable(id)?,
+                ("enable", None) => inner.enable(id)?,
+                _ => return Err(malformed()),
+            }
+        }
+        Ok(inner)
+    }
+
+    /// Appends an operation to the index of this client
+    fn append_to_index(&self, line: &str) -> Result<(), Error> {
+        let mut index = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.client_dir_path.join(INDEX_FILE))?;
+        writeln!(index, "{line}")?;
+        Ok(())
+    }
+
+    /// Path to the corpus directory shared by all clients
+    #[must_use]
+    pub fn dir_path(&self) -> &PathBuf {
+        &self.dir_path
+    }
+
+    /// Path to the directory of this client, containing its index and metadata
+    #[must_use]
+    pub fn client_dir_path(&self) -> &PathBuf {
+        &self.client_dir_path
+    }
+
+    /// Records that the in-memory [`Testcase`] of the entry `id` refers to the object `name`
+    fn track_object(&self, name: Option<String>, id: CorpusId) {
+        if let Some(name) = name {
+            self.object_ids
+                .borrow_mut()
+                .entry(name)
+                .or_default()
+                .push(id);
+        }
+    }
+
+    /// Forgets that the in-memory [`Testcase`] of the entry `id` refers to the object `name`
+    fn untrack_object(&self, name: Option<&String>, id: CorpusId) {
+        let Some(name) = name else {
+            return;
+        };
+        let mut object_ids = self.object_ids.borrow_mut();
+        if let Some(ids) = object_ids.get_mut(name) {
+            ids.retain(|other| *other != id);
+            if ids.is_empty() {
+                object_ids.remove(name);
+            }
+        }
+    }
+}
+
+impl<I> DedupOnDiskCorpus<I>
+where
+    I: Input,
+{
+    /// The name of the object the given input is stored as, unless a different input with the same hash was stored first.
+    #[must_use]
+    pub fn object_name(input: &I) -> String {
+        format!("{:016x}", generic_hash_std(input))
+    }
+
+    /// The id of the given [`Testcase`], if it is an entry of this corpus
+    fn id_of(&self, testcase: &Testcase<I>) -> Option<CorpusId> {
+        let object_ids = self.object_ids.borrow();
+        // Entries with the same input share their object, so compare the candidates by address
+        object_ids
+            .get(testcase.filename().as_ref()?)?
+            .iter()
+            .copied()
+            .find(|id| {
+                self.inner
+                    .get_from_all(*id)
+                    .is_ok_and(|entry| core::ptr::eq(entry.as_ptr(), testcase))
+            })
+    }
+
+    /// The id of the given [`Testcase`], if [`Corpus::store_input_from`] wrote a new object for it
+    fn rewritten_id(&self, testcase: &Testcase<I>) -> Option<CorpusId> {
+        if self.rewritten.borrow().is_empty() {
+            return None;
+        }
+        self.id_of(testcase)
+            .filter(|id| self.rewritten.borrow().contains_key(id))
+    }
+
+    /// Stores the input as object, if it does not exist yet, and returns its name and path
+    fn store_object(&self, input: &I) -> Result<(String, PathBuf), Error> {
+        let hash = Self::object_name(input);
+        let objects_dir = self.dir_path.join(OBJECTS_DIR);
+
+        // Other clients must never see partially written objects, so we write to a private file first.
+        let tmpfile_path = objects_dir.join(format!(
+            ".{hash}.{}.tmp",
+            self.client_dir_path.file_name().unwrap().to_string_lossy()
+        ));
+        input.to_file(&tmpfile_path)?;
+        let result = Self::link_object(&objects_dir, &hash, &tmpfile_path);
+        fs::remove_file(&tmpfile_path)?;
+        result
+    }
+
+    /// Links the written input at `tmpfile_path` into the objects, unless an object with the same content exists.
+    ///
+    /// The hash is not collision resistant, so the content of an object with the same name is compared,
+    /// and a different input gets the next free `<hash>-<n>` name.
+    /// Linking fails if the name is taken, so concurrent clients never replace each others objects.
+    fn link_object(
+        objects_dir: &Path,
+        hash: &str,
+        tmpfile_path: &Path,
+    ) -> Result<(String, PathBuf), Error> {
+        let mut content = None;
+        for n in 0_u64.. {
+            let name = if n == 0 {
+                hash.into()
+            } else {
+                format!("{hash}-{n}")
+            };
+            let object_path = objects_dir.join(&name);
+            match fs::hard_link(tmpfile_path, &object_path) {
+                Ok(()) => return Ok((name, object_path)),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    if content.is_none() {
+                        content = Some(fs::read(tmpfile_path)?);
+                    }
+                    if content.as_deref() == Some(fs::read(&object_path)?.as_slice()) {
+                        return Ok((name, object_path));
+                    }
+                    log::info!("Hash collision for object {name}, trying the next name");
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        unreachable!("Ran out of object names for {hash}")
+    }
+
+    fn save_testcase(
+        &self,
+        testcase: &mut Testcase<I>,
+        id: CorpusId,
+        operation: &str,
+    ) -> Result<(), Error> {
+        let Some(input) = testcase.input() else {
+            return Err(Error::illegal_argument(
+                "No input available for testcase. Could not store anything.",
+            ));
+        };
+        let (name, object_path) = self.store_object(input)?;
+
+        if let Some(meta_format) = &self.meta_format {
+            let metafile_path = self.client_dir_path.join(format!("{id}.metadata"));
+            let tmpfile_path = self.client_dir_path.join(format!(".{id}.metadata.tmp"));
+
+            let ondisk_meta = OnDiskMetadata {
+                metadata: testcase.metadata_map(),
+                exec_time: testcase.exec_time(),
+                executions: testcase.executions(),
+            };
+            let mut tmpfile = File::create(&tmpfile_path)?;
+            tmpfile.write_all(&ondisk_meta.to_vec(meta_format)?)?;
+            fs::rename(&tmpfile_path, &metafile_path)?;
+            *testcase.metadata_path_mut() = Some(metafile_path);
+        }
+
+        self.append_to_index(&format!("{operation} {id} {name}"))?;
+        *testcase.filename_mut() = Some(name);
+        *testcase.file_path_mut() = Some(object_path);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use libafl_bolts::ClientId;
+
+    use super::{DedupOnDiskCorpus, INDEX_FILE, OBJECTS_DIR};
+    use crate::{
+        corpus::{Corpus, EnableDisableCorpus, Testcase},
+        inputs::{BytesInput, Input},
+    };
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_dedup_ondisk_corpus() {
+        let dir =
+            env::temp_dir().join(format!("libafl_test_dedup_ondisk_corpus_{}", process::id()));
+        drop(fs::remove_dir_all(&dir));
+
+        let mut first = DedupOnDiskCorpus::no_meta(&dir, ClientId(0)).unwrap();
+        let mut second = DedupOnDiskCorpus::no_meta(&dir, ClientId(1)).unwrap();
+
+        let input = BytesInput::new(vec![1, 2, 3, 4]);
+        first.add(Testcase::new(input.clone())).unwrap();
+        let id = second.add(Testcase::new(input.clone())).unwrap();
+        let other = second
+            .add(Testcase::new(BytesInput::new(vec![5, 6])))
+            .unwrap();
+
+        // The same input is only stored once
+        assert_eq!(fs::read_dir(dir.join(OBJECTS_DIR)).unwrap().count(), 2);
+        assert_eq!(second.cloned_input_for_id(id).unwrap(), input);
+
+        let index = fs::read_to_string(dir.join("client_1").join(INDEX_FILE)).unwrap();
+        assert_eq!(index.lines().count(), 2);
+        assert!(index.starts_with(&format!(
+            "add {id} {}",
+            DedupOnDiskCorpus::object_name(&input)
+        )));
+
+        // A restarted client restores its corpus from the index
+        second.disable(other).unwrap();
+        drop(second);
+        let second = DedupOnDiskCorpus::<BytesInput>::no_meta(&dir, ClientId(1)).unwrap();
+        assert_eq!(second.count(), 1);
+        assert_eq!(second.count_disabled(), 1);
+        assert_eq!(second.cloned_input_for_id(id).unwrap(), input);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_dedup_ondisk_collision() {
+        let dir = env::temp_dir().join(format!(
+            "libafl_test_dedup_ondisk_collision_{}",
+            process::id()
+        ));
+        drop(fs::remove_dir_all(&dir));
+
+        let mut corpus = DedupOnDiskCorpus::no_meta(&dir, ClientId(0)).unwrap();
+        let input = BytesInput::new(vec![1, 2, 3, 4]);
+        let name = DedupOnDiskCorpus::object_name(&input);
+
+        // Pretend a different input with the same hash was stored before
+        let colliding = BytesInput::new(vec![5, 6, 7, 8]);
+        colliding
+            .to_file(dir.join(OBJECTS_DIR).join(&name))
+            .unwrap();
+
+        let id = corpus.add(Testcase::new(input.clone())).unwrap();
+        assert_eq!(
+            corpus.get(id).unwrap().borrow().filename().as_deref(),
+            Some(format!("{name}-1").as_str())
+        );
+        assert_eq!(corpus.cloned_input_for_id(id).unwrap(), input);
+
+        // Storing the same input again finds the existing object
+        let again = corpus.add(Testcase::new(input.clone())).unwrap();
+        assert_eq!(
+            corpus.get(again).unwrap().borrow().filename(),
+            corpus.get(id).unwrap().borrow().filename()
+        );
+        assert_eq!(fs::read_dir(dir.join(OBJECTS_DIR)).unwrap().count(), 2);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_dedup_ondisk_store_input() {
+        let dir = env::temp_dir().join(format!(
+            "libafl_test_dedup_ondisk_store_input_{}",
+            process::id()
+        ));
+        drop(fs::remove_dir_all(&dir));
+
+        let mut corpus = DedupOnDiskCorpus::no_meta(&dir, ClientId(0)).unwrap();
+        let id = corpus
+            .add(Testcase::new(BytesInput::new(vec![1, 2, 3, 4])))
+            .unwrap();
+
+        let rewritten = BytesInput::new(vec![5, 6]);
+        {
+            let mut testcase = corpus.get(id).unwrap().borrow_mut();
+            testcase.set_input(rewritten.clone());
+            corpus.store_input_from(&testcase).unwrap();
+            *testcase.input_mut() = None;
+        }
+        // The entry now loads the new object
+        assert_eq!(corpus.cloned_input_for_id(id).unwrap(), rewritten);
+        assert_eq!(
+            corpus.get(id).unwrap().borrow().filename().as_deref(),
+            Some(DedupOnDiskCorpus::object_name(&rewritten).as_str())
+        );
+
+        // And so does a restarted client
+        drop(corpus);
+        let corpus = DedupOnDiskCorpus::<BytesInput>::no_meta(&dir, ClientId(0)).unwrap();
+        assert_eq!(corpus.count(), 1);
+        assert_eq!(corpus.cloned_input_for_id(id).unwrap(), rewritten);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -15,8 +15,6 @@ use std::{
 };
 
 use fs2::FileExt;
-#[cfg(feature = "gzip")]
-use libafl_bolts::compress::GzipCompressor;
 use serde::{Deserialize, Serialize};
 
 use super::{
@@ -413,7 +411,7 @@ impl<I> InMemoryOnDiskCorpus<I> {
         }
         *testcase.filename_mut() = Some(file_name);
 
-        if self.meta_format.is_some() {
+        if let Some(meta_format) = &self.meta_format {
             let metafile_name = if self.locking {
                 format!(
                     ".{}_{}.metadata",
@@ -435,21 +433,7 @@ impl<I> InMemoryOnDiskCorpus<I> {
 
             let mut tmpfile = File::create(&tmpfile_path)?;
 
-            let json_error =
-                |err| Error::serialize(format!("Failed to json-ify metadata: {err:?}"));
-
-            let serialized = match self.meta_format.as_ref().unwrap() {
-                OnDiskMetadataFormat::Postcard => postcard::to_allocvec(&ondisk_meta)?,
-                OnDiskMetadataFormat::Json => {
-                    serde_json::to_vec(&ondisk_meta).map_err(json_error)?
-                }
-                OnDiskMetadataFormat::JsonPretty => {
-                    serde_json::to_vec_pretty(&ondisk_meta).map_err(json_error)?
-                }
-                #[cfg(feature = "gzip")]
-                OnDiskMetadataFormat::JsonGzip => GzipCompressor::new()
-                    .compress(&serde_json::to_vec_pretty(&ondisk_meta).map_err(json_error)?),
-            };
+            let serialized = ondisk_meta.to_vec(meta_format)?;
             tmpfile.write_all(&serialized)?;
             fs::rename(&tmpfile_path, &metafile_path)?;
             *testcase.metadata_path_mut() = Some(metafile_path);
@@ -1,5 +1,9 @@
 //! Whole corpus minimizers, for reducing the number of samples/the total size/the average runtime
 //! of your corpus.
+//!
+//! The coverage of the corpus can be collected serially, in forked children, or in shards, for
+//! example one per [`crate::events::launcher::Launcher`] client, which are then merged.
+//! The entries to keep are selected either greedily, or optimally using z3 with the `cmin` feature.
 
 use alloc::{borrow::Cow, string::ToString, vec::Vec};
 use core::{hash::Hash, marker::PhantomData};
@@ -10,11 +14,13 @@ use libafl_bolts::{
     tuples::{Handle, Handled},
 };
 use num_traits::ToPrimitive;
+use serde::{Deserialize, Serialize};
+#[cfg(all(feature = "cmin", unix))]
 use z3::{Config, Context, Optimize, ast::Bool};
 
 use crate::{
     Error, HasMetadata, HasScheduler,
-    corpus::Corpus,
+    corpus::{Corpus, CorpusId},
     events::{Event, EventFirer, EventWithStats, LogSeverity},
     executors::{Executor, HasObservers},
     inputs::Input,
@@ -24,6 +30,180 @@ use crate::{
     state::{HasCorpus, HasExecutions},
 };
 
+/// The algorithm selecting the corpus entries to keep
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CminAlgorithm {
+    /// `afl-cmin`-style greedy selection: for each covered map entry, rarest first, keep the
+    /// entry with the lowest weight covering it. Fast, but not necessarily minimal.
+    Greedy,
+    /// Optimal selection by solving a weighted MaxSAT problem with z3; may be slow on large corpora
+    #[cfg(all(feature = "cmin", unix))]
+    Z3,
+}
+
+/// The coverage of a single corpus entry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EntryCoverage<T> {
+    /// The id of the entry in the corpus
+    pub id: CorpusId,
+    /// The weight of the entry, as computed by the `TestcaseScore`; lower is better
+    pub weight: u64,
+    /// The covered map indices, with their values
+    pub map: Vec<(usize, T)>,
+}
+
+/// The coverage of (a part of) the corpus, collected by a [`MapCorpusMinimizer`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CorpusCoverage<T> {
+    entries: Vec<EntryCoverage<T>>,
+}
+
+impl<T> Default for CorpusCoverage<T> {
+    fn default() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+}
+
+impl<T> CorpusCoverage<T>
+where
+    T: Copy + Hash + Eq,
+{
+    /// Creates an empty [`CorpusCoverage`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the coverage of a corpus entry
+    pub fn push(&mut self, entry: EntryCoverage<T>) {
+        self.entries.push(entry);
+    }
+
+    /// Merges the coverage collected for another part of the corpus
+    pub fn merge(&mut self, other: Self) {
+        self.entries.extend(other.entries);
+        // keep the selection independent of the order the parts were merged in
+        self.entries.sort_by_key(|entry| entry.id);
+    }
+
+    /// The coverage of the entries
+    #[must_use]
+    pub fn entries(&self) -> &[EntryCoverage<T>] {
+        &self.entries
+    }
+
+    /// Selects the entries to keep with the given algorithm
+    pub fn solve(&self, algorithm: CminAlgorithm) -> Result<HashSet<CorpusId>, Error> {
+        match algorithm {
+            CminAlgorithm::Greedy => Ok(self.solve_greedy()),
+            #[cfg(all(feature = "cmin", unix))]
+            CminAlgorithm::Z3 => self.solve_z3(),
+        }
+    }
+
+    /// Greedily selects the entries to keep, like `afl-cmin` does.
+    ///
+    /// Every (map index, value) pair is assigned the entry with the lowest weight covering it.
+    /// Then, starting with the rarest pairs, the assigned entry of each pair not yet covered is kept.
+    #[must_use]
+    pub fn solve_greedy(&self) -> HashSet<CorpusId> {
+        let mut order = Vec::new();
+        let mut candidates: HashMap<(usize, T), (usize, usize)> = HashMap::new();
+        for (idx, entry) in self.entries.iter().enumerate() {
+            for &pair in &entry.map {
+                candidates
+                    .entry(pair)
+                    .and_modify(|(count, best)| {
+                        *count += 1;
+                        if entry.weight < self.entries[*best].weight {
+                            *best = idx;
+                        }
+                    })
+                    .or_insert_with(|| {
+                        order.push(pair);
+                        (1, idx)
+                    });
+            }
+        }
+        order.sort_by_key(|pair| candidates[pair].0);
+
+        let mut covered = HashSet::new();
+        let mut keep = HashSet::new();
+        for pair in order {
+            if covered.contains(&pair) {
+                continue;
+            }
+            let best = &self.entries[candidates[&pair].1];
+            keep.insert(best.id);
+            covered.extend(best.map.iter().copied());
+        }
+        keep
+    }
+
+    /// Selects the entries to keep by solving a weighted MaxSAT problem with z3.
+    ///
+    /// Algorithm based on WMOPT: <https://hexhive.epfl.ch/publications/files/21ISSTA2.pdf>
+    #[cfg(all(feature = "cmin", unix))]
+    pub fn solve_z3(&self) -> Result<HashSet<CorpusId>, Error> {
+        let cfg = Config::default();
+        let ctx = Context::new(&cfg);
+        let opt = Optimize::new(&ctx);
+
+        let mut seed_exprs = Vec::with_capacity(self.entries.len());
+        let mut cov_map = HashMap::new();
+        for entry in &self.entries {
+            let seed_expr = Bool::fresh_const(&ctx, "seed");
+            // Store coverage, mapping coverage map indices to hit counts (if present) and the
+            // associated seeds for the map indices with those hit counts.
+            for &(i, e) in &entry.map {
+                cov_map
+                    .entry(i)
+                    .or_insert_with(HashMap::new)
+                    .entry(e)
+                    .or_insert_with(Vec::new)
+                    .push(seed_expr.clone());
+            }
+            // Keep track of that seed's index and weight
+            seed_exprs.push((seed_expr, entry.id, entry.weight));
+        }
+
+        for (_, cov) in cov_map {
+            for (_, seeds) in cov {
+                // At least one seed for each hit count of each coverage map index
+                if let Some(reduced) = seeds.into_iter().reduce(|s1, s2| s1 | s2) {
+                    opt.assert(&reduced);
+                }
+            }
+        }
+        for (seed, _, weight) in &seed_exprs {
+            // opt will attempt to minimise the number of violated assertions.
+            //
+            // To tell opt to minimize the number of seeds, we tell opt to maximize the number of
+            // not seeds.
+            //
+            // Additionally, each seed has a weight associated with them; the higher, the more z3
+            // doesn't want to violate the assertion. Thus, inputs which have higher weights will be
+            // less likely to appear in the final corpus -- provided all their coverage points are
+            // hit by at least one other input.
+            opt.assert_soft(&!seed, *weight, None);
+        }
+
+        // Perform the optimization!
+        opt.check(&[]);
+
+        let model = opt
+            .get_model()
+            .ok_or_else(|| Error::unknown("Corpus minimization failed; unsat."))?;
+        Ok(seed_exprs
+            .into_iter()
+            .filter(|(seed, _, _)| model.eval(seed, true).unwrap().as_bool().unwrap())
+            .map(|(_, id, _)| id)
+            .collect())
+    }
+}
+
 /// Minimizes a corpus according to coverage maps, weighting by the specified `TestcaseScore`.
 ///
 /// Algorithm based on WMOPT: <https://hexhive.epfl.ch/publications/files/21ISSTA2.pdf>
@@ -60,8 +240,8 @@ where
     T: Copy + Hash + Eq,
     TS: TestcaseScore<I, S>,
 {
-    /// Do the minimization
-    #[expect(clippy::too_many_lines)]
+    /// Do the minimization, selecting the entries to keep with z3
+    #[cfg(all(feature = "cmin", unix))]
     pub fn minimize<CS, EM, Z>(
         &self,
         fuzzer: &mut Z,
@@ -76,27 +256,250 @@ where
         EM: EventFirer<I, S>,
         Z: HasScheduler<I, S, Scheduler = CS>,
     {
-        // don't delete this else it won't work after restart
-        let current = *state.corpus().current();
+        self.minimize_with(fuzzer, executor, manager, state, CminAlgorithm::Z3)
+    }
 
-        let cfg = Config::default();
-        let ctx = Context::new(&cfg);
-        let opt = Optimize::new(&ctx);
+    /// Do the minimization, selecting the entries to keep with the given algorithm
+    pub fn minimize_with<CS, EM, Z>(
+        &self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        manager: &mut EM,
+        state: &mut S,
+        algorithm: CminAlgorithm,
+    ) -> Result<(), Error>
+    where
+        E: Executor<EM, I, S, Z> + HasObservers,
+        E::Observers: ObserversTuple<I, S>,
+        CS: Scheduler<I, S> + RemovableScheduler<I, S>,
+        EM: EventFirer<I, S>,
+        Z: HasScheduler<I, S, Scheduler = CS>,
+    {
+        let coverage = self.collect_coverage(fuzzer, executor, manager, state)?;
+        self.select(fuzzer, manager, state, &coverage, algorithm)
+    }
 
-        let mut seed_exprs = HashMap::new();
-        let mut cov_map = HashMap::new();
+    /// Do the minimization, replaying the corpus in `jobs` forked children in parallel, and
+    /// selecting the entries to keep with the given algorithm.
+    ///
+    /// The executor must not take down the process when the target crashes (for example a
+    /// forkserver, or an in-process fork executor), else the minimization fails.
+    #[cfg(all(feature = "std", unix))]
+    pub fn minimize_parallel<CS, EM, Z>(
+        &self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        manager: &mut EM,
+        state: &mut S,
+        algorithm: CminAlgorithm,
+        jobs: usize,
+    ) -> Result<(), Error>
+    where
+        E: Executor<EM, I, S, Z> + HasObservers,
+        E::Observers: ObserversTuple<I, S>,
+        CS: Scheduler<I, S> + RemovableScheduler<I, S>,
+        EM: EventFirer<I, S>,
+        T: Serialize + for<'de> Deserialize<'de>,
+        Z: HasScheduler<I, S, Scheduler = CS>,
+    {
+        let coverage = self.collect_coverage_parallel(fuzzer, executor, manager, state, jobs)?;
+        self.select(fuzzer, manager, state, &coverage, algorithm)
+    }
 
-        let mut cur_id = state.corpus().first();
+    fn select<CS, EM, Z>(
+        &self,
+        fuzzer: &mut Z,
+        manager: &mut EM,
+        state: &mut S,
+        coverage: &CorpusCoverage<T>,
+        algorithm: CminAlgorithm,
+    ) -> Result<(), Error>
+    where
+        CS: Scheduler<I, S> + RemovableScheduler<I, S>,
+        EM: EventFirer<I, S>,
+        Z: HasScheduler<I, S, Scheduler = CS>,
+    {
+        let message = match algorithm {
+            CminAlgorithm::Greedy => "Selecting inputs greedily...",
+            #[cfg(all(feature = "cmin", unix))]
+            CminAlgorithm::Z3 => "Performing MaxSAT...",
+        };
+        manager.log(state, LogSeverity::Info, message.to_string())?;
+        let keep = coverage.solve(algorithm)?;
+        self.retain(fuzzer, state, &keep)
+    }
 
+    /// Executes each input of the corpus, and collects the coverage
+    pub fn collect_coverage<EM, Z>(
+        &self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        manager: &mut EM,
+        state: &mut S,
+    ) -> Result<CorpusCoverage<T>, Error>
+    where
+        E: Executor<EM, I, S, Z> + HasObservers,
+        E::Observers: ObserversTuple<I, S>,
+        EM: EventFirer<I, S>,
+    {
         manager.log(
             state,
             LogSeverity::Info,
             "Executing each input...".to_string(),
         )?;
+        self.collect(fuzzer, executor, manager, state, 0, 1, true)
+    }
+
+    /// Executes every `num_shards`-th input of the corpus, starting at `shard`, and collects the
+    /// coverage.
+    ///
+    /// Use this to distribute the replay, for example across the clients of a
+    /// [`crate::events::launcher::Launcher`]; the [`CorpusCoverage`] of all shards can then be
+    /// merged and solved. No events are fired while collecting.
+    pub fn collect_coverage_shard<EM, Z>(
+        &self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        manager: &mut EM,
+        state: &mut S,
+        shard: usize,
+        num_shards: usize,
+    ) -> Result<CorpusCoverage<T>, Error>
+    where
+        E: Executor<EM, I, S, Z> + HasObservers,
+        E::Observers: ObserversTuple<I, S>,
+        EM: EventFirer<I, S>,
+    {
+        if shard >= num_shards {
+            return Err(Error::illegal_argument(format!(
+                "Shard {shard} out of range for {num_shards} shards"
+            )));
+        }
+        self.collect(fuzzer, executor, manager, state, shard, num_shards, false)
+    }
+
+    /// Executes the inputs of the corpus in `jobs` forked children in parallel, and collects the
+    /// merged coverage.
+    ///
+    /// The executor must not take down the process when the target crashes (for example a
+    /// forkserver, or an in-process fork executor), else the collection fails.
+    #[cfg(all(feature = "std", unix))]
+    pub fn collect_coverage_parallel<EM, Z>(
+        &self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        manager: &mut EM,
+        state: &mut S,
+        jobs: usize,
+    ) -> Result<CorpusCoverage<T>, Error>
+    where
+        E: Executor<EM, I, S, Z> + HasObservers,
+        E::Observers: ObserversTuple<I, S>,
+        EM: EventFirer<I, S>,
+        T: Serialize + for<'de> Deserialize<'de>,
+    {
+        use std::io::{Read, Write};
+
+        use libafl_bolts::os::{ForkResult, fork, pipes::Pipe};
+
+        if jobs <= 1 {
+            return self.collect_coverage(fuzzer, executor, manager, state);
+        }
+        manager.log(
+            state,
+            LogSeverity::Info,
+            format!("Executing each input in {jobs} processes..."),
+        )?;
+
+        let mut children = Vec::with_capacity(jobs);
+        for shard in 0..jobs {
+            let mut pipe = Pipe::new()?;
+            // # Safety
+            // The child only replays its shard, reports back through the pipe, and exits.
+            match unsafe { fork()? } {
+                ForkResult::Child => {
+                    pipe.close_read_end();
+                    let result = self
+                        .collect_coverage_shard(fuzzer, executor, manager, state, shard, jobs)
+                        .and_then(|coverage| Ok(postcard::to_allocvec(&coverage)?))
+                        .and_then(|bytes| Ok(pipe.write_all(&bytes)?));
+                    let status = match result {
+                        Ok(()) => 0,
+                        Err(err) => {
+                            log::error!("Collecting the coverage of shard {shard} failed: {err}");
+                            1
+                        }
+                    };
+                    // Do not run any destructors or exit handlers of the parent
+                    unsafe { libc::_exit(status) }
+                }
+                ForkResult::Parent(child) => {
+                    pipe.close_write_end();
+                    children.push((child, pipe));
+                }
+            }
+        }
+
+        let mut coverage = CorpusCoverage::new();
+        let mut failed = None;
+        for (shard, (child, mut pipe)) in children.into_iter().enumerate() {
+            let mut bytes = Vec::new();
+            let read = pipe.read_to_end(&mut bytes);
+            // always reap the child, even if another one failed
+            let status = child.status();
+            let shard_coverage = read.map_err(Error::from).and_then(|_| {
+                if status == 0 {
+                    Ok(postcard::from_bytes::<CorpusCoverage<T>>(&bytes)?)
+                } else {
+                    Err(Error::unknown(format!("exit status {status}")))
+                }
+            });
+            match shard_coverage {
+                Ok(shard_coverage) => coverage.merge(shard_coverage),
+                Err(err) => {
+                    failed.get_or_insert(Error::illegal_state(format!(
+                        "The child collecting the coverage of shard {shard} failed, did the target crash? ({err})"
+                    )));
+                }
+            }
+        }
+        if let Some(err) = failed {
+            return Err(err);
+        }
+
+        *state.executions_mut() += coverage.entries.len() as u64;
+        Ok(coverage)
+    }
+
+    #[expect(clippy::too_many_arguments)]
+    fn collect<EM, Z>(
+        &self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        manager: &mut EM,
+        state: &mut S,
+        shard: usize,
+        num_shards: usize,
+        report: bool,
+    ) -> Result<CorpusCoverage<T>, Error>
+    where
+        E: Executor<EM, I, S, Z> + HasObservers,
+        E::Observers: ObserversTuple<I, S>,
+        EM: EventFirer<I, S>,
+    {
+        let mut coverage = CorpusCoverage::new();
+        let mut cur_id = state.corpus().first();
 
         let total = state.corpus().count() as u64;
         let mut curr = 0;
+        let mut idx = 0;
         while let Some(id) = cur_id {
+            cur_id = state.corpus().next(id);
+            idx += 1;
+            if (idx - 1) % num_shards != shard {
+                continue;
+            }
+
             let (weight, input) = {
                 let mut testcase = state.corpus().get(id)?.borrow_mut();
                 let weight = TS::compute(state, &mut *testcase)?
@@ -121,101 +524,107 @@ where
 
             curr += 1;
 
-            manager.fire(
-                state,
-                EventWithStats::with_current_time(
-                    Event::UpdateUserStats {
-                        name: Cow::from("minimisation exec pass"),
-                        value: UserStats::new(
-                            UserStatsValue::Ratio(curr, total),
-                            AggregatorOps::None,
-                        ),
-                        phantom: PhantomData,
-                    },
-                    executions,
-                ),
-            )?;
+            if report {
+                manager.fire(
+                    state,
+                    EventWithStats::with_current_time(
+                        Event::UpdateUserStats {
+                            name: Cow::from("minimisation exec pass"),
+                            value: UserStats::new(
+                                UserStatsValue::Ratio(curr, total),
+                                AggregatorOps::None,
+                            ),
+                            phantom: PhantomData,
+                        },
+                        executions,
+                    ),
+                )?;
+            }
 
-            let seed_expr = Bool::fresh_const(&ctx, "seed");
             let observers = executor.observers();
             let obs = observers[&self.observer_handle].as_ref();
-
-            // Store coverage, mapping coverage map indices to hit counts (if present) and the
-            // associated seeds for the map indices with those hit counts.
-            for (i, e) in obs.as_iter().map(|x| *x).enumerate() {
-                if e != obs.initial() {
-                    cov_map
-                        .entry(i)
-                        .or_insert_with(HashMap::new)
-                        .entry(e)
-                        .or_insert_with(HashSet::new)
-                        .insert(seed_expr.clone());
-                }
-            }
-
-            // Keep track of that seed's index and weight
-            seed_exprs.insert(seed_expr, (id, weight));
-
-            cur_id = state.corpus().next(id);
+            let initial = obs.initial();
+            coverage.push(EntryCoverage {
+                id,
+                weight,
+                map: obs
+                    .as_iter()
+                    .map(|x| *x)
+                    .enumerate()
+                    .filter(|(_, e)| *e != initial)
+                    .collect(),
+            });
         }
+        Ok(coverage)
+    }
 
-        manager.log(
-            state,
-            LogSeverity::Info,
-            "Preparing Z3 assertions...".to_string(),
-        )?;
+    /// Removes all entries from the corpus which are not in `keep`
+    pub fn retain<CS, Z>(
+        &self,
+        fuzzer: &mut Z,
+        state: &mut S,
+        keep: &HashSet<CorpusId>,
+    ) -> Result<(), Error>
+    where
+        CS: Scheduler<I, S> + RemovableScheduler<I, S>,
+        Z: HasScheduler<I, S, Scheduler = CS>,
+    {
+        // don't delete this else it won't work after restart
+        let current = *state.corpus().current();
 
-        for (_, cov) in cov_map {
-            for (_, seeds) in cov {
-                // At least one seed for each hit count of each coverage map index
-                if let Some(reduced) = seeds.into_iter().reduce(|s1, s2| s1 | s2) {
-                    opt.assert(&reduced);
-                }
+        let mut removed = state
+            .corpus()
+            .ids()
+            .filter(|id| !keep.contains(id))
+            .collect::<Vec<_>>();
+        // reverse order; if indexes are stored in a vec, we need to remove from back to front
+        removed.sort_unstable_by(|id1, id2| id2.cmp(id1));
+        for id in removed {
+            if current == Some(id) {
+                continue;
             }
-        }
-        for (seed, (_, weight)) in &seed_exprs {
-            // opt will attempt to minimise the number of violated assertions.
-            //
-            // To tell opt to minimize the number of seeds, we tell opt to maximize the number of
-            // not seeds.
-            //
-            // Additionally, each seed has a weight associated with them; the higher, the more z3
-            // doesn't want to violate the assertion. Thus, inputs which have higher weights will be
-            // less likely to appear in the final corpus -- provided all their coverage points are
-            // hit by at least one other input.
-            opt.assert_soft(&!seed, *weight, None);
-        }
 
-        manager.log(state, LogSeverity::Info, "Performing MaxSAT...".to_string())?;
-        // Perform the optimization!
-        opt.check(&[]);
+            let removed = state.corpus_mut().remove(id)?;
+            // scheduler needs to know we've removed the input, or it will continue to try
+            // to use now-missing inputs
+            fuzzer
+                .scheduler_mut()
+                .on_remove(state, id, &Some(removed))?;
+        }
 
-        if let Some(model) = opt.get_model() {
-            let mut removed = Vec::with_capacity(state.corpus().count());
-            for (seed, (id, _)) in seed_exprs {
-                // if the model says the seed isn't there, mark it for deletion
-                if !model.eval(&seed, true).unwrap().as_bool().unwrap() {
-                    removed.push(id);
-                }
-            }
-            // reverse order; if indexes are stored in a vec, we need to remove from back to front
-            removed.sort_unstable_by(|id1, id2| id2.cmp(id1));
-            for id in removed {
-                if let Some(_cur) = current {
-                    continue;
-                }
+        *state.corpus_mut().current_mut() = None; //we may have removed the current ID from the corpus
+        Ok(())
+    }
+}
 
-                let removed = state.corpus_mut().remove(id)?;
-                // scheduler needs to know we've removed the input, or it will continue to try
-                // to use now-missing inputs
-                fuzzer
-                    .scheduler_mut()
-                    .on_remove(state, id, &Some(removed))?;
-            }
+#[cfg(test)]
+mod tests {
+    use super::{CorpusCoverage, EntryCoverage};
+    use crate::corpus::CorpusId;
 
-            *state.corpus_mut().current_mut() = None; //we may have removed the current ID from the corpus
-            return Ok(());
+    fn entry(id: usize, weight: u64, map: &[usize]) -> EntryCoverage<u8> {
+        EntryCoverage {
+            id: CorpusId(id),
+            weight,
+            map: map.iter().map(|&idx| (idx, 1)).collect(),
         }
-        Err(Error::unknown("Corpus minimization failed; unsat."))
+    }
+
+    #[test]
+    fn test_greedy_cmin() {
+        let mut coverage = CorpusCoverage::new();
+        coverage.push(entry(0, 10, &[0, 1, 2]));
+        coverage.push(entry(1, 1, &[0, 1]));
+        let mut other = CorpusCoverage::new();
+        other.push(entry(3, 1, &[3]));
+        other.push(entry(2, 1, &[2]));
+        other.push(entry(4, 5, &[1, 3]));
+        coverage.merge(other);
+
+        let keep = coverage.solve_greedy();
+        assert_eq!(keep.len(), 3);
+        assert!(keep.contains(&CorpusId(1)));
+        assert!(keep.contains(&CorpusId(2)));
+        assert!(keep.contains(&CorpusId(3)));
     }
 }
@@ -27,11 +27,19 @@ pub mod cached;
 #[cfg(feature = "std")]
 pub use cached::CachedOnDiskCorpus;
 
-#[cfg(all(feature = "cmin", unix))]
+#[cfg(feature = "std")]
+pub mod dedup_ondisk;
+#[cfg(feature = "std")]
+pub use dedup_ondisk::DedupOnDiskCorpus;
+
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteCorpus;
+
 pub mod minimizer;
 
 pub mod nop;
-#[cfg(all(feature = "cmin", unix))]
 pub use minimizer::*;
 pub use nop::NopCorpus;
 
@@ -5,13 +5,15 @@
 //! For any other occasions, consider using [`CachedOnDiskCorpus`]
 //! which stores a certain number of [`Testcase`]s in memory and removes additional ones in a FIFO manner.
 
-use alloc::string::String;
+use alloc::{string::String, vec::Vec};
 use core::{
     cell::{Ref, RefCell, RefMut},
     time::Duration,
 };
 use std::path::{Path, PathBuf};
 
+#[cfg(feature = "gzip")]
+use libafl_bolts::compress::GzipCompressor;
 use libafl_bolts::serdeany::SerdeAnyMap;
 use serde::{Deserialize, Serialize};
 
@@ -47,6 +49,24 @@ pub struct OnDiskMetadata<'a> {
     pub executions: &'a u64,
 }
 
+impl OnDiskMetadata<'_> {
+    /// Serializes this metadata in the given [`OnDiskMetadataFormat`]
+    pub fn to_vec(&self, meta_format: &OnDiskMetadataFormat) -> Result<Vec<u8>, Error> {
+        let json_error = |err| Error::serialize(format!("Failed to json-ify metadata: {err:?}"));
+
+        Ok(match meta_format {
+            OnDiskMetadataFormat::Postcard => postcard::to_allocvec(self)?,
+            OnDiskMetadataFormat::Json => serde_json::to_vec(self).map_err(json_error)?,
+            OnDiskMetadataFormat::JsonPretty => {
+                serde_json::to_vec_pretty(self).map_err(json_error)?
+            }
+            #[cfg(feature = "gzip")]
+            OnDiskMetadataFormat::JsonGzip => GzipCompressor::new()
+                .compress(&serde_json::to_vec_pretty(self).map_err(json_error)?),
+        })
+    }
+}
+
 /// A corpus able to store [`Testcase`]s to disk, and load them from disk, when they are being used.
 ///
 /// Metadata is written to a `.<filename>.metadata` file in the same folder by default.
@@ -0,0 +1,661 @@
+//! The [`SqliteCorpus`] stores [`Testcase`]s, including their inputs and metadata, in a single `SQLite` database.
+//!
+//! Compared to the one-file-per-testcase layout of [`crate::corpus::InMemoryOnDiskCorpus`],
+//! the whole corpus lives in a single file that is easy to back up and to query.
+//! All [`Testcase`]s are kept in memory, but their inputs are only loaded from the database when needed.
+//! To also limit the amount of metadata in memory, use it as backing store of a [`crate::corpus::CachedOnDiskCorpus`],
+//! see [`crate::corpus::CachedOnDiskCorpus::with_backing_corpus`].
+//!
+//! The database contains the following tables, shared by all clients using the same file:
+//! - `testcases(client, corpus_id, name, input, metadata, exec_time_ns, executions, scheduled_count, parent_id, disabled)`:
+//!   one row per [`Testcase`]. `input` is the `postcard`-serialized input, `metadata` the json-serialized [`SerdeAnyMap`].
+//! - `testcase_indexes(client, corpus_id, idx)`: the map indexes covered by each [`Testcase`],
+//!   taken from its [`MapIndexesMetadata`], if any.
+//!
+//! For example, `SELECT client, corpus_id FROM testcase_indexes WHERE idx = 1337` lists all testcases covering edge `1337`.
+
+use alloc::{format, string::String, vec::Vec};
+use core::{
+    cell::{OnceCell, Ref, RefCell, RefMut},
+    time::Duration,
+};
+use std::path::{Path, PathBuf};
+
+use hashbrown::HashMap;
+use libafl_bolts::{ClientId, serdeany::SerdeAnyMap};
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Error, HasMetadata,
+    corpus::{Corpus, CorpusId, EnableDisableCorpus, HasTestcase, InMemoryCorpus, Testcase},
+    feedbacks::MapIndexesMetadata,
+    inputs::Input,
+};
+
+/// How long to wait for other clients holding a lock on the database
+const BUSY_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The tables (and indexes) used by the [`SqliteCorpus`]
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS testcases (
+    client INTEGER NOT NULL,
+    corpus_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    input BLOB NOT NULL,
+    metadata TEXT NOT NULL,
+    exec_time_ns INTEGER,
+    executions INTEGER NOT NULL,
+    scheduled_count INTEGER NOT NULL,
+    parent_id INTEGER,
+    disabled INTEGER NOT NULL,
+    PRIMARY KEY (client, corpus_id)
+);
+CREATE INDEX IF NOT EXISTS testcases_name ON testcases (client, name);
+CREATE TABLE IF NOT EXISTS testcase_indexes (
+    client INTEGER NOT NULL,
+    corpus_id INTEGER NOT NULL,
+    idx INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS testcase_indexes_idx ON testcase_indexes (idx);
+CREATE INDEX IF NOT EXISTS testcase_indexes_entry ON testcase_indexes (client, corpus_id);
+";
+
+/// A row of the `testcases` table, as read by [`SqliteCorpus::load`]:
+/// `(corpus_id, name, metadata, exec_time_ns, executions, scheduled_count, parent_id, disabled)`
+type TestcaseRow = (
+    usize,
+    String,
+    String,
+    Option<u64>,
+    u64,
+    usize,
+    Option<usize>,
+    bool,
+);
+
+/// Converts a [`rusqlite::Error`] to an [`Error`]
+#[expect(clippy::needless_pass_by_value)]
+fn sql_error(err: rusqlite::Error) -> Error {
+    Error::unknown(format!("SQLite error: {err}"))
+}
+
+/// Opens the database at the given path and creates the tables, if needed
+fn open_connection(db_path: &Path) -> Result<Connection, Error> {
+    let conn = Connection::open(db_path).map_err(sql_error)?;
+    // Multiple clients may write to the same database.
+    conn.busy_timeout(BUSY_TIMEOUT).map_err(sql_error)?;
+    conn.query_row("PRAGMA journal_mode=WAL", [], |_| Ok(()))
+        .map_err(sql_error)?;
+    conn.execute_batch(SCHEMA).map_err(sql_error)?;
+    Ok(conn)
+}
+
+/// A corpus storing all [`Testcase`]s in a `SQLite` database, while also keeping them in memory, without their inputs.
+///
+/// Several clients can share the same database, their [`Testcase`]s are told apart by the [`ClientId`].
+/// Metadata is written when a [`Testcase`] is added, replaced, enabled or disabled.
+/// Use [`SqliteCorpus::sync_metadata`] to write metadata that changed in the meantime, e.g., scheduler metadata.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SqliteCorpus<I> {
+    inner: InMemoryCorpus<I>,
+    db_path: PathBuf,
+    client: ClientId,
+    /// The connection is reopened lazily after deserialization
+    #[serde(skip)]
+    connection: OnceCell<Connection>,
+}
+
+impl<I> Corpus<I> for SqliteCorpus<I>
+where
+    I: Input,
+{
+    /// Returns the number of all enabled entries
+    #[inline]
+    fn count(&self) -> usize {
+        self.inner.count()
+    }
+
+    /// Returns the number of all disabled entries
+    fn count_disabled(&self) -> usize {
+        self.inner.count_disabled()
+    }
+
+    /// Returns the number of elements including disabled entries
+    #[inline]
+    fn count_all(&self) -> usize {
+        self.inner.count_all()
+    }
+
+    /// Add an enabled testcase to the corpus and return its index
+    #[inline]
+    fn add(&mut self, mut testcase: Testcase<I>) -> Result<CorpusId, Error> {
+        // Write to the database first, so a failed write leaves the corpus unchanged.
+        self.save_testcase(&mut testcase, self.inner.peek_free_id(), false)?;
+        *testcase.input_mut() = None;
+        self.inner.add(testcase)
+    }
+
+    /// Add a disabled testcase to the corpus and return its index
+    #[inline]
+    fn add_disabled(&mut self, mut testcase: Testcase<I>) -> Result<CorpusId, Error> {
+        self.save_testcase(&mut testcase, self.inner.peek_free_id(), true)?;
+        *testcase.input_mut() = None;
+        self.inner.add_disabled(testcase)
+    }
+
+    /// Replaces the testcase at the given idx
+    #[inline]
+    fn replace(&mut self, id: CorpusId, mut testcase: Testcase<I>) -> Result<Testcase<I>, Error> {
+        // Make sure the entry exists before overwriting its row.
+        self.inner.get(id)?;
+        self.save_testcase(&mut testcase, id, false)?;
+        *testcase.input_mut() = None;
+        self.inner.replace(id, testcase)
+    }
+
+    /// Removes an entry from the corpus, returning it if it was present; considers both enabled and disabled corpus
+    #[inline]
+    fn remove(&mut self, id: CorpusId) -> Result<Testcase<I>, Error> {
+        // Delete from the database first, so a failed delete leaves the corpus unchanged.
+        self.inner.get_from_all(id)?;
+        self.remove_testcase(id)?;
+        self.inner.remove(id)
+    }
+
+    /// Get by id; considers only enabled testcases
+    #[inline]
+    fn get(&self, id: CorpusId) -> Result<&RefCell<Testcase<I>>, Error> {
+        self.inner.get(id)
+    }
+
+    /// Get by id; considers both enabled and disabled testcases
+    #[inline]
+    fn get_from_all(&self, id: CorpusId) -> Result<&RefCell<Testcase<I>>, Error> {
+        self.inner.get_from_all(id)
+    }
+
+    /// Current testcase scheduled
+    #[inline]
+    fn current(&self) -> &Option<CorpusId> {
+        self.inner.current()
+    }
+
+    /// Current testcase scheduled (mutable)
+    #[inline]
+    fn current_mut(&mut self) -> &mut Option<CorpusId> {
+        self.inner.current_mut()
+    }
+
+    #[inline]
+    fn next(&self, id: CorpusId) -> Option<CorpusId> {
+        self.inner.next(id)
+    }
+
+    /// Peek the next free corpus id
+    #[inline]
+    fn peek_free_id(&self) -> CorpusId {
+        self.inner.peek_free_id()
+    }
+
+    #[inline]
+    fn prev(&self, id: CorpusId) -> Option<CorpusId> {
+        self.inner.prev(id)
+    }
+
+    #[inline]
+    fn first(&self) -> Option<CorpusId> {
+        self.inner.first()
+    }
+
+    #[inline]
+    fn last(&self) -> Option<CorpusId> {
+        self.inner.last()
+    }
+
+    /// Get the nth corpus id; considers only enabled testcases
+    #[inline]
+    fn nth(&self, nth: usize) -> CorpusId {
+        self.inner.nth(nth)
+    }
+    /// Get the nth corpus id; considers both enabled and disabled testcases
+    #[inline]
+    fn nth_from_all(&self, nth: usize) -> CorpusId {
+        self.inner.nth_from_all(nth)
+    }
+
+    fn load_input_into(&self, testcase: &mut Testcase<I>) -> Result<(), Error> {
+        if testcase.input().is_none() {
+            let Some(name) = testcase.filename().as_ref() else {
+                return Err(Error::illegal_argument(
+                    "No name set for testcase. Could not load input from the database.",
+                ));
+            };
+            let bytes: Vec<u8> = self
+                .connection()?
+                .query_row(
+                    "SELECT input FROM testcases WHERE client = ?1 AND name = ?2 LIMIT 1",
+                    params![self.client.0, name],
+                    |row| row.get(0),
+                )
+                .optional()
+                .map_err(sql_error)?
+                .ok_or_else(|| {
+                    Error::key_not_found(format!("No input named {name} in the database"))
+                })?;
+            testcase.set_input(postcard::from_bytes(&bytes)?);
+        }
+        Ok(())
+    }
+
+    fn store_input_from(&self, testcase: &Testcase<I>) -> Result<(), Error> {
+        let Some(name) = testcase.filename() else {
+            return Err(Error::illegal_argument(
+                "No name set for testcase. Could not store input to the database.",
+            ));
+        };
+        let Some(input) = testcase.input() else {
+            return Err(Error::illegal_argument(
+                "No input available for testcase. Could not store anything.",
+            ));
+        };
+        self.connection()?
+            .execute(
+                "UPDATE testcases SET input = ?1 WHERE client = ?2 AND name = ?3",
+                params![postcard::to_allocvec(input)?, self.client.0, name],
+            )
+            .map_err(sql_error)?;
+        Ok(())
+    }
+}
+
+impl<I> EnableDisableCorpus for SqliteCorpus<I>
+where
+    I: Input,
+{
+    #[inline]
+    fn disable(&mut self, id: CorpusId) -> Result<(), Error> {
+        self.inner.disable(id)?;
+        self.set_disabled(id, true)
+    }
+
+    #[inline]
+    fn enable(&mut self, id: CorpusId) -> Result<(), Error> {
+        self.inner.enable(id)?;
+        self.set_disabled(id, false)
+    }
+}
+
+impl<I> HasTestcase<I> for SqliteCorpus<I>
+where
+    I: Input,
+{
+    fn testcase(&self, id: CorpusId) -> Result<Ref<'_, Testcase<I>>, Error> {
+        Ok(self.get(id)?.borrow())
+    }
+
+    fn testcase_mut(&self, id: CorpusId) -> Result<RefMut<'_, Testcase<I>>, Error> {
+        Ok(self.get(id)?.borrow_mut())
+    }
+}
+
+impl<I> SqliteCorpus<I> {
+    /// Creates a [`SqliteCorpus`] for client `0`, see [`SqliteCorpus::with_client`].
+    pub fn new<P>(db_path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        Self::with_client(db_path, ClientId(0))
+    }
+
+    /// Creates an empty [`SqliteCorpus`] storing its [`Testcase`]s in the database at `db_path`,
+    /// creating the database if it does not exist yet.
+    ///
+    /// Entries previously stored by the same `client` are removed from the database.
+    /// To continue with them instead, use [`SqliteCorpus::load`].
+    pub fn with_client<P>(db_path: P, client: ClientId) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let corpus = Self::_new(db_path.as_ref(), client)?;
+        let conn = corpus.connection()?;
+        conn.execute("DELETE FROM testcases WHERE client = ?1", params![client.0])
+            .map_err(sql_error)?;
+        conn.execute(
+            "DELETE FROM testcase_indexes WHERE client = ?1",
+            params![client.0],
+        )
+        .map_err(sql_error)?;
+        Ok(corpus)
+    }
+
+    /// Private fn to create a new corpus for the given (non-generic) path
+    fn _new(db_path: &Path, client: ClientId) -> Result<Self, Error> {
+        Ok(Self {
+            inner: InMemoryCorpus::new(),
+            db_path: db_path.into(),
+            client,
+            // Opened right away, to fail early if the database is not usable
+            connection: OnceCell::from(open_connection(db_path)?),
+        })
+    }
+
+    /// The connection to the database, for custom queries
+    pub fn connection(&self) -> Result<&Connection, Error> {
+        if let Some(conn) = self.connection.get() {
+            return Ok(conn);
+        }
+        let conn = open_connection(&self.db_path)?;
+        Ok(self.connection.get_or_init(|| conn))
+    }
+
+    /// Path to the database associated with this corpus
+    #[must_use]
+    pub fn db_path(&self) -> &PathBuf {
+        &self.db_path
+    }
+
+    /// The client the entries of this corpus belong to
+    #[must_use]
+    pub fn client(&self) -> ClientId {
+        self.client
+    }
+
+    /// Deletes the row and the covered indexes of an entry, in a single transaction.
+    fn remove_testcase(&self, id: CorpusId) -> Result<(), Error> {
+        let tx = self
+            .connection()?
+            .unchecked_transaction()
+            .map_err(sql_error)?;
+        tx.execute(
+            "DELETE FROM testcases WHERE client = ?1 AND corpus_id = ?2",
+            params![self.client.0, id.0],
+        )
+        .map_err(sql_error)?;
+        tx.execute(
+            "DELETE FROM testcase_indexes WHERE client = ?1 AND corpus_id = ?2",
+            params![self.client.0, id.0],
+        )
+        .map_err(sql_error)?;
+        tx.commit().map_err(sql_error)
+    }
+
+    fn set_disabled(&self, id: CorpusId, disabled: bool) -> Result<(), Error> {
+        self.connection()?
+            .execute(
+                "UPDATE testcases SET disabled = ?1 WHERE client = ?2 AND corpus_id = ?3",
+                params![disabled, self.client.0, id.0],
+            )
+            .map_err(sql_error)?;
+        Ok(())
+    }
+
+    /// Writes the metadata, exec time, and stats of the [`Testcase`] to its row, and updates its covered indexes.
+    fn update_testcase(
+        &self,
+        conn: &Connection,
+        testcase: &Testcase<I>,
+        id: CorpusId,
+        disabled: bool,
+    ) -> Result<(), Error> {
+        let metadata = serde_json::to_string(testcase.metadata_map())
+            .map_err(|err| Error::serialize(format!("Failed to json-ify metadata: {err:?}")))?;
+        let exec_time_ns = testcase
+            .exec_time()
+            .map(|time| u64::try_from(time.as_nanos()).unwrap_or(u64::MAX));
+        conn.execute(
+            "UPDATE testcases SET metadata = ?1, exec_time_ns = ?2, executions = ?3, scheduled_count = ?4,
+                parent_id = ?5, disabled = ?6 WHERE client = ?7 AND corpus_id = ?8",
+            params![
+                metadata,
+                exec_time_ns,
+                testcase.executions(),
+                testcase.scheduled_count(),
+                testcase.parent_id().map(|parent| parent.0),
+                disabled,
+                self.client.0,
+                id.0
+            ],
+        )
+        .map_err(sql_error)?;
+
+        conn.execute(
+            "DELETE FROM testcase_indexes WHERE client = ?1 AND corpus_id = ?2",
+            params![self.client.0, id.0],
+        )
+        .map_err(sql_error)?;
+        if let Ok(meta) = testcase.metadata::<MapIndexesMetadata>() {
+            let mut stmt = conn
+                .prepare_cached(
+                    "INSERT INTO testcase_indexes (client, corpus_id, idx) VALUES (?1, ?2, ?3)",
+                )
+                .map_err(sql_error)?;
+            for idx in &meta.list {
+                stmt.execute(params![self.client.0, id.0, idx])
+                    .map_err(sql_error)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the current metadata of all [`Testcase`]s to the database, in a single transaction.
+    ///
+    /// Metadata is only written automatically when a [`Testcase`] is added or changes state.
+    /// Call this from time to time to persist metadata added later on, such as scheduler metadata.
+    pub fn sync_metadata(&self) -> Result<(), Error> {
+        let tx = self
+            .connection()?
+            .unchecked_transaction()
+            .map_err(sql_error)?;
+        for nth in 0..self.inner.count_all() {
+            let id = self.inner.nth_from_all(nth);
+            let disabled = self.inner.get(id).is_err();
+            let testcase = self.inner.get_from_all(id)?.borrow();
+            self.update_testcase(&tx, &testcase, id, disabled)?;
+        }
+        tx.commit().map_err(sql_error)
+    }
+}
+
+impl<I> SqliteCorpus<I>
+where
+    I: Input,
+{
+    /// Opens the database at `db_path`, and loads the [`Testcase`]s of the given `client`
+    /// (without their inputs) into the corpus, e.g., to continue a previous campaign.
+    ///
+    /// Entries get new, consecutive [`CorpusId`]s, in the same order as before; the database is updated accordingly.
+    pub fn load<P>(db_path: P, client: ClientId) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let db_path = db_path.as_ref();
+        let conn = open_connection(db_path)?;
+
+        let rows: Vec<TestcaseRow> = {
+            let mut stmt = conn
+                .prepare(
+                    "SELECT corpus_id, name, metadata, exec_time_ns, executions, scheduled_count, parent_id, disabled
+                    FROM testcases WHERE client = ?1 ORDER BY corpus_id",
+                )
+                .map_err(sql_error)?;
+            stmt.query_map(params![client.0], |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                    row.get(5)?,
+                    row.get(6)?,
+                    row.get(7)?,
+                ))
+            })
+            .map_err(sql_error)?
+            .collect::<Result<_, _>>()
+            .map_err(sql_error)?
+        };
+
+        let mut inner = InMemoryCorpus::new();
+        let mut new_ids = HashMap::with_capacity(rows.len());
+        let tx = conn.unchecked_transaction().map_err(sql_error)?;
+        for (
+            old_id,
+            name,
+            metadata,
+            exec_time_ns,
+            executions,
+            scheduled_count,
+            parent_id,
+            disabled,
+        ) in rows
+        {
+            let mut testcase = Testcase::default();
+            *testcase.filename_mut() = Some(name);
+            *testcase.metadata_map_mut() = serde_json::from_str::<SerdeAnyMap>(&metadata)
+                .map_err(|err| Error::serialize(format!("Failed to parse metadata: {err:?}")))?;
+            *testcase.exec_time_mut() = exec_time_ns.map(Duration::from_nanos);
+            testcase.set_executions(executions);
+            testcase.set_scheduled_count(scheduled_count);
+            testcase
+                .set_parent_id_optional(parent_id.and_then(|parent| new_ids.get(&parent).copied()));
+            let parent_id = testcase.parent_id().map(|parent| parent.0);
+
+            let id = if disabled {
+                testcase.set_disabled(true);
+                inner.add_disabled(testcase)?
+            } else {
+                inner.add(testcase)?
+            };
+            new_ids.insert(old_id, id);
+
+            // New ids are never larger than the old ones, and we update in ascending order, so they cannot collide.
+            tx.execute(
+                "UPDATE testcases SET corpus_id = ?1, parent_id = ?2 WHERE client = ?3 AND corpus_id = ?4",
+                params![id.0, parent_id, client.0, old_id],
+            )
+            .map_err(sql_error)?;
+            tx.execute(
+                "UPDATE testcase_indexes SET corpus_id = ?1 WHERE client = ?2 AND corpus_id = ?3",
+                params![id.0, client.0, old_id],
+            )
+            .map_err(sql_error)?;
+        }
+        tx.commit().map_err(sql_error)?;
+
+        Ok(Self {
+            inner,
+            db_path: db_path.into(),
+            client,
+            connection: OnceCell::from(conn),
+        })
+    }
+
+    fn save_testcase(
+        &self,
+        testcase: &mut Testcase<I>,
+        id: CorpusId,
+        disabled: bool,
+    ) -> Result<(), Error> {
+        let Some(input) = testcase.input() else {
+            return Err(Error::illegal_argument(
+                "No input available for testcase. Could not store anything.",
+            ));
+        };
+        let name = match testcase.filename() {
+            Some(name) => name.clone(),
+            None => input.generate_name(Some(id)),
+        };
+        let input = postcard::to_allocvec(input)?;
+
+        let tx = self
+            .connection()?
+            .unchecked_transaction()
+            .map_err(sql_error)?;
+        tx.execute(
+            "INSERT OR REPLACE INTO testcases (client, corpus_id, name, input, metadata, executions, scheduled_count, disabled)
+            VALUES (?1, ?2, ?3, ?4, '{}', 0, 0, ?5)",
+            params![self.client.0, id.0, name, input, disabled],
+        )
+        .map_err(sql_error)?;
+        *testcase.filename_mut() = Some(name);
+        self.update_testcase(&tx, testcase, id, disabled)?;
+        tx.commit().map_err(sql_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use libafl_bolts::{ClientId, current_nanos};
+
+    use super::SqliteCorpus;
+    use crate::{
+        corpus::{CachedOnDiskCorpus, Corpus, EnableDisableCorpus, Testcase},
+        inputs::BytesInput,
+    };
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_sqlite_corpus() {
+        let db_path = env::temp_dir().join(format!(
+            "libafl_test_sqlite_corpus_{}_{}.db",
+            process::id(),
+            current_nanos()
+        ));
+        drop(fs::remove_file(&db_path));
+
+        let mut corpus = CachedOnDiskCorpus::with_backing_corpus(
+            SqliteCorpus::with_client(&db_path, ClientId(1)).unwrap(),
+            1,
+        )
+        .unwrap();
+        let first = corpus
+            .add(Testcase::new(BytesInput::new(vec![1, 2, 3])))
+            .unwrap();
+        let second = corpus
+            .add(Testcase::new(BytesInput::new(vec![4, 5])))
+            .unwrap();
+        corpus.disable(first).unwrap();
+        // A testcase without an input cannot be stored, and is not added to the corpus
+        assert!(corpus.add(Testcase::default()).is_err());
+        assert_eq!(corpus.count(), 1);
+        assert_eq!(corpus.count_all(), 2);
+
+        // Only one input fits in the cache, the other one has to come from the database.
+        assert_eq!(
+            corpus.cloned_input_for_id(second).unwrap(),
+            BytesInput::new(vec![4, 5])
+        );
+        assert_eq!(
+            corpus
+                .get_from_all(first)
+                .unwrap()
+                .borrow()
+                .input()
+                .as_ref(),
+            Some(&BytesInput::new(vec![1, 2, 3]))
+        );
+        drop(corpus);
+
+        let mut corpus = SqliteCorpus::<BytesInput>::load(&db_path, ClientId(1)).unwrap();
+        assert_eq!(corpus.count(), 1);
+        assert_eq!(corpus.count_disabled(), 1);
+        let id = corpus.nth(0);
+        assert_eq!(
+            corpus.cloned_input_for_id(id).unwrap(),
+            BytesInput::new(vec![4, 5])
+        );
+
+        // Removed entries are gone from the database as well
+        corpus.remove(id).unwrap();
+        assert!(corpus.remove(id).is_err());
+        drop(corpus);
+        let corpus = SqliteCorpus::<BytesInput>::load(&db_path, ClientId(1)).unwrap();
+        assert_eq!(corpus.count(), 0);
+        assert_eq!(corpus.count_disabled(), 1);
+
+        drop(fs::remove_file(&db_path));
+    }
+}
@@ -0,0 +1,392 @@
+//! A durable, append-only log of all messages passing through an [`libafl_bolts::llmp::LlmpBroker`].
+//!
+//! The [`EventLogHook`] records every message, and an [`EventLogReader`] reads them back in order.
+//! Logs can be replayed into another broker, or into a fuzzer, to reconstruct how the corpus of a
+//! campaign evolved, to debug the message flow, or to bootstrap a new campaign.
+
+use alloc::vec::Vec;
+use core::time::Duration;
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufReader, ErrorKind, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+#[cfg(feature = "llmp_compression")]
+use libafl_bolts::compress::GzipCompressor;
+use libafl_bolts::{
+    ClientId, current_time,
+    llmp::{
+        Flags, LLMP_FLAG_COMPRESSED, LlmpBrokerInner, LlmpClient, LlmpHook, LlmpMsgHookResult, Tag,
+    },
+    shmem::{ShMem, ShMemProvider},
+};
+use serde::de::DeserializeOwned;
+
+#[cfg(unix)]
+use crate::events::centralized::_LLMP_TAG_TO_MAIN;
+use crate::{
+    Error,
+    events::{Event, EventWithStats, llmp::LLMP_TAG_EVENT_TO_BOTH},
+    fuzzer::Evaluator,
+};
+
+/// The magic bytes every event log starts with
+pub const EVENT_LOG_MAGIC: [u8; 8] = *b"LIBAFLEL";
+
+/// Time, client id, tag, flags, and payload length
+const RECORD_HEADER_LEN: usize = 8 + 4 + 4 + 4 + 4;
+
+/// A message recorded by the [`EventLogHook`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventLogRecord {
+    /// When the broker received the message, since the unix epoch
+    pub time: Duration,
+    /// The client that sent the message
+    pub client_id: ClientId,
+    /// The tag of the message
+    pub tag: Tag,
+    /// The flags of the message
+    pub flags: Flags,
+    /// The message as it was sent, possibly compressed
+    pub payload: Vec<u8>,
+}
+
+impl EventLogRecord {
+    /// Deserialize the event carried by this record, if it carries one.
+    ///
+    /// The log has to be read with the same `LibAFL` version, input type,
+    /// and features (like `multi_machine`) as the campaign that recorded it.
+    pub fn event<I>(&self) -> Result<Option<EventWithStats<I>>, Error>
+    where
+        I: DeserializeOwned,
+    {
+        #[cfg(unix)]
+        let is_event = self.tag == LLMP_TAG_EVENT_TO_BOTH || self.tag == _LLMP_TAG_TO_MAIN;
+        #[cfg(not(unix))]
+        let is_event = self.tag == LLMP_TAG_EVENT_TO_BOTH;
+        if !is_event {
+            return Ok(None);
+        }
+
+        if self.flags & LLMP_FLAG_COMPRESSED == LLMP_FLAG_COMPRESSED {
+            #[cfg(feature = "llmp_compression")]
+            {
+                let payload = GzipCompressor::new().decompress(&self.payload)?;
+                return Ok(Some(postcard::from_bytes(&payload)?));
+            }
+            #[cfg(not(feature = "llmp_compression"))]
+            return Err(Error::unsupported(
+                "The event is compressed, enable the `llmp_compression` feature to read it",
+            ));
+        }
+
+        Ok(Some(postcard::from_bytes(&self.payload)?))
+    }
+
+    fn header(&self) -> Result<[u8; RECORD_HEADER_LEN], Error> {
+        let mut header = [0; RECORD_HEADER_LEN];
+        header[..8].copy_from_slice(&u64::try_from(self.time.as_nanos())?.to_le_bytes());
+        header[8..12].copy_from_slice(&self.client_id.0.to_le_bytes());
+        header[12..16].copy_from_slice(&self.tag.0.to_le_bytes());
+        header[16..20].copy_from_slice(&self.flags.0.to_le_bytes());
+        header[20..].copy_from_slice(&u32::try_from(self.payload.len())?.to_le_bytes());
+        Ok(header)
+    }
+}
+
+/// A broker hook appending every message it sees to an event log.
+///
+/// Put it first in the hooks of the broker, as messages that an earlier hook handles never reach it.
+/// Each record is appended with a single write, so a crashing broker loses at most the record it was
+/// writing. The log is flushed to disk on every broker heartbeat, and when the hook is dropped.
+#[derive(Debug)]
+pub struct EventLogHook {
+    file: File,
+}
+
+impl EventLogHook {
+    /// Log to the given file, appending to it if it already is an event log.
+    ///
+    /// A partially written record at the end of the log, left by a crash, is discarded.
+    pub fn new<P>(path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        if file.metadata()?.len() == 0 {
+            file.write_all(&EVENT_LOG_MAGIC)?;
+        } else {
+            let mut reader = EventLogReader::new(BufReader::new(&file))?;
+            while reader.next_record()?.is_some() {}
+            let end = reader.offset();
+            file.set_len(end)?;
+        }
+        file.seek(SeekFrom::End(0))?;
+
+        Ok(Self { file })
+    }
+
+    /// Append a record to the log
+    pub fn append(&mut self, record: &EventLogRecord) -> Result<(), Error> {
+        let mut buf = Vec::with_capacity(RECORD_HEADER_LEN + record.payload.len());
+        buf.extend_from_slice(&record.header()?);
+        buf.extend_from_slice(&record.payload);
+        self.file.write_all(&buf)?;
+        Ok(())
+    }
+}
+
+impl Drop for EventLogHook {
+    fn drop(&mut self) {
+        if let Err(e) = self.file.sync_data() {
+            log::error!("Could not flush the event log: {e}");
+        }
+    }
+}
+
+impl<SHM, SP> LlmpHook<SHM, SP> for EventLogHook {
+    fn on_new_message(
+        &mut self,
+        _broker_inner: &mut LlmpBrokerInner<SHM, SP>,
+        client_id: ClientId,
+        msg_tag: &mut Tag,
+        msg_flags: &mut Flags,
+        msg: &mut [u8],
+        _new_msgs: &mut Vec<(Tag, Flags, Vec<u8>)>,
+    ) -> Result<LlmpMsgHookResult, Error> {
+        let record = EventLogRecord {
+            time: current_time(),
+            client_id,
+            tag: *msg_tag,
+            flags: *msg_flags,
+            payload: msg.to_vec(),
+        };
+        // Keep fuzzing, even if the disk is full
+        if let Err(e) = self.append(&record) {
+            log::error!("Could not log a message from {client_id:?}: {e}");
+        }
+        Ok(LlmpMsgHookResult::ForwardToClients)
+    }
+
+    fn on_timeout(&mut self) -> Result<(), Error> {
+        if let Err(e) = self.file.sync_data() {
+            log::error!("Could not flush the event log: {e}");
+        }
+        Ok(())
+    }
+}
+
+/// Reads the records of an event log written by an [`EventLogHook`], in order.
+#[derive(Debug)]
+pub struct EventLogReader<R> {
+    reader: R,
+    offset: u64,
+}
+
+impl EventLogReader<BufReader<File>> {
+    /// Open the event log at the given path
+    pub fn open<P>(path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R> EventLogReader<R>
+where
+    R: Read,
+{
+    /// Read an event log from the given reader, which has to be at the start of the log.
+    pub fn new(mut reader: R) -> Result<Self, Error> {
+        let mut magic = [0; EVENT_LOG_MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if magic != EVENT_LOG_MAGIC {
+            return Err(Error::illegal_argument("Not an event log"));
+        }
+        Ok(Self {
+            reader,
+            offset: EVENT_LOG_MAGIC.len() as u64,
+        })
+    }
+
+    /// The offset of the end of the last record read
+    #[must_use]
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Read the next record, or `None` at the end of the log.
+    ///
+    /// A partially written record at the end of the log counts as the end of the log.
+    pub fn next_record(&mut self) -> Result<Option<EventLogRecord>, Error> {
+        let mut header = [0; RECORD_HEADER_LEN];
+        if !read_complete(&mut self.reader, &mut header)? {
+            return Ok(None);
+        }
+        let payload_len = u32::from_le_bytes(header[20..].try_into().unwrap());
+
+        // Do not trust the length with an allocation, the record may be cut short
+        let mut payload = Vec::new();
+        (&mut self.reader)
+            .take(payload_len.into())
+            .read_to_end(&mut payload)?;
+        if payload.len() != payload_len as usize {
+            log::warn!("Ignoring a partial record at the end of the event log");
+            return Ok(None);
+        }
+
+        self.offset += (RECORD_HEADER_LEN + payload.len()) as u64;
+        Ok(Some(EventLogRecord {
+            time: Duration::from_nanos(u64::from_le_bytes(header[..8].try_into().unwrap())),
+            client_id: ClientId(u32::from_le_bytes(header[8..12].try_into().unwrap())),
+            tag: Tag(u32::from_le_bytes(header[12..16].try_into().unwrap())),
+            flags: Flags(u32::from_le_bytes(header[16..20].try_into().unwrap())),
+            payload,
+        }))
+    }
+
+    /// Send all remaining records, with their original tags and flags, through an llmp client.
+    ///
+    /// Attach the client to a fresh broker to replay the campaign to its hooks and clients.
+    /// Returns the number of replayed records.
+    pub fn replay_to_client<SHM, SP>(
+        &mut self,
+        client: &mut LlmpClient<SHM, SP>,
+    ) -> Result<usize, Error>
+    where
+        SHM: ShMem,
+        SP: ShMemProvider<ShMem = SHM>,
+    {
+        let mut count = 0;
+        while let Some(record) = self.next_record()? {
+            client.send_buf_with_flags(record.tag, record.flags, &record.payload)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Evaluate the inputs of all remaining testcase and objective events, in the order they were found.
+    ///
+    /// Use this to bootstrap a new campaign with the discoveries of another one.
+    /// Returns the number of evaluated inputs.
+    pub fn replay_to_fuzzer<E, EM, I, S, Z>(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+    ) -> Result<usize, Error>
+    where
+        I: DeserializeOwned,
+        Z: Evaluator<E, EM, I, S>,
+    {
+        let mut count = 0;
+        while let Some(record) = self.next_record()? {
+            let Some(event) = record.event::<I>()? else {
+                continue;
+            };
+            let (Event::NewTestcase { input, .. }
+            | Event::Objective {
+                input: Some(input), ..
+            }) = event.event()
+            else {
+                continue;
+            };
+            fuzzer.evaluate_input(state, executor, manager, input)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+}
+
+impl<R> Iterator for EventLogReader<R>
+where
+    R: Read,
+{
+    type Item = Result<EventLogRecord, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_record().transpose()
+    }
+}
+
+/// Fill the buffer, or return `false` if the reader ends first.
+fn read_complete<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<bool, io::Error> {
+    let mut read = 0;
+    while read < buf.len() {
+        match reader.read(&mut buf[read..]) {
+            Ok(0) => {
+                if read > 0 {
+                    log::warn!("Ignoring a partial record at the end of the event log");
+                }
+                return Ok(false);
+            }
+            Ok(n) => read += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+    use core::time::Duration;
+    use std::{env, fs, io::Write, process};
+
+    use libafl_bolts::{
+        ClientId,
+        llmp::{Flags, Tag},
+    };
+
+    use super::{EventLogHook, EventLogReader, EventLogRecord};
+
+    #[test]
+    fn test_event_log() {
+        let path = env::temp_dir().join(format!("libafl_event_log_{}", process::id()));
+        drop(fs::remove_file(&path));
+
+        let records: Vec<EventLogRecord> = (0..3_u8)
+            .map(|i| EventLogRecord {
+                time: Duration::from_secs(i.into()),
+                client_id: ClientId(i.into()),
+                tag: Tag(0x2B0741),
+                flags: Flags(0),
+                payload: vec![i; usize::from(i) * 10],
+            })
+            .collect();
+
+        let mut hook = EventLogHook::new(&path).unwrap();
+        hook.append(&records[0]).unwrap();
+        hook.append(&records[1]).unwrap();
+        drop(hook);
+
+        // Simulate a crash while writing
+        fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(&[1, 2, 3])
+            .unwrap();
+        let reader = EventLogReader::open(&path).unwrap();
+        assert_eq!(reader.collect::<Result<Vec<_>, _>>().unwrap(), records[..2]);
+
+        // Reopening drops the partial record
+        let mut hook = EventLogHook::new(&path).unwrap();
+        hook.append(&records[2]).unwrap();
+        drop(hook);
+        let reader = EventLogReader::open(&path).unwrap();
+        assert_eq!(reader.collect::<Result<Vec<_>, _>>().unwrap(), records);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
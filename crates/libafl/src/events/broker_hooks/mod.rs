@@ -24,6 +24,12 @@ pub mod centralized;
 #[cfg(all(unix, feature = "std"))]
 pub use centralized::*;
 
+/// Durable event log hook, and replay of logged events
+#[cfg(feature = "std")]
+pub mod event_log;
+#[cfg(feature = "std")]
+pub use event_log::*;
+
 /// Multi-machine hook
 #[cfg(all(unix, feature = "multi_machine"))]
 pub mod centralized_multi_machine;
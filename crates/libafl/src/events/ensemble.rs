@@ -0,0 +1,785 @@
+//! Ensemble fuzzing: heterogeneous fuzzers sharing one broker, with cores allocated by their contribution.
+//!
+//! Each strategy is a separate fuzzer binary, for example one running a `ForkserverExecutor` with cmplog,
+//! one running QEMU, and one running concolic execution. The [`EnsembleLauncher`] spawns the broker,
+//! and then one strategy client per core. Clients connect to the broker with the [`EventConfig`] of their
+//! strategy, so testcases are re-executed between strategies instead of reusing observers. Clients with a
+//! different input type can convert testcases with an [`crate::events::LlmpEventConverter`].
+//!
+//! Every client adds a [`crate::stages::EnsembleStage`], which reports its own finds to the launcher.
+//! Periodically, the launcher decays the finds of each strategy, redistributes the cores proportionally,
+//! and asks the clients of cores that changed strategy to exit, restarting the core with its new strategy.
+//!
+//! A strategy binary picks up its assignment with [`EnsembleClient::from_env`]:
+//!
+//! ```rust,ignore
+//! let client = EnsembleClient::from_env(StdShMemProvider::new()?)?;
+//! let (state, mut mgr) = RestartingMgr::builder()
+//!     .shmem_provider(StdShMemProvider::new()?)
+//!     .broker_port(client.broker_port())
+//!     .kind(ManagerKind::Client {
+//!         client_description: client.client_description().clone(),
+//!     })
+//!     .configuration(client.configuration())
+//!     .hooks(tuple_list!())
+//!     .build()
+//!     .launch()?;
+//! // ...
+//! let mut stages = tuple_list!(StdMutationalStage::new(mutator), EnsembleStage::new(client));
+//! ```
+
+use alloc::{string::String, vec::Vec};
+use core::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+use std::{
+    env,
+    ffi::OsString,
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+    thread,
+    time::Instant,
+};
+
+use libafl_bolts::{
+    core_affinity::Cores,
+    llmp::LlmpBroker,
+    os::{ForkResult, fork},
+    shmem::{ShMem, ShMemProvider},
+    tuples::tuple_list,
+};
+use serde::de::DeserializeOwned;
+use typed_builder::TypedBuilder;
+
+use crate::{
+    Error,
+    events::{ClientDescription, EventConfig, StdLlmpEventHook},
+    monitors::Monitor,
+};
+
+/// The env variable holding the board shared by the launcher and its clients
+const _LIBAFL_ENSEMBLE_BOARD: &str = "_LIBAFL_ENSEMBLE_BOARD";
+/// The env variable telling a client its assignment
+const _LIBAFL_ENSEMBLE_CLIENT: &str = "_LIBAFL_ENSEMBLE_CLIENT";
+/// The env variable to set in order to enable client output
+const LIBAFL_DEBUG_OUTPUT: &str = "LIBAFL_DEBUG_OUTPUT";
+
+/// Marks a core without a strategy on the [`EnsembleBoard`]
+const UNASSIGNED: u64 = u64::MAX;
+
+/// How often the launcher checks on its clients
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// A client exiting sooner than this after its spawn failed to start, delaying the next restart of its core
+const QUICK_EXIT: Duration = Duration::from_secs(10);
+/// The longest the launcher waits before restarting a core whose clients keep failing to start
+const MAX_RESTART_BACKOFF: Duration = Duration::from_secs(60);
+
+/// A fuzzer binary taking part in an ensemble
+#[derive(Debug, Clone)]
+pub struct EnsembleStrategy {
+    name: String,
+    program: PathBuf,
+    args: Vec<OsString>,
+    envs: Vec<(OsString, OsString)>,
+    min_cores: usize,
+}
+
+impl EnsembleStrategy {
+    /// A strategy running `program`, identified by a unique `name`.
+    ///
+    /// By default, each strategy keeps at least one core.
+    #[must_use]
+    pub fn new<P>(name: &str, program: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self {
+            name: name.into(),
+            program: program.into(),
+            args: Vec::new(),
+            envs: Vec::new(),
+            min_cores: 1,
+        }
+    }
+
+    /// Add an argument for the program
+    #[must_use]
+    pub fn arg<A>(mut self, arg: A) -> Self
+    where
+        A: Into<OsString>,
+    {
+        self.args.push(arg.into());
+        self
+    }
+
+    /// Add arguments for the program
+    #[must_use]
+    pub fn args<IT, A>(mut self, args: IT) -> Self
+    where
+        IT: IntoIterator<Item = A>,
+        A: Into<OsString>,
+    {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// Set an env variable for the program
+    #[must_use]
+    pub fn env<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: Into<OsString>,
+        V: Into<OsString>,
+    {
+        self.envs.push((key.into(), value.into()));
+        self
+    }
+
+    /// The number of cores this strategy keeps, no matter its contribution
+    #[must_use]
+    pub fn min_cores(mut self, min_cores: usize) -> Self {
+        self.min_cores = min_cores;
+        self
+    }
+
+    /// The name of this strategy
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The [`EventConfig`] the clients of this strategy use
+    #[must_use]
+    pub fn configuration(&self) -> EventConfig {
+        EventConfig::from_name(&self.name)
+    }
+}
+
+/// Shared memory between the [`EnsembleLauncher`] and its clients.
+///
+/// It holds the number of testcases each strategy found, and the strategy assigned to each core.
+#[derive(Debug)]
+pub struct EnsembleBoard<SHM> {
+    shmem: SHM,
+    strategies: usize,
+    cores: usize,
+}
+
+impl<SHM> EnsembleBoard<SHM>
+where
+    SHM: ShMem,
+{
+    /// Create a board for the given number of strategies and cores
+    pub fn new<SP>(shmem_provider: &mut SP, strategies: usize, cores: usize) -> Result<Self, Error>
+    where
+        SP: ShMemProvider<ShMem = SHM>,
+    {
+        let shmem = shmem_provider.new_shmem((2 + strategies + cores) * size_of::<u64>())?;
+        let board = Self {
+            shmem,
+            strategies,
+            cores,
+        };
+        board.word(0).store(strategies as u64, Ordering::Relaxed);
+        board.word(1).store(cores as u64, Ordering::Relaxed);
+        for strategy in 0..strategies {
+            board.word(2 + strategy).store(0, Ordering::Relaxed);
+        }
+        for core in 0..cores {
+            board
+                .word(2 + strategies + core)
+                .store(UNASSIGNED, Ordering::Release);
+        }
+        Ok(board)
+    }
+
+    /// Map the board of the launcher, passed in the env
+    pub fn from_env<SP>(shmem_provider: &mut SP) -> Result<Self, Error>
+    where
+        SP: ShMemProvider<ShMem = SHM>,
+    {
+        Self::from_shmem(shmem_provider.existing_from_env(_LIBAFL_ENSEMBLE_BOARD)?)
+    }
+
+    /// Use an existing mapping of the board of the launcher
+    pub fn from_shmem(shmem: SHM) -> Result<Self, Error> {
+        if shmem.len() < 2 * size_of::<u64>() {
+            return Err(Error::illegal_state("The ensemble board is too small"));
+        }
+        let mut board = Self {
+            shmem,
+            strategies: 0,
+            cores: 0,
+        };
+        board.strategies = board.word(0).load(Ordering::Relaxed) as usize;
+        board.cores = board.word(1).load(Ordering::Relaxed) as usize;
+        if board.shmem.len() < (2 + board.strategies + board.cores) * size_of::<u64>() {
+            return Err(Error::illegal_state("The ensemble board is too small"));
+        }
+        Ok(board)
+    }
+
+    /// The shared map of the board
+    #[must_use]
+    pub fn shmem(&self) -> &SHM {
+        &self.shmem
+    }
+
+    #[expect(clippy::cast_ptr_alignment)]
+    fn word(&self, idx: usize) -> &AtomicU64 {
+        assert!((idx + 1) * size_of::<u64>() <= self.shmem.len());
+        // # Safety
+        // Shared maps are page aligned, we checked the bounds, and the words are only ever accessed atomically.
+        unsafe { &*self.shmem.as_ptr().cast::<AtomicU64>().add(idx) }
+    }
+
+    /// The number of testcases the clients of a strategy found, in total
+    #[must_use]
+    pub fn finds(&self, strategy: usize) -> u64 {
+        assert!(strategy < self.strategies);
+        self.word(2 + strategy).load(Ordering::Relaxed)
+    }
+
+    /// Count new testcases found by a client of a strategy
+    pub fn add_finds(&self, strategy: usize, finds: u64) {
+        assert!(strategy < self.strategies);
+        self.word(2 + strategy).fetch_add(finds, Ordering::Relaxed);
+    }
+
+    /// The strategy assigned to a core, by the index of the core in the [`Cores`] of the launcher
+    #[must_use]
+    pub fn assignment(&self, core: usize) -> Option<usize> {
+        assert!(core < self.cores);
+        match self
+            .word(2 + self.strategies + core)
+            .load(Ordering::Acquire)
+        {
+            UNASSIGNED => None,
+            strategy => Some(strategy as usize),
+        }
+    }
+
+    /// Assign a strategy to a core
+    pub fn assign(&self, core: usize, strategy: usize) {
+        assert!(core < self.cores && strategy < self.strategies);
+        self.word(2 + self.strategies + core)
+            .store(strategy as u64, Ordering::Release);
+    }
+}
+
+/// A client spawned by an [`EnsembleLauncher`], and the strategy it was assigned
+#[derive(Debug)]
+pub struct EnsembleClient<SHM> {
+    board: EnsembleBoard<SHM>,
+    strategy: usize,
+    strategy_name: String,
+    core: usize,
+    client_description: ClientDescription,
+    broker_port: u16,
+}
+
+impl<SHM> EnsembleClient<SHM>
+where
+    SHM: ShMem,
+{
+    /// The assignment of this process, if it was spawned by an [`EnsembleLauncher`]
+    pub fn from_env<SP>(mut shmem_provider: SP) -> Result<Self, Error>
+    where
+        SP: ShMemProvider<ShMem = SHM>,
+    {
+        let assignment = env::var(_LIBAFL_ENSEMBLE_CLIENT).map_err(|_| {
+            Error::illegal_state("Not spawned by an EnsembleLauncher, no assignment in the env")
+        })?;
+        Self::with_assignment(EnsembleBoard::from_env(&mut shmem_provider)?, &assignment)
+    }
+
+    /// The client for an assignment, as passed in the env by the launcher
+    pub(crate) fn with_assignment(
+        board: EnsembleBoard<SHM>,
+        assignment: &str,
+    ) -> Result<Self, Error> {
+        // The name goes last, it may contain anything
+        let mut parts = assignment.splitn(5, ';');
+        let mut next = || {
+            parts
+                .next()
+                .ok_or_else(|| Error::illegal_state("Invalid ensemble assignment in the env"))
+        };
+        let strategy = next()?.parse()?;
+        let core = next()?.parse()?;
+        let client_description = ClientDescription::from_safe_string(next()?);
+        let broker_port = next()?.parse()?;
+        let strategy_name = next()?.into();
+
+        if strategy >= board.strategies || core >= board.cores {
+            return Err(Error::illegal_state(
+                "Invalid ensemble assignment in the env",
+            ));
+        }
+        Ok(Self {
+            board,
+            strategy,
+            strategy_name,
+            core,
+            client_description,
+            broker_port,
+        })
+    }
+
+    /// The name of the strategy of this client
+    #[must_use]
+    pub fn strategy_name(&self) -> &str {
+        &self.strategy_name
+    }
+
+    /// The [`EventConfig`] of the strategy of this client
+    #[must_use]
+    pub fn configuration(&self) -> EventConfig {
+        EventConfig::from_name(&self.strategy_name)
+    }
+
+    /// The description to launch the event manager of this client with
+    #[must_use]
+    pub fn client_description(&self) -> &ClientDescription {
+        &self.client_description
+    }
+
+    /// The port of the broker of the ensemble
+    #[must_use]
+    pub fn broker_port(&self) -> u16 {
+        self.broker_port
+    }
+
+    /// If the core of this client is still assigned to its strategy.
+    ///
+    /// Once it is not, the client should exit, so that the launcher can start the new strategy.
+    #[must_use]
+    pub fn is_assigned(&self) -> bool {
+        self.board.assignment(self.core) == Some(self.strategy)
+    }
+
+    /// Credit the strategy of this client with new testcases
+    pub fn report_finds(&self, finds: u64) {
+        self.board.add_finds(self.strategy, finds);
+    }
+}
+
+/// Split `total` cores between strategies, proportionally to their scores.
+///
+/// Each strategy first gets its minimum, the rest is split by score, with the largest remainders rounded up.
+/// If no strategy scored, the rest is split evenly.
+#[must_use]
+#[expect(clippy::cast_precision_loss, clippy::cast_sign_loss)]
+pub fn allocate_cores(scores: &[f64], min_cores: &[usize], total: usize) -> Vec<usize> {
+    assert_eq!(scores.len(), min_cores.len());
+    let mut counts = min_cores.to_vec();
+    let remaining = total.saturating_sub(min_cores.iter().sum());
+    if remaining == 0 || scores.is_empty() {
+        return counts;
+    }
+
+    let sum: f64 = scores.iter().map(|score| score.max(0.0)).sum();
+    let weights: Vec<f64> = if sum > 0.0 {
+        scores.iter().map(|score| score.max(0.0) / sum).collect()
+    } else {
+        vec![1.0 / scores.len() as f64; scores.len()]
+    };
+
+    let shares: Vec<f64> = weights.iter().map(|w| w * remaining as f64).collect();
+    let mut given = 0;
+    for (count, share) in counts.iter_mut().zip(&shares) {
+        let floor = share.floor() as usize;
+        *count += floor;
+        given += floor;
+    }
+    let mut by_remainder: Vec<usize> = (0..shares.len()).collect();
+    by_remainder.sort_by(|&a, &b| {
+        (shares[b] - shares[b].floor()).total_cmp(&(shares[a] - shares[a].floor()))
+    });
+    for strategy in by_remainder.into_iter().take(remaining - given) {
+        counts[strategy] += 1;
+    }
+    counts
+}
+
+/// Assign strategies to cores, so that each strategy gets `counts` cores.
+///
+/// Cores without a strategy are filled first. At most `max_moves` cores that already run a strategy
+/// are moved to another one, those of strategies with the largest surplus first.
+#[must_use]
+pub fn assign_cores(current: &[Option<usize>], counts: &[usize], max_moves: usize) -> Vec<usize> {
+    let mut have = vec![0_usize; counts.len()];
+    for strategy in current.iter().flatten() {
+        have[*strategy] += 1;
+    }
+    let most_needed = |have: &[usize]| {
+        (0..counts.len())
+            .filter(|&strategy| have[strategy] < counts[strategy])
+            .max_by_key(|&strategy| (counts[strategy] - have[strategy], usize::MAX - strategy))
+    };
+
+    let mut assignment: Vec<Option<usize>> = current.to_vec();
+    for slot in &mut assignment {
+        if slot.is_none() {
+            // Every core needs a strategy, even if all are satisfied
+            let strategy = most_needed(&have).unwrap_or(0);
+            *slot = Some(strategy);
+            have[strategy] += 1;
+        }
+    }
+
+    for _ in 0..max_moves {
+        let Some(to) = most_needed(&have) else {
+            break;
+        };
+        let Some(from) = (0..counts.len())
+            .filter(|&strategy| have[strategy] > counts[strategy])
+            .max_by_key(|&strategy| have[strategy] - counts[strategy])
+        else {
+            break;
+        };
+        let slot = assignment
+            .iter()
+            .rposition(|slot| *slot == Some(from))
+            .unwrap();
+        assignment[slot] = Some(to);
+        have[from] -= 1;
+        have[to] += 1;
+    }
+
+    assignment.into_iter().map(Option::unwrap).collect()
+}
+
+/// Restart bookkeeping of a core, backing off from clients that exit right after their spawn
+#[derive(Debug)]
+struct CoreRestarts {
+    spawned_at: Instant,
+    quick_exits: u32,
+    not_before: Instant,
+}
+
+impl Default for CoreRestarts {
+    fn default() -> Self {
+        let now = Instant::now();
+        Self {
+            spawned_at: now,
+            quick_exits: 0,
+            not_before: now,
+        }
+    }
+}
+
+impl CoreRestarts {
+    /// The client of `core` exited, double the wait before its restart if it failed to start
+    fn exited(&mut self, core: usize) {
+        if self.spawned_at.elapsed() >= QUICK_EXIT {
+            self.quick_exits = 0;
+            return;
+        }
+        let backoff = POLL_INTERVAL
+            .saturating_mul(2_u32.saturating_pow(self.quick_exits))
+            .min(MAX_RESTART_BACKOFF);
+        self.quick_exits = self.quick_exits.saturating_add(1);
+        self.not_before = Instant::now() + backoff;
+        log::warn!(
+            "The client on core {core} exited right after its start, restarting it in {backoff:?}"
+        );
+    }
+}
+
+/// Launches a broker and an ensemble of strategies on the given cores,
+/// moving cores to the strategies that recently found the most testcases.
+#[derive(TypedBuilder, Debug)]
+pub struct EnsembleLauncher<'a, MT, SP> {
+    /// The `ShmemProvider` to use
+    shmem_provider: SP,
+    /// The monitor instance to use
+    monitor: MT,
+    /// The broker port to use
+    #[builder(default = 1337_u16)]
+    broker_port: u16,
+    /// The list of cores to run on, one client per core
+    cores: &'a Cores,
+    /// The strategies to run
+    strategies: Vec<EnsembleStrategy>,
+    /// How often to redistribute the cores
+    #[builder(default = Duration::from_secs(300))]
+    reallocation_interval: Duration,
+    /// How much of its past finds a strategy keeps at each reallocation, between `0` (only the last interval counts) and `1`
+    #[builder(default = 0.5)]
+    decay: f64,
+    /// The maximum number of cores moved to another strategy at each reallocation, as each move restarts a client
+    #[builder(default = 1)]
+    max_moves: usize,
+}
+
+impl<MT, SP> EnsembleLauncher<'_, MT, SP>
+where
+    MT: Monitor + Clone,
+    SP: ShMemProvider,
+{
+    /// Launch the broker and the clients, and allocate the cores until the broker exits
+    #[expect(clippy::cast_precision_loss)]
+    pub fn launch<I>(&mut self) -> Result<(), Error>
+    where
+        I: DeserializeOwned,
+    {
+        if self.cores.ids.is_empty() {
+            return Err(Error::illegal_argument(
+                "No cores to spawn on given, cannot launch anything.",
+            ));
+        }
+        if self.strategies.is_empty() {
+            return Err(Error::illegal_argument(
+                "No strategies given to the ensemble",
+            ));
+        }
+        let min_cores: Vec<usize> = self.strategies.iter().map(|s| s.min_cores).collect();
+        if min_cores.iter().sum::<usize>() > self.cores.ids.len() {
+            return Err(Error::illegal_argument(
+                "The strategies need more cores than given to the ensemble",
+            ));
+        }
+        if !(0.0..=1.0).contains(&self.decay) {
+            return Err(Error::illegal_argument(
+                "The decay has to be between 0 and 1",
+            ));
+        }
+
+        let board = EnsembleBoard::new(
+            &mut self.shmem_provider,
+            self.strategies.len(),
+            self.cores.ids.len(),
+        )?;
+        // # Safety
+        // The launcher is single threaded.
+        unsafe {
+            board.shmem.write_to_env(_LIBAFL_ENSEMBLE_BOARD)?;
+        }
+
+        self.shmem_provider.pre_fork()?;
+        // # Safety
+        // Fork is safe in general, apart from potential side effects to the OS and other threads
+        let broker = match unsafe { fork() }? {
+            ForkResult::Parent(child) => {
+                self.shmem_provider.post_fork(false)?;
+                child
+            }
+            ForkResult::Child => {
+                self.shmem_provider.post_fork(true)?;
+                let llmp_hook = StdLlmpEventHook::<I, MT>::new(self.monitor.clone())?;
+                let mut broker = LlmpBroker::create_attach_to_tcp(
+                    self.shmem_provider.clone(),
+                    tuple_list!(llmp_hook),
+                    self.broker_port,
+                )?;
+                broker.loop_with_timeouts(Duration::from_secs(30), Some(Duration::from_millis(5)));
+                return Err(Error::shutting_down());
+            }
+        };
+
+        let initial = allocate_cores(
+            &vec![0.0; self.strategies.len()],
+            &min_cores,
+            self.cores.ids.len(),
+        );
+        for (core, strategy) in assign_cores(&vec![None; self.cores.ids.len()], &initial, 0)
+            .into_iter()
+            .enumerate()
+        {
+            board.assign(core, strategy);
+        }
+
+        let mut clients: Vec<Option<Child>> = (0..self.cores.ids.len()).map(|_| None).collect();
+        let res = self.supervise(&board, broker.pid, &min_cores, &mut clients);
+
+        for client in clients.iter_mut().flatten() {
+            // # Safety
+            // Normal libc call, no dereferences whatsoever
+            unsafe {
+                libc::kill(client.id().cast_signed(), libc::SIGINT);
+            }
+            drop(client.wait());
+        }
+        if res.is_err() {
+            // # Safety
+            // Normal libc call, no dereferences whatsoever
+            unsafe {
+                libc::kill(broker.pid, libc::SIGINT);
+            }
+            let _ = broker.status();
+        }
+        res
+    }
+
+    /// Keep the clients running and reallocate the cores, until the broker exits or something fails.
+    ///
+    /// The caller stops the remaining `clients` afterwards.
+    fn supervise<SHM>(
+        &self,
+        board: &EnsembleBoard<SHM>,
+        broker_pid: libc::pid_t,
+        min_cores: &[usize],
+        clients: &mut [Option<Child>],
+    ) -> Result<(), Error>
+    where
+        SHM: ShMem,
+    {
+        let mut restarts: Vec<CoreRestarts> = (0..clients.len())
+            .map(|_| CoreRestarts::default())
+            .collect();
+        let mut spawned = 0;
+        let mut scores = vec![0.0; self.strategies.len()];
+        let mut counted: Vec<u64> = vec![0; self.strategies.len()];
+        let mut last_reallocation = Instant::now();
+
+        loop {
+            let mut status = 0;
+            // # Safety
+            // Normal libc call, no dereferences whatsoever
+            if unsafe { libc::waitpid(broker_pid, &raw mut status, libc::WNOHANG) } == broker_pid {
+                log::info!("The broker exited, stopping the ensemble");
+                return Ok(());
+            }
+
+            for (core, client) in clients.iter_mut().enumerate() {
+                if let Some(child) = client {
+                    match child.try_wait()? {
+                        None => continue,
+                        Some(exit) => {
+                            log::info!("The client on core {core} exited: {exit}");
+                            *client = None;
+                            restarts[core].exited(core);
+                        }
+                    }
+                }
+                if Instant::now() < restarts[core].not_before {
+                    continue;
+                }
+                let strategy = board.assignment(core).unwrap();
+                spawned += 1;
+                *client = Some(self.spawn_client(strategy, core, spawned)?);
+                restarts[core].spawned_at = Instant::now();
+            }
+
+            if last_reallocation.elapsed() >= self.reallocation_interval {
+                last_reallocation = Instant::now();
+                for (strategy, score) in scores.iter_mut().enumerate() {
+                    let finds = board.finds(strategy);
+                    *score = *score * self.decay + (finds - counted[strategy]) as f64;
+                    counted[strategy] = finds;
+                }
+                let counts = allocate_cores(&scores, min_cores, self.cores.ids.len());
+                let current: Vec<Option<usize>> = (0..self.cores.ids.len())
+                    .map(|core| board.assignment(core))
+                    .collect();
+                let next = assign_cores(&current, &counts, self.max_moves);
+                for (core, strategy) in next.into_iter().enumerate() {
+                    if current[core] != Some(strategy) {
+                        log::info!(
+                            "Moving core {core} from {} to {} (scores: {scores:?})",
+                            self.strategies[current[core].unwrap()].name,
+                            self.strategies[strategy].name
+                        );
+                        // The client exits on its own, and gets replaced by the new strategy
+                        board.assign(core, strategy);
+                    }
+                }
+            }
+
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    fn spawn_client(&self, strategy: usize, core: usize, id: usize) -> Result<Child, Error> {
+        let ensemble_strategy = &self.strategies[strategy];
+        let client_description = ClientDescription::new(id, 0, self.cores.ids[core]);
+        log::info!(
+            "Spawning {} on core {:?}",
+            ensemble_strategy.name,
+            self.cores.ids[core]
+        );
+
+        let mut command = Command::new(&ensemble_strategy.program);
+        command
+            .args(&ensemble_strategy.args)
+            .envs(ensemble_strategy.envs.iter().map(|(k, v)| (k, v)))
+            .env(
+                _LIBAFL_ENSEMBLE_CLIENT,
+                format!(
+                    "{strategy};{core};{};{};{}",
+                    client_description.to_safe_string(),
+                    self.broker_port,
+                    ensemble_strategy.name
+                ),
+            );
+        if env::var(LIBAFL_DEBUG_OUTPUT).is_err() {
+            command.stdout(Stdio::null()).stderr(Stdio::null());
+        }
+        command.spawn().map_err(|e| {
+            Error::os_error(
+                e,
+                format!("Could not spawn {}", ensemble_strategy.program.display()),
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::shmem::{ShMem, ShMemProvider, StdShMemProvider};
+
+    use super::{EnsembleBoard, allocate_cores, assign_cores};
+
+    #[test]
+    fn test_ensemble_allocation() {
+        // Nothing found yet, split evenly after the minimum
+        assert_eq!(allocate_cores(&[0.0, 0.0, 0.0], &[1, 1, 1], 7), [3, 2, 2]);
+        // Proportional to the scores
+        assert_eq!(allocate_cores(&[30.0, 10.0, 0.0], &[1, 1, 1], 7), [4, 2, 1]);
+        assert_eq!(allocate_cores(&[1.0, 1.0], &[0, 0], 3), [2, 1]);
+        assert_eq!(allocate_cores(&[5.0, 0.0], &[0, 2], 2), [0, 2]);
+
+        // Fill empty cores without counting moves
+        assert_eq!(assign_cores(&[None, None, None], &[1, 2], 0), [1, 0, 1]);
+        // Move at most one core, from the largest surplus
+        assert_eq!(
+            assign_cores(&[Some(0), Some(0), Some(0), Some(1)], &[1, 3], 1),
+            [0, 0, 1, 1]
+        );
+        assert_eq!(
+            assign_cores(&[Some(0), Some(0), Some(0), Some(1)], &[1, 3], 5),
+            [0, 1, 1, 1]
+        );
+        // Nothing to do
+        assert_eq!(assign_cores(&[Some(1), Some(0)], &[1, 1], 1), [1, 0]);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_ensemble_board() {
+        let mut provider = StdShMemProvider::new().unwrap();
+        let board = EnsembleBoard::new(&mut provider, 2, 3).unwrap();
+        // A client maps the same board a second time
+        let shmem = provider
+            .shmem_from_id_and_size(board.shmem.id(), board.shmem.len())
+            .unwrap();
+        let client_board = EnsembleBoard::from_shmem(shmem).unwrap();
+
+        assert_eq!(client_board.assignment(0), None);
+        board.assign(0, 1);
+        board.assign(2, 0);
+        assert_eq!(client_board.assignment(0), Some(1));
+        assert_eq!(client_board.assignment(1), None);
+        assert_eq!(client_board.assignment(2), Some(0));
+
+        client_board.add_finds(1, 3);
+        client_board.add_finds(1, 2);
+        assert_eq!(board.finds(0), 0);
+        assert_eq!(board.finds(1), 5);
+
+        // Too small for the board
+        let small = provider.new_shmem(8).unwrap();
+        assert!(EnsembleBoard::from_shmem(small).is_err());
+    }
+}
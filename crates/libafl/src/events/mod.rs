@@ -10,6 +10,8 @@ pub use simple::*;
 pub mod centralized;
 #[cfg(all(unix, feature = "std"))]
 pub use centralized::*;
+#[cfg(all(unix, feature = "fork"))]
+pub mod ensemble;
 #[cfg(feature = "std")]
 pub mod launcher;
 
@@ -17,6 +19,8 @@ pub mod llmp;
 pub use llmp::*;
 #[cfg(feature = "tcp_manager")]
 pub mod tcp;
+#[cfg(feature = "transport_security")]
+pub mod transport_security;
 
 pub mod broker_hooks;
 #[cfg(feature = "introspection")]
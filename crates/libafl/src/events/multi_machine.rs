@@ -1,10 +1,18 @@
-use alloc::{boxed::Box, sync::Arc, vec::Vec};
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
 use core::{
     fmt::Display,
     sync::atomic::{AtomicU64, Ordering},
     time::Duration,
 };
-use std::{collections::HashMap, io::ErrorKind, process, sync::OnceLock};
+use std::{
+    collections::HashMap, fs, io, io::ErrorKind, path::PathBuf, process, sync::OnceLock,
+    time::SystemTime,
+};
 
 use enumflags2::{BitFlags, bitflags};
 #[cfg(feature = "llmp_compression")]
@@ -20,9 +28,13 @@ use tokio::{
     time,
 };
 use typed_builder::TypedBuilder;
+use uuid::Uuid;
 
 use crate::{
-    events::{EventWithStats, TcpMultiMachineLlmpReceiverHook, TcpMultiMachineLlmpSenderHook},
+    events::{
+        EventWithStats, TcpMultiMachineLlmpReceiverHook, TcpMultiMachineLlmpSenderHook,
+        transport_security::{DEFAULT_MAX_FRAME_LEN, SecureSession, TransportSecurity},
+    },
     inputs::{Input, NopInput},
 };
 
@@ -41,6 +53,12 @@ pub enum NodePolicy {
 
 const DUMMY_BYTE: u8 = 0x14;
 
+/// How often a node without a [`PeerDirectory`] tries to reconnect to its lost parent
+const PARENT_RECONNECT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The file extension of the node entries in a [`PeerDirectory`]
+const PEER_ENTRY_EXTENSION: &str = "peer";
+
 /// Use `OwnedRef` as much as possible here to avoid useless copies.
 /// An owned TCP message for multi machine
 #[derive(Debug, Clone)]
@@ -108,15 +126,58 @@ impl NodeId {
     }
 }
 
+/// A connection to another node
+#[derive(Debug)]
+struct NodeStream {
+    stream: TcpStream,
+    /// The session, if the connection is secured by [`NodeDescriptor::transport_security`]
+    session: Option<SecureSession>,
+}
+
+impl NodeStream {
+    /// Run the [`TransportSecurity`] handshake on a new connection, if configured.
+    async fn establish(
+        mut stream: TcpStream,
+        security: Option<&TransportSecurity>,
+        is_listener: bool,
+        timeout: Duration,
+    ) -> Result<Self, Error> {
+        let Some(security) = security else {
+            return Ok(Self {
+                stream,
+                session: None,
+            });
+        };
+
+        let handshake = async {
+            if is_listener {
+                security.accept(&mut stream).await
+            } else {
+                security.connect(&mut stream).await
+            }
+        };
+        let session = time::timeout(timeout, handshake)
+            .await
+            .map_err(|_| Error::invalid_input("Transport security handshake timed out"))??;
+
+        Ok(Self {
+            stream,
+            session: Some(session),
+        })
+    }
+}
+
 /// The state of the hook shared between the background threads and the main thread.
 #[derive(Debug)]
 pub struct TcpMultiMachineState<A> {
     node_descriptor: NodeDescriptor<A>,
     /// the parent to which the testcases should be forwarded when deemed interesting
-    parent: Option<TcpStream>,
+    parent: Option<NodeStream>,
     /// The children who connected during the fuzzing session.
-    children: HashMap<NodeId, TcpStream>, // The children who connected during the fuzzing session.
+    children: HashMap<NodeId, NodeStream>, // The children who connected during the fuzzing session.
     old_msgs: Vec<Vec<u8>>,
+    /// The name of the entry of this node in the [`PeerDirectory`], if it registered one
+    peer_entry: Option<String>,
     #[cfg(feature = "llmp_compression")]
     compressor: GzipCompressor,
 }
@@ -138,6 +199,142 @@ pub struct NodeDescriptor<A> {
     /// Node flags
     #[builder(default_code = "BitFlags::default()")]
     pub flags: BitFlags<NodePolicy>, // The policy for shared messages between nodes.
+
+    /// Authenticate, and by default encrypt, the connections to the parent and the children
+    /// with a pre-shared key. All nodes of the tree need the same setting.
+    ///
+    /// Without it, anybody who can reach the listening port can inject testcases.
+    #[builder(default = None)]
+    pub transport_security: Option<TransportSecurity>,
+
+    /// Find the parent through a [`PeerDirectory`] instead of `parent_addr`,
+    /// and attach to another node whenever the parent goes away.
+    #[builder(default = None)]
+    pub discovery: Option<PeerDirectory>,
+}
+
+impl<A> NodeDescriptor<A> {
+    /// The address this node advertises in the [`PeerDirectory`], if other nodes can reach it
+    fn advertised_addr(&self) -> Option<String> {
+        let host = self.discovery.as_ref()?.advertised_host.as_ref()?;
+        let port = self.node_listening_port?;
+        if host.contains(':') {
+            Some(format!("[{host}]:{port}"))
+        } else {
+            Some(format!("{host}:{port}"))
+        }
+    }
+}
+
+/// Lets the nodes of a campaign find each other through a directory they all share, for example on NFS.
+///
+/// Each node that others can reach writes its address to the directory, and refreshes it every
+/// `heartbeat`. Nodes are ordered by the time they joined, and a node only ever attaches to an
+/// older node, so the nodes always form a tree. Nodes can join and leave at any time: when the
+/// parent of a node goes away, the node attaches to another live node and catches it up on all
+/// the messages it has seen.
+#[derive(Debug, Clone, TypedBuilder)]
+pub struct PeerDirectory {
+    /// The shared directory
+    #[builder(setter(into))]
+    pub path: PathBuf,
+
+    /// The host name or IP other nodes reach this node at, on its `node_listening_port`.
+    /// Without it, the node does not register, and never becomes a parent.
+    #[builder(default = None)]
+    pub advertised_host: Option<String>,
+
+    /// How often a node refreshes its entry, and checks on its parent
+    #[builder(default = Duration::from_secs(5))]
+    pub heartbeat: Duration,
+
+    /// The time after its last refresh after which a node is considered gone
+    #[builder(default = Duration::from_secs(30))]
+    pub expiry: Duration,
+
+    /// The number of children a node should have, at most, for the tree to stay balanced
+    #[builder(default = 4)]
+    pub fanout: usize,
+}
+
+impl PeerDirectory {
+    /// A new entry name, which sorts after the entries of all the nodes that joined before
+    fn new_entry() -> String {
+        format!(
+            "{:020}-{}.{PEER_ENTRY_EXTENSION}",
+            current_time().as_nanos(),
+            Uuid::new_v4().simple()
+        )
+    }
+
+    /// Create or refresh the entry of a node
+    fn register(&self, entry: &str, addr: &str) -> Result<(), Error> {
+        fs::create_dir_all(&self.path)?;
+        // Write the entry atomically, so other nodes never read a partial address
+        let tmp_path = self.path.join(format!(".{entry}.tmp"));
+        fs::write(&tmp_path, addr)?;
+        fs::rename(&tmp_path, self.path.join(entry))?;
+        Ok(())
+    }
+
+    /// The entries and addresses of all live nodes, oldest first
+    fn peers(&self) -> Result<Vec<(String, String)>, Error> {
+        let dir = match fs::read_dir(&self.path) {
+            Ok(dir) => dir,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(Error::os_error(e, "Could not read the peer directory")),
+        };
+
+        let now = SystemTime::now();
+        let mut peers = Vec::new();
+        for dir_entry in dir {
+            let dir_entry = dir_entry?;
+            let name = dir_entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with('.') || !name.ends_with(PEER_ENTRY_EXTENSION) {
+                continue;
+            }
+
+            // Entries can disappear at any time, skip them
+            let Ok(modified) = dir_entry
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+            else {
+                continue;
+            };
+            if now.duration_since(modified).unwrap_or_default() > self.expiry {
+                log::debug!("Removing the expired peer {name}");
+                drop(fs::remove_file(dir_entry.path()));
+                continue;
+            }
+            let Ok(addr) = fs::read_to_string(dir_entry.path()) else {
+                continue;
+            };
+            peers.push((name, addr.trim().to_string()));
+        }
+
+        peers.sort_unstable();
+        Ok(peers)
+    }
+
+    /// The addresses of the nodes a node may attach to, the preferred one first.
+    ///
+    /// Only nodes that joined before `own_entry` qualify. The preferred one is the parent the node
+    /// would have in a complete tree of all live nodes with `fanout` children each.
+    fn parent_candidates(&self, own_entry: Option<&str>) -> Result<Vec<String>, Error> {
+        let mut candidates: Vec<String> = self
+            .peers()?
+            .into_iter()
+            .take_while(|(name, _)| own_entry.is_none_or(|own_entry| name.as_str() < own_entry))
+            .map(|(_, addr)| addr)
+            .collect();
+
+        if !candidates.is_empty() {
+            // This node comes right after all older nodes in the tree.
+            let preferred = candidates.remove((candidates.len() - 1) / self.fanout.max(1));
+            candidates.insert(0, preferred);
+        }
+        Ok(candidates)
+    }
 }
 
 /// A set of multi-machine `broker_hooks`.
@@ -224,6 +421,7 @@ where
                 parent: None,
                 children: HashMap::default(),
                 old_msgs: Vec::new(),
+                peer_entry: None,
                 #[cfg(feature = "llmp_compression")]
                 compressor: GzipCompressor::new(),
             }));
@@ -264,16 +462,18 @@ where
             let parent_mutex = self_mutex.clone();
             let mut parent_lock = parent_mutex.write().await;
 
-            if let Some(parent_addr) = &parent_lock.node_descriptor.parent_addr {
+            if parent_lock.node_descriptor.discovery.is_none()
+                && let Some(parent_addr) = &parent_lock.node_descriptor.parent_addr
+            {
                 let timeout = current_time() + parent_lock.node_descriptor.timeout;
 
-                parent_lock.parent = loop {
+                let stream = loop {
                     log::debug!("Trying to connect to parent @ {parent_addr}..");
                     match TcpStream::connect(parent_addr).await {
                         Ok(stream) => {
                             log::debug!("Connected to parent @ {parent_addr}");
 
-                            break Some(stream);
+                            break stream;
                         }
                         Err(e) => {
                             if current_time() > timeout {
@@ -284,6 +484,21 @@ where
 
                     time::sleep(Duration::from_secs(1)).await;
                 };
+
+                parent_lock.parent = Some(
+                    NodeStream::establish(
+                        stream,
+                        parent_lock.node_descriptor.transport_security.as_ref(),
+                        false,
+                        parent_lock.node_descriptor.timeout,
+                    )
+                    .await
+                    .map_err(|e| {
+                        Error::illegal_state(format!(
+                            "Unable to secure the connection to the parent: {e}"
+                        ))
+                    })?,
+                );
             }
 
             Ok(())
@@ -291,6 +506,7 @@ where
 
         // Now, setup the background tasks for the children to connect to
         if let Some(listening_port) = node_descriptor.node_listening_port {
+            let node_descriptor = node_descriptor.clone();
             let bg_state = self_mutex.clone();
             let _handle: JoinHandle<Result<(), Error>> = rt.spawn(async move {
                 let addr = format!("0.0.0.0:{listening_port}");
@@ -301,28 +517,49 @@ where
                 let state = bg_state;
 
                 // The main listening loop. Should never fail.
-                'listening: loop {
+                loop {
                     log::debug!("listening for children on {listener:?}...");
                     match listener.accept().await {
-                        Ok((mut stream, addr)) => {
-                            log::debug!("{addr} joined the children.");
-                            let mut state_guard = state.write().await;
-
-                            if let Err(e) = state_guard
-                                .send_old_events_to_stream::<I>(&mut stream)
+                        Ok((stream, addr)) => {
+                            log::debug!("{addr} is joining the children.");
+                            let state = state.clone();
+                            let security = node_descriptor.transport_security.clone();
+                            let timeout = node_descriptor.timeout;
+
+                            // Do not hold up other children while this one authenticates.
+                            tokio::spawn(async move {
+                                let mut child = match NodeStream::establish(
+                                    stream,
+                                    security.as_ref(),
+                                    true,
+                                    timeout,
+                                )
                                 .await
-                            {
-                                log::error!("Error while send old messages: {e:?}.");
-                                log::error!("The loop will resume");
-                                continue 'listening;
-                            }
-
-                            state_guard.children.insert(NodeId::new(), stream);
-                            log::debug!(
-                                "[pid {}]{addr} added the child. nb children: {}",
-                                process::id(),
-                                state_guard.children.len()
-                            );
+                                {
+                                    Ok(child) => child,
+                                    Err(e) => {
+                                        log::error!("Rejected child {addr}: {e}");
+                                        return;
+                                    }
+                                };
+
+                                let mut state_guard = state.write().await;
+
+                                if let Err(e) =
+                                    state_guard.send_old_events_to_stream::<I>(&mut child).await
+                                {
+                                    log::error!("Error while send old messages: {e:?}.");
+                                    log::error!("The loop will resume");
+                                    return;
+                                }
+
+                                state_guard.children.insert(NodeId::new(), child);
+                                log::debug!(
+                                    "[pid {}]{addr} added the child. nb children: {}",
+                                    process::id(),
+                                    state_guard.children.len()
+                                );
+                            });
                         }
                         Err(e) => {
                             log::error!("Error while accepting child {e:?}.");
@@ -332,6 +569,118 @@ where
             });
         }
 
+        // Join the campaign, and keep (re)attaching to a parent in the background
+        if let Some(discovery) = &node_descriptor.discovery {
+            rt.block_on(async {
+                let mut state = self_mutex.write().await;
+                if node_descriptor.advertised_addr().is_some() {
+                    state.peer_entry = Some(PeerDirectory::new_entry());
+                }
+            });
+            rt.block_on(Self::maintain::<I>(self_mutex))?;
+
+            Self::spawn_maintenance::<I>(self_mutex, rt, discovery.heartbeat);
+        } else if node_descriptor.parent_addr.is_some() {
+            Self::spawn_maintenance::<I>(self_mutex, rt, PARENT_RECONNECT_INTERVAL);
+        }
+
+        Ok(())
+    }
+
+    /// Periodically run [`Self::maintain`] in the background.
+    fn spawn_maintenance<I: Input>(
+        self_mutex: &Arc<RwLock<Self>>,
+        rt: &Arc<Runtime>,
+        interval: Duration,
+    ) {
+        let state = self_mutex.clone();
+        let _handle: JoinHandle<()> = rt.spawn(async move {
+            let mut interval = time::interval(interval);
+            loop {
+                interval.tick().await;
+                if let Err(e) = Self::maintain::<I>(&state).await {
+                    log::error!("Error while looking for a parent: {e:?}");
+                }
+            }
+        });
+    }
+
+    /// Refresh the [`PeerDirectory`] entry of this node, and attach to a new parent
+    /// if the old one went away.
+    async fn maintain<I: Input>(self_mutex: &Arc<RwLock<Self>>) -> Result<(), Error> {
+        let (node_descriptor, peer_entry, has_parent) = {
+            let state = self_mutex.read().await;
+            (
+                state.node_descriptor.clone(),
+                state.peer_entry.clone(),
+                state.parent.is_some(),
+            )
+        };
+
+        if let Some(discovery) = &node_descriptor.discovery
+            && let Some(peer_entry) = &peer_entry
+            && let Some(addr) = node_descriptor.advertised_addr()
+        {
+            discovery.register(peer_entry, &addr)?;
+        }
+
+        if has_parent {
+            return Ok(());
+        }
+
+        let candidates = if let Some(discovery) = &node_descriptor.discovery {
+            discovery.parent_candidates(peer_entry.as_deref())?
+        } else if let Some(parent_addr) = &node_descriptor.parent_addr {
+            vec![parent_addr.to_string()]
+        } else {
+            return Ok(());
+        };
+
+        for candidate in candidates {
+            log::debug!("Trying to attach to {candidate}..");
+            let stream = match time::timeout(
+                node_descriptor.timeout,
+                TcpStream::connect(&candidate),
+            )
+            .await
+            {
+                Ok(Ok(stream)) => stream,
+                Ok(Err(e)) => {
+                    log::info!("Could not reach {candidate}: {e}");
+                    continue;
+                }
+                Err(_) => {
+                    log::info!("Could not reach {candidate}: timed out");
+                    continue;
+                }
+            };
+            let mut parent = match NodeStream::establish(
+                stream,
+                node_descriptor.transport_security.as_ref(),
+                false,
+                node_descriptor.timeout,
+            )
+            .await
+            {
+                Ok(parent) => parent,
+                Err(e) => {
+                    log::error!("Could not attach to {candidate}: {e}");
+                    continue;
+                }
+            };
+
+            // Catch the new parent up on all messages this node has seen, including the ones that
+            // were still in flight to the old parent. Fuzzers re-evaluate duplicates and drop them.
+            let mut state = self_mutex.write().await;
+            if let Err(e) = state.send_old_events_to_stream::<I>(&mut parent).await {
+                log::error!("Could not catch up {candidate}: {e:?}");
+                continue;
+            }
+            state.parent = Some(parent);
+            log::info!("Attached to the parent @ {candidate}");
+            return Ok(());
+        }
+
         Ok(())
     }
 
@@ -349,10 +698,12 @@ where
     /// Read a [`TcpMultiMachineMsg`] from a stream.
     /// Expects a message written by [`TcpMultiMachineState::write_msg`].
     /// If there is nothing to read from the stream, return asap with Ok(None).
-    #[expect(clippy::uninit_vec)]
+    /// Malformed or unauthenticated messages are returned as errors, after which the stream is unusable.
     async fn read_msg<'a, I: Input + 'a>(
-        stream: &mut TcpStream,
+        node: &mut NodeStream,
     ) -> Result<Option<MultiMachineMsg<'a, I>>, Error> {
+        let stream = &mut node.stream;
+
         // 0. Check if we should try to fetch something from the stream
         let mut dummy_byte: [u8; 1] = [0u8];
         log::debug!("Starting read msg...");
@@ -368,32 +719,51 @@ where
         log::debug!("msg read.");
 
         if n_read == 0 {
-            log::debug!("No dummy byte received...");
-            return Ok(None); // Nothing to read from this stream
+            // The other node closed the connection
+            return Err(Error::os_error(
+                io::Error::from(ErrorKind::UnexpectedEof),
+                "The node disconnected",
+            ));
         }
 
         log::debug!("Received dummy byte!");
 
         // we should always read the dummy byte at this point.
-        assert_eq!(u8::from_le_bytes(dummy_byte), DUMMY_BYTE);
-
-        // 1. Read msg size
-        let mut node_msg_len: [u8; 4] = [0; 4];
-        log::debug!("Receiving msg len...");
-        stream.read_exact(&mut node_msg_len).await?;
-        log::debug!("msg len received.");
-        let node_msg_len = u32::from_le_bytes(node_msg_len) as usize;
-
-        // 2. Read msg
-        // do not store msg on the stack to avoid overflow issues
-        // TODO: optimize with less allocations...
-        let mut node_msg: Vec<u8> = Vec::with_capacity(node_msg_len);
-        unsafe {
-            node_msg.set_len(node_msg_len);
+        if dummy_byte[0] != DUMMY_BYTE {
+            return Err(Error::invalid_input(format!(
+                "Expected a message to start with {DUMMY_BYTE:#x}, got {:#x}",
+                dummy_byte[0]
+            )));
         }
-        log::debug!("Receiving msg...");
-        stream.read_exact(node_msg.as_mut_slice()).await?;
-        log::debug!("msg received.");
+
+        let node_msg = if let Some(session) = &mut node.session {
+            // 1. & 2. Read, check, and decrypt the frame
+            log::debug!("Receiving frame...");
+            let node_msg = session.opener().read_frame(stream).await?;
+            log::debug!("frame received.");
+            node_msg
+        } else {
+            // 1. Read msg size
+            let mut node_msg_len: [u8; 4] = [0; 4];
+            log::debug!("Receiving msg len...");
+            stream.read_exact(&mut node_msg_len).await?;
+            log::debug!("msg len received.");
+            let node_msg_len = u32::from_le_bytes(node_msg_len) as usize;
+            if node_msg_len > DEFAULT_MAX_FRAME_LEN {
+                return Err(Error::invalid_input(format!(
+                    "Received a message with an invalid length of {node_msg_len} bytes"
+                )));
+            }
+
+            // 2. Read msg
+            // do not store msg on the stack to avoid overflow issues
+            // TODO: optimize with less allocations...
+            let mut node_msg: Vec<u8> = vec![0; node_msg_len];
+            log::debug!("Receiving msg...");
+            stream.read_exact(node_msg.as_mut_slice()).await?;
+            log::debug!("msg received.");
+            node_msg
+        };
         let node_msg = node_msg.into_boxed_slice();
 
         Ok(Some(MultiMachineMsg::from_llmp_msg(node_msg)))
@@ -402,17 +772,28 @@ where
     /// Write an [`OwnedTcpMultiMachineMsg`] to a stream.
     /// Can be read back using [`TcpMultiMachineState::read_msg`].
     async fn write_msg<I: Input>(
-        stream: &mut TcpStream,
+        node: &mut NodeStream,
         msg: &MultiMachineMsg<'_, I>,
     ) -> Result<(), Error> {
+        let stream = &mut node.stream;
         let serialized_msg = msg.serialize_as_ref();
-        let msg_len = u32::to_le_bytes(serialized_msg.len() as u32);
 
         // 0. Write the dummy byte
         log::debug!("Sending dummy byte...");
         stream.write_all(&[DUMMY_BYTE]).await?;
         log::debug!("dummy byte sent.");
 
+        if let Some(session) = &mut node.session {
+            // 1. & 2. Write the authenticated frame
+            log::debug!("Sending frame...");
+            let frame = session.sealer().seal(serialized_msg)?;
+            stream.write_all(&frame).await?;
+            log::debug!("frame sent.");
+            return Ok(());
+        }
+
+        let msg_len = u32::to_le_bytes(serialized_msg.len() as u32);
+
         // 1. Write msg size
         log::debug!("Sending msg len...");
         stream.write_all(&msg_len).await?;
@@ -426,9 +807,9 @@ where
         Ok(())
     }
 
-    pub(crate) async fn send_old_events_to_stream<I: Input>(
+    async fn send_old_events_to_stream<I: Input>(
         &mut self,
-        stream: &mut TcpStream,
+        stream: &mut NodeStream,
     ) -> Result<(), Error> {
         log::debug!("Send old events to new child...");
 
@@ -539,8 +920,13 @@ where
                     }
 
                     Err(e) => {
-                        log::debug!("An error occurred and was not expected.");
-                        return Err(e);
+                        // The stream is out of sync or was tampered with, it can't be trusted anymore
+                        log::error!(
+                            "The parent sent an invalid message. We won't try to communicate with it again."
+                        );
+                        log::error!("Error: {e:?}");
+                        self.parent.take();
+                        break;
                     }
                 }
             }
@@ -586,9 +972,13 @@ where
                     }
 
                     Err(e) => {
-                        // Other error
-                        log::debug!("An error occurred and was not expected.");
-                        return Err(e);
+                        // The stream is out of sync or was tampered with, it can't be trusted anymore
+                        log::error!(
+                            "The child sent an invalid message. We won't try to communicate with it again."
+                        );
+                        log::error!("Error: {e:?}");
+                        ids_to_remove.push(*child_id);
+                        break;
                     }
                 }
             }
@@ -603,3 +993,66 @@ where
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec::Vec};
+    use core::time::Duration;
+    use std::{env, fs, fs::File, time::SystemTime};
+
+    use super::PeerDirectory;
+
+    #[test]
+    fn test_peer_directory() {
+        let path = env::temp_dir().join(format!("libafl_peer_directory_{}", std::process::id()));
+        drop(fs::remove_dir_all(&path));
+        let discovery = PeerDirectory::builder().path(&path).fanout(2).build();
+        assert!(discovery.parent_candidates(None).unwrap().is_empty());
+
+        let entries: Vec<String> = (0..6).map(|i| format!("{i:020}-node.peer")).collect();
+        for (i, entry) in entries.iter().enumerate() {
+            discovery
+                .register(entry, &format!("10.0.0.{i}:50000"))
+                .unwrap();
+        }
+        // The root has no parent, and the others fill a binary tree
+        assert!(
+            discovery
+                .parent_candidates(Some(&entries[0]))
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(
+            discovery.parent_candidates(Some(&entries[5])).unwrap(),
+            [
+                "10.0.0.2:50000",
+                "10.0.0.0:50000",
+                "10.0.0.1:50000",
+                "10.0.0.3:50000",
+                "10.0.0.4:50000"
+            ]
+        );
+        // Nodes that did not register may attach anywhere
+        assert_eq!(discovery.parent_candidates(None).unwrap().len(), 6);
+
+        // Nodes that stopped refreshing their entry are gone
+        File::options()
+            .write(true)
+            .open(path.join(&entries[2]))
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(60))
+            .unwrap();
+        assert_eq!(
+            discovery.parent_candidates(Some(&entries[5])).unwrap(),
+            [
+                "10.0.0.1:50000",
+                "10.0.0.0:50000",
+                "10.0.0.3:50000",
+                "10.0.0.4:50000"
+            ]
+        );
+        assert!(!path.join(&entries[2]).exists());
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+}
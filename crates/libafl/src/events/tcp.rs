@@ -32,9 +32,10 @@ use libafl_bolts::{
 };
 use serde::{Serialize, de::DeserializeOwned};
 use tokio::{
-    io::{AsyncReadExt, AsyncWriteExt},
+    io::{AsyncRead, AsyncReadExt, AsyncWriteExt},
     sync::{broadcast, broadcast::error::RecvError, mpsc},
     task::{JoinHandle, spawn},
+    time,
 };
 use typed_builder::TypedBuilder;
 
@@ -47,6 +48,9 @@ use crate::{
         BrokerEventResult, Event, EventConfig, EventFirer, EventManagerHooksTuple, EventManagerId,
         EventReceiver, EventRestarter, EventWithStats, HasEventManagerId, ProgressReporter,
         std_on_restart,
+        transport_security::{
+            DEFAULT_MAX_FRAME_LEN, FrameOpener, FrameSealer, SecureSession, TransportSecurity,
+        },
     },
     inputs::Input,
     monitors::{Monitor, stats::ClientStatsManager},
@@ -56,6 +60,9 @@ use crate::{
     },
 };
 
+/// How long a connecting client may take to authenticate and to send its [`ClientId`]
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(30);
+
 /// Tries to create (synchronously) a [`TcpListener`] that is `nonblocking` (for later use in tokio).
 /// Will error if the port is already in use (or other errors occur)
 fn create_nonblocking_listener<A: ToSocketAddrs>(addr: A) -> Result<TcpListener, Error> {
@@ -64,6 +71,97 @@ fn create_nonblocking_listener<A: ToSocketAddrs>(addr: A) -> Result<TcpListener,
     Ok(listener)
 }
 
+/// Protocol: read the [`ClientId`] a client sends when connecting, or the broker's answer to it.
+async fn read_client_id<R>(
+    read: &mut R,
+    opener: Option<&mut FrameOpener>,
+) -> Result<ClientId, Error>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut client_id = [0; 4];
+    if let Some(opener) = opener {
+        client_id = opener
+            .read_frame(read)
+            .await?
+            .as_slice()
+            .try_into()
+            .map_err(|_| Error::invalid_input("Received an invalid client id"))?;
+    } else {
+        read.read_exact(&mut client_id).await?;
+    }
+    Ok(ClientId(u32::from_le_bytes(client_id)))
+}
+
+/// Protocol: encode a [`ClientId`] to be sent when connecting.
+fn encode_client_id(
+    sealer: Option<&mut FrameSealer>,
+    client_id_bytes: [u8; 4],
+) -> Result<Vec<u8>, Error> {
+    match sealer {
+        Some(sealer) => sealer.seal(&client_id_bytes),
+        None => Ok(client_id_bytes.to_vec()),
+    }
+}
+
+/// Read a message, the [`ClientId`] of the sender followed by the event, from the other end.
+async fn read_msg<R>(read: &mut R, opener: Option<&mut FrameOpener>) -> Result<Vec<u8>, Error>
+where
+    R: AsyncRead + Unpin,
+{
+    let buf = if let Some(opener) = opener {
+        opener.read_frame(read).await?
+    } else {
+        let mut len_buf = [0; 4];
+        read.read_exact(&mut len_buf).await?;
+
+        // we forward the sender id as well, so we add 4 bytes to the message length
+        let len = u32::from_le_bytes(len_buf) as usize + 4;
+        log::debug!("TCP Manager - len +4 = {len:?}");
+        if len > DEFAULT_MAX_FRAME_LEN {
+            return Err(Error::invalid_input(format!(
+                "Received a message with an invalid length of {len} bytes"
+            )));
+        }
+
+        let mut buf = vec![0; len];
+        read.read_exact(&mut buf).await?;
+        buf
+    };
+
+    if buf.len() < 4 {
+        return Err(Error::invalid_input(
+            "Received a message without a client id",
+        ));
+    }
+    Ok(buf)
+}
+
+/// Encode a message, the [`ClientId`] of the sender followed by the event, for the other end.
+fn encode_msg(sealer: Option<&mut FrameSealer>, msg: &[u8]) -> Result<Vec<u8>, Error> {
+    if let Some(sealer) = sealer {
+        return sealer.seal(msg);
+    }
+
+    // subtract 4 since the client_id isn't part of the actual message.
+    let len = u32::try_from(msg.len() - 4)?;
+    let mut buf = Vec::with_capacity(4 + msg.len());
+    buf.extend_from_slice(&len.to_le_bytes());
+    buf.extend_from_slice(msg);
+    Ok(buf)
+}
+
+/// Read the body of a [`SecureSession`] frame from a blocking stream, after its length header.
+fn read_frame_blocking<R: Read>(
+    opener: &mut FrameOpener,
+    read: &mut R,
+    header: [u8; 4],
+) -> Result<Vec<u8>, Error> {
+    let mut body = vec![0; opener.body_len(header)?];
+    read.read_exact(&mut body)?;
+    opener.open(&body)
+}
+
 /// An TCP-backed event manager for simple multi-processed fuzzing
 #[derive(Debug)]
 pub struct TcpEventBroker<I, MT>
@@ -77,6 +175,8 @@ where
     listener: Option<TcpListener>,
     /// Amount of all clients ever, after which (when all are disconnected) this broker should quit.
     exit_cleanly_after: Option<NonZeroUsize>,
+    /// Clients have to authenticate with this, if set.
+    security: Option<TransportSecurity>,
     client_stats_manager: ClientStatsManager,
     phantom: PhantomData<I>,
 }
@@ -104,6 +204,7 @@ where
             client_stats_manager: ClientStatsManager::default(),
             phantom: PhantomData,
             exit_cleanly_after: None,
+            security: None,
         }
     }
 
@@ -112,6 +213,13 @@ where
         self.exit_cleanly_after = Some(n_clients);
     }
 
+    /// Only accept clients that authenticate with the given [`TransportSecurity`].
+    ///
+    /// Otherwise, anybody who can reach the broker can inject events.
+    pub fn set_transport_security(&mut self, security: TransportSecurity) {
+        self.security = Some(security);
+    }
+
     /// Run in the broker until all clients exit
     // TODO: remove expect(clippy::needless_return) when clippy is fixed
     #[tokio::main(flavor = "current_thread")]
@@ -121,6 +229,7 @@ where
         let (tx, mut rx_mpsc) = mpsc::channel(65536);
 
         let exit_cleanly_after = self.exit_cleanly_after;
+        let security = self.security.clone();
 
         let listener = self
             .listener
@@ -132,7 +241,53 @@ where
             let mut recv_handles: Vec<JoinHandle<_>> = vec![];
             let mut receivers: Vec<Arc<tokio::sync::Mutex<broadcast::Receiver<_>>>> = vec![];
 
-            loop {
+            // Accept the clients in the background. Each client authenticates and sends its id in its own task,
+            // and the finished sessions are handed back here, so a slow client does not hold up the others.
+            let (tx_session, mut rx_session) = mpsc::channel(64);
+            spawn(async move {
+                loop {
+                    // Asynchronously wait for an inbound socket.
+                    let (mut socket, addr) = match listener.accept().await {
+                        Ok(client) => client,
+                        Err(e) => {
+                            log::error!("Accept failed: {e}");
+                            continue;
+                        }
+                    };
+
+                    let security = security.clone();
+                    let tx_session = tx_session.clone();
+                    spawn(async move {
+                        let handshake = async {
+                            // Protocol: the new client authenticates, if required
+                            let session = match &security {
+                                Some(security) => Some(security.accept(&mut socket).await?),
+                                None => None,
+                            };
+                            let (sealer, mut opener) = session.map(SecureSession::split).unzip();
+                            let (mut read, write) = tokio::io::split(socket);
+
+                            // Protocol: the new client communicate its old ClientId or -1 if new
+                            let this_client_id = read_client_id(&mut read, opener.as_mut()).await?;
+                            Ok::<_, Error>((read, write, sealer, opener, this_client_id))
+                        };
+                        match time::timeout(HANDSHAKE_TIMEOUT, handshake).await {
+                            Ok(Ok(client)) => {
+                                // Fails only once the broker is gone
+                                let _ = tx_session.send(client).await;
+                            }
+                            Ok(Err(e)) => log::error!("Rejected the client {addr}: {e}"),
+                            Err(_) => {
+                                log::error!("Rejected the client {addr}: the handshake timed out");
+                            }
+                        }
+                    });
+                }
+            });
+
+            while let Some((mut read, mut write, mut sealer, mut opener, this_client_id)) =
+                rx_session.recv().await
+            {
                 let mut reached_max = false;
                 if let Some(max_clients) = exit_cleanly_after {
                     if max_clients.get() <= recv_handles.len() {
@@ -141,17 +296,6 @@ where
                     }
                 }
 
-                // Asynchronously wait for an inbound socket.
-                let (socket, _) = listener.accept().await.expect("Accept failed");
-                let (mut read, mut write) = tokio::io::split(socket);
-
-                // Protocol: the new client communicate its old ClientId or -1 if new
-                let mut this_client_id = [0; 4];
-                read.read_exact(&mut this_client_id)
-                    .await
-                    .expect("Socket closed?");
-                let this_client_id = ClientId(u32::from_le_bytes(this_client_id));
-
                 let (this_client_id, is_old) = if this_client_id == UNDEFINED_CLIENT_ID {
                     if reached_max {
                         (UNDEFINED_CLIENT_ID, false) // Dumb id
@@ -163,10 +307,23 @@ where
                     (this_client_id, true)
                 };
 
+                let client_idx = this_client_id.0 as usize;
+                if is_old && client_idx >= recv_handles.len() {
+                    log::error!("Rejected a client with the unknown id {this_client_id:?}");
+                    continue;
+                }
+
                 let this_client_id_bytes = this_client_id.0.to_le_bytes();
 
                 // Protocol: Send the client id for this node;
-                write.write_all(&this_client_id_bytes).await.unwrap();
+                let sent = match encode_client_id(sealer.as_mut(), this_client_id_bytes) {
+                    Ok(buf) => write.write_all(&buf).await.map_err(Error::from),
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = sent {
+                    log::error!("Could not send the client id to {this_client_id:?}: {e}");
+                    continue;
+                }
 
                 if !is_old && reached_max {
                     continue;
@@ -177,40 +334,34 @@ where
                 let handle = async move {
                     // In a loop, read data from the socket and write the data back.
                     loop {
-                        let mut len_buf = [0; 4];
-
-                        if read.read_exact(&mut len_buf).await.is_err() {
-                            // The socket is closed, the client is restarting
-                            log::info!("Socket closed, client restarting");
-                            return;
-                        }
-
-                        let mut len = u32::from_le_bytes(len_buf);
-                        // we forward the sender id as well, so we add 4 bytes to the message length
-                        len += 4;
-
-                        log::debug!("TCP Manager - len +4 = {len:?}");
-
-                        let mut buf = vec![0; len as usize];
+                        let buf = match read_msg(&mut read, opener.as_mut()).await {
+                            Ok(buf) => buf,
+                            Err(Error::OsError(..)) => {
+                                // The socket is closed, the client is restarting
+                                log::info!("Socket closed, client restarting");
+                                return;
+                            }
+                            Err(e) => {
+                                // The stream can't be trusted anymore, drop the client
+                                log::error!(
+                                    "Client {this_client_id:?} sent an invalid message, disconnecting: {e}"
+                                );
+                                return;
+                            }
+                        };
 
-                        if read
-                            .read_exact(&mut buf)
-                            .await
-                            // .expect("Failed to read data from socket"); // TODO verify if we have to handle this error
-                            .is_err()
-                        {
-                            // The socket is closed, the client is restarting
-                            log::info!("Socket closed, client restarting");
-                            return;
+                        if buf[..4] != this_client_id_bytes {
+                            log::warn!(
+                                "Dropping a message from {this_client_id:?} claiming to come from another client"
+                            );
+                            continue;
                         }
 
-                        log::debug!("TCP Manager - len: {len:?} - {buf:?}");
+                        log::debug!("TCP Manager - len: {:?} - {buf:?}", buf.len());
                         tx_inner.send(buf).await.expect("Could not send");
                     }
                 };
 
-                let client_idx = this_client_id.0 as usize;
-
                 // Keep all handles around.
                 if is_old {
                     recv_handles[client_idx].abort();
@@ -251,17 +402,17 @@ where
                             continue;
                         }
 
-                        // subtract 4 since the client_id isn't part of the actual message.
-                        let len = u32::try_from(buf.len() - 4).unwrap();
-                        let len_buf: [u8; 4] = len.to_le_bytes();
+                        let buf = match encode_msg(sealer.as_mut(), &buf) {
+                            Ok(buf) => buf,
+                            Err(e) => {
+                                log::error!(
+                                    "Could not forward a message to {this_client_id:?}: {e}"
+                                );
+                                continue;
+                            }
+                        };
 
-                        // Write message length
-                        if write.write_all(&len_buf).await.is_err() {
-                            // The socket is closed, the client is restarting
-                            log::info!("Socket closed, client restarting");
-                            return;
-                        }
-                        // Write the rest
+                        // Write the message
                         if write.write_all(&buf).await.is_err() {
                             // The socket is closed, the client is restarting
                             log::info!("Socket closed, client restarting");
@@ -290,20 +441,20 @@ where
             // cut off the ID.
             let event_bytes = &buf[4..];
 
-            #[cfg(feature = "tcp_compression")]
-            let event_bytes = &GzipCompressor::new().decompress(event_bytes)?;
-
-            let event: EventWithStats<I> = postcard::from_bytes(event_bytes)?;
-            match Self::handle_in_broker(
-                &mut self.monitor,
-                &mut self.client_stats_manager,
-                client_id,
-                &event,
-            )? {
-                BrokerEventResult::Forward => {
-                    tx_bc.send(buf).expect("Could not send");
-                }
-                BrokerEventResult::Handled => (),
+            // Malformed events from a client must not take the broker down.
+            match Self::decode_event(event_bytes) {
+                Ok(event) => match Self::handle_in_broker(
+                    &mut self.monitor,
+                    &mut self.client_stats_manager,
+                    client_id,
+                    &event,
+                )? {
+                    BrokerEventResult::Forward => {
+                        tx_bc.send(buf).expect("Could not send");
+                    }
+                    BrokerEventResult::Handled => (),
+                },
+                Err(e) => log::error!("Dropping an invalid event from {client_id:?}: {e}"),
             }
 
             if tokio_broker.is_finished() {
@@ -316,6 +467,14 @@ where
         Err(Error::shutting_down())
     }
 
+    /// Deserialize an event sent by a client
+    fn decode_event(event_bytes: &[u8]) -> Result<EventWithStats<I>, Error> {
+        #[cfg(feature = "tcp_compression")]
+        let event_bytes = &GzipCompressor::new().decompress(event_bytes)?;
+
+        Ok(postcard::from_bytes(event_bytes)?)
+    }
+
     /// Handle arriving events in the broker
     fn handle_in_broker(
         monitor: &mut MT,
@@ -416,6 +575,8 @@ pub struct TcpEventManager<EMH, I, S> {
     hooks: EMH,
     /// The TCP stream for inter process communication
     tcp: TcpStream,
+    /// The session, if the connection to the broker is secured
+    session: Option<SecureSession>,
     /// Our `CientId`
     client_id: ClientId,
     #[cfg(feature = "tcp_compression")]
@@ -436,10 +597,11 @@ impl<I, S> TcpEventManager<(), I, S> {
 }
 
 /// Builder for `TcpEventManager`
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Clone)]
 pub struct TcpEventManagerBuilder<EMH, I, S> {
     throttle: Option<Duration>,
     hooks: EMH,
+    transport_security: Option<TransportSecurity>,
     phantom: PhantomData<(I, S)>,
 }
 
@@ -456,6 +618,7 @@ impl<I, S> TcpEventManagerBuilder<(), I, S> {
         Self {
             throttle: None,
             hooks: (),
+            transport_security: None,
             phantom: PhantomData,
         }
     }
@@ -466,6 +629,7 @@ impl<I, S> TcpEventManagerBuilder<(), I, S> {
         TcpEventManagerBuilder {
             throttle: self.throttle,
             hooks,
+            transport_security: self.transport_security,
             phantom: PhantomData,
         }
     }
@@ -479,6 +643,15 @@ impl<EMH, I, S> TcpEventManagerBuilder<EMH, I, S> {
         self
     }
 
+    /// Authenticate to the broker with the given [`TransportSecurity`].
+    ///
+    /// The broker needs the same setting, see [`TcpEventBroker::set_transport_security`].
+    #[must_use]
+    pub fn transport_security(mut self, transport_security: TransportSecurity) -> Self {
+        self.transport_security = Some(transport_security);
+        self
+    }
+
     /// Create a manager from a raw TCP client with hooks
     pub fn build_from_client<A: ToSocketAddrs>(
         self,
@@ -489,11 +662,25 @@ impl<EMH, I, S> TcpEventManagerBuilder<EMH, I, S> {
         let mut tcp = TcpStream::connect(addr)?;
 
         let mut our_client_id_buf = client_id.0.to_le_bytes();
-        tcp.write_all(&our_client_id_buf)
-            .expect("Cannot write to the broker");
+        let session = if let Some(security) = &self.transport_security {
+            let mut session = security.connect_blocking(&mut tcp)?;
+            tcp.write_all(&session.sealer().seal(&our_client_id_buf)?)?;
+
+            let mut header = [0; 4];
+            tcp.read_exact(&mut header)?;
+            our_client_id_buf = read_frame_blocking(session.opener(), &mut tcp, header)?
+                .as_slice()
+                .try_into()
+                .map_err(|_| Error::invalid_input("The broker sent an invalid client id"))?;
+            Some(session)
+        } else {
+            tcp.write_all(&our_client_id_buf)
+                .expect("Cannot write to the broker");
 
-        tcp.read_exact(&mut our_client_id_buf)
-            .expect("Cannot read from the broker");
+            tcp.read_exact(&mut our_client_id_buf)
+                .expect("Cannot read from the broker");
+            None
+        };
         let client_id = ClientId(u32::from_le_bytes(our_client_id_buf));
 
         log::info!("Our client id: {client_id:?}");
@@ -503,6 +690,7 @@ impl<EMH, I, S> TcpEventManagerBuilder<EMH, I, S> {
             last_sent: Duration::from_secs(0),
             hooks: self.hooks,
             tcp,
+            session,
             client_id,
             #[cfg(feature = "tcp_compression")]
             compressor: GzipCompressor::new(),
@@ -542,7 +730,9 @@ impl<EMH, I, S> TcpEventManagerBuilder<EMH, I, S> {
 impl<EMH, I, S> core::fmt::Debug for TcpEventManager<EMH, I, S> {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         let mut debug_struct = f.debug_struct("TcpEventManager");
-        let debug = debug_struct.field("tcp", &self.tcp);
+        let debug = debug_struct
+            .field("tcp", &self.tcp)
+            .field("session", &self.session);
         //.field("custom_buf_handlers", &self.custom_buf_handlers)
         #[cfg(feature = "tcp_compression")]
         let debug = debug.field("compressor", &self.compressor);
@@ -606,10 +796,17 @@ where
         #[cfg(feature = "tcp_compression")]
         let serialized = self.compressor.compress(&serialized);
 
-        let size = u32::try_from(serialized.len())?;
-        self.tcp.write_all(&size.to_le_bytes())?;
-        self.tcp.write_all(&self.client_id.0.to_le_bytes())?;
-        self.tcp.write_all(&serialized)?;
+        if let Some(session) = &mut self.session {
+            let mut msg = Vec::with_capacity(4 + serialized.len());
+            msg.extend_from_slice(&self.client_id.0.to_le_bytes());
+            msg.extend_from_slice(&serialized);
+            self.tcp.write_all(&session.sealer().seal(&msg)?)?;
+        } else {
+            let size = u32::try_from(serialized.len())?;
+            self.tcp.write_all(&size.to_le_bytes())?;
+            self.tcp.write_all(&self.client_id.0.to_le_bytes())?;
+            self.tcp.write_all(&serialized)?;
+        }
 
         self.last_sent = libafl_bolts::current_time();
         Ok(())
@@ -650,9 +847,20 @@ where
             match self.tcp.read_exact(&mut len_buf) {
                 Ok(()) => {
                     self.tcp.set_nonblocking(false).expect("set to blocking");
-                    let len = u32::from_le_bytes(len_buf);
-                    let mut buf = vec![0_u8; 4_usize + len as usize];
-                    self.tcp.read_exact(&mut buf)?;
+                    let buf = if let Some(session) = &mut self.session {
+                        let buf = read_frame_blocking(session.opener(), &mut self.tcp, len_buf)?;
+                        if buf.len() < 4 {
+                            return Err(Error::invalid_input(
+                                "Received a message without a client id",
+                            ));
+                        }
+                        buf
+                    } else {
+                        let len = u32::from_le_bytes(len_buf);
+                        let mut buf = vec![0_u8; 4_usize + len as usize];
+                        self.tcp.read_exact(&mut buf)?;
+                        buf
+                    };
 
                     let mut client_id_buf = [0_u8; 4];
                     client_id_buf.copy_from_slice(&buf[..4]);
@@ -1032,6 +1240,9 @@ pub struct TcpRestartingMgr<EMH, I, MT, S, SP> {
     /// Tell the manager to serialize or not the state on restart
     #[builder(default = true)]
     serialize_state: bool,
+    /// Authenticate, and by default encrypt, the connections between the broker and its clients
+    #[builder(default = None)]
+    transport_security: Option<TransportSecurity>,
     /// The hooks for `handle_in_client`
     hooks: EMH,
     #[builder(setter(skip), default = PhantomData)]
@@ -1053,6 +1264,15 @@ where
         + Stoppable,
     SP: ShMemProvider,
 {
+    /// The builder for our client, connecting to the broker
+    fn client_builder(&self) -> TcpEventManagerBuilder<EMH, I, S> {
+        let builder = TcpEventManagerBuilder::new().hooks(self.hooks);
+        match &self.transport_security {
+            Some(transport_security) => builder.transport_security(transport_security.clone()),
+            None => builder,
+        }
+    }
+
     /// Launch the restarting manager
     pub fn launch(
         &mut self,
@@ -1070,6 +1290,9 @@ where
                 if let Some(exit_cleanly_after) = self.exit_cleanly_after {
                     broker.set_exit_cleanly_after(exit_cleanly_after);
                 }
+                if let Some(transport_security) = self.transport_security.clone() {
+                    broker.set_transport_security(transport_security);
+                }
 
                 broker.broker_loop()
             };
@@ -1096,13 +1319,11 @@ where
                         }
                         Err(Error::OsError(..)) => {
                             // port was likely already bound
-                            let mgr = TcpEventManagerBuilder::new()
-                                .hooks(self.hooks)
-                                .build_from_client(
-                                    &("127.0.0.1", self.broker_port),
-                                    UNDEFINED_CLIENT_ID,
-                                    self.configuration,
-                                )?;
+                            let mgr = self.client_builder().build_from_client(
+                                &("127.0.0.1", self.broker_port),
+                                UNDEFINED_CLIENT_ID,
+                                self.configuration,
+                            )?;
                             (mgr, None)
                         }
                         Err(e) => {
@@ -1123,9 +1344,11 @@ where
                 }
                 TcpManagerKind::Client { cpu_core } => {
                     // We are a client
-                    let mgr = TcpEventManagerBuilder::new()
-                        .hooks(self.hooks)
-                        .build_on_port(self.broker_port, UNDEFINED_CLIENT_ID, self.configuration)?;
+                    let mgr = self.client_builder().build_on_port(
+                        self.broker_port,
+                        UNDEFINED_CLIENT_ID,
+                        self.configuration,
+                    )?;
 
                     (mgr, cpu_core)
                 }
@@ -1259,9 +1482,11 @@ where
             (
                 state_opt,
                 TcpRestartingEventManager::with_save_state(
-                    TcpEventManagerBuilder::new()
-                        .hooks(self.hooks)
-                        .build_on_port(self.broker_port, this_id, self.configuration)?,
+                    self.client_builder().build_on_port(
+                        self.broker_port,
+                        this_id,
+                        self.configuration,
+                    )?,
                     staterestorer,
                     self.serialize_state,
                 ),
@@ -1269,13 +1494,11 @@ where
         } else {
             log::info!("First run. Let's set it all up");
             // Mgr to send and receive msgs from/to all other fuzzer instances
-            let mgr = TcpEventManagerBuilder::new()
-                .hooks(self.hooks)
-                .build_existing_from_env(
-                    &("127.0.0.1", self.broker_port),
-                    _ENV_FUZZER_BROKER_CLIENT_INITIAL,
-                    self.configuration,
-                )?;
+            let mgr = self.client_builder().build_existing_from_env(
+                &("127.0.0.1", self.broker_port),
+                _ENV_FUZZER_BROKER_CLIENT_INITIAL,
+                self.configuration,
+            )?;
 
             (
                 None,
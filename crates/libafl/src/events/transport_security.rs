@@ -0,0 +1,635 @@
+//! Pre-shared key authentication and encryption for the TCP-based event transports.
+//!
+//! Both ends of a connection prove knowledge of a [`PreSharedKey`] in a mutual challenge-response
+//! handshake before any event is exchanged. Each connection then derives fresh keys per direction,
+//! and every frame carries a tag over its payload and its position in the stream, so forged,
+//! modified, replayed, or reordered frames are rejected. Unless only authentication is requested,
+//! frames are also encrypted with `ChaCha20-Poly1305`.
+//!
+//! This is used by the [`crate::events::tcp`] event manager and by the multi-machine hooks.
+//! There is no certificate handling: every node has to be given the same key out of band.
+
+use alloc::vec::Vec;
+use core::fmt;
+use std::{env, io};
+
+use chacha20poly1305::{
+    ChaCha20Poly1305, Key, KeyInit, Nonce,
+    aead::{Aead, OsRng, rand_core::RngCore},
+};
+use hmac::{Hmac, Mac};
+use libafl_bolts::Error;
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+type HmacSha256 = Hmac<Sha256>;
+type HandshakeNonce = [u8; HANDSHAKE_NONCE_LEN];
+
+/// The default maximum size of a frame payload, larger frames are rejected before allocating.
+pub const DEFAULT_MAX_FRAME_LEN: usize = 256 * 1024 * 1024;
+
+const HANDSHAKE_MAGIC: [u8; 8] = *b"LIBAFLPK";
+const HANDSHAKE_NONCE_LEN: usize = 32;
+const MAC_LEN: usize = 32;
+const AEAD_TAG_LEN: usize = 16;
+
+/// Magic, protection, client nonce
+const HELLO_LEN: usize = HANDSHAKE_MAGIC.len() + 1 + HANDSHAKE_NONCE_LEN;
+/// Server nonce, server proof
+const REPLY_LEN: usize = HANDSHAKE_NONCE_LEN + MAC_LEN;
+/// Client proof
+const FINISH_LEN: usize = MAC_LEN;
+
+/// A 256 bit key shared by all peers of a fuzzing campaign.
+#[derive(Clone, PartialEq, Eq)]
+pub struct PreSharedKey([u8; 32]);
+
+impl PreSharedKey {
+    /// Use the given raw key.
+    #[must_use]
+    pub fn new(key: [u8; 32]) -> Self {
+        Self(key)
+    }
+
+    /// Derive the key from a passphrase.
+    ///
+    /// The passphrase is hashed without any key stretching, so it should be long and random.
+    #[must_use]
+    pub fn from_passphrase(passphrase: &[u8]) -> Self {
+        let mut hasher = Sha256::new();
+        hasher.update(b"libafl transport psk");
+        hasher.update(passphrase);
+        Self(hasher.finalize().into())
+    }
+
+    /// Derive the key from the passphrase stored in the given environment variable.
+    pub fn from_env(env_name: &str) -> Result<Self, Error> {
+        let passphrase = env::var(env_name)?;
+        if passphrase.is_empty() {
+            return Err(Error::illegal_argument(format!(
+                "The pre-shared key in {env_name} is empty"
+            )));
+        }
+        Ok(Self::from_passphrase(passphrase.as_bytes()))
+    }
+}
+
+impl fmt::Debug for PreSharedKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PreSharedKey(..)")
+    }
+}
+
+/// How frames are protected after the handshake.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum TransportProtection {
+    /// Frames are authenticated with `HMAC-SHA256`, but sent in the clear.
+    Authenticated,
+    /// Frames are encrypted and authenticated with `ChaCha20-Poly1305`.
+    #[default]
+    Encrypted,
+}
+
+impl TransportProtection {
+    fn id(self) -> u8 {
+        match self {
+            TransportProtection::Authenticated => 1,
+            TransportProtection::Encrypted => 2,
+        }
+    }
+
+    fn tag_len(self) -> usize {
+        match self {
+            TransportProtection::Authenticated => MAC_LEN,
+            TransportProtection::Encrypted => AEAD_TAG_LEN,
+        }
+    }
+}
+
+/// The transport security configuration of a peer.
+///
+/// Both ends of a connection need the same key and [`TransportProtection`].
+#[derive(Debug, Clone)]
+pub struct TransportSecurity {
+    key: PreSharedKey,
+    protection: TransportProtection,
+    max_frame_len: usize,
+}
+
+impl TransportSecurity {
+    /// Encrypt and authenticate all frames with the given key.
+    #[must_use]
+    pub fn new(key: PreSharedKey) -> Self {
+        Self {
+            key,
+            protection: TransportProtection::Encrypted,
+            max_frame_len: DEFAULT_MAX_FRAME_LEN,
+        }
+    }
+
+    /// Only authenticate frames, without encrypting them.
+    #[must_use]
+    pub fn authenticate_only(mut self) -> Self {
+        self.protection = TransportProtection::Authenticated;
+        self
+    }
+
+    /// Set the maximum payload size of a frame, defaults to [`DEFAULT_MAX_FRAME_LEN`].
+    #[must_use]
+    pub fn max_frame_len(mut self, max_frame_len: usize) -> Self {
+        self.max_frame_len = max_frame_len;
+        self
+    }
+
+    /// The [`TransportProtection`] of the frames
+    #[must_use]
+    pub fn protection(&self) -> TransportProtection {
+        self.protection
+    }
+
+    /// Run the handshake as the connecting peer.
+    pub async fn connect<S>(&self, stream: &mut S) -> Result<SecureSession, Error>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let (client_nonce, hello) = self.client_hello();
+        stream.write_all(&hello).await?;
+        let mut reply = [0; REPLY_LEN];
+        stream.read_exact(&mut reply).await?;
+        let (finish, session) = self.client_finish(&client_nonce, &reply)?;
+        stream.write_all(&finish).await?;
+        Ok(session)
+    }
+
+    /// Run the handshake as the connecting peer, on a blocking stream.
+    pub fn connect_blocking<S>(&self, stream: &mut S) -> Result<SecureSession, Error>
+    where
+        S: io::Read + io::Write,
+    {
+        let (client_nonce, hello) = self.client_hello();
+        stream.write_all(&hello)?;
+        let mut reply = [0; REPLY_LEN];
+        stream.read_exact(&mut reply)?;
+        let (finish, session) = self.client_finish(&client_nonce, &reply)?;
+        stream.write_all(&finish)?;
+        Ok(session)
+    }
+
+    /// Run the handshake as the listening peer.
+    ///
+    /// Fails if the other peer does not know the key.
+    pub async fn accept<S>(&self, stream: &mut S) -> Result<SecureSession, Error>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let mut hello = [0; HELLO_LEN];
+        stream.read_exact(&mut hello).await?;
+        let (client_nonce, server_nonce, reply) = self.server_reply(&hello)?;
+        stream.write_all(&reply).await?;
+        let mut finish = [0; FINISH_LEN];
+        stream.read_exact(&mut finish).await?;
+        self.server_finish(&client_nonce, &server_nonce, &finish)
+    }
+
+    fn client_hello(&self) -> (HandshakeNonce, [u8; HELLO_LEN]) {
+        let mut client_nonce = [0; HANDSHAKE_NONCE_LEN];
+        OsRng.fill_bytes(&mut client_nonce);
+
+        let mut hello = [0; HELLO_LEN];
+        hello[..HANDSHAKE_MAGIC.len()].copy_from_slice(&HANDSHAKE_MAGIC);
+        hello[HANDSHAKE_MAGIC.len()] = self.protection.id();
+        hello[HANDSHAKE_MAGIC.len() + 1..].copy_from_slice(&client_nonce);
+        (client_nonce, hello)
+    }
+
+    fn server_reply(
+        &self,
+        hello: &[u8; HELLO_LEN],
+    ) -> Result<(HandshakeNonce, HandshakeNonce, [u8; REPLY_LEN]), Error> {
+        if hello[..HANDSHAKE_MAGIC.len()] != HANDSHAKE_MAGIC {
+            return Err(Error::invalid_input(
+                "The peer did not start a transport security handshake",
+            ));
+        }
+        if hello[HANDSHAKE_MAGIC.len()] != self.protection.id() {
+            return Err(Error::invalid_input(
+                "The peer uses a different transport protection",
+            ));
+        }
+        let mut client_nonce = [0; HANDSHAKE_NONCE_LEN];
+        client_nonce.copy_from_slice(&hello[HANDSHAKE_MAGIC.len() + 1..]);
+        let mut server_nonce = [0; HANDSHAKE_NONCE_LEN];
+        OsRng.fill_bytes(&mut server_nonce);
+
+        let mut reply = [0; REPLY_LEN];
+        reply[..HANDSHAKE_NONCE_LEN].copy_from_slice(&server_nonce);
+        reply[HANDSHAKE_NONCE_LEN..].copy_from_slice(&self.derive(
+            b"server proof",
+            &client_nonce,
+            &server_nonce,
+        ));
+        Ok((client_nonce, server_nonce, reply))
+    }
+
+    fn client_finish(
+        &self,
+        client_nonce: &HandshakeNonce,
+        reply: &[u8; REPLY_LEN],
+    ) -> Result<([u8; FINISH_LEN], SecureSession), Error> {
+        let mut server_nonce = [0; HANDSHAKE_NONCE_LEN];
+        server_nonce.copy_from_slice(&reply[..HANDSHAKE_NONCE_LEN]);
+        self.verify(
+            b"server proof",
+            client_nonce,
+            &server_nonce,
+            &reply[HANDSHAKE_NONCE_LEN..],
+        )?;
+
+        let finish = self.derive(b"client proof", client_nonce, &server_nonce);
+        let session = SecureSession {
+            sealer: self.frame_sealer(b"client to server", client_nonce, &server_nonce),
+            opener: self.frame_opener(b"server to client", client_nonce, &server_nonce),
+        };
+        Ok((finish, session))
+    }
+
+    fn server_finish(
+        &self,
+        client_nonce: &HandshakeNonce,
+        server_nonce: &HandshakeNonce,
+        finish: &[u8; FINISH_LEN],
+    ) -> Result<SecureSession, Error> {
+        self.verify(b"client proof", client_nonce, server_nonce, finish)?;
+        Ok(SecureSession {
+            sealer: self.frame_sealer(b"server to client", client_nonce, server_nonce),
+            opener: self.frame_opener(b"client to server", client_nonce, server_nonce),
+        })
+    }
+
+    fn mac(
+        &self,
+        label: &[u8],
+        client_nonce: &HandshakeNonce,
+        server_nonce: &HandshakeNonce,
+    ) -> HmacSha256 {
+        let mut mac = <HmacSha256 as Mac>::new_from_slice(&self.key.0)
+            .expect("HMAC accepts keys of any length");
+        mac.update(label);
+        mac.update(&[self.protection.id()]);
+        mac.update(client_nonce);
+        mac.update(server_nonce);
+        mac
+    }
+
+    fn derive(
+        &self,
+        label: &[u8],
+        client_nonce: &HandshakeNonce,
+        server_nonce: &HandshakeNonce,
+    ) -> [u8; MAC_LEN] {
+        self.mac(label, client_nonce, server_nonce)
+            .finalize()
+            .into_bytes()
+            .into()
+    }
+
+    fn verify(
+        &self,
+        label: &[u8],
+        client_nonce: &HandshakeNonce,
+        server_nonce: &HandshakeNonce,
+        proof: &[u8],
+    ) -> Result<(), Error> {
+        self.mac(label, client_nonce, server_nonce)
+            .verify_slice(proof)
+            .map_err(|_| Error::invalid_input("The peer does not know the pre-shared key"))
+    }
+
+    fn frame_key(
+        &self,
+        direction: &[u8],
+        client_nonce: &HandshakeNonce,
+        server_nonce: &HandshakeNonce,
+    ) -> FrameKey {
+        let key = self.derive(direction, client_nonce, server_nonce);
+        match self.protection {
+            TransportProtection::Authenticated => FrameKey::Authenticated(key),
+            TransportProtection::Encrypted => {
+                FrameKey::Encrypted(ChaCha20Poly1305::new(Key::from_slice(&key)))
+            }
+        }
+    }
+
+    fn frame_sealer(
+        &self,
+        direction: &[u8],
+        client_nonce: &HandshakeNonce,
+        server_nonce: &HandshakeNonce,
+    ) -> FrameSealer {
+        FrameSealer {
+            key: self.frame_key(direction, client_nonce, server_nonce),
+            counter: 0,
+            max_frame_len: self.max_frame_len,
+        }
+    }
+
+    fn frame_opener(
+        &self,
+        direction: &[u8],
+        client_nonce: &HandshakeNonce,
+        server_nonce: &HandshakeNonce,
+    ) -> FrameOpener {
+        FrameOpener {
+            key: self.frame_key(direction, client_nonce, server_nonce),
+            counter: 0,
+            max_frame_len: self.max_frame_len,
+        }
+    }
+}
+
+/// The per-direction key of an established session
+enum FrameKey {
+    Authenticated([u8; MAC_LEN]),
+    Encrypted(ChaCha20Poly1305),
+}
+
+impl FrameKey {
+    fn protection(&self) -> TransportProtection {
+        match self {
+            FrameKey::Authenticated(_) => TransportProtection::Authenticated,
+            FrameKey::Encrypted(_) => TransportProtection::Encrypted,
+        }
+    }
+
+    fn frame_mac(key: &[u8; MAC_LEN], counter: u64, payload: &[u8]) -> HmacSha256 {
+        let mut mac =
+            <HmacSha256 as Mac>::new_from_slice(key).expect("HMAC accepts keys of any length");
+        mac.update(&counter.to_le_bytes());
+        mac.update(payload);
+        mac
+    }
+
+    fn nonce(counter: u64) -> Nonce {
+        let mut nonce = Nonce::default();
+        nonce[..8].copy_from_slice(&counter.to_le_bytes());
+        nonce
+    }
+}
+
+/// Returns the current frame counter and advances it.
+fn next_counter(counter: &mut u64) -> Result<u64, Error> {
+    let current = *counter;
+    *counter = current
+        .checked_add(1)
+        .ok_or_else(|| Error::illegal_state("Frame counter exhausted, reconnect"))?;
+    Ok(current)
+}
+
+/// Protects the outgoing frames of a [`SecureSession`].
+pub struct FrameSealer {
+    key: FrameKey,
+    counter: u64,
+    max_frame_len: usize,
+}
+
+impl FrameSealer {
+    /// Seal a payload into a frame, ready to be written to the stream.
+    ///
+    /// The frame starts with the length of its remainder as `u32` in little endian.
+    pub fn seal(&mut self, payload: &[u8]) -> Result<Vec<u8>, Error> {
+        if payload.len() > self.max_frame_len {
+            return Err(Error::illegal_argument(format!(
+                "Frame of {} bytes exceeds the maximum frame length of {} bytes",
+                payload.len(),
+                self.max_frame_len
+            )));
+        }
+        let counter = next_counter(&mut self.counter)?;
+        let body_len = payload.len() + self.key.protection().tag_len();
+
+        let mut frame = Vec::with_capacity(4 + body_len);
+        frame.extend_from_slice(&u32::try_from(body_len)?.to_le_bytes());
+        match &self.key {
+            FrameKey::Authenticated(key) => {
+                frame.extend_from_slice(payload);
+                frame.extend_from_slice(
+                    &FrameKey::frame_mac(key, counter, payload)
+                        .finalize()
+                        .into_bytes(),
+                );
+            }
+            FrameKey::Encrypted(cipher) => {
+                let ciphertext = cipher
+                    .encrypt(&FrameKey::nonce(counter), payload)
+                    .map_err(|_| Error::illegal_state("Frame encryption failed"))?;
+                frame.extend_from_slice(&ciphertext);
+            }
+        }
+        Ok(frame)
+    }
+}
+
+impl fmt::Debug for FrameSealer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FrameSealer")
+            .field("protection", &self.key.protection())
+            .field("counter", &self.counter)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Checks and unprotects the incoming frames of a [`SecureSession`].
+///
+/// Any error means the stream can no longer be trusted and should be closed.
+pub struct FrameOpener {
+    key: FrameKey,
+    counter: u64,
+    max_frame_len: usize,
+}
+
+impl FrameOpener {
+    /// The length of the frame body following the given length header.
+    ///
+    /// Fails for frames that are too short or too long, before anything is allocated for them.
+    pub fn body_len(&self, header: [u8; 4]) -> Result<usize, Error> {
+        let body_len = u32::from_le_bytes(header) as usize;
+        let tag_len = self.key.protection().tag_len();
+        if body_len < tag_len || body_len - tag_len > self.max_frame_len {
+            return Err(Error::invalid_input(format!(
+                "Received a frame with an invalid length of {body_len} bytes"
+            )));
+        }
+        Ok(body_len)
+    }
+
+    /// Check and unprotect the body of the next frame.
+    ///
+    /// Frames have to be opened in the order they were sealed in.
+    pub fn open(&mut self, body: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut counter = self.counter;
+        let current = next_counter(&mut counter)?;
+        let payload = match &self.key {
+            FrameKey::Authenticated(key) => {
+                let (payload, tag) = body
+                    .split_at_checked(body.len().wrapping_sub(MAC_LEN))
+                    .ok_or_else(|| Error::invalid_input("Received a truncated frame"))?;
+                FrameKey::frame_mac(key, current, payload)
+                    .verify_slice(tag)
+                    .map_err(|_| Error::invalid_input("Received an unauthenticated frame"))?;
+                payload.to_vec()
+            }
+            FrameKey::Encrypted(cipher) => cipher
+                .decrypt(&FrameKey::nonce(current), body)
+                .map_err(|_| Error::invalid_input("Received an unauthenticated frame"))?,
+        };
+        self.counter = counter;
+        Ok(payload)
+    }
+
+    /// Read the next frame from the stream, and return its payload.
+    pub async fn read_frame<R>(&mut self, reader: &mut R) -> Result<Vec<u8>, Error>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let mut header = [0; 4];
+        reader.read_exact(&mut header).await?;
+        let mut body = vec![0; self.body_len(header)?];
+        reader.read_exact(&mut body).await?;
+        self.open(&body)
+    }
+}
+
+impl fmt::Debug for FrameOpener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FrameOpener")
+            .field("protection", &self.key.protection())
+            .field("counter", &self.counter)
+            .finish_non_exhaustive()
+    }
+}
+
+/// An authenticated connection, created by a [`TransportSecurity`] handshake.
+#[derive(Debug)]
+pub struct SecureSession {
+    sealer: FrameSealer,
+    opener: FrameOpener,
+}
+
+impl SecureSession {
+    /// The sealer for outgoing frames
+    pub fn sealer(&mut self) -> &mut FrameSealer {
+        &mut self.sealer
+    }
+
+    /// The opener for incoming frames
+    pub fn opener(&mut self) -> &mut FrameOpener {
+        &mut self.opener
+    }
+
+    /// Split the session, to read and write on different tasks.
+    #[must_use]
+    pub fn split(self) -> (FrameSealer, FrameOpener) {
+        (self.sealer, self.opener)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::TcpStream, thread};
+
+    use tokio::{io::duplex, net::TcpListener, runtime::Builder};
+
+    use super::{PreSharedKey, SecureSession, TransportSecurity};
+
+    fn handshake(
+        client: &TransportSecurity,
+        server: &TransportSecurity,
+    ) -> (
+        Result<SecureSession, libafl_bolts::Error>,
+        Result<SecureSession, libafl_bolts::Error>,
+    ) {
+        let rt = Builder::new_current_thread().build().unwrap();
+        rt.block_on(async {
+            let (mut client_stream, mut server_stream) = duplex(1024);
+            let (client_res, server_res) = tokio::join!(
+                async {
+                    let res = client.connect(&mut client_stream).await;
+                    drop(client_stream);
+                    res
+                },
+                async {
+                    let res = server.accept(&mut server_stream).await;
+                    drop(server_stream);
+                    res
+                },
+            );
+            (client_res, server_res)
+        })
+    }
+
+    #[test]
+    fn test_transport_security() {
+        let key = PreSharedKey::from_passphrase(b"correct horse battery staple");
+        for security in [
+            TransportSecurity::new(key.clone()),
+            TransportSecurity::new(key.clone()).authenticate_only(),
+        ] {
+            let (client, server) = handshake(&security, &security);
+            let (mut client_sealer, mut client_opener) = client.unwrap().split();
+            let (mut server_sealer, mut server_opener) = server.unwrap().split();
+
+            let frame = client_sealer.seal(b"testcase").unwrap();
+            let len = server_opener
+                .body_len(frame[..4].try_into().unwrap())
+                .unwrap();
+            assert_eq!(len, frame.len() - 4);
+            assert_eq!(server_opener.open(&frame[4..]).unwrap(), b"testcase");
+
+            let frame = server_sealer.seal(b"reply").unwrap();
+            assert_eq!(client_opener.open(&frame[4..]).unwrap(), b"reply");
+
+            // Tampered and replayed frames are rejected
+            let frame = client_sealer.seal(b"testcase").unwrap();
+            let mut tampered = frame.clone();
+            *tampered.last_mut().unwrap() ^= 1;
+            assert!(server_opener.open(&tampered[4..]).is_err());
+            assert!(server_opener.open(&frame[4..]).is_ok());
+            assert!(server_opener.open(&frame[4..]).is_err());
+
+            assert!(server_opener.body_len(u32::MAX.to_le_bytes()).is_err());
+            assert!(server_opener.body_len(0_u32.to_le_bytes()).is_err());
+        }
+
+        // A peer with another key or protection is rejected
+        let security = TransportSecurity::new(key.clone());
+        let (client, server) = handshake(
+            &TransportSecurity::new(PreSharedKey::from_passphrase(b"wrong")),
+            &security,
+        );
+        assert!(client.is_err() && server.is_err());
+        let (client, server) = handshake(&security.clone().authenticate_only(), &security);
+        assert!(client.is_err() && server.is_err());
+    }
+
+    #[test]
+    fn test_transport_security_blocking() {
+        let security = TransportSecurity::new(PreSharedKey::new([0x42; 32]));
+        let rt = Builder::new_current_thread().enable_io().build().unwrap();
+        let listener = rt.block_on(TcpListener::bind("127.0.0.1:0")).unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let client_security = security.clone();
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            let mut session = client_security.connect_blocking(&mut stream).unwrap();
+            session.sealer().seal(b"testcase").unwrap()
+        });
+
+        let mut session = rt.block_on(async {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            security.accept(&mut stream).await.unwrap()
+        });
+        let frame = client.join().unwrap();
+        assert_eq!(session.opener().open(&frame[4..]).unwrap(), b"testcase");
+    }
+}
@@ -0,0 +1,336 @@
+//! Confines the children of executors in a cgroup v2, limiting their memory, cpu and number of processes.
+//!
+//! Set the [`CgroupLimits`] with [`StdChildArgs::cgroup`](crate::executors::StdChildArgs::cgroup) on the builder of the
+//! [`CommandExecutor`](crate::executors::CommandExecutor) or the `ForkserverExecutor`.
+//! Each executor gets its own [`Cgroup`], below the cgroup of the fuzzer or the given parent. The parent has to be
+//! delegated to the user running the fuzzer, and may not contain any processes itself, as cgroup v2 only allows
+//! to enable controllers for cgroups without processes.
+//!
+//! The forkserver of the `ForkserverExecutor` forks its children on its own, so the forkserver itself joins the
+//! cgroup, and its children inherit it. The limits then apply to the forkserver and the current child together:
+//! the forkserver counts towards `pids.max`, and `memory.max` needs some headroom for the memory of the forkserver.
+//!
+//! Targets killed by the OOM killer of their cgroup are reported as [`ExitKind::Oom`](crate::executors::ExitKind::Oom).
+
+use alloc::{format, string::ToString, vec::Vec};
+use core::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    os::fd::{AsRawFd, RawFd},
+    path::{Path, PathBuf},
+    process, thread,
+};
+
+use nix::unistd::Pid;
+
+use crate::Error;
+
+/// The number of cgroups created by this process, to give each a unique name
+static CGROUP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// How often to retry removing a cgroup whose processes are still exiting
+const REMOVE_RETRIES: usize = 100;
+
+/// The limits of a [`Cgroup`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CgroupLimits {
+    parent: Option<PathBuf>,
+    memory_max: Option<u64>,
+    cpu_max: Option<(Duration, Duration)>,
+    pids_max: Option<u64>,
+}
+
+impl CgroupLimits {
+    /// No limits, below the cgroup of the fuzzer
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates the cgroups below the given (delegated) cgroup directory, instead of the cgroup of the fuzzer
+    #[must_use]
+    pub fn parent(mut self, parent: PathBuf) -> Self {
+        self.parent = Some(parent);
+        self
+    }
+
+    /// Limits the memory of the target to `bytes` (`memory.max`); swapping is disabled.
+    #[must_use]
+    pub fn memory_max(mut self, bytes: u64) -> Self {
+        self.memory_max = Some(bytes);
+        self
+    }
+
+    /// Limits the target to `quota` cpu time every `period` (`cpu.max`)
+    #[must_use]
+    pub fn cpu_max(mut self, quota: Duration, period: Duration) -> Self {
+        self.cpu_max = Some((quota, period));
+        self
+    }
+
+    /// Limits the number of processes and threads of the target (`pids.max`)
+    #[must_use]
+    pub fn pids_max(mut self, pids: u64) -> Self {
+        self.pids_max = Some(pids);
+        self
+    }
+
+    /// The controllers needed for these limits
+    fn controllers(&self) -> Vec<&'static str> {
+        let mut controllers = Vec::new();
+        if self.memory_max.is_some() {
+            controllers.push("memory");
+        }
+        if self.cpu_max.is_some() {
+            controllers.push("cpu");
+        }
+        if self.pids_max.is_some() {
+            controllers.push("pids");
+        }
+        controllers
+    }
+}
+
+/// Finds the directory of the cgroup v2 of this process in `mountinfo` and `cgroup` from `/proc/self`
+fn parse_own_cgroup(mountinfo: &str, cgroup: &str) -> Option<PathBuf> {
+    let mount_point = mountinfo.lines().find_map(|line| {
+        let (mount, fs) = line.split_once(" - ")?;
+        if fs.split(' ').next()? != "cgroup2" {
+            return None;
+        }
+        mount.split(' ').nth(4)
+    })?;
+    let path = cgroup
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))?
+        .trim_start_matches('/');
+    Some(Path::new(mount_point).join(path))
+}
+
+/// Reads the `oom_kill` counter from the contents of a `memory.events` file
+fn parse_oom_kills(memory_events: &str) -> Option<u64> {
+    memory_events.lines().find_map(|line| {
+        let (key, value) = line.split_once(' ')?;
+        if key == "oom_kill" {
+            value.trim().parse().ok()
+        } else {
+            None
+        }
+    })
+}
+
+/// Writes `value` to the cgroup interface file `file` in `dir`
+fn write_cgroup_file(dir: &Path, file: &str, value: &str) -> Result<(), Error> {
+    let path = dir.join(file);
+    fs::write(&path, value).map_err(|err| {
+        Error::os_error(
+            err,
+            format!("Could not write {value} to {}", path.display()),
+        )
+    })
+}
+
+/// A cgroup v2 for the children of an executor, removed when dropped
+#[derive(Debug)]
+pub struct Cgroup {
+    path: PathBuf,
+    procs: Option<File>,
+    /// The OOM kills seen so far, `None` without the memory controller
+    oom_kills: Option<u64>,
+}
+
+impl Cgroup {
+    /// Creates a new cgroup with the given limits
+    pub fn new(limits: &CgroupLimits) -> Result<Self, Error> {
+        let parent = match &limits.parent {
+            Some(parent) => parent.clone(),
+            None => Self::own_cgroup()?,
+        };
+
+        let controllers = limits.controllers();
+        if !controllers.is_empty() {
+            let enabled = fs::read_to_string(parent.join("cgroup.subtree_control"))?;
+            for controller in controllers {
+                if enabled.split_whitespace().any(|c| c == controller) {
+                    continue;
+                }
+                fs::write(parent.join("cgroup.subtree_control"), format!("+{controller}")).map_err(|err| {
+                    Error::os_error(
+                        err,
+                        format!(
+                            "Could not enable the {controller} controller for {}. The cgroup must be delegated to this user and must not contain processes; set a suitable parent in the CgroupLimits",
+                            parent.display()
+                        ),
+                    )
+                })?;
+            }
+        }
+
+        let name = format!(
+            "libafl-{}-{}",
+            process::id(),
+            CGROUP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        );
+        let path = parent.join(name);
+        fs::create_dir(&path).map_err(|err| {
+            Error::os_error(
+                err,
+                format!("Could not create the cgroup {}", path.display()),
+            )
+        })?;
+        // From here on, the cgroup is removed again on errors
+        let mut cgroup = Self {
+            path,
+            procs: None,
+            oom_kills: None,
+        };
+
+        if let Some(memory_max) = limits.memory_max {
+            write_cgroup_file(&cgroup.path, "memory.max", &memory_max.to_string())?;
+            // Without swap, the OOM killer triggers reliably when the limit is reached.
+            if cgroup.path.join("memory.swap.max").exists() {
+                write_cgroup_file(&cgroup.path, "memory.swap.max", "0")?;
+            }
+        }
+        if let Some((quota, period)) = limits.cpu_max {
+            write_cgroup_file(
+                &cgroup.path,
+                "cpu.max",
+                &format!("{} {}", quota.as_micros(), period.as_micros()),
+            )?;
+        }
+        if let Some(pids_max) = limits.pids_max {
+            write_cgroup_file(&cgroup.path, "pids.max", &pids_max.to_string())?;
+        }
+
+        cgroup.procs = Some(
+            OpenOptions::new()
+                .write(true)
+                .open(cgroup.path.join("cgroup.procs"))?,
+        );
+        if cgroup.path.join("memory.events").exists() {
+            cgroup.oom_kills = Some(cgroup.oom_kills()?);
+        }
+        Ok(cgroup)
+    }
+
+    /// The directory of the cgroup v2 this process is in
+    pub fn own_cgroup() -> Result<PathBuf, Error> {
+        let mountinfo = fs::read_to_string("/proc/self/mountinfo")?;
+        let cgroup = fs::read_to_string("/proc/self/cgroup")?;
+        parse_own_cgroup(&mountinfo, &cgroup).ok_or_else(|| {
+            Error::unsupported(
+                "No cgroup v2 found for this process, is the unified hierarchy mounted?",
+            )
+        })
+    }
+
+    /// The directory of this cgroup
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The file descriptor of the opened `cgroup.procs` file.
+    ///
+    /// A process writing `0` to it moves itself into this cgroup, before `exec`ing the target.
+    #[must_use]
+    pub fn procs_fd(&self) -> RawFd {
+        self.procs.as_ref().unwrap().as_raw_fd()
+    }
+
+    /// Moves the process with the given pid into this cgroup
+    pub fn add_process(&self, pid: Pid) -> Result<(), Error> {
+        let mut procs = self.procs.as_ref().unwrap();
+        procs.write_all(pid.to_string().as_bytes())?;
+        Ok(())
+    }
+
+    /// The number of processes killed by the OOM killer of this cgroup so far
+    pub fn oom_kills(&self) -> Result<u64, Error> {
+        let events = fs::read_to_string(self.path.join("memory.events"))?;
+        parse_oom_kills(&events).ok_or_else(|| {
+            Error::illegal_state(format!(
+                "No oom_kill entry in {}/memory.events",
+                self.path.display()
+            ))
+        })
+    }
+
+    /// Whether the OOM killer of this cgroup killed a process since the last call.
+    ///
+    /// Always `false` if the memory controller is not enabled for this cgroup.
+    pub fn take_oom_kill(&mut self) -> bool {
+        let Some(seen) = self.oom_kills else {
+            return false;
+        };
+        match self.oom_kills() {
+            Ok(oom_kills) => {
+                self.oom_kills = Some(oom_kills);
+                oom_kills > seen
+            }
+            Err(err) => {
+                log::warn!("Could not read the OOM kills of the cgroup: {err}");
+                false
+            }
+        }
+    }
+
+    /// Whether the OOM killer of this cgroup killed a process since the last call to [`Self::take_oom_kill`]
+    #[must_use]
+    pub fn has_oom_kill(&self) -> bool {
+        self.oom_kills
+            .is_some_and(|seen| self.oom_kills().is_ok_and(|oom_kills| oom_kills > seen))
+    }
+}
+
+impl Drop for Cgroup {
+    fn drop(&mut self) {
+        self.procs = None;
+        // Kill all remaining processes (since Linux 5.14), then wait for them to be gone.
+        let _ = fs::write(self.path.join("cgroup.kill"), "1");
+        for _ in 0..REMOVE_RETRIES {
+            match fs::remove_dir(&self.path) {
+                Ok(()) => return,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => return,
+                Err(_) => thread::sleep(Duration::from_millis(1)),
+            }
+        }
+        log::warn!("Could not remove the cgroup {}", self.path.display());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{CgroupLimits, parse_oom_kills, parse_own_cgroup};
+
+    #[test]
+    fn test_cgroup_parsing() {
+        let mountinfo = "22 1 0:21 / /proc rw,nosuid - proc proc rw\n\
+             35 24 0:30 / /sys/fs/cgroup rw,nosuid,nodev,noexec - cgroup2 cgroup2 rw,nsdelegate\n";
+        let cgroup = "0::/user.slice/user-1000.slice/fuzz.scope\n";
+        assert_eq!(
+            parse_own_cgroup(mountinfo, cgroup),
+            Some(PathBuf::from(
+                "/sys/fs/cgroup/user.slice/user-1000.slice/fuzz.scope"
+            ))
+        );
+        assert_eq!(
+            parse_own_cgroup("22 1 0:21 / /proc rw - proc proc rw\n", cgroup),
+            None
+        );
+
+        let events = "low 0\nhigh 0\nmax 12\noom 3\noom_kill 2\noom_group_kill 0\n";
+        assert_eq!(parse_oom_kills(events), Some(2));
+        assert_eq!(parse_oom_kills("low 0\n"), None);
+
+        let limits = CgroupLimits::new().memory_max(1 << 30).pids_max(64);
+        assert_eq!(limits.controllers(), ["memory", "pids"]);
+    }
+}
@@ -5,8 +5,12 @@ use alloc::ffi::CString;
 use alloc::string::{String, ToString};
 #[cfg(all(feature = "intel_pt", target_os = "linux"))]
 use alloc::vec::Vec;
+#[cfg(all(unix, feature = "fork"))]
+use alloc::{string::ToString, sync::Arc};
 #[cfg(all(feature = "intel_pt", target_os = "linux"))]
 use core::ffi::CStr;
+#[cfg(all(unix, feature = "fork"))]
+use core::sync::atomic::{AtomicI32, Ordering};
 use core::{
     fmt::{self, Debug, Formatter},
     marker::PhantomData,
@@ -17,17 +21,28 @@ use core::{
 use std::ffi::OsStr;
 #[cfg(not(unix))]
 use std::ffi::OsString;
-#[cfg(all(feature = "intel_pt", target_os = "linux"))]
+#[cfg(any(
+    all(feature = "intel_pt", target_os = "linux"),
+    all(unix, feature = "fork")
+))]
 use std::os::fd::AsRawFd;
+#[cfg(all(unix, feature = "fork"))]
+use std::os::{fd::BorrowedFd, unix::process::CommandExt};
 #[cfg(unix)]
 use std::os::{fd::RawFd, unix::ffi::OsStrExt};
 use std::{
-    io::{Read, Write},
+    io::{self, Read, Write},
     process::{Child, Command, Stdio},
 };
 
 #[cfg(unix)]
 use libafl_bolts::{AsSlice, tuples::MatchNameRef};
+#[cfg(all(unix, feature = "fork"))]
+use libafl_bolts::{
+    AsSliceMut, PreloadSource,
+    os::pipes::Pipe,
+    shmem::{ShMem, ShMemProvider, UnixShMem, UnixShMemProvider},
+};
 use libafl_bolts::{
     InputLocation, StdTargetArgs, StdTargetArgsInner,
     ownedref::OwnedSlice,
@@ -37,6 +52,12 @@ use libafl_bolts::{
 use libafl_bolts::{core_affinity::CoreId, os::dup2};
 #[cfg(all(feature = "intel_pt", target_os = "linux"))]
 use libc::STDIN_FILENO;
+#[cfg(all(unix, feature = "fork"))]
+use nix::sys::{
+    select::{FdSet, pselect},
+    signal::SigSet,
+    time::TimeSpec,
+};
 #[cfg(target_os = "linux")]
 use nix::{
     errno::Errno,
@@ -57,9 +78,15 @@ use typed_builder::TypedBuilder;
 
 #[cfg(all(target_family = "unix", feature = "fork"))]
 use super::forkserver::ConfigTarget;
+#[cfg(all(unix, feature = "fork"))]
+use super::forkserver::{MAX_INPUT_SIZE_DEFAULT, SHMEM_FUZZ_HDR_SIZE};
 use super::{HasTimeout, StdChildArgs, StdChildArgsInner};
+#[cfg(all(target_os = "linux", feature = "fork"))]
+use super::{cgroup::Cgroup, sandbox::Sandbox};
 #[cfg(target_os = "linux")]
 use crate::executors::hooks::ExecutorHooksTuple;
+#[cfg(target_os = "linux")]
+use crate::observers::HangStackObserver;
 use crate::{
     Error,
     executors::{Executor, ExitKind, HasObservers},
@@ -68,6 +95,34 @@ use crate::{
     state::HasExecutions,
 };
 
+/// Environment variable key for the shared memory id the [`PersistentCommandExecutor`] delivers the inputs in.
+///
+/// The size of the shared memory is in the same key, suffixed with `_SIZE`.
+#[cfg(unix)]
+pub const PERSISTENT_SHM_ENV_VAR: &str = "__LIBAFL_PERSISTENT_SHM_ID";
+/// Pinned fd number the children of the [`PersistentCommandExecutor`] read the control messages from.
+///
+/// They write their status messages to `PERSISTENT_FD + 1`.
+#[cfg(unix)]
+pub const PERSISTENT_FD: i32 = 196;
+/// The first message of a persistent child, once it is ready to receive inputs
+#[cfg(unix)]
+pub const PERSISTENT_HELLO: u32 = 0x4c41_4650;
+/// Environment variable key for the shared memory id the input of [`InputLocation::Preload`] is delivered in.
+///
+/// The size of the shared memory is in the same key, suffixed with `_SIZE`.
+#[cfg(unix)]
+pub const PRELOAD_SHM_ENV_VAR: &str = "__LIBAFL_PRELOAD_SHM_ID";
+/// Environment variable key for the path the preload library serves the input at, for [`libafl_bolts::PreloadSource::Path`]
+#[cfg(unix)]
+pub const PRELOAD_PATH_ENV_VAR: &str = "__LIBAFL_PRELOAD_PATH";
+/// Environment variable key for the fd the preload library serves the input on, for [`libafl_bolts::PreloadSource::Fd`]
+#[cfg(unix)]
+pub const PRELOAD_FD_ENV_VAR: &str = "__LIBAFL_PRELOAD_FD";
+/// How long to wait for a persistent child to send [`PERSISTENT_HELLO`]
+#[cfg(all(unix, feature = "fork"))]
+const PERSISTENT_STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+
 /// How do we capture stdout/stderr. Not intended for public use.
 #[derive(Debug, Default)]
 #[allow(dead_code)]
@@ -126,12 +181,30 @@ pub struct StdCommandConfigurator {
     timeout: Duration,
     /// true: input gets delivered via stdin
     input_location: InputLocation,
+    /// The shared memory the input is written to, for [`InputLocation::Preload`]
+    #[cfg(all(unix, feature = "fork"))]
+    preload_shmem: Option<UnixShMem>,
     /// The Command to execute
     command: Command,
+    /// The cgroup the children run in
+    #[cfg(all(target_os = "linux", feature = "fork"))]
+    cgroup: Option<Cgroup>,
+    /// The namespace sandbox the children run in
+    #[cfg(all(target_os = "linux", feature = "fork"))]
+    sandbox: Option<Sandbox>,
+    /// The observer sampling the stacks of timed-out children
+    #[cfg(target_os = "linux")]
+    hang_stack_observer: Option<Handle<HangStackObserver<'static>>>,
 }
 
 impl CommandConfigurator<Child> for StdCommandConfigurator {
     fn spawn_child(&mut self, target_bytes: OwnedSlice<'_, u8>) -> Result<Child, Error> {
+        #[cfg(all(target_os = "linux", feature = "fork"))]
+        if let Some(cgroup) = &mut self.cgroup {
+            // Only count the OOM kills of the upcoming child
+            cgroup.take_oom_kill();
+        }
+
         let mut cmd = Command::new(self.command.get_program());
         match &mut self.input_location {
             InputLocation::Arg { argnum } => {
@@ -176,6 +249,18 @@ impl CommandConfigurator<Child> for StdCommandConfigurator {
                 if let Some(cwd) = self.command.get_current_dir() {
                     cmd.current_dir(cwd);
                 }
+                #[cfg(all(target_os = "linux", feature = "fork"))]
+                if let Some(cgroup) = &self.cgroup {
+                    // # Safety
+                    // The cgroup lives as long as this configurator, which waits for its children.
+                    unsafe {
+                        cmd.set_cgroup(cgroup);
+                    }
+                }
+                #[cfg(all(target_os = "linux", feature = "fork"))]
+                if let Some(sandbox) = &self.sandbox {
+                    cmd.set_sandbox(sandbox);
+                }
                 Ok(cmd.spawn()?)
             }
             InputLocation::StdIn { input_file: _ } => {
@@ -183,13 +268,13 @@ impl CommandConfigurator<Child> for StdCommandConfigurator {
                 let mut stdin = handle.stdin.take().unwrap();
                 match stdin.write_all(&target_bytes) {
                     Err(err) => {
-                        if err.kind() != std::io::ErrorKind::BrokenPipe {
+                        if err.kind() != io::ErrorKind::BrokenPipe {
                             return Err(err.into());
                         }
                     }
                     _ => {
                         if let Err(err) = stdin.flush() {
-                            if err.kind() != std::io::ErrorKind::BrokenPipe {
+                            if err.kind() != io::ErrorKind::BrokenPipe {
                                 return Err(err.into());
                             }
                         }
@@ -202,6 +287,29 @@ impl CommandConfigurator<Child> for StdCommandConfigurator {
                 out_file.write_buf(&target_bytes)?;
                 Ok(self.command.spawn()?)
             }
+            #[cfg(all(unix, feature = "fork"))]
+            InputLocation::Preload { .. } => {
+                let shmem = self.preload_shmem.as_mut().ok_or_else(|| {
+                    Error::illegal_state("No shared memory for the preloaded input")
+                })?;
+                let len = target_bytes.as_slice().len();
+                let max_input_size = shmem.len() - SHMEM_FUZZ_HDR_SIZE;
+                if len > max_input_size {
+                    return Err(Error::illegal_argument(format!(
+                        "The input of {len} bytes does not fit into the shared memory for inputs of at most {max_input_size} bytes. Raise CommandExecutorBuilder::max_input_size, or limit the size of the inputs"
+                    )));
+                }
+                let len_msg = u32::try_from(len)?.to_ne_bytes();
+                let shmem = shmem.as_slice_mut();
+                shmem[..SHMEM_FUZZ_HDR_SIZE].copy_from_slice(&len_msg);
+                shmem[SHMEM_FUZZ_HDR_SIZE..SHMEM_FUZZ_HDR_SIZE + len]
+                    .copy_from_slice(target_bytes.as_slice());
+                Ok(self.command.spawn()?)
+            }
+            #[cfg(not(all(unix, feature = "fork")))]
+            InputLocation::Preload { .. } => Err(Error::unsupported(
+                "Preloaded inputs need LibAFL with fork support on unix",
+            )),
         }
     }
 
@@ -211,6 +319,29 @@ impl CommandConfigurator<Child> for StdCommandConfigurator {
     fn exec_timeout_mut(&mut self) -> &mut Duration {
         &mut self.timeout
     }
+
+    #[cfg(target_os = "linux")]
+    fn hang_stack_observer(&self) -> Option<Handle<HangStackObserver<'static>>> {
+        self.hang_stack_observer.clone()
+    }
+
+    #[cfg(unix)]
+    fn exit_kind_from_status(&self, status: &std::process::ExitStatus) -> ExitKind {
+        #[cfg(all(target_os = "linux", feature = "fork"))]
+        if let Some(cgroup) = &self.cgroup {
+            use crate::std::os::unix::process::ExitStatusExt;
+
+            // The cgroup tells us for sure, no need to guess from the signal
+            return if cgroup.has_oom_kill() {
+                ExitKind::Oom
+            } else if status.signal().is_some() {
+                ExitKind::Crash
+            } else {
+                ExitKind::Ok
+            };
+        }
+        exit_kind_from_signal(status)
+    }
 }
 
 /// Linux specific [`CommandConfigurator`] that leverages `ptrace`
@@ -284,6 +415,11 @@ impl CommandConfigurator<Pid> for PTraceCommandConfigurator {
                     InputLocation::File { out_file } => {
                         out_file.write_buf(&target_bytes).unwrap();
                     }
+                    InputLocation::Preload { .. } => {
+                        return Err(Error::illegal_argument(
+                            "PTraceCommandConfigurator does not support preloaded inputs",
+                        ));
+                    }
                 }
 
                 ptrace::traceme().unwrap();
@@ -388,18 +524,25 @@ where
             .configurator
             .spawn_child(target_bytes_converter.to_target_bytes(input))?;
 
-        let exit_kind = child
+        let exit_kind = if let Some(status) = child
             .wait_timeout(self.configurator.exec_timeout())
             .expect("waiting on child failed")
-            .map(|status| self.configurator.exit_kind_from_status(&status))
-            .unwrap_or_else(|| {
-                // if this fails, there is not much we can do. let's hope it failed because the process finished
-                // in the meantime.
-                drop(child.kill());
-                // finally, try to wait to properly clean up system resources.
-                drop(child.wait());
-                ExitKind::Timeout
-            });
+        {
+            self.configurator.exit_kind_from_status(&status)
+        } else {
+            #[cfg(target_os = "linux")]
+            if let Some(hang_stack_handle) = self.configurator.hang_stack_observer()
+                && let Some(hang_stack_observer) = self.observers.get_mut(&hang_stack_handle)
+            {
+                hang_stack_observer.sample(Pid::from_raw(i32::try_from(child.id())?));
+            }
+            // if this fails, there is not much we can do. let's hope it failed because the process finished
+            // in the meantime.
+            drop(child.kill());
+            // finally, try to wait to properly clean up system resources.
+            drop(child.wait());
+            ExitKind::Timeout
+        };
 
         // Manually update stdout/stderr here if we use piped implementation.
         // Reason of not putting into state and pass by post_exec_all is that
@@ -560,6 +703,8 @@ where
 pub struct CommandExecutorBuilder {
     target_inner: StdTargetArgsInner,
     child_env_inner: StdChildArgsInner,
+    #[cfg(all(unix, feature = "fork"))]
+    max_input_size: usize,
 }
 
 impl StdTargetArgs for CommandExecutorBuilder {
@@ -595,9 +740,20 @@ impl CommandExecutorBuilder {
         CommandExecutorBuilder {
             target_inner: StdTargetArgsInner::default(),
             child_env_inner: StdChildArgsInner::default(),
+            #[cfg(all(unix, feature = "fork"))]
+            max_input_size: MAX_INPUT_SIZE_DEFAULT,
         }
     }
 
+    /// The maximum size of the inputs delivered in shared memory by the [`PersistentCommandExecutor`]
+    /// and for [`InputLocation::Preload`], defaults to [`MAX_INPUT_SIZE_DEFAULT`]. Larger inputs are rejected with an error.
+    #[must_use]
+    #[cfg(all(unix, feature = "fork"))]
+    pub fn max_input_size(mut self, max_input_size: usize) -> Self {
+        self.max_input_size = max_input_size;
+        self
+    }
+
     /// Builds the `CommandExecutor`
     pub fn build<I, OT, S>(
         &self,
@@ -625,7 +781,9 @@ impl CommandExecutorBuilder {
                 }
                 command.stdin(Stdio::piped());
             }
-            InputLocation::File { .. } | InputLocation::Arg { .. } => {
+            InputLocation::File { .. }
+            | InputLocation::Arg { .. }
+            | InputLocation::Preload { .. } => {
                 command.stdin(Stdio::null());
             }
         }
@@ -640,6 +798,23 @@ impl CommandExecutorBuilder {
             command.current_dir(cwd);
         }
 
+        #[cfg(all(unix, feature = "fork"))]
+        let preload_shmem = match &self.target_inner.input_location {
+            InputLocation::Preload { source, library } => {
+                Some(self.setup_preload(&mut command, source, library)?)
+            }
+            _ => None,
+        };
+        #[cfg(not(all(unix, feature = "fork")))]
+        if matches!(
+            self.target_inner.input_location,
+            InputLocation::Preload { .. }
+        ) {
+            return Err(Error::illegal_argument(
+                "You have not compiled LibAFL with fork support or are running on Windows. LibAFL cannot deliver preloaded inputs. Use another input location or enable `fork`",
+            ));
+        }
+
         #[cfg(unix)]
         let stdout_cap = self.child_env_inner.stdout_observer.as_ref().map(|hdl| {
             observers
@@ -711,13 +886,64 @@ impl CommandExecutorBuilder {
             )));
         }
 
+        #[cfg(all(target_os = "linux", feature = "fork"))]
+        let cgroup = match &self.child_env_inner.cgroup {
+            Some(limits) => {
+                let cgroup = Cgroup::new(limits)?;
+                // # Safety
+                // The cgroup is moved into the configurator, which outlives its children.
+                unsafe {
+                    command.set_cgroup(&cgroup);
+                }
+                Some(cgroup)
+            }
+            None => None,
+        };
+        #[cfg(all(target_os = "linux", not(feature = "fork")))]
+        if self.child_env_inner.cgroup.is_some() {
+            return Err(Error::illegal_argument(
+                "You have not compiled LibAFL with fork support. LibAFL cannot move children into a cgroup right after they get spawned. Remove the `cgroup` from StdChildArgs or enable `fork`",
+            ));
+        }
+
+        // After the cgroup, as it is joined with the credentials of the host
+        #[cfg(all(target_os = "linux", feature = "fork"))]
+        let sandbox = match &self.child_env_inner.sandbox {
+            Some(config) => {
+                let input_file = match &self.target_inner.input_location {
+                    InputLocation::File { out_file } => Some(out_file.path.as_path()),
+                    // The input is in shared memory, but the library has to stay visible
+                    InputLocation::Preload { library, .. } => Some(library.as_path()),
+                    InputLocation::Arg { .. } | InputLocation::StdIn { .. } => None,
+                };
+                let sandbox = Sandbox::new(config, input_file)?;
+                command.set_sandbox(&sandbox);
+                Some(sandbox)
+            }
+            None => None,
+        };
+        #[cfg(all(target_os = "linux", not(feature = "fork")))]
+        if self.child_env_inner.sandbox.is_some() {
+            return Err(Error::illegal_argument(
+                "You have not compiled LibAFL with fork support. LibAFL cannot sandbox children right after they get spawned. Remove the `sandbox` from StdChildArgs or enable `fork`",
+            ));
+        }
+
         let configurator = StdCommandConfigurator {
             debug_child: self.child_env_inner.debug_child,
             stdout_cap,
             stderr_cap,
             input_location: self.target_inner.input_location.clone(),
+            #[cfg(all(unix, feature = "fork"))]
+            preload_shmem,
             timeout: self.child_env_inner.timeout,
             command,
+            #[cfg(all(target_os = "linux", feature = "fork"))]
+            cgroup,
+            #[cfg(all(target_os = "linux", feature = "fork"))]
+            sandbox,
+            #[cfg(target_os = "linux")]
+            hang_stack_observer: self.child_env_inner.hang_stack_observer.clone(),
         };
 
         Ok(configurator.into_executor::<I, OT, S>(
@@ -726,6 +952,410 @@ impl CommandExecutorBuilder {
             self.child_env_inner.stderr_observer.clone(),
         ))
     }
+
+    /// Creates the shared memory for [`InputLocation::Preload`] and tells the preload library about it
+    #[cfg(all(unix, feature = "fork"))]
+    fn setup_preload(
+        &self,
+        command: &mut Command,
+        source: &PreloadSource,
+        library: &std::path::Path,
+    ) -> Result<UnixShMem, Error> {
+        if !library.is_file() {
+            return Err(Error::illegal_argument(format!(
+                "The input preload library {} does not exist. Build libafl_targets with the `input_preload` feature",
+                library.display()
+            )));
+        }
+        // The library may be preloaded from a different working directory
+        let library = std::fs::canonicalize(library)?;
+        let shmem =
+            UnixShMemProvider::new()?.new_shmem(self.max_input_size + SHMEM_FUZZ_HDR_SIZE)?;
+
+        // Keep the libraries the user preloads
+        let mut preload = library.into_os_string();
+        if let Some((_, user_preload)) = self
+            .target_inner
+            .envs
+            .iter()
+            .rev()
+            .find(|(key, _)| key == "LD_PRELOAD")
+        {
+            preload.push(":");
+            preload.push(user_preload);
+        }
+        command
+            .env("LD_PRELOAD", preload)
+            .env(PRELOAD_SHM_ENV_VAR, shmem.id().to_string())
+            .env(
+                format!("{PRELOAD_SHM_ENV_VAR}_SIZE"),
+                shmem.len().to_string(),
+            );
+        match source {
+            PreloadSource::Path(path) => command.env(PRELOAD_PATH_ENV_VAR, path),
+            PreloadSource::Fd(fd) => command.env(PRELOAD_FD_ENV_VAR, fd.to_string()),
+        };
+        Ok(shmem)
+    }
+
+    /// Builds a [`PersistentCommandExecutor`], which delivers the inputs in shared memory to a long-lived child.
+    ///
+    /// The target has to loop over the inputs with `libafl_persistent.h` or the `persistent` module of `libafl_targets`.
+    /// Both map the shared memory with `shmat`, so use the `UnixShMemProvider` for them.
+    /// The input location has to be left at the default, stdin, and stdout/stderr observers are not supported.
+    /// Inputs larger than [`Self::max_input_size`] are rejected with an error.
+    #[cfg(all(unix, feature = "fork"))]
+    pub fn build_persistent<I, OT, S, SP>(
+        &self,
+        shmem_provider: &mut SP,
+        observers: OT,
+    ) -> Result<PersistentCommandExecutor<I, OT, S, SP::ShMem>, Error>
+    where
+        I: HasTargetBytes,
+        OT: MatchName + ObserversTuple<I, S>,
+        SP: ShMemProvider,
+    {
+        if !matches!(
+            self.target_inner.input_location,
+            InputLocation::StdIn { .. }
+        ) {
+            return Err(Error::illegal_argument(
+                "The PersistentCommandExecutor delivers inputs in shared memory, do not set an input location",
+            ));
+        }
+        if self.child_env_inner.stdout_observer.is_some()
+            || self.child_env_inner.stderr_observer.is_some()
+        {
+            return Err(Error::illegal_argument(
+                "StdOut and StdErr observers are not supported for persistent children",
+            ));
+        }
+
+        let CommandExecutor {
+            mut configurator,
+            observers,
+            ..
+        } = self.build::<I, OT, S>(observers)?;
+        let shmem = shmem_provider.new_shmem(self.max_input_size + SHMEM_FUZZ_HDR_SIZE)?;
+
+        let child_fds = Arc::new([AtomicI32::new(-1), AtomicI32::new(-1)]);
+        let fds = child_fds.clone();
+        let command = &mut configurator.command;
+        command
+            .stdin(Stdio::null())
+            .env(PERSISTENT_SHM_ENV_VAR, shmem.id().to_string())
+            .env(
+                format!("{PERSISTENT_SHM_ENV_VAR}_SIZE"),
+                shmem.len().to_string(),
+            );
+        // # Safety
+        // Only calls `dup2` in the child, the pipes of the upcoming child are stored in `fds` before each spawn.
+        unsafe {
+            command.pre_exec(move || {
+                for (fd, pinned) in fds.iter().zip([PERSISTENT_FD, PERSISTENT_FD + 1]) {
+                    if libc::dup2(fd.load(Ordering::Relaxed), pinned) < 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                }
+                Ok(())
+            });
+        }
+
+        Ok(PersistentCommandExecutor {
+            configurator,
+            observers,
+            shmem,
+            child: None,
+            child_fds,
+            phantom: PhantomData,
+        })
+    }
+}
+
+/// The result of waiting for a status message of a persistent child
+#[cfg(all(unix, feature = "fork"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PersistentStatus {
+    /// The child sent a message
+    Message(u32),
+    /// The child closed its end of the status pipe, it exited
+    Exited,
+    /// The child did not answer in time
+    Timeout,
+}
+
+/// A long-lived child of the [`PersistentCommandExecutor`], with the fuzzer's ends of its pipes
+#[cfg(all(unix, feature = "fork"))]
+#[derive(Debug)]
+struct PersistentChild {
+    child: Child,
+    ctl_pipe: Pipe,
+    st_pipe: Pipe,
+}
+
+#[cfg(all(unix, feature = "fork"))]
+impl PersistentChild {
+    /// Waits for the next status message of the child
+    fn read_st_timed(&mut self, timeout: Duration) -> Result<PersistentStatus, Error> {
+        let st_read = self.st_pipe.read_end().unwrap();
+        // # Safety
+        // The read end stays open as long as the pipe.
+        let st_read = unsafe { BorrowedFd::borrow_raw(st_read) };
+
+        let mut readfds = FdSet::new();
+        readfds.insert(st_read);
+        let sret = pselect(
+            Some(st_read.as_raw_fd() + 1),
+            &mut readfds,
+            None,
+            None,
+            Some(&TimeSpec::from_duration(timeout)),
+            Some(&SigSet::empty()),
+        )?;
+        if sret == 0 {
+            return Ok(PersistentStatus::Timeout);
+        }
+
+        let mut buf = [0_u8; 4];
+        match self.st_pipe.read_exact(&mut buf) {
+            Ok(()) => Ok(PersistentStatus::Message(u32::from_ne_bytes(buf))),
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(PersistentStatus::Exited),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Kills the child and cleans up after it
+    fn kill(mut self) {
+        // If this fails, the child already exited
+        drop(self.child.kill());
+        drop(self.child.wait());
+    }
+}
+
+/// A [`CommandExecutor`]-like executor, which keeps its child alive and delivers the inputs to it in shared memory.
+///
+/// Each input is written to the shared memory (a `u32` length, followed by the bytes, as for the shared memory
+/// testcases of the `ForkserverExecutor`), then the child is notified on [`PERSISTENT_FD`] and reports back on
+/// `PERSISTENT_FD + 1` once the input ran. A child which exits or crashes is restarted for the next input.
+///
+/// Construct it with [`CommandExecutorBuilder::build_persistent`]. The target implements its side of the protocol
+/// with `libafl_persistent.h` or the `persistent` module of `libafl_targets`.
+#[cfg(all(unix, feature = "fork"))]
+pub struct PersistentCommandExecutor<I, OT, S, SHM> {
+    configurator: StdCommandConfigurator,
+    observers: OT,
+    shmem: SHM,
+    child: Option<PersistentChild>,
+    /// The child ends of the control and status pipes of the upcoming child, used in `pre_exec`
+    child_fds: Arc<[AtomicI32; 2]>,
+    phantom: PhantomData<(I, S)>,
+}
+
+#[cfg(all(unix, feature = "fork"))]
+impl<I, OT, S, SHM> Debug for PersistentCommandExecutor<I, OT, S, SHM>
+where
+    OT: Debug,
+    SHM: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PersistentCommandExecutor")
+            .field("configurator", &self.configurator)
+            .field("observers", &self.observers)
+            .field("shmem", &self.shmem)
+            .field("child", &self.child)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(all(unix, feature = "fork"))]
+impl<I, OT, S, SHM> PersistentCommandExecutor<I, OT, S, SHM>
+where
+    OT: MatchName,
+    SHM: ShMem,
+{
+    /// Accesses the inner [`StdCommandConfigurator`]
+    pub fn inner(&mut self) -> &mut StdCommandConfigurator {
+        &mut self.configurator
+    }
+
+    /// The process id of the current child, if one is running
+    #[must_use]
+    pub fn child_pid(&self) -> Option<u32> {
+        self.child.as_ref().map(|child| child.child.id())
+    }
+
+    /// The maximum size of an input, see [`CommandExecutorBuilder::max_input_size`]
+    #[must_use]
+    pub fn max_input_size(&self) -> usize {
+        self.shmem.len() - SHMEM_FUZZ_HDR_SIZE
+    }
+
+    /// Spawns a new child and waits for it to be ready for inputs
+    fn spawn_child(&mut self) -> Result<PersistentChild, Error> {
+        let mut ctl_pipe = Pipe::new()?;
+        let mut st_pipe = Pipe::new()?;
+        self.child_fds[0].store(ctl_pipe.read_end().unwrap(), Ordering::Relaxed);
+        self.child_fds[1].store(st_pipe.write_end().unwrap(), Ordering::Relaxed);
+
+        #[cfg(target_os = "linux")]
+        if let Some(cgroup) = &mut self.configurator.cgroup {
+            // Only count the OOM kills of the upcoming child
+            cgroup.take_oom_kill();
+        }
+        let child = self.configurator.command.spawn()?;
+        // Once the child exits, reading the status pipe returns EOF
+        ctl_pipe.close_read_end();
+        st_pipe.close_write_end();
+        let mut child = PersistentChild {
+            child,
+            ctl_pipe,
+            st_pipe,
+        };
+
+        match child.read_st_timed(PERSISTENT_STARTUP_TIMEOUT)? {
+            PersistentStatus::Message(PERSISTENT_HELLO) => Ok(child),
+            PersistentStatus::Message(msg) => {
+                child.kill();
+                Err(Error::illegal_state(format!(
+                    "Unexpected hello message {msg:#x} from the persistent child"
+                )))
+            }
+            PersistentStatus::Exited => {
+                let status = child.child.wait()?;
+                Err(Error::illegal_state(format!(
+                    "The persistent child exited before it was ready ({status}). Does the target implement the persistent protocol of libafl_targets?"
+                )))
+            }
+            PersistentStatus::Timeout => {
+                child.kill();
+                Err(Error::illegal_state(format!(
+                    "The persistent child was not ready after {PERSISTENT_STARTUP_TIMEOUT:?}. Does the target implement the persistent protocol of libafl_targets?"
+                )))
+            }
+        }
+    }
+
+    /// Runs the given input in the persistent child, restarting the child if needed
+    fn execute_input(&mut self, target_bytes: &[u8]) -> Result<ExitKind, Error> {
+        let len = target_bytes.len();
+        if len > self.max_input_size() {
+            return Err(Error::illegal_argument(format!(
+                "The input of {len} bytes does not fit into the shared memory for inputs of at most {} bytes. Raise CommandExecutorBuilder::max_input_size, or limit the size of the inputs",
+                self.max_input_size()
+            )));
+        }
+        let len_msg = u32::try_from(len)?.to_ne_bytes();
+        let shmem = self.shmem.as_slice_mut();
+        shmem[..SHMEM_FUZZ_HDR_SIZE].copy_from_slice(&len_msg);
+        shmem[SHMEM_FUZZ_HDR_SIZE..SHMEM_FUZZ_HDR_SIZE + len].copy_from_slice(target_bytes);
+
+        let mut child = match self.child.take() {
+            Some(child) => child,
+            None => self.spawn_child()?,
+        };
+        if let Err(err) = child.ctl_pipe.write_all(&len_msg) {
+            if err.kind() != io::ErrorKind::BrokenPipe {
+                child.kill();
+                return Err(err.into());
+            }
+            // The child left its loop after the last input, start over with a new one
+            child.kill();
+            child = self.spawn_child()?;
+            child.ctl_pipe.write_all(&len_msg)?;
+        }
+
+        match child.read_st_timed(self.configurator.exec_timeout())? {
+            PersistentStatus::Message(_) => {
+                self.child = Some(child);
+                Ok(ExitKind::Ok)
+            }
+            PersistentStatus::Exited => {
+                let status = child.child.wait()?;
+                Ok(self.configurator.exit_kind_from_status(&status))
+            }
+            PersistentStatus::Timeout => {
+                #[cfg(target_os = "linux")]
+                if let Some(hang_stack_handle) = self.configurator.hang_stack_observer()
+                    && let Some(hang_stack_observer) = self.observers.get_mut(&hang_stack_handle)
+                {
+                    hang_stack_observer.sample(Pid::from_raw(i32::try_from(child.child.id())?));
+                }
+                child.kill();
+                Ok(ExitKind::Timeout)
+            }
+        }
+    }
+}
+
+#[cfg(all(unix, feature = "fork"))]
+impl<EM, I, OT, S, SHM, Z> Executor<EM, I, S, Z> for PersistentCommandExecutor<I, OT, S, SHM>
+where
+    S: HasExecutions,
+    OT: ObserversTuple<I, S>,
+    SHM: ShMem,
+    Z: ToTargetBytes<I>,
+{
+    fn run_target(
+        &mut self,
+        fuzzer: &mut Z,
+        state: &mut S,
+        _mgr: &mut EM,
+        input: &I,
+    ) -> Result<ExitKind, Error> {
+        self.observers.pre_exec_all(state, input)?;
+        *state.executions_mut() += 1;
+        let exit_kind = self.execute_input(fuzzer.to_target_bytes(input).as_slice())?;
+        self.observers
+            .post_exec_child_all(state, input, &exit_kind)?;
+        Ok(exit_kind)
+    }
+}
+
+#[cfg(all(unix, feature = "fork"))]
+impl<I, OT, S, SHM> HasTimeout for PersistentCommandExecutor<I, OT, S, SHM> {
+    #[inline]
+    fn timeout(&self) -> Duration {
+        self.configurator.exec_timeout()
+    }
+
+    #[inline]
+    fn set_timeout(&mut self, timeout: Duration) {
+        *self.configurator.exec_timeout_mut() = timeout;
+    }
+}
+
+#[cfg(all(unix, feature = "fork"))]
+impl<I, OT, S, SHM> HasObservers for PersistentCommandExecutor<I, OT, S, SHM> {
+    type Observers = OT;
+
+    fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+        RefIndexable::from(&self.observers)
+    }
+
+    fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+        RefIndexable::from(&mut self.observers)
+    }
+}
+
+#[cfg(all(unix, feature = "fork"))]
+impl<I, OT, S, SHM> Drop for PersistentCommandExecutor<I, OT, S, SHM> {
+    fn drop(&mut self) {
+        if let Some(child) = self.child.take() {
+            child.kill();
+        }
+    }
+}
+
+/// Guesses the `ExitKind` from the signal that terminated the child, if any
+#[cfg(unix)]
+fn exit_kind_from_signal(status: &std::process::ExitStatus) -> ExitKind {
+    use crate::std::os::unix::process::ExitStatusExt;
+    match status.signal() {
+        // for reference: https://www.man7.org/linux/man-pages/man7/signal.7.html
+        Some(9) => ExitKind::Oom,
+        Some(_) => ExitKind::Crash,
+        None => ExitKind::Ok,
+    }
 }
 
 /// A [`CommandConfigurator`] takes care of creating and spawning a [`Command`] for the [`CommandExecutor`].
@@ -797,17 +1427,17 @@ pub trait CommandConfigurator<C>: Sized {
     /// Set the timeout duration for execution of the child process.
     fn exec_timeout_mut(&mut self) -> &mut Duration;
 
+    /// The observer sampling the stacks of timed-out children, before they get killed
+    #[cfg(target_os = "linux")]
+    fn hang_stack_observer(&self) -> Option<Handle<HangStackObserver<'static>>> {
+        None
+    }
+
     /// Maps the exit status of the child process to an `ExitKind`.
     #[cfg(unix)]
     #[inline]
     fn exit_kind_from_status(&self, status: &std::process::ExitStatus) -> ExitKind {
-        use crate::std::os::unix::process::ExitStatusExt;
-        match status.signal() {
-            // for reference: https://www.man7.org/linux/man-pages/man7/signal.7.html
-            Some(9) => ExitKind::Oom,
-            Some(_) => ExitKind::Crash,
-            None => ExitKind::Ok,
-        }
+        exit_kind_from_signal(status)
     }
 
     /// Maps the exit status of the child process to an `ExitKind`.
@@ -876,6 +1506,8 @@ fn waitpid_filtered(pid: Pid, options: Option<WaitPidFlag>) -> Result<WaitStatus
 #[cfg(test)]
 mod tests {
     use libafl_bolts::StdTargetArgs;
+    #[cfg(all(unix, feature = "fork"))]
+    use libafl_bolts::shmem::{ShMemProvider, UnixShMemProvider};
     #[cfg(unix)]
     use libafl_bolts::tuples::Handled;
     #[cfg(unix)]
@@ -948,4 +1580,84 @@ mod tests {
 
         assert!(executor.observers.0.output.is_some());
     }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    #[cfg(all(unix, feature = "fork"))]
+    fn test_persistent_not_ready() {
+        let mut mgr: SimpleEventManager<NopInput, _, NopState<NopInput>> =
+            SimpleEventManager::new(SimpleMonitor::new(|status| {
+                log::info!("{status}");
+            }));
+
+        // A program without the persistent protocol exits right away
+        let mut shmem_provider = UnixShMemProvider::new().unwrap();
+        let mut executor = CommandExecutor::builder()
+            .program("true")
+            .build_persistent::<BytesInput, (), NopState<NopInput>, _>(&mut shmem_provider, ())
+            .unwrap();
+
+        assert!(
+            executor
+                .run_target(
+                    &mut NopFuzzer::new(),
+                    &mut NopState::<NopInput>::new(),
+                    &mut mgr,
+                    &BytesInput::new(b"test".to_vec()),
+                )
+                .is_err()
+        );
+        assert_eq!(executor.child_pid(), None);
+
+        assert!(
+            CommandExecutor::builder()
+                .program("true")
+                .input(InputLocation::Arg { argnum: 0 })
+                .build_persistent::<BytesInput, (), NopState<NopInput>, _>(&mut shmem_provider, ())
+                .is_err()
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    #[cfg(all(target_os = "linux", feature = "fork"))]
+    fn test_cgroup_oom_kill() {
+        use crate::{
+            events::NopEventManager,
+            executors::{Cgroup, CgroupLimits, ExitKind},
+        };
+
+        let limits = CgroupLimits::new().memory_max(32 << 20);
+        if let Err(err) = Cgroup::new(&limits) {
+            log::warn!(
+                "Skipping the cgroup test, cannot create a cgroup with a memory limit: {err}"
+            );
+            return;
+        }
+
+        let run = |script: &str| {
+            let mut executor = CommandExecutor::builder()
+                .program("sh")
+                .args(["-c", script])
+                .cgroup(limits.clone())
+                .build(())
+                .unwrap();
+            executor
+                .run_target(
+                    &mut NopFuzzer::new(),
+                    &mut NopState::<NopInput>::new(),
+                    &mut NopEventManager::new(),
+                    &BytesInput::new(vec![]),
+                )
+                .unwrap()
+        };
+
+        // A SIGKILL from elsewhere is a crash, not an OOM kill
+        assert_eq!(run("kill -9 $$"), ExitKind::Crash);
+        // Holding more memory than the cgroup allows gets the shell killed by the OOM killer
+        assert_eq!(
+            run("x=$(head -c 268435456 /dev/zero | tr '\\0' x)"),
+            ExitKind::Oom
+        );
+    }
 }
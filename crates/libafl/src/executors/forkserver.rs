@@ -18,8 +18,10 @@ use std::{
     process::{Child, Command, Stdio},
 };
 
+#[cfg(any(feature = "regex", target_os = "linux"))]
+use libafl_bolts::tuples::Handle;
 #[cfg(feature = "regex")]
-use libafl_bolts::tuples::{Handle, Handled};
+use libafl_bolts::tuples::Handled;
 use libafl_bolts::{
     AsSlice, AsSliceMut, InputLocation, StdTargetArgs, StdTargetArgsInner, Truncate,
     core_affinity::CoreId,
@@ -40,6 +42,10 @@ use nix::{
 };
 
 use super::{HasTimeout, StdChildArgs, StdChildArgsInner};
+#[cfg(target_os = "linux")]
+use super::{cgroup::Cgroup, sandbox::Sandbox};
+#[cfg(target_os = "linux")]
+use crate::observers::HangStackObserver;
 #[cfg(feature = "regex")]
 use crate::observers::{
     AsanBacktraceObserver, get_asan_runtime_flags, get_asan_runtime_flags_with_log_path,
@@ -114,7 +120,7 @@ pub const FS_ERROR_OLD_CMPLOG_QEMU: i32 = 64_u32 as i32;
 pub const FS_OPT_ERROR: i32 = 0xf800008f_u32 as i32;
 
 /// Forkserver message. We'll reuse it in a testcase.
-const FAILED_TO_START_FORKSERVER_MSG: &str = "Failed to start forkserver";
+pub(crate) const FAILED_TO_START_FORKSERVER_MSG: &str = "Failed to start forkserver";
 
 fn report_error_and_exit(status: i32) -> Result<(), Error> {
     /* Report on the error received via the forkserver controller and exit */
@@ -201,6 +207,19 @@ pub trait ConfigTarget {
 
     /// Bind children to a single core
     fn bind(&mut self, core: CoreId) -> &mut Self;
+
+    /// Moves the children into the given [`Cgroup`] before `exec`.
+    ///
+    /// For a forkserver target, this is the forkserver itself, and all processes it forks inherit the cgroup.
+    ///
+    /// # Safety
+    /// The [`Cgroup`] must outlive the spawned children, its `cgroup.procs` file descriptor is used in the child.
+    #[cfg(target_os = "linux")]
+    unsafe fn set_cgroup(&mut self, cgroup: &Cgroup) -> &mut Self;
+
+    /// Runs the children in the given namespace [`Sandbox`]
+    #[cfg(target_os = "linux")]
+    fn set_sandbox(&mut self, sandbox: &Sandbox) -> &mut Self;
 }
 
 impl ConfigTarget for Command {
@@ -326,6 +345,35 @@ impl ConfigTarget for Command {
         // This calls our non-shady function from above.
         unsafe { self.pre_exec(func) }
     }
+
+    #[cfg(target_os = "linux")]
+    unsafe fn set_cgroup(&mut self, cgroup: &Cgroup) -> &mut Self {
+        let procs_fd = cgroup.procs_fd();
+        let func = move || {
+            // # Safety
+            // The fd is valid as long as the cgroup lives. Writing `0` moves the calling process.
+            let ret = unsafe { libc::write(procs_fd, b"0".as_ptr().cast(), 1) };
+            if ret < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        };
+        // # Safety
+        // This calls our non-shady function from above.
+        unsafe { self.pre_exec(func) }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn set_sandbox(&mut self, sandbox: &Sandbox) -> &mut Self {
+        // The closure owns a reference to the sandbox, keeping its descriptors open
+        let sandbox = sandbox.clone();
+        // # Safety
+        // The closure runs in the freshly forked child.
+        let func = move || unsafe { sandbox.enter() };
+        // # Safety
+        // This calls our non-shady function from above.
+        unsafe { self.pre_exec(func) }
+    }
 }
 
 /// The [`Forkserver`] is communication channel with a child process that forks on request of the fuzzer.
@@ -346,6 +394,9 @@ pub struct Forkserver {
     last_run_timed_out: i32,
     /// The signal this [`Forkserver`] will use to kill (defaults to [`self.kill_signal`])
     kill_signal: Signal,
+    /// The cgroup the forkserver and its children run in, removed after the processes were killed on drop
+    #[cfg(target_os = "linux")]
+    cgroup: Option<Cgroup>,
 }
 
 impl Drop for Forkserver {
@@ -412,6 +463,8 @@ impl Forkserver {
         stderr_memfd: Option<RawFd>,
         cwd: Option<PathBuf>,
         core: Option<CoreId>,
+        #[cfg(target_os = "linux")] cgroup: Option<Cgroup>,
+        #[cfg(target_os = "linux")] sandbox: Option<&Sandbox>,
     ) -> Result<Self, Error> {
         let Some(coverage_map_size) = coverage_map_size else {
             return Err(Error::unknown(
@@ -478,6 +531,21 @@ impl Forkserver {
             command.bind(core);
         }
 
+        #[cfg(target_os = "linux")]
+        if let Some(cgroup) = &cgroup {
+            // # Safety
+            // The cgroup is owned by the forkserver and outlives its processes.
+            unsafe {
+                command.set_cgroup(cgroup);
+            }
+        }
+
+        // After the cgroup, as it is joined with the credentials of the host
+        #[cfg(target_os = "linux")]
+        if let Some(sandbox) = sandbox {
+            command.set_sandbox(sandbox);
+        }
+
         command.env(AFL_MAP_SIZE_ENV_VAR, format!("{coverage_map_size}"));
 
         // Persistent, deferred forkserver
@@ -548,9 +616,25 @@ impl Forkserver {
             status: 0,
             last_run_timed_out: 0,
             kill_signal,
+            #[cfg(target_os = "linux")]
+            cgroup,
         })
     }
 
+    /// The cgroup the forkserver and its children run in, if any
+    #[cfg(target_os = "linux")]
+    #[must_use]
+    pub fn cgroup(&self) -> Option<&Cgroup> {
+        self.cgroup.as_ref()
+    }
+
+    /// The mutable cgroup the forkserver and its children run in, if any
+    #[cfg(target_os = "linux")]
+    #[must_use]
+    pub fn cgroup_mut(&mut self) -> Option<&mut Cgroup> {
+        self.cgroup.as_mut()
+    }
+
     /// If the last run timed out (as in-target i32)
     #[must_use]
     pub fn last_run_timed_out_raw(&self) -> i32 {
@@ -710,6 +794,8 @@ pub struct ForkserverExecutor<I, OT, S, SHM> {
     max_input_size: usize,
     #[cfg(feature = "regex")]
     asan_obs: Handle<AsanBacktraceObserver>,
+    #[cfg(target_os = "linux")]
+    hang_stack_obs: Option<Handle<HangStackObserver<'static>>>,
     timeout: TimeSpec,
     crash_exitcode: Option<i8>,
 }
@@ -810,8 +896,21 @@ where
     /// Execute input, but side-step the execution counter.
     #[inline]
     fn execute_input_uncounted(&mut self, input: &[u8]) -> Result<ExitKind, Error> {
-        let mut exit_kind = ExitKind::Ok;
+        self.start_child(input)?;
 
+        let timeout = self.timeout;
+        if let Some(exit_kind) = self.wait_child_timed(&timeout)? {
+            Ok(exit_kind)
+        } else {
+            self.kill_timed_out_child()
+        }
+    }
+
+    /// Requests a new child for the given input from the forkserver, without waiting for it to finish.
+    ///
+    /// This is for targets which receive (more of) their input while running, for example over the network.
+    /// Collect the result of the child with [`Self::wait_child`] or [`Self::stop_child`] afterwards.
+    pub fn start_child(&mut self, input: &[u8]) -> Result<Pid, Error> {
         let last_run_timed_out = self.forkserver.last_run_timed_out_raw();
 
         let mut input_size = input.len();
@@ -850,40 +949,96 @@ where
             ));
         }
 
-        self.forkserver.set_child_pid(Pid::from_raw(pid));
+        let pid = Pid::from_raw(pid);
+        self.forkserver.set_child_pid(pid);
+        Ok(pid)
+    }
 
-        if let Some(status) = self.forkserver.read_st_timed(&self.timeout)? {
-            self.forkserver.set_status(status);
-            let exitcode_is_crash = if let Some(crash_exitcode) = self.crash_exitcode {
-                (libc::WEXITSTATUS(self.forkserver().status()) as i8) == crash_exitcode
-            } else {
-                false
-            };
-            if libc::WIFSIGNALED(self.forkserver().status()) || exitcode_is_crash {
-                exit_kind = ExitKind::Crash;
-                #[cfg(feature = "regex")]
-                if let Some(asan_observer) = self.observers.get_mut(&self.asan_obs) {
-                    asan_observer.parse_asan_output_from_asan_log_file(pid)?;
-                }
+    /// Waits up to `timeout` for the child started with [`Self::start_child`] to finish.
+    ///
+    /// Returns `None` if the child is still running.
+    pub fn wait_child(&mut self, timeout: Duration) -> Result<Option<ExitKind>, Error> {
+        self.wait_child_timed(&TimeSpec::from_duration(timeout))
+    }
+
+    /// Stops the child started with [`Self::start_child`] with the given signal, at the end of its session.
+    ///
+    /// If the child was terminated by this signal, the run is considered [`ExitKind::Ok`]; if the child does not
+    /// finish within the timeout of this executor, it is killed and the run is a [`ExitKind::Timeout`].
+    pub fn stop_child(&mut self, signal: Signal) -> Result<ExitKind, Error> {
+        let _ = kill(self.forkserver().child_pid(), signal);
+        let timeout = self.timeout;
+        match self.wait_child_timed(&timeout)? {
+            Some(ExitKind::Crash)
+                if libc::WIFSIGNALED(self.forkserver().status())
+                    && libc::WTERMSIG(self.forkserver().status()) == signal as i32 =>
+            {
+                Ok(ExitKind::Ok)
             }
-        } else {
-            self.forkserver.set_last_run_timed_out(true);
+            Some(exit_kind) => Ok(exit_kind),
+            None => self.kill_timed_out_child(),
+        }
+    }
 
-            // We need to kill the child in case he has timed out, or we can't get the correct pid in the next call to self.executor.forkserver_mut().read_st()?
-            let _ = kill(self.forkserver().child_pid(), self.forkserver.kill_signal);
-            if let Err(err) = self.forkserver.read_st() {
-                return Err(Error::unknown(format!(
-                    "Could not kill timed-out child: {err:?}"
-                )));
+    fn wait_child_timed(&mut self, timeout: &TimeSpec) -> Result<Option<ExitKind>, Error> {
+        let Some(status) = self.forkserver.read_st_timed(timeout)? else {
+            return Ok(None);
+        };
+
+        let mut exit_kind = ExitKind::Ok;
+        self.forkserver.set_status(status);
+        let exitcode_is_crash = if let Some(crash_exitcode) = self.crash_exitcode {
+            (libc::WEXITSTATUS(self.forkserver().status()) as i8) == crash_exitcode
+        } else {
+            false
+        };
+        #[cfg(target_os = "linux")]
+        let oom_killed = libc::WIFSIGNALED(self.forkserver().status())
+            && self
+                .forkserver
+                .cgroup_mut()
+                .is_some_and(Cgroup::take_oom_kill);
+        #[cfg(not(target_os = "linux"))]
+        let oom_killed = false;
+        if oom_killed {
+            exit_kind = ExitKind::Oom;
+        } else if libc::WIFSIGNALED(self.forkserver().status()) || exitcode_is_crash {
+            exit_kind = ExitKind::Crash;
+            #[cfg(feature = "regex")]
+            if let Some(asan_observer) = self.observers.get_mut(&self.asan_obs) {
+                asan_observer
+                    .parse_asan_output_from_asan_log_file(self.forkserver.child_pid().as_raw())?;
             }
-            exit_kind = ExitKind::Timeout;
         }
 
         if !libc::WIFSTOPPED(self.forkserver().status()) {
             self.forkserver.reset_child_pid();
         }
+        Ok(Some(exit_kind))
+    }
 
-        Ok(exit_kind)
+    fn kill_timed_out_child(&mut self) -> Result<ExitKind, Error> {
+        self.forkserver.set_last_run_timed_out(true);
+
+        #[cfg(target_os = "linux")]
+        if let Some(hang_stack_obs) = &self.hang_stack_obs
+            && let Some(hang_stack_observer) = self.observers.get_mut(hang_stack_obs)
+        {
+            hang_stack_observer.sample(self.forkserver.child_pid());
+        }
+
+        // We need to kill the child in case he has timed out, or we can't get the correct pid in the next call to self.executor.forkserver_mut().read_st()?
+        let _ = kill(self.forkserver().child_pid(), self.forkserver.kill_signal);
+        if let Err(err) = self.forkserver.read_st() {
+            return Err(Error::unknown(format!(
+                "Could not kill timed-out child: {err:?}"
+            )));
+        }
+
+        if !libc::WIFSTOPPED(self.forkserver().status()) {
+            self.forkserver.reset_child_pid();
+        }
+        Ok(ExitKind::Timeout)
     }
 }
 
@@ -995,6 +1150,8 @@ where
                 .asan_obs
                 .clone()
                 .unwrap_or(AsanBacktraceObserver::default().handle()),
+            #[cfg(target_os = "linux")]
+            hang_stack_obs: self.child_env_inner.hang_stack_observer.clone(),
             crash_exitcode: self.crash_exitcode,
         })
     }
@@ -1055,6 +1212,8 @@ where
                 .asan_obs
                 .clone()
                 .unwrap_or(AsanBacktraceObserver::default().handle()),
+            #[cfg(target_os = "linux")]
+            hang_stack_obs: self.child_env_inner.hang_stack_observer.clone(),
             crash_exitcode: self.crash_exitcode,
         })
     }
@@ -1079,6 +1238,11 @@ where
                     "forkserver doesn't support argument mutation",
                 ));
             }
+            InputLocation::Preload { .. } => {
+                return Err(Error::illegal_argument(
+                    "forkserver doesn't support preloaded inputs, use its shared memory input delivery instead",
+                ));
+            }
             InputLocation::File { out_file } => out_file.clone(),
         };
 
@@ -1100,6 +1264,17 @@ where
             }
         };
 
+        #[cfg(target_os = "linux")]
+        let sandbox = match &self.child_env_inner.sandbox {
+            Some(config) if config.has_pid_namespace() => {
+                return Err(Error::illegal_argument(
+                    "The forkserver does not support PID namespaces, as it reports the pids of its children in the namespace. Only the CommandExecutor supports SandboxConfig::pid_namespace",
+                ));
+            }
+            Some(config) => Some(Sandbox::new(config, Some(&input_file.path))?),
+            None => None,
+        };
+
         let mut forkserver = match &self.target_inner.program {
             Some(t) => Forkserver::new(
                 t.clone(),
@@ -1131,6 +1306,14 @@ where
                 }),
                 self.child_env_inner.current_directory.clone(),
                 self.child_env_inner.core,
+                #[cfg(target_os = "linux")]
+                self.child_env_inner
+                    .cgroup
+                    .as_ref()
+                    .map(Cgroup::new)
+                    .transpose()?,
+                #[cfg(target_os = "linux")]
+                sandbox.as_ref(),
             )?,
             None => {
                 return Err(Error::illegal_argument(
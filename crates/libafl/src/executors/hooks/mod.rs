@@ -16,6 +16,10 @@ pub mod inprocess_fork;
 /// The hook for inprocess executor
 pub mod inprocess;
 
+/// Restores the writable memory of the target after each run
+#[cfg(all(feature = "std", target_os = "linux"))]
+pub mod snapshot;
+
 /// Timer-related stuff
 #[cfg(feature = "std")]
 pub mod timer;
@@ -0,0 +1,608 @@
+//! The [`SnapshotHook`](crate::executors::hooks::snapshot::SnapshotHook) restores the writable memory of a natively compiled target after each run.
+//!
+//! It snapshots the writable mappings of the target once, lets the kernel track the pages written during each run,
+//! and copies back only those pages after the run.
+//! This resets global state the harness leaks between runs, without the cost of a `fork` per execution.
+//!
+//! Written pages are tracked with asynchronous `userfaultfd` write protection where available, else with the
+//! soft-dirty bits of `/proc/self/pagemap`. Without either, all snapshotted pages are restored after each run.
+//!
+//! The fuzzer runs in the same process, so only the mappings of the target may be snapshotted, never the whole heap.
+//! Exclude the coverage maps if they live in a snapshotted mapping, else the coverage is reset before it is observed.
+
+use alloc::{string::String, vec::Vec};
+use core::ops::Range;
+use std::{
+    fs::{self, File, OpenOptions},
+    io::Write,
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+        unix::fs::FileExt,
+    },
+};
+
+use crate::{Error, executors::hooks::ExecutorHook};
+
+/// The soft-dirty bit of a `/proc/self/pagemap` entry
+const PAGEMAP_SOFT_DIRTY: u64 = 1 << 55;
+/// The value to write to `/proc/self/clear_refs` to clear the soft-dirty bits of all pages
+const CLEAR_SOFT_DIRTY: &[u8] = b"4";
+
+const UFFD_API: u64 = 0xaa;
+const UFFD_USER_MODE_ONLY: libc::c_int = 1;
+const UFFD_FEATURE_WP_UNPOPULATED: u64 = 1 << 13;
+const UFFD_FEATURE_WP_ASYNC: u64 = 1 << 15;
+const UFFDIO_REGISTER_MODE_WP: u64 = 1 << 1;
+const UFFDIO_WRITEPROTECT_MODE_WP: u64 = 1 << 0;
+const UFFDIO_API: libc::Ioctl = 0xc018_aa3f;
+const UFFDIO_REGISTER: libc::Ioctl = 0xc020_aa00;
+const UFFDIO_WRITEPROTECT: libc::Ioctl = 0xc018_aa06;
+const PAGEMAP_SCAN: libc::Ioctl = 0xc060_6610;
+/// The `PAGE_IS_WRITTEN` category of `PAGEMAP_SCAN`
+const PAGE_IS_WRITTEN: u64 = 1 << 1;
+
+/// The snapshot of a range of writable memory
+#[derive(Debug)]
+struct SnapshotRange {
+    start: usize,
+    data: Vec<u8>,
+}
+
+/// Builder for the [`SnapshotHook`]
+#[derive(Debug, Default)]
+pub struct SnapshotHookBuilder {
+    modules: Vec<String>,
+    ranges: Vec<Range<usize>>,
+    excluded: Vec<Range<usize>>,
+    track_dirty_pages: bool,
+}
+
+impl SnapshotHookBuilder {
+    /// Creates a new [`SnapshotHookBuilder`], tracking dirty pages by default
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            track_dirty_pages: true,
+            ..Self::default()
+        }
+    }
+
+    /// Snapshots the writable mappings of the module whose path ends with `name`, including its `.bss`
+    #[must_use]
+    pub fn module<S: Into<String>>(mut self, name: S) -> Self {
+        self.modules.push(name.into());
+        self
+    }
+
+    /// Snapshots the pages overlapping the given address range
+    #[must_use]
+    pub fn range(mut self, range: Range<usize>) -> Self {
+        self.ranges.push(range);
+        self
+    }
+
+    /// Never restores the pages overlapping the given address range, such as the ones of coverage maps
+    #[must_use]
+    pub fn exclude(mut self, range: Range<usize>) -> Self {
+        self.excluded.push(range);
+        self
+    }
+
+    /// Sets whether to only restore the pages dirtied during a run, instead of all snapshotted pages.
+    ///
+    /// Dirty page tracking is disabled anyway if the kernel supports neither `userfaultfd` write protection,
+    /// nor soft-dirty bits.
+    #[must_use]
+    pub fn track_dirty_pages(mut self, track_dirty_pages: bool) -> Self {
+        self.track_dirty_pages = track_dirty_pages;
+        self
+    }
+
+    /// Builds the [`SnapshotHook`], taking the snapshot right away.
+    ///
+    /// Build it after the target was initialized, as runs start over from this state.
+    pub fn build(self) -> Result<SnapshotHook, Error> {
+        let page_size = page_size()?;
+        let mut pages = Vec::new();
+
+        if !self.modules.is_empty() {
+            let mut module_end = None;
+            for line in fs::read_to_string("/proc/self/maps")?.lines() {
+                let mut fields = line.split_whitespace();
+                let (Some(range), Some(perms)) = (fields.next(), fields.next()) else {
+                    continue;
+                };
+                let path = fields.nth(3);
+                let Some((start, end)) = range.split_once('-') else {
+                    continue;
+                };
+                let start = usize::from_str_radix(start, 16)
+                    .map_err(|_| Error::illegal_state(format!("Unexpected mapping {line}")))?;
+                let end = usize::from_str_radix(end, 16)
+                    .map_err(|_| Error::illegal_state(format!("Unexpected mapping {line}")))?;
+
+                let in_module = path.is_some_and(|path| {
+                    self.modules
+                        .iter()
+                        .any(|module| path.ends_with(module.as_str()))
+                });
+                // The `.bss` of a module continues in an anonymous mapping right after its file mapping
+                let is_bss = path.is_none() && module_end == Some(start);
+                module_end = if in_module { Some(end) } else { None };
+
+                if (in_module || is_bss) && perms.starts_with("rw") {
+                    pages.push(start..end);
+                }
+            }
+            if pages.is_empty() {
+                return Err(Error::illegal_argument(format!(
+                    "No writable mappings of the modules {:?}",
+                    self.modules
+                )));
+            }
+        }
+        for range in self.ranges {
+            pages.push(range.start & !(page_size - 1)..range.end.next_multiple_of(page_size));
+        }
+
+        let mut excluded: Vec<_> = self
+            .excluded
+            .iter()
+            .map(|range| range.start & !(page_size - 1)..range.end.next_multiple_of(page_size))
+            .collect();
+        excluded.sort_by_key(|range| range.start);
+        pages.sort_by_key(|range| range.start);
+
+        let mut merged: Vec<Range<usize>> = Vec::with_capacity(pages.len());
+        for range in pages {
+            match merged.last_mut() {
+                Some(last) if last.end >= range.start => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+
+        let mut ranges = Vec::new();
+        for range in merged {
+            let mut start = range.start;
+            for exclude in &excluded {
+                if exclude.end <= start || exclude.start >= range.end {
+                    continue;
+                }
+                if exclude.start > start {
+                    ranges.push(SnapshotRange::take(start..exclude.start));
+                }
+                start = exclude.end;
+            }
+            if start < range.end {
+                ranges.push(SnapshotRange::take(start..range.end));
+            }
+        }
+
+        let mut hook = SnapshotHook {
+            ranges,
+            page_size,
+            pagemap: File::open("/proc/self/pagemap")?,
+            tracking: DirtyPageTracking::None,
+            uffd: None,
+            clear_refs: None,
+            entries: Vec::new(),
+            regions: vec![PageRegion::default(); 64],
+            dirty: Vec::new(),
+            restored_pages: 0,
+        };
+        if self.track_dirty_pages {
+            match hook.setup_userfaultfd() {
+                Ok(uffd) => {
+                    hook.uffd = Some(uffd);
+                    hook.tracking = DirtyPageTracking::Userfaultfd;
+                }
+                Err(uffd_err) => match hook.probe_soft_dirty() {
+                    Ok(clear_refs) => {
+                        hook.clear_refs = Some(clear_refs);
+                        hook.tracking = DirtyPageTracking::SoftDirty;
+                    }
+                    Err(soft_dirty_err) => log::warn!(
+                        "No dirty page tracking, restoring all snapshotted pages. userfaultfd: {uffd_err}, soft-dirty: {soft_dirty_err}"
+                    ),
+                },
+            }
+        }
+        Ok(hook)
+    }
+}
+
+impl SnapshotRange {
+    /// Copies the current content of the range
+    fn take(range: Range<usize>) -> Self {
+        // # Safety
+        // The range is a writable, thus readable, mapping of this process.
+        let data = unsafe { core::slice::from_raw_parts(range.start as *const u8, range.len()) };
+        Self {
+            start: range.start,
+            data: data.to_vec(),
+        }
+    }
+
+    /// The address range of the snapshot
+    fn range(&self) -> Range<usize> {
+        self.start..self.start + self.data.len()
+    }
+}
+
+/// How the [`SnapshotHook`] finds the pages written during a run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DirtyPageTracking {
+    /// No tracking, all snapshotted pages get restored
+    None,
+    /// Asynchronous `userfaultfd` write protection, queried with the `PAGEMAP_SCAN` ioctl, needs Linux 6.7
+    Userfaultfd,
+    /// The soft-dirty bits of `/proc/self/pagemap`, needs `CONFIG_MEM_SOFT_DIRTY`
+    SoftDirty,
+}
+
+/// `struct uffdio_api` of `linux/userfaultfd.h`
+#[repr(C)]
+struct UffdioApi {
+    api: u64,
+    features: u64,
+    ioctls: u64,
+}
+
+/// `struct uffdio_register` of `linux/userfaultfd.h`
+#[repr(C)]
+struct UffdioRegister {
+    start: u64,
+    len: u64,
+    mode: u64,
+    ioctls: u64,
+}
+
+/// `struct uffdio_writeprotect` of `linux/userfaultfd.h`
+#[repr(C)]
+struct UffdioWriteprotect {
+    start: u64,
+    len: u64,
+    mode: u64,
+}
+
+/// `struct page_region` of `linux/fs.h`
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct PageRegion {
+    start: u64,
+    end: u64,
+    categories: u64,
+}
+
+/// `struct pm_scan_arg` of `linux/fs.h`
+#[repr(C)]
+#[derive(Default)]
+struct PmScanArg {
+    size: u64,
+    flags: u64,
+    start: u64,
+    end: u64,
+    walk_end: u64,
+    vec: u64,
+    vec_len: u64,
+    max_pages: u64,
+    category_inverted: u64,
+    category_mask: u64,
+    category_anyof_mask: u64,
+    return_mask: u64,
+}
+
+/// Restores the snapshotted writable memory of the target after each run, see the [module docs](self)
+#[derive(Debug)]
+pub struct SnapshotHook {
+    ranges: Vec<SnapshotRange>,
+    page_size: usize,
+    pagemap: File,
+    tracking: DirtyPageTracking,
+    /// The `userfaultfd` write protecting the snapshotted pages, for [`DirtyPageTracking::Userfaultfd`]
+    uffd: Option<OwnedFd>,
+    /// `/proc/self/clear_refs`, for [`DirtyPageTracking::SoftDirty`]
+    clear_refs: Option<File>,
+    /// Buffer for the pagemap entries of a range
+    entries: Vec<u8>,
+    /// Buffer for the written regions returned by `PAGEMAP_SCAN`
+    regions: Vec<PageRegion>,
+    /// The dirty pages of the range being restored
+    dirty: Vec<Range<usize>>,
+    restored_pages: usize,
+}
+
+impl SnapshotHook {
+    /// Creates a new [`SnapshotHookBuilder`]
+    #[must_use]
+    pub fn builder() -> SnapshotHookBuilder {
+        SnapshotHookBuilder::new()
+    }
+
+    /// The number of pages restored after the last run
+    #[must_use]
+    pub fn restored_pages(&self) -> usize {
+        self.restored_pages
+    }
+
+    /// How the pages written during a run are found
+    #[must_use]
+    pub fn dirty_page_tracking(&self) -> DirtyPageTracking {
+        self.tracking
+    }
+
+    /// The total size of the snapshot, in bytes
+    #[must_use]
+    pub fn snapshot_size(&self) -> usize {
+        self.ranges.iter().map(|range| range.data.len()).sum()
+    }
+
+    /// Write protects the snapshotted pages with a `userfaultfd`, resolving the faults asynchronously in the kernel
+    fn setup_userfaultfd(&mut self) -> Result<OwnedFd, Error> {
+        // # Safety
+        // Creating a new fd, no preconditions.
+        let fd = unsafe {
+            libc::syscall(
+                libc::SYS_userfaultfd,
+                libc::O_CLOEXEC | libc::O_NONBLOCK | UFFD_USER_MODE_ONLY,
+            )
+        };
+        if fd < 0 {
+            return Err(Error::last_os_error("Failed to create a userfaultfd"));
+        }
+        // # Safety
+        // The fd was just created, we own it.
+        let uffd = unsafe { OwnedFd::from_raw_fd(fd as RawFd) };
+
+        let mut api = UffdioApi {
+            api: UFFD_API,
+            features: UFFD_FEATURE_WP_ASYNC | UFFD_FEATURE_WP_UNPOPULATED,
+            ioctls: 0,
+        };
+        ioctl(uffd.as_raw_fd(), UFFDIO_API, &raw mut api, "UFFDIO_API")?;
+        for range in &self.ranges {
+            let mut register = UffdioRegister {
+                start: range.start as u64,
+                len: range.data.len() as u64,
+                mode: UFFDIO_REGISTER_MODE_WP,
+                ioctls: 0,
+            };
+            ioctl(
+                uffd.as_raw_fd(),
+                UFFDIO_REGISTER,
+                &raw mut register,
+                "UFFDIO_REGISTER",
+            )?;
+            write_protect(&uffd, range.range())?;
+        }
+
+        // Make sure `PAGEMAP_SCAN` is supported, too
+        if let Some(range) = self.ranges.first() {
+            self.dirty.clear();
+            self.scan_written(range.range())?;
+        }
+        Ok(uffd)
+    }
+
+    /// Checks that the kernel marks written pages as soft-dirty
+    fn probe_soft_dirty(&mut self) -> Result<File, Error> {
+        let mut clear_refs = OpenOptions::new()
+            .write(true)
+            .open("/proc/self/clear_refs")?;
+        let mut probe = vec![0_u8; 2 * self.page_size];
+        let offset = probe.as_ptr().addr().next_multiple_of(self.page_size) - probe.as_ptr().addr();
+
+        clear_refs.write_all(CLEAR_SOFT_DIRTY)?;
+        // # Safety
+        // The page is within the probe buffer.
+        let page = unsafe { probe.as_mut_ptr().add(offset) };
+        unsafe {
+            page.write_volatile(1);
+        }
+        let mut entry = [0_u8; 8];
+        self.pagemap
+            .read_exact_at(&mut entry, (page.addr() / self.page_size * 8) as u64)?;
+        drop(probe);
+
+        if u64::from_ne_bytes(entry) & PAGEMAP_SOFT_DIRTY == 0 {
+            return Err(Error::unsupported(
+                "Written pages are not marked soft-dirty, is CONFIG_MEM_SOFT_DIRTY enabled?",
+            ));
+        }
+        Ok(clear_refs)
+    }
+
+    /// Clears the soft-dirty bits, falling back to restoring all pages on failure
+    fn clear_soft_dirty(&mut self) {
+        if let Some(clear_refs) = &mut self.clear_refs
+            && let Err(err) = clear_refs.write_all(CLEAR_SOFT_DIRTY)
+        {
+            log::error!(
+                "Failed to clear the soft-dirty bits, restoring all pages from now on: {err}"
+            );
+            self.clear_refs = None;
+            self.tracking = DirtyPageTracking::None;
+        }
+    }
+
+    /// Adds the pages of `range` written since they were write protected to the dirty pages
+    fn scan_written(&mut self, range: Range<usize>) -> Result<(), Error> {
+        let mut start = range.start as u64;
+        let end = range.end as u64;
+        while start < end {
+            let mut arg = PmScanArg {
+                size: size_of::<PmScanArg>() as u64,
+                start,
+                end,
+                vec: self.regions.as_mut_ptr().addr() as u64,
+                vec_len: self.regions.len() as u64,
+                category_mask: PAGE_IS_WRITTEN,
+                return_mask: PAGE_IS_WRITTEN,
+                ..PmScanArg::default()
+            };
+            let found = ioctl(
+                self.pagemap.as_raw_fd(),
+                PAGEMAP_SCAN,
+                &raw mut arg,
+                "PAGEMAP_SCAN",
+            )?;
+            for region in &self.regions[..found] {
+                self.dirty.push(region.start as usize..region.end as usize);
+            }
+            start = arg.walk_end;
+        }
+        Ok(())
+    }
+
+    /// Adds the soft-dirty pages of `range` to the dirty pages
+    fn scan_soft_dirty(&mut self, range: Range<usize>) -> Result<(), Error> {
+        let page_size = self.page_size;
+        self.entries.resize(range.len() / page_size * 8, 0);
+        self.pagemap
+            .read_exact_at(&mut self.entries, (range.start / page_size * 8) as u64)?;
+        for (page, entry) in self.entries.chunks_exact(8).enumerate() {
+            if u64::from_ne_bytes(entry.try_into().unwrap()) & PAGEMAP_SOFT_DIRTY != 0 {
+                let start = range.start + page * page_size;
+                match self.dirty.last_mut() {
+                    Some(last) if last.end == start => last.end += page_size,
+                    _ => self.dirty.push(start..start + page_size),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Copies the snapshotted content back into the dirtied pages
+    fn restore(&mut self) {
+        self.restored_pages = 0;
+        for idx in 0..self.ranges.len() {
+            let range = self.ranges[idx].range();
+            self.dirty.clear();
+            let scanned = match self.tracking {
+                DirtyPageTracking::None => Ok(()),
+                DirtyPageTracking::Userfaultfd => self.scan_written(range.clone()),
+                DirtyPageTracking::SoftDirty => self.scan_soft_dirty(range.clone()),
+            };
+            if self.tracking == DirtyPageTracking::None {
+                self.dirty.push(range.clone());
+            } else if let Err(err) = scanned {
+                log::error!("Failed to find the dirty pages, restoring all of them: {err}");
+                self.dirty.clear();
+                self.dirty.push(range.clone());
+            }
+
+            let snapshot = &self.ranges[idx];
+            for dirty in &self.dirty {
+                // # Safety
+                // The dirty pages are part of a writable mapping of the target, which we snapshotted before.
+                unsafe {
+                    core::ptr::copy_nonoverlapping(
+                        snapshot.data.as_ptr().add(dirty.start - snapshot.start),
+                        dirty.start as *mut u8,
+                        dirty.len(),
+                    );
+                }
+                self.restored_pages += dirty.len() / self.page_size;
+
+                if let Some(uffd) = &self.uffd
+                    && let Err(err) = write_protect(uffd, dirty.clone())
+                {
+                    log::error!("Failed to write protect the restored pages: {err}");
+                }
+            }
+        }
+    }
+}
+
+impl<I, S> ExecutorHook<I, S> for SnapshotHook {
+    fn init(&mut self, _state: &mut S) {}
+
+    fn pre_exec(&mut self, _state: &mut S, _input: &I) {
+        self.clear_soft_dirty();
+    }
+
+    fn post_exec(&mut self, _state: &mut S, _input: &I) {
+        self.restore();
+    }
+}
+
+/// Write protects the pages of `range`, registered with the `uffd` before
+fn write_protect(uffd: &OwnedFd, range: Range<usize>) -> Result<(), Error> {
+    let mut writeprotect = UffdioWriteprotect {
+        start: range.start as u64,
+        len: range.len() as u64,
+        mode: UFFDIO_WRITEPROTECT_MODE_WP,
+    };
+    ioctl(
+        uffd.as_raw_fd(),
+        UFFDIO_WRITEPROTECT,
+        &raw mut writeprotect,
+        "UFFDIO_WRITEPROTECT",
+    )?;
+    Ok(())
+}
+
+/// Calls the `ioctl` on `fd` with the given argument, returning its non-negative result
+fn ioctl<T>(fd: RawFd, request: libc::Ioctl, arg: *mut T, name: &str) -> Result<usize, Error> {
+    // # Safety
+    // The argument has the layout the request expects.
+    let ret = unsafe { libc::ioctl(fd, request, arg) };
+    usize::try_from(ret).map_err(|_| Error::last_os_error(format!("{name} failed")))
+}
+
+/// Gets the page size of this system
+fn page_size() -> Result<usize, Error> {
+    // # Safety
+    // `sysconf` has no preconditions.
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    usize::try_from(page_size).map_err(|_| Error::last_os_error("Failed to get the page size"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DirtyPageTracking, SnapshotHook, page_size};
+    use crate::executors::hooks::ExecutorHook;
+
+    #[repr(C, align(65536))]
+    struct Pages([u8; 3 * 65536]);
+
+    static mut PAGES: Pages = Pages([0; 3 * 65536]);
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_snapshot_restore() {
+        let page_size = page_size().unwrap();
+        let pages =
+            unsafe { core::slice::from_raw_parts_mut((&raw mut PAGES.0).cast::<u8>(), 3 * 65536) };
+        pages[0] = 1;
+        let start = pages.as_ptr().addr();
+
+        let mut hook = SnapshotHook::builder()
+            .range(start..start + 3 * page_size)
+            .exclude(start + 2 * page_size..start + 3 * page_size)
+            .build()
+            .unwrap();
+        assert_eq!(hook.snapshot_size(), 2 * page_size);
+
+        ExecutorHook::<(), ()>::pre_exec(&mut hook, &mut (), &());
+        pages[page_size] = 2;
+        pages[2 * page_size] = 3;
+        ExecutorHook::<(), ()>::post_exec(&mut hook, &mut (), &());
+
+        assert_eq!(pages[0], 1);
+        assert_eq!(pages[page_size], 0);
+        assert_eq!(pages[2 * page_size], 3);
+        if hook.dirty_page_tracking() == DirtyPageTracking::None {
+            assert_eq!(hook.restored_pages(), 2);
+        } else {
+            assert_eq!(hook.restored_pages(), 1);
+        }
+
+        // The restored pages are tracked again
+        ExecutorHook::<(), ()>::pre_exec(&mut hook, &mut (), &());
+        pages[0] = 4;
+        ExecutorHook::<(), ()>::post_exec(&mut hook, &mut (), &());
+        assert_eq!(pages[0], 1);
+        assert_eq!(pages[page_size], 0);
+    }
+}
@@ -34,6 +34,9 @@ use crate::{
 pub mod inner;
 /// A version of `InProcessExecutor` with a state accessible from the harness.
 pub mod stateful;
+/// A version of `InProcessExecutor` running a thread-safe harness on several worker threads.
+#[cfg(all(unix, feature = "std"))]
+pub mod threaded;
 
 /// The process executor simply calls a target function, as mutable reference to a closure.
 pub type InProcessExecutor<'a, EM, H, I, OT, S, Z> =
@@ -42,6 +45,20 @@ pub type InProcessExecutor<'a, EM, H, I, OT, S, Z> =
 /// The inprocess executor that allows hooks
 pub type HookableInProcessExecutor<'a, EM, H, HT, I, OT, S, Z> =
     GenericInProcessExecutor<EM, H, &'a mut H, HT, I, OT, S, Z>;
+/// The inprocess executor restoring the writable memory of the target after each run, see [`SnapshotHook`]
+///
+/// [`SnapshotHook`]: crate::executors::hooks::snapshot::SnapshotHook
+#[cfg(all(feature = "std", target_os = "linux"))]
+pub type InProcessSnapshotExecutor<'a, EM, H, I, OT, S, Z> = HookableInProcessExecutor<
+    'a,
+    EM,
+    H,
+    (crate::executors::hooks::snapshot::SnapshotHook, ()),
+    I,
+    OT,
+    S,
+    Z,
+>;
 /// The process executor simply calls a target function, as boxed `FnMut` trait object
 pub type OwnedInProcessExecutor<EM, I, OT, S, Z> = GenericInProcessExecutor<
     EM,
@@ -0,0 +1,737 @@
+//! The [`InProcessThreadedExecutor`](crate::executors::inprocess::threaded::InProcessThreadedExecutor) runs a thread-safe harness on several worker threads of the fuzzer process.
+//!
+//! All workers share the target state of the process and feed a single fuzzer state, instead of one process per
+//! core, each with its own copy of the target state. Each worker records its coverage in its own map, and the
+//! observations of each input are restored into the observers before it gets evaluated.
+//! Use it with the [`BatchMutationalStage`](crate::stages::BatchMutationalStage) to keep all workers busy.
+//!
+//! Crashes and panics are attributed to the input of the worker they happened on. Like for the
+//! [`InProcessExecutor`](crate::executors::InProcessExecutor), the objective is saved and the process exits, so it
+//! should be paired with a restarting event manager.
+
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
+use core::{
+    cell::Cell,
+    ffi::c_void,
+    fmt::{self, Debug, Formatter},
+    marker::PhantomData,
+    mem,
+    num::NonZeroUsize,
+    ptr::{self, null, null_mut, write_volatile},
+    slice,
+    sync::atomic::{AtomicBool, AtomicPtr, Ordering, compiler_fence},
+    time::Duration,
+};
+use std::{
+    panic,
+    sync::{
+        Once,
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    },
+    thread::{self, JoinHandle},
+    time::Instant,
+};
+
+#[cfg(not(miri))]
+use libafl_bolts::os::unix_signals::setup_signal_handler;
+use libafl_bolts::{
+    AsSliceMut,
+    minibsod::generate_minibsod_to_vec,
+    os::unix_signals::{Signal, ucontext_t},
+    ownedref::OwnedMutSlice,
+    tuples::RefIndexable,
+};
+use libc::siginfo_t;
+
+use crate::{
+    Error,
+    events::{EventFirer, EventRestarter},
+    executors::{
+        BatchExecutor, Executor, ExitKind, HasObservers, HasTimeout,
+        hooks::inprocess::{GLOBAL_STATE, InProcessExecutorHandlerData},
+        inprocess::run_observers_and_save_state,
+    },
+    feedbacks::Feedback,
+    fuzzer::HasObjective,
+    inputs::Input,
+    observers::ObserversTuple,
+    state::{HasCorpus, HasCurrentTestcase, HasExecutions, HasSolutions},
+};
+
+std::thread_local! {
+    /// The id of the worker running on this thread, if any
+    static WORKER_ID: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+/// Set by the first thread saving an objective, the others wait for it to exit the process
+static SAVING_OBJECTIVE: AtomicBool = AtomicBool::new(false);
+
+/// The [`worker_panic_handler`] of the executor in the target right now, null outside of the target
+static PANIC_HANDLER: AtomicPtr<()> = AtomicPtr::new(null_mut());
+
+/// Installs the panic hook only once, for all executors, whatever their type parameters are
+static PANIC_HOOK: Once = Once::new();
+
+/// The size of the signal stack of each worker, the one of `std` is too small for the crash handler
+const WORKER_SIGNAL_STACK_SIZE: usize = 2 << 22;
+
+/// The coverage maps of the workers of an [`InProcessThreadedExecutor`]
+#[derive(Debug)]
+pub struct WorkerMaps {
+    workers: NonZeroUsize,
+    map: OwnedMutSlice<'static, u8>,
+    redirect: unsafe fn(*mut u8),
+}
+
+impl WorkerMaps {
+    /// Creates the description of the coverage maps of `workers` workers.
+    ///
+    /// `map` is the map the map observer reads. Each worker gets its own map of the same size, and calls `redirect`
+    /// on it once, which has to make the instrumentation of the calling thread record its coverage there instead.
+    /// For the sancov `pc_guard` instrumentation, use `thread_edges_worker_maps` of `libafl_targets`.
+    #[must_use]
+    pub fn new(
+        workers: NonZeroUsize,
+        map: OwnedMutSlice<'static, u8>,
+        redirect: unsafe fn(*mut u8),
+    ) -> Self {
+        Self {
+            workers,
+            map,
+            redirect,
+        }
+    }
+}
+
+/// The part of a worker the signal handlers need
+#[derive(Debug)]
+struct WorkerSlot {
+    /// The input the worker runs right now, null while it waits for the next one
+    input: AtomicPtr<c_void>,
+    /// The coverage map of the worker
+    map: AtomicPtr<u8>,
+}
+
+/// The executor running a thread-safe harness on several worker threads, see the [module docs](self)
+pub struct InProcessThreadedExecutor<EM, H, I, OT, S, Z> {
+    observers: OT,
+    /// The map the observers read, the coverage of the workers is copied there
+    map: OwnedMutSlice<'static, u8>,
+    /// The coverage maps of all workers, one after another
+    worker_maps: *mut [u8],
+    slots: Arc<[WorkerSlot]>,
+    jobs: Vec<Sender<I>>,
+    results: Receiver<(usize, ExitKind)>,
+    threads: Vec<JoinHandle<()>>,
+    timeout: Duration,
+    phantom: PhantomData<(EM, H, S, Z)>,
+}
+
+impl<EM, H, I, OT, S, Z> Debug for InProcessThreadedExecutor<EM, H, I, OT, S, Z>
+where
+    OT: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InProcessThreadedExecutor")
+            .field("observers", &self.observers)
+            .field("workers", &self.jobs.len())
+            .field("timeout", &self.timeout)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<EM, H, I, OT, S, Z> InProcessThreadedExecutor<EM, H, I, OT, S, Z>
+where
+    H: Fn(&I) -> ExitKind + Send + Sync + 'static,
+    I: Input + Send + 'static,
+    OT: ObserversTuple<I, S>,
+    EM: EventFirer<I, S> + EventRestarter<S>,
+    S: HasExecutions + HasSolutions<I> + HasCorpus<I> + HasCurrentTestcase<I>,
+    Z: HasObjective,
+    Z::Objective: Feedback<EM, I, OT, S>,
+{
+    /// Creates a new [`InProcessThreadedExecutor`], and starts its workers.
+    ///
+    /// Each execution, on any worker, may take at most `timeout`.
+    /// This may return an error if the signal handler setup fails.
+    pub fn new(
+        harness_fn: H,
+        observers: OT,
+        _fuzzer: &mut Z,
+        _state: &mut S,
+        _event_mgr: &mut EM,
+        timeout: Duration,
+        worker_maps: WorkerMaps,
+    ) -> Result<Self, Error> {
+        let WorkerMaps {
+            workers,
+            map,
+            redirect,
+        } = worker_maps;
+        let map_len = map.as_ref().len();
+        let worker_maps = Box::into_raw(vec![0_u8; workers.get() * map_len].into_boxed_slice());
+
+        let slots: Arc<[WorkerSlot]> = (0..workers.get())
+            .map(|worker| WorkerSlot {
+                input: AtomicPtr::new(null_mut()),
+                // # Safety
+                // The maps of the workers are within the allocation.
+                map: AtomicPtr::new(unsafe { worker_maps.cast::<u8>().add(worker * map_len) }),
+            })
+            .collect();
+
+        let harness_fn = Arc::new(harness_fn);
+        let (result_sender, results) = mpsc::channel();
+        let mut jobs = Vec::with_capacity(workers.get());
+        let mut threads = Vec::with_capacity(workers.get());
+        for worker in 0..workers.get() {
+            let (job_sender, job_receiver) = mpsc::channel();
+            let harness_fn = harness_fn.clone();
+            let slots = slots.clone();
+            let result_sender = result_sender.clone();
+            let thread = thread::Builder::new()
+                .name(format!("libafl-worker-{worker}"))
+                .spawn(move || {
+                    worker_loop(
+                        worker,
+                        &*harness_fn,
+                        &slots[worker],
+                        map_len,
+                        redirect,
+                        &job_receiver,
+                        &result_sender,
+                    );
+                })?;
+            jobs.push(job_sender);
+            threads.push(thread);
+        }
+
+        setup_worker_panic_hook();
+        // # Safety
+        // Setting up the signal handlers with a pointer to the `GLOBAL_STATE`, which is only dereferenced on crashes.
+        #[cfg(not(miri))]
+        unsafe {
+            setup_signal_handler(&raw mut GLOBAL_STATE)?;
+        }
+        compiler_fence(Ordering::SeqCst);
+
+        Ok(Self {
+            observers,
+            map,
+            worker_maps,
+            slots,
+            jobs,
+            results,
+            threads,
+            timeout,
+            phantom: PhantomData,
+        })
+    }
+
+    /// The number of workers
+    #[must_use]
+    pub fn workers(&self) -> usize {
+        self.jobs.len()
+    }
+
+    /// Sets the global pointers used by the crash handler
+    ///
+    /// # Safety
+    /// The pointers are only valid until [`Self::leave_target`] is called.
+    unsafe fn enter_target(&mut self, fuzzer: &mut Z, state: &mut S, mgr: &mut EM) {
+        // # Safety
+        // This writes pointers to global state, only accessed in the signal handlers.
+        unsafe {
+            let data = &raw mut GLOBAL_STATE;
+            assert!((*data).crash_handler.is_null());
+            write_volatile(
+                &raw mut (*data).crash_handler,
+                worker_crash_handler::<EM, H, I, OT, S, Z> as *const c_void,
+            );
+            write_volatile(
+                &raw mut (*data).executor_ptr,
+                ptr::from_ref(self) as *const c_void,
+            );
+            write_volatile(
+                &raw mut (*data).state_ptr,
+                ptr::from_mut(state) as *mut c_void,
+            );
+            write_volatile(
+                &raw mut (*data).event_mgr_ptr,
+                ptr::from_mut(mgr) as *mut c_void,
+            );
+            write_volatile(
+                &raw mut (*data).fuzzer_ptr,
+                ptr::from_mut(fuzzer) as *mut c_void,
+            );
+            compiler_fence(Ordering::SeqCst);
+        }
+        PANIC_HANDLER.store(
+            worker_panic_handler::<EM, H, I, OT, S, Z> as unsafe fn(usize) as *mut (),
+            Ordering::Release,
+        );
+    }
+
+    /// Resets the global pointers used by the crash handler
+    fn leave_target() {
+        PANIC_HANDLER.store(null_mut(), Ordering::Release);
+        // # Safety
+        // We set the global pointers to null, no direct safety concerns arise.
+        unsafe {
+            let data = &raw mut GLOBAL_STATE;
+            write_volatile(&raw mut (*data).crash_handler, null());
+            write_volatile(&raw mut (*data).executor_ptr, null());
+            compiler_fence(Ordering::SeqCst);
+        }
+    }
+
+    /// Saves the input of a crashed or timed-out worker as objective, if no other thread does already.
+    ///
+    /// Never returns if another thread saves its objective, as that one exits the process afterwards.
+    fn save_worker_objective(
+        &mut self,
+        worker: usize,
+        input: &I,
+        fuzzer: &mut Z,
+        state: &mut S,
+        mgr: &mut EM,
+        exit_kind: ExitKind,
+    ) {
+        if SAVING_OBJECTIVE.swap(true, Ordering::AcqRel) {
+            loop {
+                thread::park();
+            }
+        }
+        if exit_kind == ExitKind::Timeout {
+            // The hanging worker still writes to its map, so its coverage cannot be read
+            self.map.as_slice_mut().fill(0);
+        } else {
+            self.load_worker_map(worker);
+        }
+        run_observers_and_save_state::<Self, EM, I, Z::Objective, S, Z>(
+            self, state, input, fuzzer, mgr, exit_kind,
+        );
+    }
+}
+
+impl<EM, H, I, OT, S, Z> InProcessThreadedExecutor<EM, H, I, OT, S, Z> {
+    /// Copies the coverage map of a worker into the map of the observers
+    fn load_worker_map(&mut self, worker: usize) {
+        let map = self.map.as_slice_mut();
+        // # Safety
+        // The worker is idle or stopped, it does not write to its map.
+        let worker_map = unsafe {
+            slice::from_raw_parts(self.slots[worker].map.load(Ordering::Acquire), map.len())
+        };
+        map.copy_from_slice(worker_map);
+    }
+}
+
+impl<EM, H, I, OT, S, Z> Executor<EM, I, S, Z> for InProcessThreadedExecutor<EM, H, I, OT, S, Z>
+where
+    H: Fn(&I) -> ExitKind + Send + Sync + 'static,
+    I: Input + Send + 'static,
+    OT: ObserversTuple<I, S>,
+    EM: EventFirer<I, S> + EventRestarter<S>,
+    S: HasExecutions + HasSolutions<I> + HasCorpus<I> + HasCurrentTestcase<I>,
+    Z: HasObjective,
+    Z::Objective: Feedback<EM, I, OT, S>,
+{
+    fn run_target(
+        &mut self,
+        fuzzer: &mut Z,
+        state: &mut S,
+        mgr: &mut EM,
+        input: &I,
+    ) -> Result<ExitKind, Error> {
+        let exit_kind = self.run_batch(fuzzer, state, mgr, slice::from_ref(input))?[0];
+        self.load_worker_map(0);
+        Ok(exit_kind)
+    }
+}
+
+impl<EM, H, I, OT, S, Z> BatchExecutor<EM, I, S, Z>
+    for InProcessThreadedExecutor<EM, H, I, OT, S, Z>
+where
+    H: Fn(&I) -> ExitKind + Send + Sync + 'static,
+    I: Input + Send + 'static,
+    OT: ObserversTuple<I, S>,
+    EM: EventFirer<I, S> + EventRestarter<S>,
+    S: HasExecutions + HasSolutions<I> + HasCorpus<I> + HasCurrentTestcase<I>,
+    Z: HasObjective,
+    Z::Objective: Feedback<EM, I, OT, S>,
+{
+    fn batch_size(&self) -> usize {
+        self.workers()
+    }
+
+    fn run_batch(
+        &mut self,
+        fuzzer: &mut Z,
+        state: &mut S,
+        mgr: &mut EM,
+        inputs: &[I],
+    ) -> Result<Vec<ExitKind>, Error> {
+        if inputs.len() > self.workers() {
+            return Err(Error::illegal_argument(format!(
+                "Cannot run {} inputs at once on {} workers",
+                inputs.len(),
+                self.workers()
+            )));
+        }
+        *state.executions_mut() += inputs.len() as u64;
+
+        // # Safety
+        // The pointers stay valid until we leave the target below, or exit the process.
+        unsafe {
+            self.enter_target(fuzzer, state, mgr);
+        }
+        for (jobs, input) in self.jobs.iter().zip(inputs) {
+            if jobs.send(input.clone()).is_err() {
+                Self::leave_target();
+                return Err(Error::illegal_state(
+                    "A worker of the InProcessThreadedExecutor is gone",
+                ));
+            }
+        }
+
+        let deadline = Instant::now() + self.timeout;
+        let mut exit_kinds = vec![None; inputs.len()];
+        for _ in inputs {
+            match self
+                .results
+                .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+            {
+                Ok((worker, exit_kind)) => exit_kinds[worker] = Some(exit_kind),
+                Err(RecvTimeoutError::Timeout) => {
+                    let worker = exit_kinds.iter().position(Option::is_none).unwrap();
+                    log::error!("Timeout in fuzz run of worker {worker}.");
+                    self.save_worker_objective(
+                        worker,
+                        &inputs[worker],
+                        fuzzer,
+                        state,
+                        mgr,
+                        ExitKind::Timeout,
+                    );
+                    log::info!("Exiting");
+                    // # Safety
+                    // The hanging worker cannot be stopped, start over in a new process.
+                    unsafe {
+                        libc::_exit(55);
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    Self::leave_target();
+                    return Err(Error::illegal_state(
+                        "The workers of the InProcessThreadedExecutor are gone",
+                    ));
+                }
+            }
+        }
+        Self::leave_target();
+
+        Ok(exit_kinds.into_iter().map(Option::unwrap).collect())
+    }
+
+    fn restore_batch_observation(&mut self, idx: usize) {
+        self.load_worker_map(idx);
+    }
+}
+
+impl<EM, H, I, OT, S, Z> HasObservers for InProcessThreadedExecutor<EM, H, I, OT, S, Z> {
+    type Observers = OT;
+
+    #[inline]
+    fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+        RefIndexable::from(&self.observers)
+    }
+
+    #[inline]
+    fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+        RefIndexable::from(&mut self.observers)
+    }
+}
+
+impl<EM, H, I, OT, S, Z> HasTimeout for InProcessThreadedExecutor<EM, H, I, OT, S, Z> {
+    #[inline]
+    fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    #[inline]
+    fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+}
+
+impl<EM, H, I, OT, S, Z> Drop for InProcessThreadedExecutor<EM, H, I, OT, S, Z> {
+    fn drop(&mut self) {
+        // Without jobs, the workers leave their loop
+        self.jobs.clear();
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+        // # Safety
+        // The maps were allocated in `new`, and the workers are gone.
+        drop(unsafe { Box::from_raw(self.worker_maps) });
+    }
+}
+
+/// Runs the jobs of a worker, until the executor is gone
+fn worker_loop<H, I>(
+    worker: usize,
+    harness_fn: &H,
+    slot: &WorkerSlot,
+    map_len: usize,
+    redirect: unsafe fn(*mut u8),
+    jobs: &Receiver<I>,
+    results: &Sender<(usize, ExitKind)>,
+) where
+    H: Fn(&I) -> ExitKind,
+{
+    WORKER_ID.set(Some(worker));
+    let mut signal_stack = vec![0_u8; WORKER_SIGNAL_STACK_SIZE].into_boxed_slice();
+    set_signal_stack(signal_stack.as_mut_ptr(), signal_stack.len());
+    let map = slot.map.load(Ordering::Acquire);
+    // # Safety
+    // The map has the size of the map of the observers, and outlives this thread.
+    unsafe {
+        redirect(map);
+    }
+
+    while let Ok(input) = jobs.recv() {
+        // # Safety
+        // Only this worker writes to its map, the executor reads it after the result was sent.
+        unsafe {
+            map.write_bytes(0, map_len);
+        }
+        slot.input
+            .store(ptr::from_ref(&input) as *mut c_void, Ordering::Release);
+        let exit_kind = harness_fn(&input);
+        slot.input.store(null_mut(), Ordering::Release);
+
+        if results.send((worker, exit_kind)).is_err() {
+            break;
+        }
+    }
+
+    set_signal_stack(null_mut(), 0);
+}
+
+/// Sets the signal stack of the current thread, or disables it for a null `stack`
+fn set_signal_stack(stack: *mut u8, size: usize) {
+    let stack = libc::stack_t {
+        ss_sp: stack.cast(),
+        ss_flags: if stack.is_null() { libc::SS_DISABLE } else { 0 },
+        ss_size: size,
+    };
+    // # Safety
+    // The stack outlives its use, it is disabled before it is freed.
+    if unsafe { libc::sigaltstack(&raw const stack, null_mut()) } != 0 {
+        log::warn!(
+            "Failed to set the signal stack of a worker: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+}
+
+/// Invokes the [`worker_panic_handler`] of the executor in the target in case of a panic on a worker thread
+fn setup_worker_panic_hook() {
+    PANIC_HOOK.call_once(|| {
+        let old_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |panic_info| {
+            old_hook(panic_info);
+            let Some(worker) = WORKER_ID.get() else {
+                return;
+            };
+            let handler = PANIC_HANDLER.load(Ordering::Acquire);
+            if handler.is_null() {
+                return;
+            }
+            // # Safety
+            // The handler was set by the executor in the target, for its own type parameters.
+            unsafe {
+                let handler = mem::transmute::<*mut (), unsafe fn(usize)>(handler);
+                handler(worker);
+            }
+        }));
+    });
+}
+
+/// Saves the input of a panicking worker as objective, and exits the process.
+///
+/// # Safety
+/// The executor in the [`GLOBAL_STATE`] has to be an [`InProcessThreadedExecutor`] with the same type parameters.
+unsafe fn worker_panic_handler<EM, H, I, OT, S, Z>(worker: usize)
+where
+    H: Fn(&I) -> ExitKind + Send + Sync + 'static,
+    I: Input + Send + 'static,
+    OT: ObserversTuple<I, S>,
+    EM: EventFirer<I, S> + EventRestarter<S>,
+    S: HasExecutions + HasSolutions<I> + HasCorpus<I> + HasCurrentTestcase<I>,
+    Z: HasObjective,
+    Z::Objective: Feedback<EM, I, OT, S>,
+{
+    // # Safety
+    // The panicking worker is stopped, and the executor waits for its result, or is gone already.
+    unsafe {
+        let data = &raw mut GLOBAL_STATE;
+        if (*data).executor_ptr.is_null() {
+            return;
+        }
+        let executor = (*data).executor_mut::<InProcessThreadedExecutor<EM, H, I, OT, S, Z>>();
+        let input = executor.slots[worker].input.load(Ordering::Acquire) as *const I;
+        if let Some(input) = input.as_ref() {
+            log::error!("Worker {worker} panicked!");
+            executor.save_worker_objective(
+                worker,
+                input,
+                (*data).fuzzer_mut::<Z>(),
+                (*data).state_mut::<S>(),
+                (*data).event_mgr_mut::<EM>(),
+                ExitKind::Crash,
+            );
+            libc::_exit(128 + 6); // SIGABRT exit code
+        }
+    }
+}
+
+/// Crash handler of the [`InProcessThreadedExecutor`], attributing the crash to the input of the crashed worker.
+///
+/// # Safety
+/// Well, signal handling is not safe
+#[allow(clippy::needless_pass_by_value)] // nightly no longer requires this
+unsafe fn worker_crash_handler<EM, H, I, OT, S, Z>(
+    signal: Signal,
+    info: &mut siginfo_t,
+    context: Option<&mut ucontext_t>,
+    data: &mut InProcessExecutorHandlerData,
+) where
+    H: Fn(&I) -> ExitKind + Send + Sync + 'static,
+    I: Input + Send + 'static,
+    OT: ObserversTuple<I, S>,
+    EM: EventFirer<I, S> + EventRestarter<S>,
+    S: HasExecutions + HasSolutions<I> + HasCorpus<I> + HasCurrentTestcase<I>,
+    Z: HasObjective,
+    Z::Objective: Feedback<EM, I, OT, S>,
+{
+    unsafe {
+        let input = WORKER_ID.get().and_then(|worker| {
+            let executor = data.executor_mut::<InProcessThreadedExecutor<EM, H, I, OT, S, Z>>();
+            let input = executor.slots[worker].input.load(Ordering::Acquire) as *const I;
+            input.as_ref().map(|input| (worker, executor, input))
+        });
+        let Some((worker, executor, input)) = input else {
+            log::error!(
+                "Crashed with {signal}, but not in a worker running the target... Bug in the fuzzer? Exiting."
+            );
+            libc::_exit(128 + (signal as i32));
+        };
+        log::error!("Worker {worker} crashed with {signal}!");
+        if let Ok(bsod) = generate_minibsod_to_vec(signal, info, context.as_deref())
+            && let Ok(bsod) = core::str::from_utf8(&bsod)
+        {
+            log::error!("input: {:?}\n{bsod}", input.generate_name(None));
+        }
+        executor.save_worker_objective(
+            worker,
+            input,
+            data.fuzzer_mut::<Z>(),
+            data.state_mut::<S>(),
+            data.event_mgr_mut::<EM>(),
+            ExitKind::Crash,
+        );
+        libc::_exit(128 + (signal as i32));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+    use core::{num::NonZeroUsize, slice, time::Duration};
+
+    use libafl_bolts::{ownedref::OwnedMutSlice, rands::XkcdRand, tuples::tuple_list};
+
+    use super::{InProcessThreadedExecutor, WorkerMaps};
+    use crate::{
+        StdFuzzer,
+        corpus::InMemoryCorpus,
+        events::NopEventManager,
+        executors::{BatchExecutor, ExitKind},
+        feedbacks::CrashFeedback,
+        inputs::BytesInput,
+        schedulers::RandScheduler,
+        state::{HasExecutions, NopState, StdState},
+    };
+
+    std::thread_local! {
+        static TEST_MAP: core::cell::Cell<*mut u8> = const { core::cell::Cell::new(core::ptr::null_mut()) };
+    }
+
+    unsafe fn redirect(map: *mut u8) {
+        TEST_MAP.set(map);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_threaded_exec() {
+        static mut MAP: [u8; 16] = [0; 16];
+
+        // Each input marks its first byte in the map of its worker
+        let harness = |input: &BytesInput| {
+            let byte = input.as_ref()[0] as usize;
+            unsafe {
+                TEST_MAP.get().add(byte % 16).write(1);
+            }
+            ExitKind::Ok
+        };
+        let rand = XkcdRand::new();
+        let corpus = InMemoryCorpus::<BytesInput>::new();
+        let solutions = InMemoryCorpus::new();
+        let mut objective = CrashFeedback::new();
+        let mut feedback = tuple_list!();
+        let sche: RandScheduler<NopState<BytesInput>> = RandScheduler::new();
+        let mut mgr = NopEventManager::new();
+        let mut state =
+            StdState::new(rand, corpus, solutions, &mut feedback, &mut objective).unwrap();
+        let mut fuzzer = StdFuzzer::new(sche, feedback, objective);
+
+        let map = unsafe { OwnedMutSlice::from_raw_parts_mut(&raw mut MAP as *mut u8, 16) };
+        let mut executor = InProcessThreadedExecutor::new(
+            harness,
+            tuple_list!(),
+            &mut fuzzer,
+            &mut state,
+            &mut mgr,
+            Duration::from_secs(5),
+            WorkerMaps::new(NonZeroUsize::new(4).unwrap(), map, redirect),
+        )
+        .unwrap();
+
+        let inputs: Vec<_> = (0..4_u8).map(|i| BytesInput::new(vec![i])).collect();
+        let exit_kinds = executor
+            .run_batch(&mut fuzzer, &mut state, &mut mgr, &inputs)
+            .unwrap();
+        assert_eq!(exit_kinds, [ExitKind::Ok; 4]);
+        assert_eq!(*state.executions(), 4);
+
+        for idx in 0..4 {
+            executor.restore_batch_observation(idx);
+            let map = unsafe { slice::from_raw_parts(&raw const MAP as *const u8, 16) };
+            assert_eq!(map.iter().position(|&v| v != 0), Some(idx));
+            assert_eq!(map.iter().filter(|&&v| v != 0).count(), 1);
+        }
+
+        assert!(
+            executor
+                .run_batch(
+                    &mut fuzzer,
+                    &mut state,
+                    &mut mgr,
+                    &[inputs.as_slice(), &inputs].concat()
+                )
+                .is_err()
+        );
+    }
+}
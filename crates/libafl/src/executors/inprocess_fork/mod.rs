@@ -390,4 +390,58 @@ mod tests {
             .run_target(&mut fuzzer, &mut state, &mut mgr, &input)
             .unwrap();
     }
+
+    #[test]
+    #[serial]
+    #[cfg_attr(miri, ignore)]
+    #[cfg(feature = "regex")]
+    fn test_inprocessfork_backtrace_observer() {
+        use core::time::Duration;
+
+        use libafl_bolts::{
+            ownedref::OwnedRefMut,
+            shmem::{ShMemProvider, StdShMemProvider},
+            tuples::Handled,
+        };
+
+        use crate::{
+            events::SimpleEventManager,
+            executors::{HasObservers, InProcessForkExecutor},
+            fuzzer::NopFuzzer,
+            observers::{BacktraceObserver, HarnessType, ObserverWithHashField},
+            state::NopState,
+        };
+
+        let mut provider = StdShMemProvider::new().unwrap();
+        let mut backtrace = provider.new_on_shmem::<Option<u64>>(None).unwrap();
+        let observer = BacktraceObserver::new(
+            "BacktraceObserver",
+            unsafe { OwnedRefMut::from_shmem(&mut backtrace) },
+            HarnessType::Child,
+        );
+        let handle = observer.handle();
+
+        let mut harness = |_buf: &NopInput| -> ExitKind { unsafe { libc::abort() } };
+        let mut fuzzer = NopFuzzer::new();
+        let mut state = NopState::new();
+        let mut mgr: SimpleEventManager<NopInput, _, NopState<NopInput>> =
+            SimpleEventManager::printing();
+        let mut executor = InProcessForkExecutor::new(
+            &mut harness,
+            tuple_list!(observer),
+            &mut fuzzer,
+            &mut state,
+            &mut mgr,
+            Duration::from_secs(5),
+            provider,
+        )
+        .unwrap();
+
+        let exit_kind = executor
+            .run_target(&mut fuzzer, &mut state, &mut mgr, &NopInput {})
+            .unwrap();
+        assert_eq!(exit_kind, ExitKind::Crash);
+        // The child hashes the stack of the crash, the parent reads the hash through shared memory
+        assert!(executor.observers()[&handle].hash().is_some());
+    }
 }
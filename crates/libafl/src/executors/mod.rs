@@ -5,6 +5,8 @@ use core::{fmt::Debug, time::Duration};
 #[cfg(feature = "std")]
 use std::path::PathBuf;
 
+#[cfg(all(feature = "std", target_os = "linux"))]
+pub use cgroup::{Cgroup, CgroupLimits};
 pub use combined::CombinedExecutor;
 #[cfg(feature = "std")]
 pub use command::CommandExecutor;
@@ -12,6 +14,10 @@ pub use differential::DiffExecutor;
 #[cfg(all(feature = "std", feature = "fork", unix))]
 pub use forkserver::{Forkserver, ForkserverExecutor};
 pub use inprocess::InProcessExecutor;
+#[cfg(all(feature = "std", target_os = "linux"))]
+pub use inprocess::InProcessSnapshotExecutor;
+#[cfg(all(unix, feature = "std"))]
+pub use inprocess::threaded::InProcessThreadedExecutor;
 #[cfg(all(feature = "std", feature = "fork", unix))]
 pub use inprocess_fork::InProcessForkExecutor;
 #[cfg(unix)]
@@ -19,14 +25,24 @@ use libafl_bolts::os::unix_signals::Signal;
 use libafl_bolts::tuples::RefIndexable;
 #[cfg(feature = "std")]
 use libafl_bolts::{core_affinity::CoreId, tuples::Handle};
+#[cfg(feature = "std")]
+pub use multi_differential::{MultiDiffExecutor, MultiDiffObserver};
+#[cfg(all(feature = "std", unix))]
+pub use network::NetworkExecutor;
+#[cfg(all(feature = "std", target_os = "linux"))]
+pub use sandbox::{Sandbox, SandboxConfig};
 use serde::{Deserialize, Serialize};
 pub use shadow::ShadowExecutor;
 pub use with_observers::WithObservers;
 
 use crate::Error;
+#[cfg(all(feature = "std", target_os = "linux"))]
+use crate::observers::HangStackObserver;
 #[cfg(feature = "std")]
 use crate::observers::{StdErrObserver, StdOutObserver};
 
+#[cfg(all(feature = "std", target_os = "linux"))]
+pub mod cgroup;
 pub mod combined;
 #[cfg(feature = "std")]
 pub mod command;
@@ -34,9 +50,15 @@ pub mod differential;
 #[cfg(all(feature = "std", feature = "fork", unix))]
 pub mod forkserver;
 pub mod inprocess;
+#[cfg(feature = "std")]
+pub mod multi_differential;
+#[cfg(all(feature = "std", unix))]
+pub mod network;
 pub mod nop;
 /// SAND(<https://github.com/wtdcode/sand-aflpp>) implementation
 pub mod sand;
+#[cfg(all(feature = "std", target_os = "linux"))]
+pub mod sandbox;
 
 /// The module for inproc fork executor
 #[cfg(all(feature = "std", unix))]
@@ -136,6 +158,27 @@ pub trait Executor<EM, I, S, Z> {
     ) -> Result<ExitKind, Error>;
 }
 
+/// An executor running several inputs at once, such as the
+/// [`InProcessThreadedExecutor`].
+pub trait BatchExecutor<EM, I, S, Z>: Executor<EM, I, S, Z> + HasObservers {
+    /// The maximum number of inputs in a batch
+    fn batch_size(&self) -> usize;
+
+    /// Runs up to [`Self::batch_size`] inputs at once, returning their [`ExitKind`]s in order.
+    ///
+    /// The observers are not run, use [`Self::restore_batch_observation`] to evaluate each input afterwards.
+    fn run_batch(
+        &mut self,
+        fuzzer: &mut Z,
+        state: &mut S,
+        mgr: &mut EM,
+        inputs: &[I],
+    ) -> Result<Vec<ExitKind>, Error>;
+
+    /// Restores what the observers saw while running the input at `idx` of the last batch
+    fn restore_batch_observation(&mut self, idx: usize);
+}
+
 /// A trait that allows to get/set an `Executor`'s timeout thresold
 pub trait HasTimeout {
     /// Get a timeout
@@ -249,6 +292,15 @@ pub struct StdChildArgsInner {
     pub debug_child: bool,
     /// Core to bind for the children
     pub core: Option<CoreId>,
+    /// The limits of the cgroup the children run in
+    #[cfg(target_os = "linux")]
+    pub cgroup: Option<CgroupLimits>,
+    /// The namespace sandbox the children run in
+    #[cfg(target_os = "linux")]
+    pub sandbox: Option<SandboxConfig>,
+    /// The observer sampling the stacks of timed-out children
+    #[cfg(target_os = "linux")]
+    pub hang_stack_observer: Option<Handle<HangStackObserver<'static>>>,
 }
 
 #[cfg(feature = "std")]
@@ -261,6 +313,12 @@ impl Default for StdChildArgsInner {
             current_directory: None,
             debug_child: false,
             core: None,
+            #[cfg(target_os = "linux")]
+            cgroup: None,
+            #[cfg(target_os = "linux")]
+            sandbox: None,
+            #[cfg(target_os = "linux")]
+            hang_stack_observer: None,
         }
     }
 }
@@ -322,6 +380,33 @@ pub trait StdChildArgs: Sized {
         self.inner_mut().core = Some(core);
         self
     }
+
+    #[must_use]
+    #[cfg(target_os = "linux")]
+    /// Runs the children in a new cgroup v2 with the given limits.
+    /// For the `ForkserverExecutor`, the forkserver runs in the cgroup together with its children.
+    /// Children killed by its OOM killer are reported as [`ExitKind::Oom`].
+    fn cgroup(mut self, limits: CgroupLimits) -> Self {
+        self.inner_mut().cgroup = Some(limits);
+        self
+    }
+
+    #[must_use]
+    #[cfg(target_os = "linux")]
+    /// Runs the children in new user, mount and network namespaces, see [`SandboxConfig`].
+    /// A PID namespace is opt-in, with [`SandboxConfig::pid_namespace`], and only supported by the [`CommandExecutor`].
+    fn sandbox(mut self, config: SandboxConfig) -> Self {
+        self.inner_mut().sandbox = Some(config);
+        self
+    }
+
+    #[must_use]
+    #[cfg(target_os = "linux")]
+    /// Sets the observer sampling the stacks of timed-out children, before they get killed.
+    fn hang_stack_observer(mut self, hang_stack: Handle<HangStackObserver<'static>>) -> Self {
+        self.inner_mut().hang_stack_observer = Some(hang_stack);
+        self
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,628 @@
+//! Executor for N-way differential fuzzing.
+//!
+//! In contrast to the [`crate::executors::DiffExecutor`], which compares exactly two executors,
+//! the [`MultiDiffExecutor`] runs every executor of an [`ExecutorsTuple`](crate::executors::ExecutorsTuple)-like
+//! list with the same input, and takes a majority vote over their exit kinds and (normalized) `stdout`.
+//! The executors that disagree with the majority are recorded in the [`MultiDiffObserver`].
+use alloc::{borrow::Cow, vec::Vec};
+use core::{cell::UnsafeCell, ptr, time::Duration};
+
+use libafl_bolts::{
+    Named,
+    ownedref::OwnedMutPtr,
+    tuples::{Handle, MatchName, MatchNameRef, RefIndexable},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Error,
+    executors::{Executor, ExitKind, HasObservers, HasTimeout},
+    observers::{Observer, ObserversTuple, OutputNormalizersTuple, StdOutObserver},
+};
+
+/// A list of executors, run one after the other by the [`MultiDiffExecutor`]
+pub trait DiffExecutorsTuple<EM, I, S, Z> {
+    /// Run all executors with the given input, including the pre- and post-exec hooks of their
+    /// observers, and push the resulting [`ExitKind`]s to `exit_kinds`.
+    fn run_diff_all(
+        &mut self,
+        fuzzer: &mut Z,
+        state: &mut S,
+        mgr: &mut EM,
+        input: &I,
+        exit_kinds: &mut Vec<ExitKind>,
+    ) -> Result<(), Error>;
+}
+
+impl<EM, I, S, Z> DiffExecutorsTuple<EM, I, S, Z> for () {
+    fn run_diff_all(
+        &mut self,
+        _fuzzer: &mut Z,
+        _state: &mut S,
+        _mgr: &mut EM,
+        _input: &I,
+        _exit_kinds: &mut Vec<ExitKind>,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<Head, Tail, EM, I, S, Z> DiffExecutorsTuple<EM, I, S, Z> for (Head, Tail)
+where
+    Head: Executor<EM, I, S, Z> + HasObservers,
+    Head::Observers: ObserversTuple<I, S>,
+    Tail: DiffExecutorsTuple<EM, I, S, Z>,
+{
+    fn run_diff_all(
+        &mut self,
+        fuzzer: &mut Z,
+        state: &mut S,
+        mgr: &mut EM,
+        input: &I,
+        exit_kinds: &mut Vec<ExitKind>,
+    ) -> Result<(), Error> {
+        self.0.observers_mut().pre_exec_all(state, input)?;
+        let exit_kind = self.0.run_target(fuzzer, state, mgr, input)?;
+        self.0
+            .observers_mut()
+            .post_exec_all(state, input, &exit_kind)?;
+        exit_kinds.push(exit_kind);
+        self.1.run_diff_all(fuzzer, state, mgr, input, exit_kinds)
+    }
+}
+
+/// A list of executors whose observers can be proxied by the [`MultiDiffExecutor`]
+pub trait ExecutorsObserversTuple {
+    /// Pointers to the observers of all executors
+    type ObserversPtrs: ObserversPtrsTuple;
+
+    /// Get pointers to the observers of all executors.
+    /// They stay valid as long as the executors are neither moved nor dropped.
+    fn observers_ptrs(&self) -> Self::ObserversPtrs;
+
+    /// Set the timeout of all executors
+    fn set_timeout_all(&mut self, timeout: Duration);
+
+    /// The timeout of the first executor, if any
+    fn first_timeout(&self) -> Option<Duration>;
+}
+
+impl ExecutorsObserversTuple for () {
+    type ObserversPtrs = ();
+
+    fn observers_ptrs(&self) -> Self::ObserversPtrs {}
+
+    fn set_timeout_all(&mut self, _timeout: Duration) {}
+
+    fn first_timeout(&self) -> Option<Duration> {
+        None
+    }
+}
+
+impl<Head, Tail> ExecutorsObserversTuple for (Head, Tail)
+where
+    Head: HasObservers + HasTimeout,
+    Head::Observers: MatchName,
+    Tail: ExecutorsObserversTuple,
+{
+    type ObserversPtrs = (OwnedMutPtr<Head::Observers>, Tail::ObserversPtrs);
+
+    fn observers_ptrs(&self) -> Self::ObserversPtrs {
+        (
+            OwnedMutPtr::Ptr(ptr::from_ref(&*self.0.observers()).cast_mut()),
+            self.1.observers_ptrs(),
+        )
+    }
+
+    fn set_timeout_all(&mut self, timeout: Duration) {
+        self.0.set_timeout(timeout);
+        self.1.set_timeout_all(timeout);
+    }
+
+    fn first_timeout(&self) -> Option<Duration> {
+        Some(self.0.timeout())
+    }
+}
+
+/// Pointers to the observers tuples of several executors, searched in order
+pub trait ObserversPtrsTuple {
+    /// Find the first observer with the given type and name in any of the observers tuples
+    fn match_name_ptrs<T>(&self, name: &str) -> Option<&T>;
+
+    /// Find the first observer with the given type and name in any of the observers tuples (mutable)
+    fn match_name_ptrs_mut<T>(&mut self, name: &str) -> Option<&mut T>;
+}
+
+impl ObserversPtrsTuple for () {
+    fn match_name_ptrs<T>(&self, _name: &str) -> Option<&T> {
+        None
+    }
+
+    fn match_name_ptrs_mut<T>(&mut self, _name: &str) -> Option<&mut T> {
+        None
+    }
+}
+
+impl<Head, Tail> ObserversPtrsTuple for (OwnedMutPtr<Head>, Tail)
+where
+    Head: MatchName,
+    Tail: ObserversPtrsTuple,
+{
+    #[expect(deprecated)]
+    fn match_name_ptrs<T>(&self, name: &str) -> Option<&T> {
+        match self.0.as_ref().match_name::<T>(name) {
+            Some(t) => Some(t),
+            None => self.1.match_name_ptrs::<T>(name),
+        }
+    }
+
+    #[expect(deprecated)]
+    fn match_name_ptrs_mut<T>(&mut self, name: &str) -> Option<&mut T> {
+        match self.0.as_mut().match_name_mut::<T>(name) {
+            Some(t) => Some(t),
+            None => self.1.match_name_ptrs_mut::<T>(name),
+        }
+    }
+}
+
+/// Records the outcome of the majority vote of the last [`MultiDiffExecutor`] run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MultiDiffObserver {
+    name: Cow<'static, str>,
+    exit_kinds: Vec<ExitKind>,
+    outliers: Vec<usize>,
+}
+
+impl MultiDiffObserver {
+    /// Create a new [`MultiDiffObserver`] with the given name
+    #[must_use]
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name: Cow::from(name),
+            exit_kinds: Vec::new(),
+            outliers: Vec::new(),
+        }
+    }
+
+    /// The [`ExitKind`] of every executor during the last run, in order
+    #[must_use]
+    pub fn exit_kinds(&self) -> &[ExitKind] {
+        &self.exit_kinds
+    }
+
+    /// The indices of the executors that disagreed with the majority during the last run.
+    /// If there was no majority, all executors are outliers.
+    #[must_use]
+    pub fn outliers(&self) -> &[usize] {
+        &self.outliers
+    }
+
+    /// Returns `true` if any executor disagreed during the last run
+    #[must_use]
+    pub fn is_diff(&self) -> bool {
+        !self.outliers.is_empty()
+    }
+}
+
+impl Named for MultiDiffObserver {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<I, S> Observer<I, S> for MultiDiffObserver {
+    fn pre_exec(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.exit_kinds.clear();
+        self.outliers.clear();
+        Ok(())
+    }
+
+    fn pre_exec_child(&mut self, state: &mut S, input: &I) -> Result<(), Error> {
+        self.pre_exec(state, input)
+    }
+}
+
+/// Proxy the observers of all executors wrapped by a [`MultiDiffExecutor`],
+/// together with its [`MultiDiffObserver`].
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(bound = "P: Serialize + serde::de::DeserializeOwned")]
+pub struct MultiDiffObserversTuple<P> {
+    executors: P,
+    vote: (MultiDiffObserver, ()),
+}
+
+impl<P> MultiDiffObserversTuple<P> {
+    /// The [`MultiDiffObserver`] holding the outcome of the last vote
+    #[must_use]
+    pub fn vote(&self) -> &MultiDiffObserver {
+        &self.vote.0
+    }
+}
+
+impl<P> MatchName for MultiDiffObserversTuple<P>
+where
+    P: ObserversPtrsTuple,
+{
+    #[expect(deprecated)]
+    fn match_name<T>(&self, name: &str) -> Option<&T> {
+        match self.vote.match_name::<T>(name) {
+            Some(t) => Some(t),
+            None => self.executors.match_name_ptrs::<T>(name),
+        }
+    }
+
+    #[expect(deprecated)]
+    fn match_name_mut<T>(&mut self, name: &str) -> Option<&mut T> {
+        match self.vote.match_name_mut::<T>(name) {
+            Some(t) => Some(t),
+            None => self.executors.match_name_ptrs_mut::<T>(name),
+        }
+    }
+}
+
+impl<I, P, S> ObserversTuple<I, S> for MultiDiffObserversTuple<P>
+where
+    P: ObserversPtrsTuple,
+{
+    fn pre_exec_all(&mut self, state: &mut S, input: &I) -> Result<(), Error> {
+        self.vote.pre_exec_all(state, input)
+    }
+
+    fn post_exec_all(
+        &mut self,
+        _state: &mut S,
+        _input: &I,
+        _exit_kind: &ExitKind,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn pre_exec_child_all(&mut self, state: &mut S, input: &I) -> Result<(), Error> {
+        self.vote.pre_exec_child_all(state, input)
+    }
+
+    fn post_exec_child_all(
+        &mut self,
+        _state: &mut S,
+        _input: &I,
+        _exit_kind: &ExitKind,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// A [`MultiDiffExecutor`] runs N executors with the same input and reports the ones that
+/// disagree with the majority.
+///
+/// Two runs agree if they finish with the same [`ExitKind`] and, if `stdout` observers were
+/// given with [`MultiDiffExecutor::with_outputs`], produce the same output after running the
+/// [`OutputNormalizersTuple`]. If any run disagrees, the executor returns [`ExitKind::Diff`] with
+/// the exit kind of the majority as `primary` and the one of the first outlier as `secondary`.
+#[derive(Debug)]
+pub struct MultiDiffExecutor<ET, N>
+where
+    ET: ExecutorsObserversTuple,
+{
+    executors: ET,
+    observers: UnsafeCell<MultiDiffObserversTuple<ET::ObserversPtrs>>,
+    outputs: Vec<Handle<StdOutObserver>>,
+    normalizers: N,
+}
+
+impl<ET> MultiDiffExecutor<ET, ()>
+where
+    ET: ExecutorsObserversTuple,
+{
+    /// Create a new [`MultiDiffExecutor`], comparing the exit kinds of the given executors.
+    /// The outcome of each vote is recorded in the [`MultiDiffObserver`] with the given name.
+    pub fn new(executors: ET, name: &'static str) -> Self {
+        let observers = UnsafeCell::new(MultiDiffObserversTuple {
+            executors: executors.observers_ptrs(),
+            vote: (MultiDiffObserver::new(name), ()),
+        });
+        Self {
+            executors,
+            observers,
+            outputs: Vec::new(),
+            normalizers: (),
+        }
+    }
+}
+
+impl<ET, N> MultiDiffExecutor<ET, N>
+where
+    ET: ExecutorsObserversTuple,
+{
+    /// Also compare the `stdout` of the executors, captured by the given observers.
+    /// The observers must be part of the executors' observers, and need distinct names.
+    #[must_use]
+    pub fn with_outputs(mut self, outputs: Vec<Handle<StdOutObserver>>) -> Self {
+        self.outputs = outputs;
+        self
+    }
+
+    /// Normalize the captured `stdout` with the given [`OutputNormalizersTuple`] before comparing
+    pub fn with_normalizers<N2>(self, normalizers: N2) -> MultiDiffExecutor<ET, N2> {
+        MultiDiffExecutor {
+            executors: self.executors,
+            observers: self.observers,
+            outputs: self.outputs,
+            normalizers,
+        }
+    }
+
+    /// Retrieve the wrapped executors
+    pub fn executors(&mut self) -> &mut ET {
+        &mut self.executors
+    }
+
+    fn refresh_observers(&self) {
+        // # Safety
+        // The pointers are only ever dereferenced through `self.observers`, after refreshing them here.
+        unsafe {
+            (*self.observers.get()).executors = self.executors.observers_ptrs();
+        }
+    }
+}
+
+impl<ET, N> MultiDiffExecutor<ET, N>
+where
+    ET: ExecutorsObserversTuple,
+    N: OutputNormalizersTuple,
+{
+    /// Group the runs by exit kind and normalized output, and return the index of a run of the
+    /// majority, if there is a single largest group, together with the outliers.
+    fn majority_vote(
+        &mut self,
+        exit_kinds: &[ExitKind],
+        outputs: Vec<Option<Vec<u8>>>,
+    ) -> (Option<usize>, Vec<usize>) {
+        let runs: Vec<_> = exit_kinds
+            .iter()
+            .zip(outputs)
+            .map(|(exit_kind, output)| {
+                (
+                    exit_kind,
+                    output.map(|output| self.normalizers.normalize_all(output)),
+                )
+            })
+            .collect();
+        let votes: Vec<usize> = runs
+            .iter()
+            .map(|run| runs.iter().filter(|other| *other == run).count())
+            .collect();
+
+        let max = votes.iter().copied().max().unwrap_or(0);
+        let Some(winner) = votes.iter().position(|votes| *votes == max) else {
+            return (None, Vec::new());
+        };
+        let unique = runs
+            .iter()
+            .zip(&votes)
+            .all(|(run, votes)| *votes != max || *run == runs[winner]);
+        if unique {
+            let outliers = (0..runs.len())
+                .filter(|idx| runs[*idx] != runs[winner])
+                .collect();
+            (Some(winner), outliers)
+        } else {
+            (None, (0..runs.len()).collect())
+        }
+    }
+}
+
+impl<EM, ET, I, N, S, Z> Executor<EM, I, S, Z> for MultiDiffExecutor<ET, N>
+where
+    ET: DiffExecutorsTuple<EM, I, S, Z> + ExecutorsObserversTuple,
+    N: OutputNormalizersTuple,
+{
+    fn run_target(
+        &mut self,
+        fuzzer: &mut Z,
+        state: &mut S,
+        mgr: &mut EM,
+        input: &I,
+    ) -> Result<ExitKind, Error> {
+        let mut exit_kinds = Vec::new();
+        self.executors
+            .run_diff_all(fuzzer, state, mgr, input, &mut exit_kinds)?;
+        if exit_kinds.is_empty() {
+            return Ok(ExitKind::Ok);
+        }
+
+        self.refresh_observers();
+        let observers = self.observers.get_mut();
+        let outputs = if self.outputs.is_empty() {
+            vec![None; exit_kinds.len()]
+        } else {
+            if self.outputs.len() != exit_kinds.len() {
+                return Err(Error::illegal_argument(format!(
+                    "MultiDiffExecutor: got {} stdout observers for {} executors",
+                    self.outputs.len(),
+                    exit_kinds.len()
+                )));
+            }
+            self.outputs
+                .iter()
+                .map(|handle| {
+                    observers
+                        .get(handle)
+                        .map(|observer| observer.output.clone().or_else(|| Some(Vec::new())))
+                        .ok_or_else(|| {
+                            Error::illegal_argument(format!(
+                                "MultiDiffExecutor: stdout observer {} not found",
+                                handle.name()
+                            ))
+                        })
+                })
+                .collect::<Result<Vec<_>, _>>()?
+        };
+
+        let (winner, outliers) = self.majority_vote(&exit_kinds, outputs);
+        let ret = match (winner, outliers.first()) {
+            (Some(winner), None) => exit_kinds[winner],
+            (Some(winner), Some(outlier)) => ExitKind::Diff {
+                primary: exit_kinds[winner].into(),
+                secondary: exit_kinds[*outlier].into(),
+            },
+            // No majority, there are at least two groups of runs
+            (None, _) => ExitKind::Diff {
+                primary: exit_kinds[0].into(),
+                secondary: exit_kinds[1].into(),
+            },
+        };
+
+        let vote = &mut self.observers.get_mut().vote.0;
+        vote.exit_kinds = exit_kinds;
+        vote.outliers = outliers;
+        Ok(ret)
+    }
+}
+
+impl<ET, N> HasTimeout for MultiDiffExecutor<ET, N>
+where
+    ET: ExecutorsObserversTuple,
+{
+    #[inline]
+    fn set_timeout(&mut self, timeout: Duration) {
+        self.executors.set_timeout_all(timeout);
+    }
+
+    #[inline]
+    fn timeout(&self) -> Duration {
+        self.executors.first_timeout().unwrap_or_default()
+    }
+}
+
+impl<ET, N> HasObservers for MultiDiffExecutor<ET, N>
+where
+    ET: ExecutorsObserversTuple,
+{
+    type Observers = MultiDiffObserversTuple<ET::ObserversPtrs>;
+
+    #[inline]
+    fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+        self.refresh_observers();
+        // # Safety
+        // No mutable reference to the observers is alive while `self` is borrowed immutably.
+        unsafe { RefIndexable::from(self.observers.get().as_ref().unwrap()) }
+    }
+
+    #[inline]
+    fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+        self.refresh_observers();
+        RefIndexable::from(self.observers.get_mut())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::borrow::Cow;
+    use core::time::Duration;
+
+    use libafl_bolts::tuples::{Handled, RefIndexable, tuple_list};
+
+    use super::{MultiDiffExecutor, MultiDiffObserver};
+    use crate::{
+        executors::{DiffExitKind, Executor, ExitKind, HasObservers, HasTimeout},
+        inputs::BytesInput,
+        observers::{ObserversTuple, StdOutObserver, WhitespaceNormalizer},
+        state::NopState,
+    };
+
+    /// Reports a fixed exit kind and writes a fixed output to its [`StdOutObserver`]
+    struct MockExecutor {
+        exit_kind: ExitKind,
+        output: &'static [u8],
+        observers: (StdOutObserver, ()),
+    }
+
+    impl MockExecutor {
+        fn new(name: &'static str, exit_kind: ExitKind, output: &'static [u8]) -> Self {
+            Self {
+                exit_kind,
+                output,
+                observers: tuple_list!(StdOutObserver::new_piped(Cow::from(name)).unwrap()),
+            }
+        }
+    }
+
+    impl<EM, I, S, Z> Executor<EM, I, S, Z> for MockExecutor {
+        fn run_target(
+            &mut self,
+            _fuzzer: &mut Z,
+            _state: &mut S,
+            _mgr: &mut EM,
+            _input: &I,
+        ) -> Result<ExitKind, crate::Error> {
+            self.observers.0.observe(self.output.to_vec());
+            Ok(self.exit_kind)
+        }
+    }
+
+    impl HasObservers for MockExecutor {
+        type Observers = (StdOutObserver, ());
+
+        fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+            RefIndexable::from(&self.observers)
+        }
+
+        fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+            RefIndexable::from(&mut self.observers)
+        }
+    }
+
+    impl HasTimeout for MockExecutor {
+        fn timeout(&self) -> Duration {
+            Duration::from_secs(1)
+        }
+
+        fn set_timeout(&mut self, _timeout: Duration) {}
+    }
+
+    #[test]
+    fn test_multi_diff_vote() {
+        let executors = tuple_list!(
+            MockExecutor::new("out_a", ExitKind::Ok, b"1 2 3"),
+            MockExecutor::new("out_b", ExitKind::Ok, b"123\n"),
+            MockExecutor::new("out_c", ExitKind::Ok, b"124"),
+            MockExecutor::new("out_d", ExitKind::Crash, b""),
+        );
+        let outputs = vec![
+            executors.0.observers.0.handle(),
+            executors.1.0.observers.0.handle(),
+            executors.1.1.0.observers.0.handle(),
+            executors.1.1.1.0.observers.0.handle(),
+        ];
+        let mut executor = MultiDiffExecutor::new(executors, "vote")
+            .with_outputs(outputs)
+            .with_normalizers(tuple_list!(WhitespaceNormalizer));
+        let vote = MultiDiffObserver::new("vote").handle();
+
+        let mut state = NopState::<BytesInput>::new();
+        let input = BytesInput::new(vec![0]);
+        executor
+            .observers_mut()
+            .pre_exec_all(&mut state, &input)
+            .unwrap();
+        let exit_kind = executor
+            .run_target(&mut (), &mut state, &mut (), &input)
+            .unwrap();
+        assert_eq!(
+            exit_kind,
+            ExitKind::Diff {
+                primary: DiffExitKind::Ok,
+                secondary: DiffExitKind::Ok,
+            }
+        );
+        assert_eq!(executor.observers()[&vote].outliers(), &[2, 3]);
+
+        // two groups of the same size leave no majority, everyone is an outlier
+        executor.executors().1.1.1.0.exit_kind = ExitKind::Ok;
+        executor.executors().1.1.1.0.output = b"124";
+        executor
+            .run_target(&mut (), &mut state, &mut (), &input)
+            .unwrap();
+        assert_eq!(executor.observers()[&vote].outliers(), &[0, 1, 2, 3]);
+    }
+}
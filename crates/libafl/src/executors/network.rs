@@ -0,0 +1,1231 @@
+//! The [`NetworkExecutor`] fuzzes servers by sending each input as a sequence of messages over a TCP or UDP socket.
+//!
+//! The server is either started for each run, by a [`ServerProcess`] like the [`ForkserverExecutor`]
+//! (so that coverage of the server child is collected as usual) or the [`StdServer`], or already running and attached to with [`StdServer::attach`].
+//! The responses of the server can be captured with a [`ResponseObserver`], or any other observer implementing [`HasResponses`],
+//! like the [`ProtocolStateObserver`](crate::observers::ProtocolStateObserver) for stateful fuzzing.
+
+use alloc::{boxed::Box, vec::Vec};
+use core::{
+    fmt::{self, Debug, Formatter},
+    marker::PhantomData,
+    net::SocketAddr,
+    time::Duration,
+};
+use std::{
+    io::{self, ErrorKind, Read, Write},
+    net::{TcpStream, UdpSocket},
+    os::unix::process::ExitStatusExt,
+    process::{Child, Command, ExitStatus},
+    thread,
+    time::Instant,
+};
+
+#[cfg(feature = "fork")]
+use libafl_bolts::shmem::ShMem;
+use libafl_bolts::{
+    ownedref::OwnedSlice,
+    tuples::{Handle, Handled, RefIndexable},
+};
+use nix::{
+    sys::signal::{Signal, kill},
+    unistd::Pid,
+};
+
+#[cfg(feature = "fork")]
+use crate::executors::ForkserverExecutor;
+#[cfg(feature = "multipart_inputs")]
+use crate::inputs::ListInput;
+use crate::{
+    Error,
+    executors::{Executor, ExitKind, HasObservers, HasTimeout},
+    inputs::{BytesInput, HasTargetBytes},
+    observers::{HasResponses, ObserversTuple, ResponseObserver},
+    state::HasExecutions,
+};
+
+/// The size of the buffer used to receive responses
+const RECV_BUF_SIZE: usize = 64 * 1024;
+
+/// The interval in which the [`StdServer`] polls a spawned server for its exit
+const SERVER_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Inputs which can be sent to a server as a sequence of messages
+pub trait NetworkMessages {
+    /// The messages to send, in order
+    fn network_messages(&self) -> Vec<OwnedSlice<'_, u8>>;
+}
+
+impl NetworkMessages for BytesInput {
+    fn network_messages(&self) -> Vec<OwnedSlice<'_, u8>> {
+        vec![self.target_bytes()]
+    }
+}
+
+/// Every part of the list is one message
+#[cfg(feature = "multipart_inputs")]
+impl<I> NetworkMessages for ListInput<I>
+where
+    I: HasTargetBytes,
+{
+    fn network_messages(&self) -> Vec<OwnedSlice<'_, u8>> {
+        self.parts()
+            .iter()
+            .map(HasTargetBytes::target_bytes)
+            .collect()
+    }
+}
+
+/// Every part of the [`MultipartInput`](crate::inputs::MultipartInput) is one message; the keys are not sent
+#[cfg(feature = "multipart_inputs")]
+impl<I, K> NetworkMessages for ListInput<(K, I)>
+where
+    I: HasTargetBytes,
+{
+    fn network_messages(&self) -> Vec<OwnedSlice<'_, u8>> {
+        self.parts()
+            .iter()
+            .map(|(_, part)| part.target_bytes())
+            .collect()
+    }
+}
+
+/// A server process a [`NetworkExecutor`] talks to.
+///
+/// For each run, the server is started, the messages are sent, and the server is either waited for or stopped.
+pub trait ServerProcess: HasObservers + HasTimeout {
+    /// Starts the server for a new run.
+    ///
+    /// `input` contains all messages of the run, for servers which read (parts of) their input from elsewhere.
+    fn start_server(&mut self, input: &[u8]) -> Result<(), Error>;
+
+    /// Waits up to `timeout` for the run of the server to finish.
+    ///
+    /// Returns `None` if the server is still running.
+    fn wait_server(&mut self, timeout: Duration) -> Result<Option<ExitKind>, Error>;
+
+    /// Stops the run of a server which did not finish on its own.
+    fn stop_server(&mut self) -> Result<ExitKind, Error>;
+}
+
+/// Forks a new server child for each run; it is stopped with `SIGTERM` at the end of the session.
+#[cfg(feature = "fork")]
+impl<I, OT, S, SHM> ServerProcess for ForkserverExecutor<I, OT, S, SHM>
+where
+    OT: ObserversTuple<I, S>,
+    SHM: ShMem,
+{
+    fn start_server(&mut self, input: &[u8]) -> Result<(), Error> {
+        self.start_child(input).map(|_| ())
+    }
+
+    fn wait_server(&mut self, timeout: Duration) -> Result<Option<ExitKind>, Error> {
+        self.wait_child(timeout)
+    }
+
+    fn stop_server(&mut self) -> Result<ExitKind, Error> {
+        self.stop_child(Signal::SIGTERM)
+    }
+}
+
+/// How the [`StdServer`] gets its server
+#[derive(Debug)]
+enum ServerTarget {
+    /// Spawn the command for each run
+    Spawn {
+        command: Box<Command>,
+        child: Option<Child>,
+    },
+    /// An already running server
+    Attach(Pid),
+}
+
+/// A server without forkserver, spawned from a [`Command`] for each run, or already running
+#[derive(Debug)]
+pub struct StdServer<OT> {
+    target: ServerTarget,
+    observers: OT,
+    timeout: Duration,
+}
+
+impl<OT> StdServer<OT> {
+    /// Spawns `command` as a fresh server for each run.
+    ///
+    /// When the session is over, the server is stopped with `SIGTERM`, and killed if it does not exit within the timeout.
+    pub fn spawn(command: Command, observers: OT) -> Self {
+        Self {
+            target: ServerTarget::Spawn {
+                command: Box::new(command),
+                child: None,
+            },
+            observers,
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    /// Attaches to the already running server with the given pid.
+    ///
+    /// The server is kept running between runs, it is never stopped; a run is a crash if the server died during it.
+    /// Once the server died, it has to be restarted from the outside.
+    pub fn attach(pid: Pid, observers: OT) -> Self {
+        Self {
+            target: ServerTarget::Attach(pid),
+            observers,
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    /// The pid of the current server, if any
+    #[must_use]
+    pub fn pid(&self) -> Option<Pid> {
+        match &self.target {
+            ServerTarget::Spawn { child, .. } => child
+                .as_ref()
+                .map(|child| Pid::from_raw(child.id().try_into().unwrap())),
+            ServerTarget::Attach(pid) => Some(*pid),
+        }
+    }
+}
+
+/// Polls `child` until it exits, for at most `timeout`
+fn poll_child(child: &mut Child, timeout: Duration) -> Result<Option<ExitStatus>, Error> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if Instant::now() >= deadline {
+            return Ok(None);
+        }
+        thread::sleep(SERVER_POLL_INTERVAL);
+    }
+}
+
+/// A server exiting due to a signal crashed
+fn exit_kind_of(status: ExitStatus) -> ExitKind {
+    if status.signal().is_some() {
+        ExitKind::Crash
+    } else {
+        ExitKind::Ok
+    }
+}
+
+impl<OT> ServerProcess for StdServer<OT> {
+    fn start_server(&mut self, _input: &[u8]) -> Result<(), Error> {
+        match &mut self.target {
+            ServerTarget::Spawn { command, child } => {
+                if let Some(mut old) = child.take() {
+                    let _ = old.kill();
+                    let _ = old.wait();
+                }
+                *child = Some(command.spawn()?);
+                Ok(())
+            }
+            ServerTarget::Attach(pid) => {
+                if kill(*pid, None).is_err() {
+                    return Err(Error::illegal_state(format!(
+                        "The attached server with pid {pid} is not running anymore"
+                    )));
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn wait_server(&mut self, timeout: Duration) -> Result<Option<ExitKind>, Error> {
+        match &mut self.target {
+            ServerTarget::Spawn { child, .. } => {
+                let Some(running) = child.as_mut() else {
+                    return Err(Error::illegal_state("The server has not been started"));
+                };
+                let exit_kind = poll_child(running, timeout)?.map(exit_kind_of);
+                if exit_kind.is_some() {
+                    *child = None;
+                }
+                Ok(exit_kind)
+            }
+            // An attached server keeps running, it only finishes a run by dying.
+            ServerTarget::Attach(pid) => {
+                let deadline = Instant::now() + timeout;
+                loop {
+                    if kill(*pid, None).is_err() {
+                        return Ok(Some(ExitKind::Crash));
+                    }
+                    if Instant::now() >= deadline {
+                        return Ok(None);
+                    }
+                    thread::sleep(SERVER_POLL_INTERVAL);
+                }
+            }
+        }
+    }
+
+    fn stop_server(&mut self) -> Result<ExitKind, Error> {
+        let timeout = self.timeout;
+        match &mut self.target {
+            ServerTarget::Spawn { child, .. } => {
+                let Some(mut running) = child.take() else {
+                    return Err(Error::illegal_state("The server has not been started"));
+                };
+                let pid = Pid::from_raw(running.id().try_into().unwrap());
+                let _ = kill(pid, Signal::SIGTERM);
+                match poll_child(&mut running, timeout)? {
+                    Some(status) if status.signal() == Some(Signal::SIGTERM as i32) => {
+                        Ok(ExitKind::Ok)
+                    }
+                    Some(status) => Ok(exit_kind_of(status)),
+                    None => {
+                        running.kill()?;
+                        running.wait()?;
+                        Ok(ExitKind::Timeout)
+                    }
+                }
+            }
+            ServerTarget::Attach(pid) => Ok(if kill(*pid, None).is_ok() {
+                ExitKind::Ok
+            } else {
+                ExitKind::Crash
+            }),
+        }
+    }
+}
+
+impl<OT> Drop for StdServer<OT> {
+    fn drop(&mut self) {
+        if let ServerTarget::Spawn {
+            child: Some(child), ..
+        } = &mut self.target
+        {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+impl<OT> HasTimeout for StdServer<OT> {
+    #[inline]
+    fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    #[inline]
+    fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+}
+
+impl<OT> HasObservers for StdServer<OT> {
+    type Observers = OT;
+
+    #[inline]
+    fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+        RefIndexable::from(&self.observers)
+    }
+
+    #[inline]
+    fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+        RefIndexable::from(&mut self.observers)
+    }
+}
+
+/// The transport protocol used to talk to the server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetworkTransport {
+    /// A single TCP connection per run
+    Tcp,
+    /// One datagram per message
+    Udp,
+}
+
+/// How to find out that the server is ready to receive messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServerReady {
+    /// Try to connect every `interval`, until `timeout` passed.
+    ///
+    /// For UDP, the server is ready once the port shows up in `/proc/net/udp` or `/proc/net/udp6`.
+    /// Other platforms cannot check this without interfering with the server, and consider it ready right away,
+    /// so use [`ServerReady::Delay`] there.
+    Connect {
+        /// How long to wait for the server at most
+        timeout: Duration,
+        /// The time between two attempts
+        interval: Duration,
+    },
+    /// Wait for a fixed time after starting the server
+    Delay(Duration),
+}
+
+impl Default for ServerReady {
+    fn default() -> Self {
+        Self::Connect {
+            timeout: Duration::from_secs(1),
+            interval: Duration::from_millis(5),
+        }
+    }
+}
+
+/// Checks whether a UDP socket is bound to `addr`, without touching the port, by looking at the socket tables in `/proc`
+#[cfg(target_os = "linux")]
+fn udp_bound(addr: SocketAddr) -> io::Result<bool> {
+    use core::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+    use std::fs;
+
+    for table in ["/proc/net/udp", "/proc/net/udp6"] {
+        let content = match fs::read_to_string(table) {
+            Ok(content) => content,
+            // No IPv6 support
+            Err(err) if err.kind() == ErrorKind::NotFound => continue,
+            Err(err) => return Err(err),
+        };
+        // Lines look like `sl local_address rem_address ...`, with the address as `<hex ip>:<hex port>`
+        for line in content.lines().skip(1) {
+            let Some((ip, port)) = line
+                .split_whitespace()
+                .nth(1)
+                .and_then(|local| local.split_once(':'))
+            else {
+                continue;
+            };
+            if u16::from_str_radix(port, 16).ok() != Some(addr.port()) {
+                continue;
+            }
+            // The kernel prints each 32 bit word of the address in host byte order
+            let words = ip
+                .as_bytes()
+                .chunks(8)
+                .map(|word| {
+                    core::str::from_utf8(word)
+                        .ok()
+                        .and_then(|word| u32::from_str_radix(word, 16).ok())
+                })
+                .collect::<Option<Vec<u32>>>();
+            let bound: IpAddr = match words.as_deref() {
+                Some(&[word]) => Ipv4Addr::from(word.to_ne_bytes()).into(),
+                Some(words @ &[_, _, _, _]) => {
+                    let mut bytes = [0; 16];
+                    for (chunk, word) in bytes.chunks_exact_mut(4).zip(words) {
+                        chunk.copy_from_slice(&word.to_ne_bytes());
+                    }
+                    Ipv6Addr::from(bytes).to_canonical()
+                }
+                _ => continue,
+            };
+            if bound.is_unspecified() || bound == addr.ip().to_canonical() {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
+
+/// An open session to the server
+#[derive(Debug)]
+enum Session {
+    Tcp(TcpStream),
+    Udp(UdpSocket),
+}
+
+/// How receiving a response ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Received {
+    /// The server was silent for the response timeout
+    Silent,
+    /// The server closed the session
+    Closed,
+    /// The session ran out of time
+    Deadline,
+}
+
+/// The time left until `deadline`, or `None` if it passed
+fn remaining(deadline: Instant) -> Option<Duration> {
+    Some(deadline.saturating_duration_since(Instant::now())).filter(|left| !left.is_zero())
+}
+
+impl Session {
+    /// Sends one message, giving up at the `deadline`
+    fn send(&mut self, message: &[u8], deadline: Instant) -> io::Result<()> {
+        let Some(left) = remaining(deadline) else {
+            return Err(ErrorKind::TimedOut.into());
+        };
+        match self {
+            Self::Tcp(stream) => {
+                stream.set_write_timeout(Some(left))?;
+                stream.write_all(message)
+            }
+            Self::Udp(socket) => {
+                socket.set_write_timeout(Some(left))?;
+                socket.send(message).map(|_| ())
+            }
+        }
+    }
+
+    /// Receives the response to the last message, until the server is silent for `timeout` or the `deadline` passed.
+    fn recv(
+        &mut self,
+        response: &mut Vec<u8>,
+        timeout: Duration,
+        deadline: Instant,
+    ) -> io::Result<Received> {
+        let mut buf = vec![0; RECV_BUF_SIZE];
+        loop {
+            let Some(left) = remaining(deadline) else {
+                return Ok(Received::Deadline);
+            };
+            let read_timeout = Some(timeout.min(left));
+            let received = match self {
+                Self::Tcp(stream) => {
+                    stream.set_read_timeout(read_timeout)?;
+                    stream.read(&mut buf)
+                }
+                Self::Udp(socket) => {
+                    socket.set_read_timeout(read_timeout)?;
+                    socket.recv(&mut buf)
+                }
+            };
+            match received {
+                Ok(0) if matches!(self, Self::Tcp(_)) => return Ok(Received::Closed),
+                Ok(len) => response.extend_from_slice(&buf[..len]),
+                Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    return Ok(if remaining(deadline).is_some() {
+                        Received::Silent
+                    } else {
+                        Received::Deadline
+                    });
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+/// The builder for the [`NetworkExecutor`]
+#[derive(Debug, Clone)]
+pub struct NetworkExecutorBuilder<RO = ResponseObserver> {
+    transport: NetworkTransport,
+    addr: Option<SocketAddr>,
+    ready: ServerReady,
+    message_delay: Duration,
+    response_timeout: Duration,
+    exit_grace: Duration,
+    response_observer: Option<Handle<RO>>,
+}
+
+impl Default for NetworkExecutorBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NetworkExecutorBuilder {
+    /// Creates a new builder; the address of the server has to be set with [`Self::tcp`] or [`Self::udp`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            transport: NetworkTransport::Tcp,
+            addr: None,
+            ready: ServerReady::default(),
+            message_delay: Duration::ZERO,
+            response_timeout: Duration::from_millis(10),
+            exit_grace: Duration::from_millis(10),
+            response_observer: None,
+        }
+    }
+}
+
+impl<RO> NetworkExecutorBuilder<RO> {
+    /// Talk to the server over TCP, at the given address
+    #[must_use]
+    pub fn tcp(mut self, addr: SocketAddr) -> Self {
+        self.transport = NetworkTransport::Tcp;
+        self.addr = Some(addr);
+        self
+    }
+
+    /// Talk to the server over UDP, at the given address
+    #[must_use]
+    pub fn udp(mut self, addr: SocketAddr) -> Self {
+        self.transport = NetworkTransport::Udp;
+        self.addr = Some(addr);
+        self
+    }
+
+    /// How to detect that the server is ready for messages
+    #[must_use]
+    pub fn ready(mut self, ready: ServerReady) -> Self {
+        self.ready = ready;
+        self
+    }
+
+    /// The delay after each message, after its response was received
+    #[must_use]
+    pub fn message_delay(mut self, message_delay: Duration) -> Self {
+        self.message_delay = message_delay;
+        self
+    }
+
+    /// How long to wait for (more of) the response of each message.
+    ///
+    /// If zero, responses are not read at all.
+    #[must_use]
+    pub fn response_timeout(mut self, response_timeout: Duration) -> Self {
+        self.response_timeout = response_timeout;
+        self
+    }
+
+    /// How long to wait for the server to exit on its own at the end of a session, before it is stopped
+    #[must_use]
+    pub fn exit_grace(mut self, exit_grace: Duration) -> Self {
+        self.exit_grace = exit_grace;
+        self
+    }
+
+    /// Stores the responses of the server in the given observer, which must be one of the server's observers
+    #[must_use]
+    pub fn response_observer<RO2>(self, observer: &RO2) -> NetworkExecutorBuilder<RO2>
+    where
+        RO2: HasResponses,
+    {
+        NetworkExecutorBuilder {
+            transport: self.transport,
+            addr: self.addr,
+            ready: self.ready,
+            message_delay: self.message_delay,
+            response_timeout: self.response_timeout,
+            exit_grace: self.exit_grace,
+            response_observer: Some(observer.handle()),
+        }
+    }
+
+    /// Builds the [`NetworkExecutor`] talking to the given server
+    pub fn build<I, S, SV>(self, server: SV) -> Result<NetworkExecutor<I, S, SV, RO>, Error> {
+        let Some(addr) = self.addr else {
+            return Err(Error::illegal_argument(
+                "NetworkExecutorBuilder::build: no server address given",
+            ));
+        };
+        Ok(NetworkExecutor {
+            server,
+            transport: self.transport,
+            addr,
+            ready: self.ready,
+            message_delay: self.message_delay,
+            response_timeout: self.response_timeout,
+            exit_grace: self.exit_grace,
+            response_observer: self.response_observer,
+            phantom: PhantomData,
+        })
+    }
+}
+
+/// An executor sending each input as a sequence of messages to a server.
+///
+/// Each session, from starting the server to its exit, is bounded by the timeout of the server (see [`HasTimeout`]).
+/// If the server does not get ready, or is still busy with the session when the timeout passes, it is stopped and the run is an [`ExitKind::Timeout`].
+pub struct NetworkExecutor<I, S, SV, RO = ResponseObserver> {
+    server: SV,
+    transport: NetworkTransport,
+    addr: SocketAddr,
+    ready: ServerReady,
+    message_delay: Duration,
+    response_timeout: Duration,
+    exit_grace: Duration,
+    response_observer: Option<Handle<RO>>,
+    phantom: PhantomData<(I, S)>,
+}
+
+impl<I, S, SV, RO> Debug for NetworkExecutor<I, S, SV, RO>
+where
+    SV: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NetworkExecutor")
+            .field("server", &self.server)
+            .field("transport", &self.transport)
+            .field("addr", &self.addr)
+            .field("ready", &self.ready)
+            .field("message_delay", &self.message_delay)
+            .field("response_timeout", &self.response_timeout)
+            .field("exit_grace", &self.exit_grace)
+            .finish_non_exhaustive()
+    }
+}
+
+impl NetworkExecutor<(), (), ()> {
+    /// Creates a builder for the [`NetworkExecutor`]
+    #[must_use]
+    pub fn builder() -> NetworkExecutorBuilder {
+        NetworkExecutorBuilder::new()
+    }
+}
+
+impl<I, S, SV, RO> NetworkExecutor<I, S, SV, RO>
+where
+    SV: ServerProcess,
+{
+    /// The server
+    pub fn server(&self) -> &SV {
+        &self.server
+    }
+
+    /// The server (mutable)
+    pub fn server_mut(&mut self) -> &mut SV {
+        &mut self.server
+    }
+
+    /// The address of the server
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Tries to open a session to the server once
+    fn try_open(&self, timeout: Duration) -> io::Result<Option<Session>> {
+        match self.transport {
+            NetworkTransport::Tcp => {
+                let stream = match TcpStream::connect_timeout(&self.addr, timeout) {
+                    Ok(stream) => stream,
+                    Err(err)
+                        if matches!(
+                            err.kind(),
+                            ErrorKind::ConnectionRefused | ErrorKind::TimedOut
+                        ) =>
+                    {
+                        return Ok(None);
+                    }
+                    Err(err) => return Err(err),
+                };
+                stream.set_nodelay(true)?;
+                Ok(Some(Session::Tcp(stream)))
+            }
+            NetworkTransport::Udp => {
+                #[cfg(target_os = "linux")]
+                if matches!(self.ready, ServerReady::Connect { .. }) && !udp_bound(self.addr)? {
+                    return Ok(None);
+                }
+                let local: SocketAddr = if self.addr.is_ipv4() {
+                    ([0, 0, 0, 0], 0).into()
+                } else {
+                    ([0; 16], 0).into()
+                };
+                let socket = UdpSocket::bind(local)?;
+                socket.connect(self.addr)?;
+                Ok(Some(Session::Udp(socket)))
+            }
+        }
+    }
+
+    /// Waits for the server to be ready and opens a session, at the latest at the `deadline`.
+    ///
+    /// Returns the exit kind instead, if the server exits or does not get ready in time.
+    fn open_session(&mut self, deadline: Instant) -> Result<Result<Session, ExitKind>, Error> {
+        let (timeout, interval) = match self.ready {
+            ServerReady::Connect { timeout, interval } => (timeout, interval),
+            ServerReady::Delay(delay) => {
+                thread::sleep(delay.min(remaining(deadline).unwrap_or_default()));
+                (Duration::ZERO, Duration::ZERO)
+            }
+        };
+        let ready_deadline = deadline.min(Instant::now() + timeout);
+        loop {
+            if let Some(exit_kind) = self.server.wait_server(Duration::ZERO)? {
+                return Ok(Err(exit_kind));
+            }
+            if let Some(session) = self.try_open(interval.max(Duration::from_millis(1)))? {
+                return Ok(Ok(session));
+            }
+            if Instant::now() >= ready_deadline {
+                log::warn!("Server at {} did not get ready in time", self.addr);
+                return Ok(Err(self.stop_hung_server()?));
+            }
+            thread::sleep(interval);
+        }
+    }
+
+    /// Sends all messages, collecting the responses.
+    ///
+    /// Returns early if the server closed the session; the flag is set if the session ran out of time.
+    fn deliver(
+        &self,
+        session: &mut Session,
+        messages: &[OwnedSlice<'_, u8>],
+        deadline: Instant,
+    ) -> (Vec<Vec<u8>>, bool) {
+        let mut responses = Vec::with_capacity(messages.len());
+        for message in messages {
+            if let Err(err) = session.send(message, deadline) {
+                log::debug!("Sending to the server failed: {err}");
+                return (responses, remaining(deadline).is_none());
+            }
+            let mut response = Vec::new();
+            let received = if self.response_timeout.is_zero() {
+                Ok(Received::Silent)
+            } else {
+                session.recv(&mut response, self.response_timeout, deadline)
+            };
+            responses.push(response);
+            match received {
+                Ok(Received::Silent) => {}
+                Ok(Received::Closed) => break,
+                Ok(Received::Deadline) => return (responses, true),
+                Err(err) => {
+                    log::debug!("Receiving from the server failed: {err}");
+                    break;
+                }
+            }
+            if !self.message_delay.is_zero() {
+                thread::sleep(self.message_delay);
+            }
+        }
+        (responses, false)
+    }
+
+    /// Stops a server whose session ran out of time; unless it crashed on the way, the run is a timeout.
+    fn stop_hung_server(&mut self) -> Result<ExitKind, Error> {
+        Ok(match self.server.stop_server()? {
+            ExitKind::Ok => ExitKind::Timeout,
+            exit_kind => exit_kind,
+        })
+    }
+
+    /// Runs one session with the server, returning the responses and how the server finished.
+    ///
+    /// The whole session, from starting the server to its exit, is bounded by the timeout of the server.
+    fn run_session(
+        &mut self,
+        messages: &[OwnedSlice<'_, u8>],
+    ) -> Result<(Vec<Vec<u8>>, ExitKind), Error> {
+        let input: Vec<u8> = messages
+            .iter()
+            .flat_map(|message| message.iter().copied())
+            .collect();
+        let deadline = Instant::now() + self.server.timeout();
+        self.server.start_server(&input)?;
+
+        let mut session = match self.open_session(deadline)? {
+            Ok(session) => session,
+            Err(exit_kind) => return Ok((Vec::new(), exit_kind)),
+        };
+        let (responses, timed_out) = self.deliver(&mut session, messages, deadline);
+        drop(session);
+        if timed_out {
+            log::debug!("The session with {} timed out", self.addr);
+            return Ok((responses, self.stop_hung_server()?));
+        }
+
+        let left = remaining(deadline).unwrap_or_default();
+        let exit_kind = match self.server.wait_server(self.exit_grace.min(left))? {
+            Some(exit_kind) => exit_kind,
+            // The server was still busy when the session ran out of time
+            None if left < self.exit_grace => self.stop_hung_server()?,
+            None => self.server.stop_server()?,
+        };
+        Ok((responses, exit_kind))
+    }
+}
+
+impl<EM, I, RO, S, SV, Z> Executor<EM, I, S, Z> for NetworkExecutor<I, S, SV, RO>
+where
+    I: NetworkMessages,
+    RO: HasResponses,
+    S: HasExecutions,
+    SV: ServerProcess,
+    SV::Observers: ObserversTuple<I, S>,
+{
+    fn run_target(
+        &mut self,
+        _fuzzer: &mut Z,
+        state: &mut S,
+        _mgr: &mut EM,
+        input: &I,
+    ) -> Result<ExitKind, Error> {
+        *state.executions_mut() += 1;
+        self.server
+            .observers_mut()
+            .pre_exec_child_all(state, input)?;
+
+        let messages = input.network_messages();
+        let (responses, exit_kind) = self.run_session(&messages)?;
+
+        if let Some(handle) = &self.response_observer {
+            self.server.observers_mut()[handle].set_responses(responses);
+        }
+        self.server
+            .observers_mut()
+            .post_exec_child_all(state, input, &exit_kind)?;
+        Ok(exit_kind)
+    }
+}
+
+impl<I, S, SV, RO> HasTimeout for NetworkExecutor<I, S, SV, RO>
+where
+    SV: HasTimeout,
+{
+    #[inline]
+    fn timeout(&self) -> Duration {
+        self.server.timeout()
+    }
+
+    #[inline]
+    fn set_timeout(&mut self, timeout: Duration) {
+        self.server.set_timeout(timeout);
+    }
+}
+
+impl<I, S, SV, RO> HasObservers for NetworkExecutor<I, S, SV, RO>
+where
+    SV: HasObservers,
+{
+    type Observers = SV::Observers;
+
+    #[inline]
+    fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+        self.server.observers()
+    }
+
+    #[inline]
+    fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+        self.server.observers_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+    use core::{net::SocketAddr, time::Duration};
+    use std::{
+        io::{Read, Write},
+        net::{TcpListener, UdpSocket},
+        process::Command,
+        thread,
+    };
+
+    use libafl_bolts::tuples::{Handled, tuple_list};
+    use nix::unistd::Pid;
+
+    use crate::{
+        events::NopEventManager,
+        executors::{
+            Executor, ExitKind, HasObservers, HasTimeout,
+            network::{NetworkExecutor, NetworkMessages, StdServer},
+        },
+        fuzzer::NopFuzzer,
+        inputs::BytesInput,
+        observers::{HasResponses, ResponseObserver},
+        state::NopState,
+    };
+
+    /// Starts an echo server in this process, closing the connection on "bye"
+    fn echo_server() -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut buf = [0; 64];
+                loop {
+                    let len = stream.read(&mut buf).unwrap();
+                    if len == 0 || &buf[..len] == b"bye" {
+                        break;
+                    }
+                    stream.write_all(&buf[..len]).unwrap();
+                }
+            }
+        });
+        addr
+    }
+
+    /// Sends `input` to the echo server, returning the responses
+    fn run_echo<I>(input: &I) -> Vec<Vec<u8>>
+    where
+        I: NetworkMessages,
+    {
+        let observer = ResponseObserver::new("responses");
+        let server = StdServer::attach(Pid::this(), tuple_list!(observer.clone()));
+        let mut executor = NetworkExecutor::builder()
+            .tcp(echo_server())
+            .response_observer(&observer)
+            .build(server)
+            .unwrap();
+
+        let mut state = NopState::<I>::new();
+        let exit_kind = executor
+            .run_target(
+                &mut NopFuzzer::new(),
+                &mut state,
+                &mut NopEventManager::new(),
+                input,
+            )
+            .unwrap();
+        assert_eq!(exit_kind, ExitKind::Ok);
+        executor.observers()[&observer.handle()]
+            .responses()
+            .to_vec()
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_network_executor_tcp() {
+        let responses = run_echo(&BytesInput::new(b"hello".to_vec()));
+        assert_eq!(responses, [b"hello".to_vec()]);
+    }
+
+    #[test]
+    #[cfg(feature = "multipart_inputs")]
+    #[cfg_attr(miri, ignore)]
+    fn test_network_executor_messages() {
+        use crate::inputs::ListInput;
+
+        let input = ListInput::from(vec![
+            BytesInput::new(b"hello".to_vec()),
+            BytesInput::new(b"world".to_vec()),
+            BytesInput::new(b"bye".to_vec()),
+            BytesInput::new(b"unsent".to_vec()),
+        ]);
+        let responses = run_echo(&input);
+        assert_eq!(
+            responses,
+            [b"hello".to_vec(), b"world".to_vec(), Vec::new()]
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_network_executor_spawn_exit() {
+        // The server exits without ever listening
+        let server = StdServer::spawn(Command::new("true"), ());
+        let mut executor = NetworkExecutor::builder()
+            .tcp(([127, 0, 0, 1], 1).into())
+            .build(server)
+            .unwrap();
+        let mut state = NopState::<BytesInput>::new();
+        let exit_kind = executor
+            .run_target(
+                &mut NopFuzzer::new(),
+                &mut state,
+                &mut NopEventManager::new(),
+                &BytesInput::new(vec![1]),
+            )
+            .unwrap();
+        assert_eq!(exit_kind, ExitKind::Ok);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_network_executor_udp() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = socket.local_addr().unwrap();
+        thread::spawn(move || {
+            let mut buf = [0; 64];
+            loop {
+                let (len, peer) = socket.recv_from(&mut buf).unwrap();
+                socket.send_to(&buf[..len], peer).unwrap();
+            }
+        });
+
+        let observer = ResponseObserver::new("responses");
+        let server = StdServer::attach(Pid::this(), tuple_list!(observer.clone()));
+        let mut executor = NetworkExecutor::builder()
+            .udp(addr)
+            .response_observer(&observer)
+            .build(server)
+            .unwrap();
+        let mut state = NopState::<BytesInput>::new();
+        let exit_kind = executor
+            .run_target(
+                &mut NopFuzzer::new(),
+                &mut state,
+                &mut NopEventManager::new(),
+                &BytesInput::new(b"hello".to_vec()),
+            )
+            .unwrap();
+        assert_eq!(exit_kind, ExitKind::Ok);
+        assert_eq!(
+            executor.observers()[&observer.handle()].responses(),
+            [b"hello".to_vec()]
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    #[cfg(target_os = "linux")]
+    fn test_udp_bound() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = socket.local_addr().unwrap();
+        assert!(super::udp_bound(addr).unwrap());
+        drop(socket);
+        assert!(!super::udp_bound(addr).unwrap());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_network_executor_timeout() {
+        // The server never stops responding
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                while stream.write_all(b"more").is_ok() {
+                    thread::sleep(Duration::from_millis(1));
+                }
+            }
+        });
+
+        let mut server = StdServer::attach(Pid::this(), ());
+        server.set_timeout(Duration::from_millis(200));
+        let mut executor = NetworkExecutor::builder().tcp(addr).build(server).unwrap();
+        let mut state = NopState::<BytesInput>::new();
+        let exit_kind = executor
+            .run_target(
+                &mut NopFuzzer::new(),
+                &mut state,
+                &mut NopEventManager::new(),
+                &BytesInput::new(b"hello".to_vec()),
+            )
+            .unwrap();
+        assert_eq!(exit_kind, ExitKind::Timeout);
+    }
+
+    /// The size of the coverage map of [`forkserver_echo_target`]
+    #[cfg(feature = "fork")]
+    const FORKSERVER_MAP_SIZE: usize = 65536;
+
+    /// Makes [`forkserver_echo_target`] serve at the given address
+    #[cfg(feature = "fork")]
+    const FORKSERVER_TARGET_ENV: &str = "LIBAFL_TEST_NETWORK_FORKSERVER_ADDR";
+
+    /// The forkserver target of [`test_network_executor_forkserver`], which runs this test binary again.
+    ///
+    /// Each server child echoes one connection, and marks the length of each message in the coverage map.
+    #[test]
+    #[cfg(feature = "fork")]
+    fn forkserver_echo_target() {
+        use std::{fs::File, os::fd::FromRawFd};
+
+        use libafl_bolts::{
+            AsSliceMut,
+            shmem::{ShMemId, ShMemProvider, UnixShMemProvider},
+        };
+        use nix::unistd::{ForkResult, fork};
+
+        use crate::executors::forkserver::{FORKSRV_FD, FS_NEW_OPT_MAPSIZE, FS_NEW_VERSION_MAX};
+
+        let Ok(addr) = std::env::var(FORKSERVER_TARGET_ENV) else {
+            return;
+        };
+        let listener = TcpListener::bind(addr).unwrap();
+        let mut map = UnixShMemProvider::new()
+            .unwrap()
+            .shmem_from_id_and_size(
+                ShMemId::from_string(&std::env::var("__AFL_SHM_ID").unwrap()),
+                FORKSERVER_MAP_SIZE,
+            )
+            .unwrap();
+        let mut ctl = unsafe { File::from_raw_fd(FORKSRV_FD) };
+        let mut st = unsafe { File::from_raw_fd(FORKSRV_FD + 1) };
+
+        let version = 0x41464c00 + FS_NEW_VERSION_MAX;
+        let mut buf = [0; 4];
+        st.write_all(&version.to_ne_bytes()).unwrap();
+        ctl.read_exact(&mut buf).unwrap();
+        for message in [
+            FS_NEW_OPT_MAPSIZE as u32,
+            FORKSERVER_MAP_SIZE as u32,
+            version,
+        ] {
+            st.write_all(&message.to_ne_bytes()).unwrap();
+        }
+
+        while ctl.read_exact(&mut buf).is_ok() {
+            match unsafe { fork() }.unwrap() {
+                ForkResult::Child => {
+                    let (mut stream, _) = listener.accept().unwrap();
+                    let mut message = [0; 64];
+                    loop {
+                        let len = stream.read(&mut message).unwrap();
+                        if len == 0 {
+                            break;
+                        }
+                        map.as_slice_mut()[len] = 1;
+                        stream.write_all(&message[..len]).unwrap();
+                    }
+                    std::process::exit(0);
+                }
+                ForkResult::Parent { child } => {
+                    st.write_all(&child.as_raw().to_ne_bytes()).unwrap();
+                    let mut status = 0;
+                    unsafe { libc::waitpid(child.as_raw(), &raw mut status, 0) };
+                    st.write_all(&status.to_ne_bytes()).unwrap();
+                }
+            }
+        }
+        std::process::exit(0);
+    }
+
+    #[test]
+    #[cfg(feature = "fork")]
+    #[serial_test::serial]
+    #[cfg_attr(miri, ignore)]
+    fn test_network_executor_forkserver() {
+        use libafl_bolts::{
+            AsSliceMut, StdTargetArgs,
+            shmem::{ShMem, ShMemProvider, UnixShMemProvider},
+        };
+
+        use crate::{
+            corpus::NopCorpus,
+            executors::forkserver::ForkserverExecutor,
+            observers::{ConstMapObserver, MapObserver},
+        };
+
+        let mut shmem_provider = UnixShMemProvider::new().unwrap();
+        let mut shmem = shmem_provider.new_shmem(FORKSERVER_MAP_SIZE).unwrap();
+        unsafe {
+            shmem.write_to_env("__AFL_SHM_ID").unwrap();
+        }
+        let shmem_buf: &mut [u8; FORKSERVER_MAP_SIZE] = shmem.as_slice_mut().try_into().unwrap();
+        let edges = ConstMapObserver::new("edges", shmem_buf);
+        let edges_handle = edges.handle();
+        let responses = ResponseObserver::new("responses");
+
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let forkserver = ForkserverExecutor::builder()
+            .program(std::env::current_exe().unwrap())
+            .args([
+                "--exact",
+                "executors::network::tests::forkserver_echo_target",
+                "--test-threads=1",
+            ])
+            .env(FORKSERVER_TARGET_ENV, format!("{addr}"))
+            .coverage_map_size(FORKSERVER_MAP_SIZE)
+            .shmem_provider(&mut shmem_provider)
+            .build::<BytesInput, _, NopCorpus<BytesInput>>(tuple_list!(edges, responses.clone()))
+            .unwrap();
+        let mut executor = NetworkExecutor::builder()
+            .tcp(addr)
+            .response_observer(&responses)
+            .build(forkserver)
+            .unwrap();
+
+        let mut state = NopState::<BytesInput>::new();
+        for message in [&b"hello"[..], b"hi"] {
+            let exit_kind = executor
+                .run_target(
+                    &mut NopFuzzer::new(),
+                    &mut state,
+                    &mut NopEventManager::new(),
+                    &BytesInput::new(message.to_vec()),
+                )
+                .unwrap();
+            assert_eq!(exit_kind, ExitKind::Ok);
+            assert_eq!(
+                executor.observers()[&responses.handle()].responses(),
+                [message.to_vec()]
+            );
+            // The coverage of the server child is collected
+            assert_eq!(executor.observers()[&edges_handle].get(message.len()), 1);
+        }
+    }
+}
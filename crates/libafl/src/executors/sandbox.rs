@@ -0,0 +1,614 @@
+//! Runs the children of executors in fresh Linux namespaces, isolating them from the host.
+//!
+//! Set the [`SandboxConfig`] with [`StdChildArgs::sandbox`](crate::executors::StdChildArgs::sandbox) on the builder
+//! of the [`CommandExecutor`](crate::executors::CommandExecutor) or the `ForkserverExecutor`.
+//! The target then runs in new user, mount and network namespaces:
+//! * the whole file system is read-only, except for private `tmpfs` mounts (by default on `/tmp`),
+//! * the input file stays visible, read-only, at its path, even if it is below one of the `tmpfs` mounts,
+//! * there is no network but an unconfigured loopback device.
+//!
+//! With [`SandboxConfig::pid_namespace`], which only the `CommandExecutor` supports, the target additionally runs
+//! in a new PID namespace: processes spawned by the target can not outlive it, and do not see the processes of the host.
+//!
+//! This needs unprivileged user namespaces (Linux 5.12 or later), which some distributions restrict.
+
+use alloc::{ffi::CString, format, string::String, sync::Arc, vec::Vec};
+use core::{ffi::CStr, ptr};
+use std::{
+    fs::{self, File, OpenOptions},
+    io,
+    os::{
+        fd::AsRawFd,
+        unix::{ffi::OsStrExt, fs::OpenOptionsExt},
+    },
+    path::{Path, PathBuf},
+};
+
+use crate::Error;
+
+/// Sets the mount read-only, for `mount_setattr`
+const MOUNT_ATTR_RDONLY: u64 = 0x0000_0001;
+
+/// The argument of the `mount_setattr` syscall, not (yet) part of `libc`
+#[repr(C)]
+struct MountAttr {
+    attr_set: u64,
+    attr_clr: u64,
+    propagation: u64,
+    userns_fd: u64,
+}
+
+/// The options of a [`Sandbox`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SandboxConfig {
+    tmpfs: Vec<PathBuf>,
+    pid_namespace: bool,
+}
+
+impl Default for SandboxConfig {
+    fn default() -> Self {
+        Self {
+            tmpfs: vec![PathBuf::from("/tmp")],
+            pid_namespace: false,
+        }
+    }
+}
+
+impl SandboxConfig {
+    /// A sandbox with a private `tmpfs` on `/tmp`, without a PID namespace, which works with all executors
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mounts an additional private, writable `tmpfs` on the given (existing) directory
+    #[must_use]
+    pub fn tmpfs(mut self, dir: PathBuf) -> Self {
+        self.tmpfs.push(dir);
+        self
+    }
+
+    /// Removes all `tmpfs` mounts, leaving the target no writable directory at all
+    #[must_use]
+    pub fn no_tmpfs(mut self) -> Self {
+        self.tmpfs.clear();
+        self
+    }
+
+    /// Whether to run the target in a new PID namespace, defaults to `false`.
+    ///
+    /// The `ForkserverExecutor` does not support PID namespaces, as the forkserver reports the pids of its
+    /// children, which are meaningless outside of the namespace.
+    #[must_use]
+    pub fn pid_namespace(mut self, pid_namespace: bool) -> Self {
+        self.pid_namespace = pid_namespace;
+        self
+    }
+
+    /// Whether the target runs in a new PID namespace
+    #[must_use]
+    pub fn has_pid_namespace(&self) -> bool {
+        self.pid_namespace
+    }
+}
+
+/// Converts a path to a [`CString`] for the raw syscalls in the child
+fn to_cstring(path: &Path) -> Result<CString, Error> {
+    CString::new(path.as_os_str().as_bytes()).map_err(|_| {
+        Error::illegal_argument(format!("Path {} contains a nul byte", path.display()))
+    })
+}
+
+/// The directories to create below `tmpfs` to mount `file` on, top down
+fn mount_point_dirs(tmpfs: &Path, file: &Path) -> Option<Vec<PathBuf>> {
+    let relative = file.strip_prefix(tmpfs).ok()?;
+    let mut dir = tmpfs.to_path_buf();
+    let mut dirs = Vec::new();
+    let components: Vec<_> = relative.components().collect();
+    for component in components.iter().take(components.len().saturating_sub(1)) {
+        dir.push(component);
+        dirs.push(dir.clone());
+    }
+    Some(dirs)
+}
+
+/// The input file, bind-mounted back into a `tmpfs` hiding it
+#[derive(Debug)]
+struct InputMount {
+    /// Reserves a descriptor number, which the child replaces with a descriptor of the file in its namespace,
+    /// so that the file stays reachable after the `tmpfs` was mounted
+    slot: File,
+    /// `/proc/self/fd/<slot>`
+    source: CString,
+    /// The path of the file
+    target: CString,
+    /// The directories to create in the `tmpfs` for the mount point
+    dirs: Vec<CString>,
+}
+
+/// Everything the child needs, prepared before forking, as it may not allocate
+#[derive(Debug)]
+struct SandboxSetup {
+    flags: libc::c_int,
+    uid_map: String,
+    gid_map: String,
+    tmpfs: Vec<CString>,
+    input: Option<InputMount>,
+    pid_namespace: bool,
+}
+
+/// Writes `content` to the file at `path`, without allocating
+unsafe fn write_file(path: &CStr, content: &[u8]) -> io::Result<()> {
+    unsafe {
+        let fd = libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let written = libc::write(fd, content.as_ptr().cast(), content.len());
+        libc::close(fd);
+        if written < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Mounts `source` on `target`, without allocating
+unsafe fn mount(
+    source: Option<&CStr>,
+    target: &CStr,
+    fstype: Option<&CStr>,
+    flags: libc::c_ulong,
+) -> io::Result<()> {
+    let ret = unsafe {
+        libc::mount(
+            source.map_or(ptr::null(), CStr::as_ptr),
+            target.as_ptr(),
+            fstype.map_or(ptr::null(), CStr::as_ptr),
+            flags,
+            ptr::null(),
+        )
+    };
+    if ret == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+impl SandboxSetup {
+    /// Enters the sandbox; runs in the forked child before `exec`
+    unsafe fn enter(&self) -> io::Result<()> {
+        unsafe {
+            if libc::unshare(self.flags) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            // Map our ids to root in the new user namespace
+            write_file(c"/proc/self/setgroups", b"deny")?;
+            write_file(c"/proc/self/uid_map", self.uid_map.as_bytes())?;
+            write_file(c"/proc/self/gid_map", self.gid_map.as_bytes())?;
+
+            // Keep all mount changes inside the namespace
+            mount(None, c"/", None, libc::MS_REC | libc::MS_PRIVATE)?;
+            if let Some(input) = &self.input {
+                // Bind mounts need a source in this namespace, so grab the input file before hiding it
+                let fd = libc::open(input.target.as_ptr(), libc::O_PATH | libc::O_CLOEXEC);
+                if fd < 0 || libc::dup3(fd, input.slot.as_raw_fd(), libc::O_CLOEXEC) < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                libc::close(fd);
+            }
+            let attr = MountAttr {
+                attr_set: MOUNT_ATTR_RDONLY,
+                attr_clr: 0,
+                propagation: 0,
+                userns_fd: 0,
+            };
+            if libc::syscall(
+                libc::SYS_mount_setattr,
+                libc::AT_FDCWD,
+                c"/".as_ptr(),
+                libc::AT_RECURSIVE,
+                &raw const attr,
+                size_of::<MountAttr>(),
+            ) != 0
+            {
+                return Err(io::Error::last_os_error());
+            }
+            for dir in &self.tmpfs {
+                mount(
+                    Some(c"tmpfs"),
+                    dir,
+                    Some(c"tmpfs"),
+                    libc::MS_NOSUID | libc::MS_NODEV,
+                )?;
+            }
+
+            if let Some(input) = &self.input {
+                for dir in &input.dirs {
+                    if libc::mkdir(dir.as_ptr(), 0o755) != 0
+                        && io::Error::last_os_error().kind() != io::ErrorKind::AlreadyExists
+                    {
+                        return Err(io::Error::last_os_error());
+                    }
+                }
+                let fd = libc::open(
+                    input.target.as_ptr(),
+                    libc::O_CREAT | libc::O_WRONLY | libc::O_CLOEXEC,
+                    0o644,
+                );
+                if fd < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                libc::close(fd);
+                mount(Some(&input.source), &input.target, None, libc::MS_BIND)?;
+            }
+
+            if self.pid_namespace {
+                Self::fork_init()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Forks the init process of the new PID namespace, which forks the target in turn.
+    ///
+    /// The target does not run as PID 1, which would ignore signals it sends to itself, like `abort` does.
+    /// Once it exits, the init process exits, killing all other processes of the namespace.
+    /// The calling process stays outside of the namespace, and exits the same way as the target,
+    /// so that its parent sees the exit status of the target.
+    unsafe fn fork_init() -> io::Result<()> {
+        unsafe {
+            // The waiting processes never `exec`, so drop the signal handlers of the fuzzer,
+            // which may, for example, write to descriptors closed below on `SIGCHLD`.
+            for signal in 1..libc::SIGRTMAX() {
+                libc::signal(signal, libc::SIG_DFL);
+            }
+
+            // The init process reports the wait status of the target through this pipe
+            let mut status_pipe = [0; 2];
+            if libc::pipe2(status_pipe.as_mut_ptr(), libc::O_CLOEXEC) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let [status_read, status_write] = status_pipe;
+
+            let init = libc::fork();
+            if init < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            if init == 0 {
+                // Die with the waiting process, for example if it gets killed on a timeout
+                libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL);
+                // Only show the processes of the namespace. This fails if parts of `/proc` are hidden by
+                // overmounts, as in many containers; the processes are still isolated then.
+                let _ = mount(
+                    Some(c"proc"),
+                    c"/proc",
+                    Some(c"proc"),
+                    libc::MS_NOSUID | libc::MS_NODEV | libc::MS_NOEXEC,
+                );
+
+                let target = libc::fork();
+                if target < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                if target == 0 {
+                    return Ok(());
+                }
+                close_fds_except(status_write);
+                let status = wait_status(target);
+                libc::write(
+                    status_write,
+                    (&raw const status).cast(),
+                    size_of::<libc::c_int>(),
+                );
+                libc::_exit(0);
+            }
+
+            close_fds_except(status_read);
+            let mut status = 0;
+            let read = loop {
+                let read = libc::read(
+                    status_read,
+                    (&raw mut status).cast(),
+                    size_of::<libc::c_int>(),
+                );
+                if read >= 0 || io::Error::last_os_error().kind() != io::ErrorKind::Interrupted {
+                    break read;
+                }
+            };
+            let init_status = wait_status(init);
+            if read != size_of::<libc::c_int>().cast_signed() {
+                // The init process died before the target
+                status = init_status;
+            }
+
+            if libc::WIFSIGNALED(status) {
+                let signal = libc::WTERMSIG(status);
+                let no_core = libc::rlimit {
+                    rlim_cur: 0,
+                    rlim_max: 0,
+                };
+                libc::setrlimit(libc::RLIMIT_CORE, &raw const no_core);
+                let mut set = core::mem::zeroed();
+                libc::sigemptyset(&raw mut set);
+                libc::sigaddset(&raw mut set, signal);
+                libc::sigprocmask(libc::SIG_UNBLOCK, &raw const set, ptr::null_mut());
+                libc::kill(libc::getpid(), signal);
+                libc::_exit(128 + signal);
+            }
+            libc::_exit(libc::WEXITSTATUS(status));
+        }
+    }
+}
+
+/// Closes all descriptors but stdio and `keep`, notably the one `Command::spawn` waits on to be closed by `exec`
+unsafe fn close_fds_except(keep: libc::c_int) {
+    let keep = keep.cast_unsigned();
+    for (first, last) in [(3, keep.saturating_sub(1)), (keep + 1, libc::c_uint::MAX)] {
+        if first > last {
+            continue;
+        }
+        // # Safety
+        // Closing descriptors is always safe in a process which does not use them any more.
+        if unsafe { libc::syscall(libc::SYS_close_range, first, last, 0) } != 0 {
+            for fd in first..=last.min(1023) {
+                unsafe { libc::close(fd.cast_signed()) };
+            }
+        }
+    }
+}
+
+/// Waits for the child `pid` to exit, returning its wait status
+unsafe fn wait_status(pid: libc::pid_t) -> libc::c_int {
+    let mut status = 0;
+    // # Safety
+    // Only writes to the local status.
+    while unsafe { libc::waitpid(pid, &raw mut status, 0) } < 0 {
+        if io::Error::last_os_error().kind() != io::ErrorKind::Interrupted {
+            // Report a failure to wait like a failed `exec`
+            return 127 << 8;
+        }
+    }
+    status
+}
+
+/// A namespace sandbox for the children of an executor
+#[derive(Debug, Clone)]
+pub struct Sandbox {
+    setup: Arc<SandboxSetup>,
+}
+
+impl Sandbox {
+    /// Prepares a sandbox with the given options, keeping the (absolute) `input_file` visible in it
+    pub fn new(config: &SandboxConfig, input_file: Option<&Path>) -> Result<Self, Error> {
+        let mut flags = libc::CLONE_NEWUSER | libc::CLONE_NEWNS | libc::CLONE_NEWNET;
+        if config.pid_namespace {
+            flags |= libc::CLONE_NEWPID;
+        }
+
+        let tmpfs = config
+            .tmpfs
+            .iter()
+            .map(|dir| {
+                fs::canonicalize(dir).map_err(|err| {
+                    Error::os_error(
+                        err,
+                        format!("Could not find the tmpfs directory {}", dir.display()),
+                    )
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let input = match input_file {
+            Some(input_file) => {
+                let path = fs::canonicalize(input_file)?;
+                // Only files hidden by a tmpfs need to be mounted again
+                match tmpfs.iter().find_map(|dir| mount_point_dirs(dir, &path)) {
+                    Some(dirs) => {
+                        let slot = OpenOptions::new()
+                            .read(true)
+                            .custom_flags(libc::O_PATH)
+                            .open(&path)?;
+                        Some(InputMount {
+                            source: CString::new(format!("/proc/self/fd/{}", slot.as_raw_fd()))
+                                .unwrap(),
+                            target: to_cstring(&path)?,
+                            dirs: dirs
+                                .iter()
+                                .map(|dir| to_cstring(dir))
+                                .collect::<Result<_, _>>()?,
+                            slot,
+                        })
+                    }
+                    None => None,
+                }
+            }
+            None => None,
+        };
+
+        // # Safety
+        // Always safe to call
+        let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+        Ok(Self {
+            setup: Arc::new(SandboxSetup {
+                flags,
+                uid_map: format!("0 {uid} 1"),
+                gid_map: format!("0 {gid} 1"),
+                tmpfs: tmpfs
+                    .iter()
+                    .map(|dir| to_cstring(dir))
+                    .collect::<Result<_, _>>()?,
+                input,
+                pid_namespace: config.pid_namespace,
+            }),
+        })
+    }
+
+    /// Enters the sandbox, to be called in the forked child right before `exec`.
+    ///
+    /// With a PID namespace, the calling process forks once more, and only returns in the new child.
+    ///
+    /// # Safety
+    /// Must only be called in a freshly forked, single-threaded child.
+    pub(crate) unsafe fn enter(&self) -> io::Result<()> {
+        unsafe { self.setup.enter() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env, fs, io,
+        os::unix::process::CommandExt,
+        path::{Path, PathBuf},
+        process::{self, Command},
+    };
+
+    use libafl_bolts::{
+        StdTargetArgs,
+        tuples::{Handled, tuple_list},
+    };
+
+    use super::{SandboxConfig, mount_point_dirs};
+    use crate::{
+        events::NopEventManager,
+        executors::{Executor, ExitKind, HasObservers, StdChildArgs, command::CommandExecutor},
+        fuzzer::NopFuzzer,
+        inputs::BytesInput,
+        observers::StdOutObserver,
+        state::NopState,
+    };
+
+    /// Whether this process may create the user and mount namespaces of the sandbox
+    fn user_namespaces_available() -> bool {
+        let mut probe = Command::new("true");
+        // # Safety
+        // Only calls `unshare` in the forked child.
+        unsafe {
+            probe.pre_exec(|| {
+                if libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNS | libc::CLONE_NEWNET) == 0
+                {
+                    Ok(())
+                } else {
+                    Err(io::Error::last_os_error())
+                }
+            });
+        }
+        probe.status().is_ok_and(|status| status.success())
+    }
+
+    #[test]
+    fn test_sandbox_mount_point_dirs() {
+        assert_eq!(
+            mount_point_dirs(Path::new("/tmp"), Path::new("/tmp/fuzz/in/.cur_input")),
+            Some(vec![
+                PathBuf::from("/tmp/fuzz"),
+                PathBuf::from("/tmp/fuzz/in")
+            ])
+        );
+        assert_eq!(
+            mount_point_dirs(Path::new("/tmp"), Path::new("/tmp/.cur_input")),
+            Some(vec![])
+        );
+        assert_eq!(
+            mount_point_dirs(Path::new("/tmp"), Path::new("/home/fuzz/.cur_input")),
+            None
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "fork")]
+    #[cfg_attr(miri, ignore)]
+    fn test_sandbox_command() {
+        if !user_namespaces_available() {
+            log::warn!("Skipping the sandbox test, unprivileged user namespaces are not available");
+            return;
+        }
+
+        let input = env::temp_dir().join(format!("libafl_sandbox_input_{}", process::id()));
+        let inside = env::temp_dir().join(format!("libafl_sandbox_tmp_{}", process::id()));
+        let outside = env::current_dir()
+            .unwrap()
+            .join(format!(".libafl_sandbox_probe_{}", process::id()));
+        // The input is readable, /tmp is writable, everything else is read-only, and there is no network
+        let script = format!(
+            "cat \"$1\"; touch {} && echo tmp; touch {} 2>/dev/null || echo ro; grep -c : /proc/net/dev",
+            inside.display(),
+            outside.display()
+        );
+
+        let stdout = StdOutObserver::new_piped("stdout".into()).unwrap();
+        let handle = stdout.handle();
+        let mut executor = CommandExecutor::builder()
+            .program("sh")
+            .args(["-c", script.as_str(), "sh"])
+            .arg_input_file(&input)
+            .sandbox(SandboxConfig::new())
+            .stdout_observer(handle.clone())
+            .build(tuple_list!(stdout))
+            .unwrap();
+        let exit_kind = executor
+            .run_target(
+                &mut NopFuzzer::new(),
+                &mut NopState::<BytesInput>::new(),
+                &mut NopEventManager::new(),
+                &BytesInput::new(b"input\n".to_vec()),
+            )
+            .unwrap();
+        let _ = fs::remove_file(&input);
+
+        assert_eq!(exit_kind, ExitKind::Ok);
+        assert_eq!(
+            executor.observers()[&handle].output.as_deref(),
+            Some(&b"input\ntmp\nro\n1\n"[..])
+        );
+        assert!(!inside.exists());
+        assert!(!outside.exists());
+    }
+
+    #[test]
+    #[cfg(feature = "fork")]
+    #[cfg_attr(miri, ignore)]
+    fn test_sandbox_forkserver() {
+        use crate::{
+            Error,
+            corpus::NopCorpus,
+            executors::{
+                forkserver::{ConfigTarget, ForkserverExecutor},
+                sandbox::Sandbox,
+            },
+        };
+
+        // The forkserver reports the pids of its children, which would be the ones of a PID namespace
+        let result = ForkserverExecutor::builder()
+            .program("true")
+            .arg_input_file_std()
+            .coverage_map_size(65536)
+            .sandbox(SandboxConfig::new().pid_namespace(true))
+            .build::<BytesInput, _, NopCorpus<BytesInput>>(());
+        assert!(matches!(result, Err(Error::IllegalArgument(..))));
+
+        if !user_namespaces_available() {
+            log::warn!("Skipping the sandbox test, unprivileged user namespaces are not available");
+            return;
+        }
+
+        // The forkserver is spawned in the sandbox like this, and its children inherit it
+        let outside = env::current_dir()
+            .unwrap()
+            .join(format!(".libafl_sandbox_fsrv_probe_{}", process::id()));
+        let script = format!(
+            "touch {} 2>/dev/null || echo ro; grep -c : /proc/net/dev",
+            outside.display()
+        );
+        let sandbox = Sandbox::new(&SandboxConfig::new(), None).unwrap();
+        let output = Command::new("sh")
+            .args(["-c", script.as_str()])
+            .set_sandbox(&sandbox)
+            .output()
+            .unwrap();
+        assert_eq!(output.stdout, b"ro\n1\n");
+        assert!(!outside.exists());
+    }
+}
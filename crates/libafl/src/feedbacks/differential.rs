@@ -1,6 +1,10 @@
 //! Diff Feedback, comparing the content of two observers of the same type.
+//!
+//! The [`MultiDiffFeedback`] reports the outliers of an N-way [`crate::executors::MultiDiffExecutor`] instead.
 
 use alloc::borrow::Cow;
+#[cfg(feature = "std")]
+use alloc::vec::Vec;
 use core::fmt::{self, Debug, Formatter};
 
 use libafl_bolts::{
@@ -16,6 +20,8 @@ use crate::{
     executors::ExitKind,
     feedbacks::{Feedback, FeedbackFactory, StateInitializer},
 };
+#[cfg(feature = "std")]
+use crate::{HasMetadata, corpus::Testcase, executors::MultiDiffObserver};
 
 /// The result of a differential test between two observers.
 #[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -175,6 +181,108 @@ where
     }
 }
 
+/// The outcome of the majority vote of a [`crate::executors::MultiDiffExecutor`],
+/// attached to testcases by the [`MultiDiffFeedback`]
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    expect(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+pub struct MultiDiffMetadata {
+    /// The [`ExitKind`] of every executor, in order
+    pub exit_kinds: Vec<ExitKind>,
+    /// The indices of the executors that disagreed with the majority
+    pub outliers: Vec<usize>,
+}
+
+#[cfg(feature = "std")]
+libafl_bolts::impl_serdeany!(MultiDiffMetadata);
+
+/// A [`MultiDiffFeedback`] considers an input interesting if any of the executors of a
+/// [`crate::executors::MultiDiffExecutor`] disagreed with the majority, and records the
+/// outliers as [`MultiDiffMetadata`].
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MultiDiffFeedback {
+    o_ref: Handle<MultiDiffObserver>,
+    // The previous run's result of `Self::is_interesting`
+    #[cfg(feature = "track_hit_feedbacks")]
+    last_result: Option<bool>,
+}
+
+#[cfg(feature = "std")]
+impl MultiDiffFeedback {
+    /// Create a new [`MultiDiffFeedback`] for the given [`MultiDiffObserver`]
+    #[must_use]
+    pub fn new(observer: &MultiDiffObserver) -> Self {
+        Self {
+            o_ref: observer.handle(),
+            #[cfg(feature = "track_hit_feedbacks")]
+            last_result: None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Named for MultiDiffFeedback {
+    fn name(&self) -> &Cow<'static, str> {
+        self.o_ref.name()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<S> StateInitializer<S> for MultiDiffFeedback {}
+
+#[cfg(feature = "std")]
+impl<EM, I, OT, S> Feedback<EM, I, OT, S> for MultiDiffFeedback
+where
+    OT: MatchName,
+{
+    fn is_interesting(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        let observer = observers
+            .get(&self.o_ref)
+            .ok_or(Error::illegal_state("MultiDiffObserver is missing"))?;
+        let res = observer.is_diff();
+        #[cfg(feature = "track_hit_feedbacks")]
+        {
+            self.last_result = Some(res);
+        }
+        Ok(res)
+    }
+
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        self.last_result.ok_or(premature_last_result_err())
+    }
+
+    fn append_metadata(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        observers: &OT,
+        testcase: &mut Testcase<I>,
+    ) -> Result<(), Error> {
+        let observer = observers
+            .get(&self.o_ref)
+            .ok_or(Error::illegal_state("MultiDiffObserver is missing"))?;
+        if observer.is_diff() {
+            testcase.add_metadata(MultiDiffMetadata {
+                exit_kinds: observer.exit_kinds().to_vec(),
+                outliers: observer.outliers().to_vec(),
+            });
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use alloc::borrow::Cow;
@@ -0,0 +1,73 @@
+//! Feedback storing the stacks sampled by the [`HangStackObserver`] as metadata of timeouts.
+
+use alloc::borrow::Cow;
+
+use libafl_bolts::{
+    Named,
+    tuples::{Handle, Handled, MatchName, MatchNameRef},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Error, HasMetadata,
+    corpus::Testcase,
+    feedbacks::{Feedback, StateInitializer},
+    observers::HangStackObserver,
+};
+
+/// Nop feedback that annotates the new testcase with the [`HangStackMetadata`](crate::observers::HangStackMetadata)
+/// of the [`HangStackObserver`], if the run timed out. The testcase is never interesting (use with an OR).
+///
+/// To drop the timeouts hanging in the same place, use a [`NewHashFeedback`](crate::feedbacks::NewHashFeedback)
+/// on the same observer.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HangStackToMetadataFeedback {
+    o_ref: Handle<HangStackObserver<'static>>,
+}
+
+impl<S> StateInitializer<S> for HangStackToMetadataFeedback {}
+
+impl<EM, I, OT, S> Feedback<EM, I, OT, S> for HangStackToMetadataFeedback
+where
+    OT: MatchName,
+{
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        Ok(false)
+    }
+
+    /// Append to the testcase the sampled stacks in case of a new corpus item.
+    #[inline]
+    fn append_metadata(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        observers: &OT,
+        testcase: &mut Testcase<I>,
+    ) -> Result<(), Error> {
+        let observer = observers
+            .get(&self.o_ref)
+            .ok_or(Error::illegal_state("HangStackObserver is missing"))?;
+        if let Some(metadata) = observer.metadata() {
+            testcase.metadata_map_mut().insert(metadata);
+        }
+        Ok(())
+    }
+}
+
+impl Named for HangStackToMetadataFeedback {
+    #[inline]
+    fn name(&self) -> &Cow<'static, str> {
+        self.o_ref.name()
+    }
+}
+
+impl HangStackToMetadataFeedback {
+    /// Creates a new [`HangStackToMetadataFeedback`].
+    #[must_use]
+    pub fn new(observer: &HangStackObserver<'static>) -> Self {
+        Self {
+            o_ref: observer.handle(),
+        }
+    }
+}
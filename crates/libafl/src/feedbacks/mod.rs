@@ -11,6 +11,8 @@ use core::{fmt::Debug, marker::PhantomData};
 #[cfg(feature = "std")]
 pub use concolic::ConcolicFeedback;
 pub use differential::DiffFeedback;
+#[cfg(feature = "std")]
+pub use differential::{MultiDiffFeedback, MultiDiffMetadata};
 use libafl_bolts::{
     Named,
     tuples::{Handle, Handled, MatchName, MatchNameRef},
@@ -39,6 +41,8 @@ pub mod concolic;
 /// The module for `CustomFilenameToTestcaseFeedback`
 pub mod custom_filename;
 pub mod differential;
+#[cfg(all(feature = "std", target_os = "linux"))]
+pub mod hang_stack;
 /// The module for list feedback
 pub mod list;
 pub mod map;
@@ -46,6 +50,10 @@ pub mod map;
 pub mod nautilus;
 #[cfg(feature = "std")]
 pub mod new_hash_feedback;
+pub mod protocol_state;
+pub use protocol_state::{
+    ProtocolStateFeedback, ProtocolStateMachineMetadata, ProtocolStateTestcaseMetadata,
+};
 #[cfg(feature = "simd")]
 pub mod simd;
 #[cfg(feature = "std")]
@@ -54,6 +62,8 @@ pub mod transferred;
 
 #[cfg(feature = "std")]
 pub use capture_feedback::CaptureTimeoutFeedback;
+#[cfg(all(feature = "std", target_os = "linux"))]
+pub use hang_stack::HangStackToMetadataFeedback;
 
 #[cfg(feature = "introspection")]
 use crate::state::HasClientPerfMonitor;
@@ -0,0 +1,272 @@
+//! The [`ProtocolStateFeedback`] builds the state machine of a stateful network protocol, as `AFLNet` does,
+//! and considers inputs interesting which reach new states or new transitions between states.
+
+use alloc::{
+    borrow::Cow,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt::{Debug, Write};
+
+use hashbrown::HashSet;
+use libafl_bolts::{
+    Named,
+    tuples::{Handle, Handled, MatchName, MatchNameRef},
+};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "track_hit_feedbacks")]
+use crate::feedbacks::premature_last_result_err;
+use crate::{
+    Error, HasMetadata, HasNamedMetadata,
+    corpus::Testcase,
+    executors::ExitKind,
+    feedbacks::{Feedback, HasObserverHandle, StateInitializer},
+    observers::{INITIAL_PROTOCOL_STATE, ProtocolStateObserver, StateExtractor},
+};
+
+/// The prefix of the metadata names
+pub const PROTOCOL_STATE_FEEDBACK_PREFIX: &str = "protocolstatefeedback_metadata_";
+
+/// The state machine of the protocol, as far as it was discovered
+#[derive(Default, Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    expect(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+pub struct ProtocolStateMachineMetadata {
+    /// All states seen so far
+    pub states: HashSet<u32>,
+    /// All transitions between two states seen so far
+    pub transitions: HashSet<(u32, u32)>,
+}
+
+libafl_bolts::impl_serdeany!(ProtocolStateMachineMetadata);
+
+impl ProtocolStateMachineMetadata {
+    /// Creates a new, empty [`ProtocolStateMachineMetadata`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the given sequence of states contains a state or a transition not seen yet
+    pub fn is_novel<It>(&self, states: It) -> bool
+    where
+        It: IntoIterator<Item = u32>,
+    {
+        let mut states = states.into_iter();
+        let Some(mut prev) = states.next() else {
+            return false;
+        };
+        if !self.states.contains(&prev) {
+            return true;
+        }
+        for state in states {
+            if !self.transitions.contains(&(prev, state)) {
+                return true;
+            }
+            prev = state;
+        }
+        false
+    }
+
+    /// Adds all states and transitions of the given sequence of states
+    pub fn add_states<It>(&mut self, states: It)
+    where
+        It: IntoIterator<Item = u32>,
+    {
+        let mut prev = None;
+        for state in states {
+            self.states.insert(state);
+            if let Some(prev) = prev {
+                self.transitions.insert((prev, state));
+            }
+            prev = Some(state);
+        }
+    }
+
+    /// The state machine in graphviz dot format
+    #[must_use]
+    pub fn to_dot(&self) -> String {
+        let mut states: Vec<_> = self.states.iter().collect();
+        states.sort_unstable();
+        let mut transitions: Vec<_> = self.transitions.iter().collect();
+        transitions.sort_unstable();
+
+        let mut dot = String::from("digraph protocol {\n");
+        for state in states {
+            writeln!(dot, "  {state};").unwrap();
+        }
+        for (from, to) in transitions {
+            writeln!(dot, "  {from} -> {to};").unwrap();
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// The protocol state codes a [`Testcase`] produced, one per message it sent
+#[derive(Default, Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    expect(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+pub struct ProtocolStateTestcaseMetadata {
+    /// The state code of each response; `None` if the response had none
+    pub codes: Vec<Option<u32>>,
+}
+
+libafl_bolts::impl_serdeany!(ProtocolStateTestcaseMetadata);
+
+impl ProtocolStateTestcaseMetadata {
+    /// The distinct states reached by this testcase, each with the number of messages needed to reach it
+    pub fn reached_states(&self) -> impl Iterator<Item = (u32, usize)> + '_ {
+        self.codes
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, code)| code.map(|code| (code, idx + 1)))
+    }
+
+    /// The number of messages needed to reach `state` the first time, if it is reached at all
+    #[must_use]
+    pub fn prefix_len(&self, state: u32) -> Option<usize> {
+        if state == INITIAL_PROTOCOL_STATE {
+            return Some(0);
+        }
+        self.reached_states()
+            .find(|(reached, _)| *reached == state)
+            .map(|(_, len)| len)
+    }
+}
+
+/// A [`ProtocolStateFeedback`] keeps the state machine of the protocol and considers interesting
+/// inputs reaching new states or new state transitions
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProtocolStateFeedback<E> {
+    name: Cow<'static, str>,
+    o_ref: Handle<ProtocolStateObserver<E>>,
+    #[cfg(feature = "track_hit_feedbacks")]
+    // The previous run's result of `Self::is_interesting`
+    last_result: Option<bool>,
+}
+
+impl<E> ProtocolStateFeedback<E> {
+    /// Returns a new [`ProtocolStateFeedback`] for the given observer
+    #[must_use]
+    pub fn new(observer: &ProtocolStateObserver<E>) -> Self {
+        Self {
+            name: Cow::from(PROTOCOL_STATE_FEEDBACK_PREFIX.to_string() + observer.name()),
+            o_ref: observer.handle(),
+            #[cfg(feature = "track_hit_feedbacks")]
+            last_result: None,
+        }
+    }
+}
+
+impl<E, S> StateInitializer<S> for ProtocolStateFeedback<E>
+where
+    S: HasNamedMetadata,
+{
+    fn init_state(&mut self, state: &mut S) -> Result<(), Error> {
+        state.add_named_metadata_checked(&self.name, ProtocolStateMachineMetadata::new())?;
+        Ok(())
+    }
+}
+
+impl<E, EM, I, OT, S> Feedback<EM, I, OT, S> for ProtocolStateFeedback<E>
+where
+    E: StateExtractor,
+    OT: MatchName,
+    S: HasNamedMetadata,
+{
+    fn is_interesting(
+        &mut self,
+        state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        let observer = observers
+            .get(&self.o_ref)
+            .expect("A ProtocolStateFeedback needs a ProtocolStateObserver");
+        let machine = state
+            .named_metadata_map()
+            .get::<ProtocolStateMachineMetadata>(&self.name)
+            .unwrap();
+
+        let res = machine.is_novel(observer.states());
+        #[cfg(feature = "track_hit_feedbacks")]
+        {
+            self.last_result = Some(res);
+        }
+        Ok(res)
+    }
+
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        self.last_result.ok_or(premature_last_result_err())
+    }
+
+    fn append_metadata(
+        &mut self,
+        state: &mut S,
+        _manager: &mut EM,
+        observers: &OT,
+        testcase: &mut Testcase<I>,
+    ) -> Result<(), Error> {
+        let observer = observers
+            .get(&self.o_ref)
+            .expect("A ProtocolStateFeedback needs a ProtocolStateObserver");
+        state
+            .named_metadata_map_mut()
+            .get_mut::<ProtocolStateMachineMetadata>(&self.name)
+            .unwrap()
+            .add_states(observer.states());
+        testcase.add_metadata(ProtocolStateTestcaseMetadata {
+            codes: observer.codes().to_vec(),
+        });
+        Ok(())
+    }
+}
+
+impl<E> Named for ProtocolStateFeedback<E> {
+    #[inline]
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<E> HasObserverHandle for ProtocolStateFeedback<E> {
+    type Observer = ProtocolStateObserver<E>;
+
+    #[inline]
+    fn observer_handle(&self) -> &Handle<ProtocolStateObserver<E>> {
+        &self.o_ref
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::feedbacks::protocol_state::ProtocolStateMachineMetadata;
+
+    #[test]
+    fn test_protocol_state_machine() {
+        let mut machine = ProtocolStateMachineMetadata::new();
+        assert!(machine.is_novel([0, 220]));
+        machine.add_states([0, 220, 331, 230]);
+
+        assert!(!machine.is_novel([0, 220, 331]));
+        assert!(!machine.is_novel([0]));
+        // A new state
+        assert!(machine.is_novel([0, 220, 530]));
+        // A new transition between known states
+        assert!(machine.is_novel([0, 220, 230]));
+
+        assert_eq!(
+            machine.to_dot(),
+            "digraph protocol {\n  0;\n  220;\n  230;\n  331;\n  0 -> 220;\n  220 -> 331;\n  331 -> 230;\n}\n"
+        );
+    }
+}
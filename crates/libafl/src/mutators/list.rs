@@ -1,17 +1,19 @@
 //! Mutator definitions for [`ListInput`]s. See [`crate::inputs::list`] for details.
 
-use alloc::borrow::Cow;
+use alloc::{borrow::Cow, format};
 use core::num::NonZero;
 
 use libafl_bolts::{Error, Named, rands::Rand};
 use tuple_list::{tuple_list, tuple_list_type};
 
 use crate::{
-    corpus::Corpus,
+    HasMetadata,
+    corpus::{Corpus, CorpusId},
     generators::Generator,
     inputs::{Input, ListInput, multi::MultipartInput},
     mutators::{MutationResult, Mutator},
     random_corpus_id,
+    schedulers::ProtocolStateSchedulerMetadata,
     state::{HasCorpus, HasMaxSize, HasRand},
 };
 
@@ -261,3 +263,190 @@ impl Named for CrossoverReplaceMutator {
         &Cow::Borrowed("CrossoverReplaceMutator")
     }
 }
+
+/// Mutator that applies the inner mutator to a random part of a [`ListInput`] or [`MultipartInput`] after the message prefix
+/// replayed to reach the protocol state targeted by the [`ProtocolStateScheduler`](crate::schedulers::ProtocolStateScheduler).
+///
+/// Without a targeted state, any part may be mutated.
+/// Returns [`MutationResult::Skipped`] if there are no parts after the prefix.
+#[derive(Debug)]
+pub struct ProtocolSuffixMutator<M> {
+    inner: M,
+    name: Cow<'static, str>,
+}
+
+impl<M> ProtocolSuffixMutator<M>
+where
+    M: Named,
+{
+    /// Create a new `ProtocolSuffixMutator` wrapping the given part mutator.
+    #[must_use]
+    pub fn new(inner: M) -> Self {
+        let name = Cow::Owned(format!("ProtocolSuffixMutator<{}>", inner.name()));
+        Self { inner, name }
+    }
+}
+
+impl<M> ProtocolSuffixMutator<M> {
+    /// Picks a random part after the prefix of the targeted protocol state, if there is any
+    fn suffix_index<S>(state: &mut S, len: usize) -> Option<usize>
+    where
+        S: HasMetadata + HasRand,
+    {
+        let prefix_len = state
+            .metadata::<ProtocolStateSchedulerMetadata>()
+            .ok()
+            .and_then(|meta| meta.target)
+            .map_or(0, |target| target.prefix_len);
+        NonZero::new(len.saturating_sub(prefix_len))
+            .map(|suffix_len| prefix_len + state.rand_mut().below(suffix_len))
+    }
+}
+
+impl<I, M, S> Mutator<ListInput<I>, S> for ProtocolSuffixMutator<M>
+where
+    I: Input,
+    M: Mutator<I, S>,
+    S: HasMetadata + HasRand,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut ListInput<I>) -> Result<MutationResult, Error> {
+        match Self::suffix_index(state, input.len()) {
+            None => Ok(MutationResult::Skipped),
+            Some(idx) => self.inner.mutate(state, &mut input.parts_mut()[idx]),
+        }
+    }
+
+    #[inline]
+    fn post_exec(&mut self, state: &mut S, new_corpus_id: Option<CorpusId>) -> Result<(), Error> {
+        self.inner.post_exec(state, new_corpus_id)
+    }
+}
+
+impl<I, K, M, S> Mutator<MultipartInput<I, K>, S> for ProtocolSuffixMutator<M>
+where
+    M: Mutator<I, S>,
+    S: HasMetadata + HasRand,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut MultipartInput<I, K>,
+    ) -> Result<MutationResult, Error> {
+        match Self::suffix_index(state, input.len()) {
+            None => Ok(MutationResult::Skipped),
+            Some(idx) => {
+                let (_key, part) = &mut input.parts_mut()[idx];
+                self.inner.mutate(state, part)
+            }
+        }
+    }
+
+    #[inline]
+    fn post_exec(&mut self, state: &mut S, new_corpus_id: Option<CorpusId>) -> Result<(), Error> {
+        self.inner.post_exec(state, new_corpus_id)
+    }
+}
+
+impl<M> Named for ProtocolSuffixMutator<M> {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::borrow::Cow;
+
+    use libafl_bolts::{Error, Named};
+
+    use super::ProtocolSuffixMutator;
+    use crate::{
+        HasMetadata,
+        corpus::CorpusId,
+        inputs::{BytesInput, ListInput, multi::MultipartInput},
+        mutators::{MutationResult, Mutator},
+        schedulers::{ProtocolStateSchedulerMetadata, protocol_state::ProtocolStateTarget},
+        state::NopState,
+    };
+
+    /// Overwrites the part it mutates
+    struct MarkMutator;
+
+    impl<S> Mutator<BytesInput, S> for MarkMutator {
+        fn mutate(
+            &mut self,
+            _state: &mut S,
+            input: &mut BytesInput,
+        ) -> Result<MutationResult, Error> {
+            *input = BytesInput::new(b"mutated".to_vec());
+            Ok(MutationResult::Mutated)
+        }
+
+        fn post_exec(
+            &mut self,
+            _state: &mut S,
+            _new_corpus_id: Option<CorpusId>,
+        ) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    impl Named for MarkMutator {
+        fn name(&self) -> &Cow<'static, str> {
+            &Cow::Borrowed("MarkMutator")
+        }
+    }
+
+    /// A state targeting a protocol state reached after the first two messages
+    fn state_with_prefix() -> NopState<BytesInput> {
+        let mut state = NopState::new();
+        state.add_metadata(ProtocolStateSchedulerMetadata {
+            target: Some(ProtocolStateTarget {
+                state: 200,
+                prefix_len: 2,
+            }),
+            ..ProtocolStateSchedulerMetadata::default()
+        });
+        state
+    }
+
+    fn messages() -> [BytesInput; 3] {
+        [b"a", b"b", b"c"].map(|message| BytesInput::new(message.to_vec()))
+    }
+
+    #[test]
+    fn test_protocol_suffix_list() {
+        let mut state = state_with_prefix();
+        let mut mutator = ProtocolSuffixMutator::new(MarkMutator);
+        let mut input = ListInput::new(messages().to_vec());
+        assert_eq!(
+            mutator.mutate(&mut state, &mut input).unwrap(),
+            MutationResult::Mutated
+        );
+        assert_eq!(input.parts()[..2], messages()[..2]);
+        assert_eq!(input.parts()[2], BytesInput::new(b"mutated".to_vec()));
+
+        // Nothing to mutate after the prefix
+        let mut input = ListInput::new(messages()[..2].to_vec());
+        assert_eq!(
+            mutator.mutate(&mut state, &mut input).unwrap(),
+            MutationResult::Skipped
+        );
+    }
+
+    #[test]
+    fn test_protocol_suffix_multipart() {
+        let mut state = state_with_prefix();
+        let mut mutator = ProtocolSuffixMutator::new(MarkMutator);
+        let mut input: MultipartInput<BytesInput, usize> =
+            ListInput::new(messages().into_iter().enumerate().collect());
+        assert_eq!(
+            mutator.mutate(&mut state, &mut input).unwrap(),
+            MutationResult::Mutated
+        );
+        let parts: alloc::vec::Vec<_> =
+            input.parts().iter().map(|(_, part)| part.clone()).collect();
+        assert_eq!(parts[..2], messages()[..2]);
+        assert_eq!(parts[2], BytesInput::new(b"mutated".to_vec()));
+    }
+}
@@ -0,0 +1,673 @@
+//! The [`HangStackObserver`] samples the stacks of targets which timed out, to find out where they hang.
+//!
+//! Timeouts hanging in the same place, for example in the same infinite loop, get the same hash. Use it with the
+//! [`NewHashFeedback`](crate::feedbacks::NewHashFeedback) to drop the duplicates, as the
+//! [`BacktraceObserver`](crate::observers::BacktraceObserver) does for crashes, and with the
+//! [`HangStackToMetadataFeedback`](crate::feedbacks::HangStackToMetadataFeedback) to store the
+//! stacks as [`HangStackMetadata`] of the timeouts.
+//!
+//! * For the `CommandExecutor` and the `ForkserverExecutor`, set the observer with
+//!   [`StdChildArgs::hang_stack_observer`](crate::executors::StdChildArgs::hang_stack_observer).
+//!   The executor stops the timed-out child with `ptrace` before killing it, and unwinds its threads along the
+//!   frame pointers: build the target with `-fno-omit-frame-pointer` to get more than the innermost frame.
+//!   The kernel stacks are added when `/proc/<pid>/task/<tid>/stack` is readable, usually only for root.
+//! * For the `InProcessForkExecutor`, create the observer with [`HangStackObserver::with_report_buffer`] in
+//!   shared memory. The child then unwinds its own stack when it times out, and reports it in the buffer.
+
+use alloc::{
+    borrow::Cow,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt::{self, Display, Formatter};
+use std::{fs, path::Path};
+
+use backtrace::{Backtrace, BacktraceSymbol, SymbolName};
+use hashbrown::HashMap;
+use libafl_bolts::{Named, generic_hash_std, ownedref::OwnedMutSlice};
+use nix::{
+    sys::{
+        ptrace,
+        wait::{WaitPidFlag, waitpid},
+    },
+    unistd::Pid,
+};
+use object::{Object, ObjectSegment, ObjectSymbol, SymbolKind};
+use serde::{Deserialize, Serialize};
+
+use super::ObserverWithHashField;
+use crate::{Error, executors::ExitKind, observers::Observer};
+
+/// The maximum number of frames unwound per thread
+const MAX_FRAMES: usize = 64;
+/// The size of the header of the report buffer: the state, the length of the report, and its hash
+const REPORT_HDR_SIZE: usize = 16;
+
+/// A frame of a [`HangStack`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct HangStackFrame {
+    /// The address of the instruction, the return address for all but the innermost frame
+    pub address: u64,
+    /// The file mapped at the address, and the offset of the address in it
+    pub module: Option<(String, u64)>,
+    /// The name of the function, if it could be symbolized
+    pub function: Option<String>,
+}
+
+impl HangStackFrame {
+    /// The part of the frame that goes into the hash.
+    ///
+    /// The innermost frame only contributes its function, as the program counter of a hanging loop moves around.
+    fn hash_key(&self, innermost: bool) -> String {
+        match (&self.function, &self.module) {
+            (Some(function), _) if innermost => function.clone(),
+            (_, Some((module, _))) if innermost => module.clone(),
+            (_, Some((module, offset))) => format!("{module}+{offset:#x}"),
+            (Some(function), None) => function.clone(),
+            (None, None) => format!("{:#x}", self.address),
+        }
+    }
+}
+
+impl Display for HangStackFrame {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#018x}", self.address)?;
+        if let Some(function) = &self.function {
+            write!(f, " in {function}")?;
+        }
+        if let Some((module, offset)) = &self.module {
+            write!(f, " ({module}+{offset:#x})")?;
+        }
+        Ok(())
+    }
+}
+
+/// The stack of one thread of a hanging target
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct HangStack {
+    /// The id of the thread
+    pub tid: i32,
+    /// The frames in user space, innermost first
+    pub frames: Vec<HangStackFrame>,
+    /// The frames in the kernel, innermost first, if they were readable.
+    /// They are not hashed, as they depend on where the thread was interrupted.
+    pub kernel_frames: Vec<String>,
+}
+
+/// The stacks of all threads of a target, sampled when it timed out
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    expect(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+pub struct HangStackMetadata {
+    /// The stacks of the threads
+    pub stacks: Vec<HangStack>,
+    /// The hash of the user space stacks, independent of the addresses the modules were loaded at
+    pub hash: u64,
+}
+
+libafl_bolts::impl_serdeany!(HangStackMetadata);
+
+impl HangStackMetadata {
+    /// Creates the metadata for the given stacks, and hashes them
+    #[must_use]
+    pub fn new(stacks: Vec<HangStack>) -> Self {
+        let mut keys: Vec<Vec<String>> = stacks
+            .iter()
+            .map(|stack| {
+                stack
+                    .frames
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, frame)| frame.hash_key(idx == 0))
+                    .collect()
+            })
+            .collect();
+        // The order of the threads does not matter
+        keys.sort_unstable();
+        Self {
+            stacks,
+            hash: generic_hash_std(&keys),
+        }
+    }
+}
+
+impl Display for HangStackMetadata {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for stack in &self.stacks {
+            writeln!(f, "Thread {}:", stack.tid)?;
+            for (idx, frame) in stack.frames.iter().enumerate() {
+                writeln!(f, "  #{idx} {frame}")?;
+            }
+            for (idx, frame) in stack.kernel_frames.iter().enumerate() {
+                writeln!(f, "  [kernel] #{idx} {frame}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A file mapped into a process, from `/proc/<pid>/maps`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Mapping {
+    start: u64,
+    end: u64,
+    offset: u64,
+    path: String,
+}
+
+/// Parses the file mappings from the contents of a `/proc/<pid>/maps` file
+fn parse_maps(maps: &str) -> Vec<Mapping> {
+    maps.lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let (start, end) = fields.next()?.split_once('-')?;
+            let offset = fields.nth(1)?;
+            let path = fields.nth(2)?;
+            if !path.starts_with('/') {
+                return None;
+            }
+            Some(Mapping {
+                start: u64::from_str_radix(start, 16).ok()?,
+                end: u64::from_str_radix(end, 16).ok()?,
+                offset: u64::from_str_radix(offset, 16).ok()?,
+                path: path.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// The segments and function symbols of a module, to symbolize addresses in it
+#[derive(Debug, Default)]
+struct ModuleSymbols {
+    /// The file offset, file size and virtual address of each segment
+    segments: Vec<(u64, u64, u64)>,
+    /// The address, size and demangled name of each function, sorted by address
+    functions: Vec<(u64, u64, String)>,
+}
+
+impl ModuleSymbols {
+    /// Reads the symbols of the ELF file at `path`
+    fn load(path: &Path) -> Option<Self> {
+        let data = fs::read(path).ok()?;
+        let file = object::File::parse(&*data).ok()?;
+        let segments = file
+            .segments()
+            .map(|segment| {
+                let (offset, size) = segment.file_range();
+                (offset, size, segment.address())
+            })
+            .collect();
+        let mut functions: Vec<_> = file
+            .symbols()
+            .chain(file.dynamic_symbols())
+            .filter(|symbol| symbol.kind() == SymbolKind::Text && symbol.address() != 0)
+            .filter_map(|symbol| {
+                let name = symbol.name_bytes().ok()?;
+                Some((
+                    symbol.address(),
+                    symbol.size(),
+                    SymbolName::new(name).to_string(),
+                ))
+            })
+            .collect();
+        functions.sort_unstable_by_key(|(address, _, _)| *address);
+        Some(Self {
+            segments,
+            functions,
+        })
+    }
+
+    /// The function at the given file offset
+    fn lookup(&self, offset: u64) -> Option<&str> {
+        let address = self
+            .segments
+            .iter()
+            .find(|(start, size, _)| (*start..start + size).contains(&offset))
+            .map(|(start, _, address)| address + (offset - start))?;
+        let idx = self
+            .functions
+            .partition_point(|(start, _, _)| *start <= address)
+            .checked_sub(1)?;
+        let (start, size, name) = &self.functions[idx];
+        (*size == 0 || address < start + size).then_some(name.as_str())
+    }
+}
+
+/// The program counter and the frame pointer of a stopped thread
+#[cfg(target_arch = "x86_64")]
+fn thread_registers(tid: Pid) -> Result<(u64, u64), Error> {
+    let regs = ptrace::getregs(tid)?;
+    Ok((regs.rip, regs.rbp))
+}
+
+/// The program counter and the frame pointer of a stopped thread
+#[cfg(target_arch = "aarch64")]
+fn thread_registers(tid: Pid) -> Result<(u64, u64), Error> {
+    let regs = ptrace::getregs(tid)?;
+    Ok((regs.pc, regs.regs[29]))
+}
+
+/// The program counter and the frame pointer of a stopped thread
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn thread_registers(_tid: Pid) -> Result<(u64, u64), Error> {
+    Err(Error::unsupported(
+        "Unwinding other processes is only supported on x86_64 and aarch64",
+    ))
+}
+
+/// Unwinds a thread stopped by `ptrace` along the frame pointers, returning the addresses of its frames
+#[expect(clippy::cast_sign_loss)]
+fn unwind_thread(tid: Pid) -> Result<Vec<u64>, Error> {
+    let (pc, mut fp) = thread_registers(tid)?;
+    let mut addresses = vec![pc];
+    // A frame record is the previous frame pointer, followed by the return address
+    while addresses.len() < MAX_FRAMES && fp != 0 && fp % 8 == 0 {
+        let Ok(next_fp) = ptrace::read(tid, fp as ptrace::AddressType) else {
+            break;
+        };
+        let Ok(ret) = ptrace::read(tid, (fp + 8) as ptrace::AddressType) else {
+            break;
+        };
+        if ret == 0 {
+            break;
+        }
+        addresses.push(ret as u64);
+        // The stack grows down, anything else is garbage
+        if next_fp as u64 <= fp {
+            break;
+        }
+        fp = next_fp as u64;
+    }
+    Ok(addresses)
+}
+
+/// An observer sampling the stacks of a target when it times out, see the [module docs](self)
+#[derive(Serialize, Deserialize, Debug)]
+pub struct HangStackObserver<'a> {
+    name: Cow<'static, str>,
+    metadata: Option<HangStackMetadata>,
+    /// The buffer an in-process fork child reports its stack in, in shared memory
+    report: Option<OwnedMutSlice<'a, u8>>,
+    /// The symbols of the modules seen so far, by path
+    #[serde(skip)]
+    symbols: HashMap<String, Option<ModuleSymbols>>,
+}
+
+impl<'a> HangStackObserver<'a> {
+    /// Creates a new [`HangStackObserver`], sampling the children of the `CommandExecutor` or `ForkserverExecutor`
+    #[must_use]
+    pub fn new<S>(name: S) -> Self
+    where
+        S: Into<Cow<'static, str>>,
+    {
+        Self {
+            name: name.into(),
+            metadata: None,
+            report: None,
+            symbols: HashMap::new(),
+        }
+    }
+
+    /// Creates a new [`HangStackObserver`] for the `InProcessForkExecutor`.
+    ///
+    /// The timed-out children unwind their own stack and report it in the `report` buffer, which has to be in shared
+    /// memory. Stacks which do not fit into the buffer are reported without frames, but still with their hash.
+    #[must_use]
+    pub fn with_report_buffer<S>(name: S, report: OwnedMutSlice<'a, u8>) -> Self
+    where
+        S: Into<Cow<'static, str>>,
+    {
+        assert!(
+            report.as_ref().len() > REPORT_HDR_SIZE,
+            "The report buffer of the HangStackObserver is too small"
+        );
+        let mut observer = Self::new(name);
+        observer.report = Some(report);
+        observer.clear();
+        observer
+    }
+
+    /// The stacks sampled in the last run, if it timed out
+    #[must_use]
+    pub fn metadata(&self) -> Option<HangStackMetadata> {
+        let Some(report) = &self.report else {
+            return self.metadata.clone();
+        };
+        let (hdr, data) = report.as_ref().split_at(REPORT_HDR_SIZE);
+        if hdr[0] == 0 {
+            return None;
+        }
+        let len = u32::from_ne_bytes(hdr[4..8].try_into().unwrap()) as usize;
+        let hash = u64::from_ne_bytes(hdr[8..16].try_into().unwrap());
+        let stacks = data
+            .get(..len)
+            .and_then(|data| postcard::from_bytes(data).ok())
+            .unwrap_or_default();
+        Some(HangStackMetadata { stacks, hash })
+    }
+
+    /// Samples the stacks of all threads of the timed-out child `pid`, before it gets killed
+    pub fn sample(&mut self, pid: Pid) {
+        let maps = fs::read_to_string(format!("/proc/{pid}/maps")).unwrap_or_default();
+        let mappings = parse_maps(&maps);
+        let tids: Vec<Pid> = fs::read_dir(format!("/proc/{pid}/task")).map_or_else(
+            |_| vec![pid],
+            |tasks| {
+                tasks
+                    .filter_map(|task| task.ok()?.file_name().to_str()?.parse().ok())
+                    .map(Pid::from_raw)
+                    .collect()
+            },
+        );
+
+        // Stop all threads first, so they do not move on while we unwind the others
+        let mut stopped = Vec::with_capacity(tids.len());
+        for tid in tids {
+            if let Err(err) = ptrace::seize(tid, ptrace::Options::empty())
+                .and_then(|()| ptrace::interrupt(tid))
+                .and_then(|()| waitpid(tid, Some(WaitPidFlag::__WALL)))
+            {
+                log::warn!("Could not stop thread {tid} of the timed-out child: {err}");
+                let _ = ptrace::detach(tid, None);
+                continue;
+            }
+            stopped.push(tid);
+        }
+
+        let mut stacks = Vec::with_capacity(stopped.len());
+        for tid in stopped {
+            let addresses = unwind_thread(tid).unwrap_or_else(|err| {
+                log::warn!("Could not unwind thread {tid} of the timed-out child: {err}");
+                Vec::new()
+            });
+            let _ = ptrace::detach(tid, None);
+            let root = format!("/proc/{pid}/root");
+            let frames = addresses
+                .into_iter()
+                .enumerate()
+                .map(|(idx, address)| self.frame(address, idx == 0, &mappings, &root))
+                .collect();
+            let kernel_frames = fs::read_to_string(format!("/proc/{pid}/task/{tid}/stack"))
+                .map(|stack| {
+                    stack
+                        .lines()
+                        .map(|line| line.split_once("] ").map_or(line, |(_, frame)| frame))
+                        .map(ToString::to_string)
+                        .collect()
+                })
+                .unwrap_or_default();
+            stacks.push(HangStack {
+                tid: tid.as_raw(),
+                frames,
+                kernel_frames,
+            });
+        }
+        self.metadata = Some(HangStackMetadata::new(stacks));
+    }
+
+    /// Symbolizes the frame at `address` with the symbols of the module mapped there, read below `root`
+    fn frame(
+        &mut self,
+        address: u64,
+        innermost: bool,
+        mappings: &[Mapping],
+        root: &str,
+    ) -> HangStackFrame {
+        let Some(mapping) = mappings
+            .iter()
+            .find(|mapping| (mapping.start..mapping.end).contains(&address))
+        else {
+            return HangStackFrame {
+                address,
+                module: None,
+                function: None,
+            };
+        };
+        let offset = address - mapping.start + mapping.offset;
+        // Return addresses may already point to the next function, look up the call instead
+        let call_offset = if innermost { offset } else { offset - 1 };
+        let function = self
+            .symbols
+            .entry(mapping.path.clone())
+            .or_insert_with(|| ModuleSymbols::load(&Path::new(root).join(&mapping.path[1..])))
+            .as_ref()
+            .and_then(|symbols| symbols.lookup(call_offset))
+            .map(ToString::to_string);
+        HangStackFrame {
+            address,
+            module: Some((mapping.path.clone(), offset)),
+            function,
+        }
+    }
+
+    /// Samples the stack of this process, in a timed-out in-process fork child, and reports it to the parent
+    fn sample_self(&mut self) {
+        let maps = fs::read_to_string("/proc/self/maps").unwrap_or_default();
+        let mappings = parse_maps(&maps);
+        let backtrace = Backtrace::new();
+        // Skip the frames of the signal handler, and the signal trampoline it returns to
+        let frames = backtrace.frames();
+        let first = frames
+            .iter()
+            .rposition(|frame| {
+                frame.symbols().iter().any(|symbol| {
+                    symbol
+                        .name()
+                        .is_some_and(|name| name.to_string().contains("handle_signal"))
+                })
+            })
+            .map_or(0, |idx| idx + 2);
+
+        let frames = frames
+            .iter()
+            .skip(first)
+            .filter(|frame| !frame.ip().is_null())
+            .enumerate()
+            .map(|(idx, frame)| {
+                let address = frame.ip() as u64;
+                let module = mappings
+                    .iter()
+                    .find(|mapping| (mapping.start..mapping.end).contains(&address))
+                    .map(|mapping| {
+                        (
+                            mapping.path.clone(),
+                            address - mapping.start + mapping.offset,
+                        )
+                    });
+                let mut function = None;
+                if idx == 0 {
+                    // The interrupted instruction is no return address, undo the adjustment `resolve` does for those
+                    backtrace::resolve(frame.ip().wrapping_byte_add(1), |symbol| {
+                        function = function
+                            .take()
+                            .or_else(|| symbol.name().map(|name| name.to_string()));
+                    });
+                } else {
+                    function = frame
+                        .symbols()
+                        .first()
+                        .and_then(BacktraceSymbol::name)
+                        .map(|name| name.to_string());
+                }
+                HangStackFrame {
+                    address,
+                    module,
+                    function,
+                }
+            })
+            .collect();
+        let metadata = HangStackMetadata::new(vec![HangStack {
+            tid: nix::unistd::gettid().as_raw(),
+            frames,
+            kernel_frames: Vec::new(),
+        }]);
+
+        let report = self.report.as_mut().unwrap().as_mut();
+        let (hdr, data) = report.split_at_mut(REPORT_HDR_SIZE);
+        let len = postcard::to_slice(&metadata.stacks, data).map_or(0, |data| data.len());
+        hdr[..4].copy_from_slice(&1_u32.to_ne_bytes());
+        hdr[4..8].copy_from_slice(&u32::try_from(len).unwrap_or(0).to_ne_bytes());
+        hdr[8..].copy_from_slice(&metadata.hash.to_ne_bytes());
+    }
+
+    /// Forgets the stacks of the last run
+    fn clear(&mut self) {
+        self.metadata = None;
+        if let Some(report) = &mut self.report {
+            report.as_mut()[..REPORT_HDR_SIZE].fill(0);
+        }
+    }
+}
+
+impl ObserverWithHashField for HangStackObserver<'_> {
+    fn hash(&self) -> Option<u64> {
+        match &self.report {
+            Some(report) => {
+                let hdr = &report.as_ref()[..REPORT_HDR_SIZE];
+                (hdr[0] != 0).then(|| u64::from_ne_bytes(hdr[8..16].try_into().unwrap()))
+            }
+            None => self.metadata.as_ref().map(|metadata| metadata.hash),
+        }
+    }
+}
+
+impl<I, S> Observer<I, S> for HangStackObserver<'_> {
+    fn pre_exec(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.clear();
+        Ok(())
+    }
+
+    fn pre_exec_child(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.clear();
+        Ok(())
+    }
+
+    fn post_exec_child(
+        &mut self,
+        _state: &mut S,
+        _input: &I,
+        exit_kind: &ExitKind,
+    ) -> Result<(), Error> {
+        // Only in-process fork children report their own stack, the others are sampled by their executor
+        if *exit_kind == ExitKind::Timeout && self.report.is_some() {
+            self.sample_self();
+        }
+        Ok(())
+    }
+}
+
+impl Named for HangStackObserver<'_> {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::ToString, vec};
+    use std::process::Command;
+
+    use libafl_bolts::ownedref::OwnedMutSlice;
+    use nix::unistd::Pid;
+
+    use super::{
+        HangStack, HangStackFrame, HangStackMetadata, HangStackObserver, Mapping, parse_maps,
+    };
+    use crate::observers::ObserverWithHashField;
+
+    #[test]
+    fn test_hang_stack_hash() {
+        let maps = "5581a6c00000-5581a6c01000 r--p 00000000 fd:01 1234 /usr/bin/target\n\
+             5581a6c01000-5581a6c05000 r-xp 00001000 fd:01 1234 /usr/bin/target\n\
+             7ffd5b1e0000-7ffd5b201000 rw-p 00000000 00:00 0 [stack]\n\
+             7f0e1c000000-7f0e1c021000 rw-p 00000000 00:00 0\n";
+        assert_eq!(
+            parse_maps(maps),
+            [
+                Mapping {
+                    start: 0x5581_a6c0_0000,
+                    end: 0x5581_a6c0_1000,
+                    offset: 0,
+                    path: "/usr/bin/target".to_string(),
+                },
+                Mapping {
+                    start: 0x5581_a6c0_1000,
+                    end: 0x5581_a6c0_5000,
+                    offset: 0x1000,
+                    path: "/usr/bin/target".to_string(),
+                },
+            ]
+        );
+
+        let frame = |address, offset, function: Option<&str>| HangStackFrame {
+            address,
+            module: Some(("/usr/bin/target".to_string(), offset)),
+            function: function.map(ToString::to_string),
+        };
+        let stack = |base, pc, tid| HangStack {
+            tid,
+            frames: vec![
+                frame(base + pc, pc, Some("spin")),
+                frame(base + 0x2345, 0x2345, Some("main")),
+            ],
+            kernel_frames: vec![],
+        };
+        // The same loop, loaded elsewhere and interrupted at another instruction
+        let first = HangStackMetadata::new(vec![stack(0x5581_a6c0_0000, 0x1234, 1)]);
+        let mut second = stack(0x7f00_0000_0000, 0x1250, 2);
+        second.kernel_frames = vec!["asm_sysvec_apic_timer_interrupt+0x1b/0x20".to_string()];
+        assert_eq!(first.hash, HangStackMetadata::new(vec![second]).hash);
+
+        let mut other = stack(0x5581_a6c0_0000, 0x1234, 1);
+        other.frames[1] = frame(0x5581_a6c0_2400, 0x2400, Some("main"));
+        assert_ne!(first.hash, HangStackMetadata::new(vec![other]).hash);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_hang_stack_sample_self() {
+        let mut observer = HangStackObserver::with_report_buffer(
+            "hang_stack",
+            OwnedMutSlice::from(vec![0; 64 * 1024]),
+        );
+        assert_eq!(observer.metadata(), None);
+
+        observer.sample_self();
+        let metadata = observer.metadata().unwrap();
+        assert_eq!(observer.hash(), Some(metadata.hash));
+        assert_eq!(metadata.stacks.len(), 1);
+        assert_eq!(metadata.stacks[0].tid, nix::unistd::gettid().as_raw());
+        // Outside of a signal handler, no frames are skipped
+        assert!(metadata.stacks[0].frames.iter().any(|frame| {
+            frame
+                .function
+                .as_ref()
+                .is_some_and(|function| function.contains("test_hang_stack_sample_self"))
+        }));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_hang_stack_sample() {
+        let mut child = Command::new("sleep").arg("10").spawn().unwrap();
+        let mut observer = HangStackObserver::new("hang_stack");
+        let pid = Pid::from_raw(i32::try_from(child.id()).unwrap());
+        observer.sample(pid);
+        child.kill().unwrap();
+        child.wait().unwrap();
+
+        let metadata = observer.metadata().unwrap();
+        assert_eq!(observer.hash(), Some(metadata.hash));
+        if metadata.stacks.is_empty() {
+            log::warn!("Skipping the rest of the hang stack test, children cannot be traced");
+            return;
+        }
+        assert_eq!(metadata.stacks[0].tid, pid.as_raw());
+        // The stopped child is interrupted in a mapped module, like libc or `sleep` itself
+        let frames = &metadata.stacks[0].frames;
+        assert!(frames.first().is_none_or(|frame| frame.module.is_some()));
+    }
+}
@@ -6,20 +6,33 @@ pub use cmp::*;
 
 #[cfg(feature = "std")]
 pub mod stdio;
+#[cfg(all(feature = "std", feature = "regex"))]
+pub use stdio::RegexNormalizer;
 #[cfg(feature = "std")]
-pub use stdio::{StdErrObserver, StdOutObserver};
+pub use stdio::{
+    JsonNormalizer, OutputNormalizer, OutputNormalizersTuple, StdErrObserver, StdOutObserver,
+    WhitespaceNormalizer,
+};
 
 #[cfg(feature = "regex")]
 pub mod stacktrace;
 #[cfg(feature = "regex")]
 pub use stacktrace::*;
 
+#[cfg(all(feature = "std", target_os = "linux"))]
+pub mod hang_stack;
+#[cfg(all(feature = "std", target_os = "linux"))]
+pub use hang_stack::*;
+
 pub mod concolic;
 pub mod map;
 pub use map::*;
 
 pub mod value;
 
+pub mod network;
+pub use network::*;
+
 /// List observer
 pub mod list;
 use core::{fmt::Debug, time::Duration};
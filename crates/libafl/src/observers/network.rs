@@ -0,0 +1,219 @@
+//! Observers for the responses of a network server to the messages of an input.
+//!
+//! The [`ResponseObserver`] captures the raw responses, the [`ProtocolStateObserver`] extracts the protocol
+//! state codes from them, for stateful protocol fuzzing.
+//! The responses are filled in by executors talking to the target over the network, like the
+//! `NetworkExecutor`.
+
+use alloc::{borrow::Cow, vec::Vec};
+
+use libafl_bolts::Named;
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, observers::Observer};
+
+/// The state a server is in before it received any message
+pub const INITIAL_PROTOCOL_STATE: u32 = 0;
+
+/// An observer the responses of a server can be stored in
+pub trait HasResponses: Named {
+    /// The responses of the last execution, in the order of the messages sent.
+    ///
+    /// A message which did not get a response has an empty response; if the server closed the
+    /// connection, there are less responses than messages.
+    fn responses(&self) -> &[Vec<u8>];
+
+    /// Sets the responses of the current execution
+    fn set_responses(&mut self, responses: Vec<Vec<u8>>);
+}
+
+/// An observer capturing the responses of a network server, one per message sent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResponseObserver {
+    name: Cow<'static, str>,
+    responses: Vec<Vec<u8>>,
+}
+
+impl ResponseObserver {
+    /// Creates a new [`ResponseObserver`] with the given name
+    #[must_use]
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name: Cow::from(name),
+            responses: Vec::new(),
+        }
+    }
+}
+
+impl HasResponses for ResponseObserver {
+    fn responses(&self) -> &[Vec<u8>] {
+        &self.responses
+    }
+
+    fn set_responses(&mut self, responses: Vec<Vec<u8>>) {
+        self.responses = responses;
+    }
+}
+
+impl Named for ResponseObserver {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<I, S> Observer<I, S> for ResponseObserver {
+    fn pre_exec(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.responses.clear();
+        Ok(())
+    }
+
+    fn pre_exec_child(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.responses.clear();
+        Ok(())
+    }
+}
+
+/// Extracts the protocol state code from a response of the server
+pub trait StateExtractor {
+    /// The state code the server is in after sending `response`, if the response contains one
+    fn extract_state(&self, response: &[u8]) -> Option<u32>;
+}
+
+/// Extracts the numeric status code at the start of a response, after an optional prefix.
+///
+/// This covers most text protocols: `220 Service ready` for FTP or SMTP, or
+/// `RTSP/1.0 200 OK` for RTSP with the `RTSP/1.0 ` prefix.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ResponseCodeExtractor {
+    prefix: Vec<u8>,
+}
+
+impl ResponseCodeExtractor {
+    /// Extracts the status code at the very start of the response
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Extracts the status code following the given prefix
+    #[must_use]
+    pub fn with_prefix(prefix: &[u8]) -> Self {
+        Self {
+            prefix: prefix.to_vec(),
+        }
+    }
+}
+
+impl StateExtractor for ResponseCodeExtractor {
+    fn extract_state(&self, response: &[u8]) -> Option<u32> {
+        let rest = response.strip_prefix(self.prefix.as_slice())?;
+        let digits = rest.iter().take_while(|b| b.is_ascii_digit()).count();
+        if digits == 0 {
+            return None;
+        }
+        rest[..digits].iter().try_fold(0_u32, |code, digit| {
+            code.checked_mul(10)?.checked_add(u32::from(digit - b'0'))
+        })
+    }
+}
+
+/// An observer extracting the sequence of protocol states a server went through from its responses.
+///
+/// There is one state code per response; responses without a state code do not change the state.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProtocolStateObserver<E> {
+    name: Cow<'static, str>,
+    extractor: E,
+    responses: Vec<Vec<u8>>,
+    codes: Vec<Option<u32>>,
+}
+
+impl<E> ProtocolStateObserver<E>
+where
+    E: StateExtractor,
+{
+    /// Creates a new [`ProtocolStateObserver`] using the given [`StateExtractor`]
+    #[must_use]
+    pub fn new(name: &'static str, extractor: E) -> Self {
+        Self {
+            name: Cow::from(name),
+            extractor,
+            responses: Vec::new(),
+            codes: Vec::new(),
+        }
+    }
+
+    /// The state code extracted from each response of the last execution
+    #[must_use]
+    pub fn codes(&self) -> &[Option<u32>] {
+        &self.codes
+    }
+
+    /// The states the server went through, starting at [`INITIAL_PROTOCOL_STATE`]
+    pub fn states(&self) -> impl Iterator<Item = u32> + '_ {
+        core::iter::once(INITIAL_PROTOCOL_STATE).chain(self.codes.iter().flatten().copied())
+    }
+}
+
+impl<E> HasResponses for ProtocolStateObserver<E>
+where
+    E: StateExtractor,
+{
+    fn responses(&self) -> &[Vec<u8>] {
+        &self.responses
+    }
+
+    fn set_responses(&mut self, responses: Vec<Vec<u8>>) {
+        self.codes = responses
+            .iter()
+            .map(|response| self.extractor.extract_state(response))
+            .collect();
+        self.responses = responses;
+    }
+}
+
+impl<E> Named for ProtocolStateObserver<E> {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<E, I, S> Observer<I, S> for ProtocolStateObserver<E> {
+    fn pre_exec(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.responses.clear();
+        self.codes.clear();
+        Ok(())
+    }
+
+    fn pre_exec_child(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.responses.clear();
+        self.codes.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{vec, vec::Vec};
+
+    use crate::observers::{
+        HasResponses, ProtocolStateObserver, ResponseCodeExtractor, StateExtractor,
+    };
+
+    #[test]
+    fn test_response_code_extractor() {
+        let ftp = ResponseCodeExtractor::new();
+        assert_eq!(ftp.extract_state(b"220 Service ready\r\n"), Some(220));
+        assert_eq!(ftp.extract_state(b"Hello"), None);
+        assert_eq!(ftp.extract_state(b"99999999999 overflow"), None);
+
+        let rtsp = ResponseCodeExtractor::with_prefix(b"RTSP/1.0 ");
+        assert_eq!(rtsp.extract_state(b"RTSP/1.0 404 Not Found\r\n"), Some(404));
+        assert_eq!(rtsp.extract_state(b"200 OK"), None);
+
+        let mut observer = ProtocolStateObserver::new("states", ftp);
+        observer.set_responses(vec![b"220 hi".to_vec(), vec![], b"331 pass?".to_vec()]);
+        assert_eq!(observer.codes(), &[Some(220), None, Some(331)]);
+        assert_eq!(observer.states().collect::<Vec<_>>(), [0, 220, 331]);
+    }
+}
@@ -104,7 +104,9 @@ pub fn collect_backtrace() -> u64 {
 pub enum HarnessType {
     /// Harness type when the target is in the same process
     InProcess,
-    /// Harness type when the target is a child process
+    /// Harness type when the target is a child process, e.g. of an [`crate::executors::InProcessForkExecutor`].
+    ///
+    /// The hash is computed in the child, by `post_exec_child`, so it has to live in shared memory to reach the parent.
     Child,
     /// Harness type with an external component filling the backtrace hash (e.g. `CrashBacktraceCollector` in `libafl_qemu`)
     External,
@@ -208,11 +210,22 @@ impl<I, S> Observer<I, S> for BacktraceObserver<'_> {
 
     fn post_exec_child(
         &mut self,
-        state: &mut S,
-        input: &I,
+        _state: &mut S,
+        _input: &I,
         exit_kind: &ExitKind,
     ) -> Result<(), Error> {
-        self.post_exec(state, input, exit_kind)
+        // The stack of the crash is only available in the child, the parent sees the hash through shared memory
+        if matches!(
+            self.harness_type,
+            HarnessType::InProcess | HarnessType::Child
+        ) {
+            if *exit_kind == ExitKind::Crash {
+                self.update_hash(collect_backtrace());
+            } else {
+                self.clear_hash();
+            }
+        }
+        Ok(())
     }
 }
 
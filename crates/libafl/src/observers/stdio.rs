@@ -2,6 +2,9 @@
 //!
 //! The [`StdOutObserver`] and [`StdErrObserver`] observers look at the stdout of a program
 //! The executor must explicitly support these observers.
+//!
+//! Captured output can be canonicalized with [`OutputNormalizer`]s, for example to ignore
+//! timestamps or formatting differences when comparing the output of several targets.
 #![cfg_attr(
     unix,
     doc = r"For example, they are supported on the [`crate::executors::CommandExecutor`] and [`crate::executors::ForkserverExecutor`]."
@@ -343,3 +346,182 @@ where
 pub type StdOutObserver = OutputObserver<StdOutMarker>;
 /// An observer that captures stderr of a target.
 pub type StdErrObserver = OutputObserver<StdErrMarker>;
+
+/// Rewrites captured output into a canonical form before it is compared,
+/// for example by the [`crate::executors::MultiDiffExecutor`].
+pub trait OutputNormalizer {
+    /// Normalize the given output
+    fn normalize(&mut self, output: Vec<u8>) -> Vec<u8>;
+}
+
+impl<F> OutputNormalizer for F
+where
+    F: FnMut(Vec<u8>) -> Vec<u8>,
+{
+    fn normalize(&mut self, output: Vec<u8>) -> Vec<u8> {
+        self(output)
+    }
+}
+
+/// A tuple of [`OutputNormalizer`]s, applied in order
+pub trait OutputNormalizersTuple {
+    /// Run all normalizers on the given output, one after the other
+    fn normalize_all(&mut self, output: Vec<u8>) -> Vec<u8>;
+}
+
+impl OutputNormalizersTuple for () {
+    fn normalize_all(&mut self, output: Vec<u8>) -> Vec<u8> {
+        output
+    }
+}
+
+impl<Head, Tail> OutputNormalizersTuple for (Head, Tail)
+where
+    Head: OutputNormalizer,
+    Tail: OutputNormalizersTuple,
+{
+    fn normalize_all(&mut self, output: Vec<u8>) -> Vec<u8> {
+        let output = self.0.normalize(output);
+        self.1.normalize_all(output)
+    }
+}
+
+/// Removes all ASCII whitespace from the output
+#[derive(Debug, Default, Clone, Copy)]
+pub struct WhitespaceNormalizer;
+
+impl OutputNormalizer for WhitespaceNormalizer {
+    fn normalize(&mut self, mut output: Vec<u8>) -> Vec<u8> {
+        output.retain(|b| !b.is_ascii_whitespace());
+        output
+    }
+}
+
+/// Re-serializes JSON output (or a stream of JSON values, such as JSON lines) in compact form,
+/// with object keys sorted. Output that is not valid JSON is left untouched.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct JsonNormalizer;
+
+impl JsonNormalizer {
+    fn canonicalize(value: serde_json::Value) -> serde_json::Value {
+        match value {
+            serde_json::Value::Object(map) => {
+                let mut entries: Vec<_> = map
+                    .into_iter()
+                    .map(|(key, value)| (key, Self::canonicalize(value)))
+                    .collect();
+                entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+                serde_json::Value::Object(entries.into_iter().collect())
+            }
+            serde_json::Value::Array(values) => {
+                serde_json::Value::Array(values.into_iter().map(Self::canonicalize).collect())
+            }
+            value => value,
+        }
+    }
+}
+
+impl OutputNormalizer for JsonNormalizer {
+    fn normalize(&mut self, output: Vec<u8>) -> Vec<u8> {
+        let mut normalized = Vec::with_capacity(output.len());
+        for value in serde_json::Deserializer::from_slice(&output).into_iter() {
+            let Ok(value) = value else {
+                return output;
+            };
+            if !normalized.is_empty() {
+                normalized.push(b'\n');
+            }
+            if serde_json::to_writer(&mut normalized, &Self::canonicalize(value)).is_err() {
+                return output;
+            }
+        }
+        normalized
+    }
+}
+
+/// Replaces every match of a regular expression in the output, for example to mask
+/// timestamps, addresses, or process ids that differ between runs.
+#[cfg(feature = "regex")]
+#[derive(Debug, Clone)]
+pub struct RegexNormalizer {
+    regex: regex::bytes::Regex,
+    replacement: Vec<u8>,
+}
+
+#[cfg(feature = "regex")]
+impl RegexNormalizer {
+    /// Create a new [`RegexNormalizer`], replacing all matches of `pattern` with `replacement`.
+    ///
+    /// The replacement may reference capture groups as `$1` or `${name}`, and needs `$$` for a literal `$`,
+    /// see [`regex::bytes::Regex::replace_all`].
+    pub fn new(pattern: &str, replacement: &[u8]) -> Result<Self, Error> {
+        let regex = regex::bytes::Regex::new(pattern)
+            .map_err(|e| Error::illegal_argument(format!("Invalid regex {pattern}: {e}")))?;
+        Ok(Self {
+            regex,
+            replacement: replacement.to_vec(),
+        })
+    }
+
+    /// A [`RegexNormalizer`] masking common timestamp formats: ISO 8601 dates,
+    /// `HH:MM:SS` times of day, and 10 or 13 digit unix epochs.
+    #[must_use]
+    pub fn timestamps() -> Self {
+        Self::new(
+            r"\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}:\d{2}(\.\d+)?(Z|[+-]\d{2}:?\d{2})?|\b\d{2}:\d{2}:\d{2}(\.\d+)?\b|\b1\d{9}(\d{3})?\b",
+            b"<timestamp>",
+        )
+        .unwrap()
+    }
+}
+
+#[cfg(feature = "regex")]
+impl OutputNormalizer for RegexNormalizer {
+    fn normalize(&mut self, output: Vec<u8>) -> Vec<u8> {
+        match self.regex.replace_all(&output, self.replacement.as_slice()) {
+            Cow::Borrowed(_) => output,
+            Cow::Owned(normalized) => normalized,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::tuples::tuple_list;
+
+    use super::{JsonNormalizer, OutputNormalizersTuple, WhitespaceNormalizer};
+
+    #[test]
+    fn test_normalizers() {
+        let mut normalizers = tuple_list!(JsonNormalizer, WhitespaceNormalizer);
+        assert_eq!(
+            normalizers
+                .normalize_all(b"{\"b\": [1, {\"d\": 2, \"c\": 3}], \"a\": null}\n{}".to_vec()),
+            b"{\"a\":null,\"b\":[1,{\"c\":3,\"d\":2}]}{}"
+        );
+        assert_eq!(
+            normalizers.normalize_all(b" not\tjson {\n".to_vec()),
+            b"notjson{"
+        );
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_regex_normalizer() {
+        use super::{OutputNormalizer, RegexNormalizer};
+
+        let mut normalizer = RegexNormalizer::timestamps();
+        assert_eq!(
+            normalizer.normalize(
+                b"[2024-05-01T12:30:00.123Z] started at 12:30:01, epoch 1714566600 id 42".to_vec()
+            ),
+            b"[<timestamp>] started at <timestamp>, epoch <timestamp> id 42"
+        );
+
+        let mut normalizer = RegexNormalizer::new(r"pid (\d+)", b"pid <$1>").unwrap();
+        assert_eq!(
+            normalizer.normalize(b"child pid 1234 exited".to_vec()),
+            b"child pid <1234> exited"
+        );
+    }
+}
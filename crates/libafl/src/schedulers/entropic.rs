@@ -0,0 +1,758 @@
+//! The Entropic power schedule, as found in libFuzzer.
+//!
+//! Each corpus entry tracks how often the executions of its mutants hit globally *rare* features.
+//! The energy of an entry is an estimate of the information it may still reveal about the rare
+//! features, see [Entropic: Boosting Fuzzing Efficiency by Entropy-based Power Schedules](https://mboehme.github.io/paper/FSE20.Entropy.pdf)
+//! and the [libFuzzer implementation](https://github.com/llvm/llvm-project/blob/main/compiler-rt/lib/fuzzer/FuzzerCorpus.h).
+//!
+//! New features are taken from the [`MapNoveltiesMetadata`] of newly added testcases,
+//! so the map feedback needs to be created from an observer with `track_novelties()`.
+
+use alloc::vec::Vec;
+use core::{hash::Hash, marker::PhantomData, num::NonZero};
+
+use hashbrown::HashMap;
+use libafl_bolts::{
+    Named, nonzero,
+    rands::Rand,
+    tuples::{Handle, Handled, MatchName, MatchNameRef},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Error, HasMetadata,
+    corpus::{Corpus, CorpusId, HasTestcase, Testcase},
+    feedbacks::{MapIndexesMetadata, MapNoveltiesMetadata},
+    observers::MapObserver,
+    schedulers::{
+        AflScheduler, HasQueueCycles, RemovableScheduler, Scheduler, on_add_metadata_default,
+        on_evaluation_metadata_default, on_next_metadata_default, powersched::SchedulerMetadata,
+        testcase_score::TestcaseScore,
+    },
+    state::{HasCorpus, HasRand},
+};
+
+/// The default number of rare features kept before the most abundant ones are dropped
+pub const DEFAULT_NUMBER_OF_RARE_FEATURES: usize = 100;
+/// The default frequency above which a rare feature can be dropped
+pub const DEFAULT_FEATURE_FREQUENCY_THRESHOLD: u16 = 0xff;
+
+/// Testcases that were fuzzed this many times more than the average get no energy
+const MAX_MUTATION_FACTOR: u64 = 20;
+/// The distribution is recomputed on average every this many calls to `next`, even without changes
+const SPARSE_ENERGY_UPDATES: usize = 100;
+
+/// The global state of the Entropic power schedule
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    expect(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EntropicMetadata {
+    /// The currently rare features and how often they were hit, globally
+    rare_features: HashMap<usize, u16>,
+    /// The global hit count of the most abundant rare feature
+    freq_of_most_abundant_rare_feature: u16,
+    /// The number of rare features kept before abundant ones are dropped
+    number_of_rare_features: usize,
+    /// The frequency a rare feature needs to reach before it can be dropped
+    feature_frequency_threshold: u16,
+    /// Scale the energy of a testcase by its execution time
+    scale_per_exec_time: bool,
+    /// The number of executions of mutants, over all testcases
+    num_executed_mutations: u64,
+}
+
+libafl_bolts::impl_serdeany!(EntropicMetadata);
+
+impl Default for EntropicMetadata {
+    fn default() -> Self {
+        Self::new(
+            DEFAULT_NUMBER_OF_RARE_FEATURES,
+            DEFAULT_FEATURE_FREQUENCY_THRESHOLD,
+            false,
+        )
+    }
+}
+
+impl EntropicMetadata {
+    /// Creates a new [`struct@EntropicMetadata`]
+    #[must_use]
+    pub fn new(
+        number_of_rare_features: usize,
+        feature_frequency_threshold: u16,
+        scale_per_exec_time: bool,
+    ) -> Self {
+        Self {
+            rare_features: HashMap::default(),
+            freq_of_most_abundant_rare_feature: 0,
+            number_of_rare_features,
+            feature_frequency_threshold,
+            scale_per_exec_time,
+            num_executed_mutations: 0,
+        }
+    }
+
+    /// The currently rare features, mapped to their global hit count
+    #[must_use]
+    pub fn rare_features(&self) -> &HashMap<usize, u16> {
+        &self.rare_features
+    }
+
+    /// Checks if the given feature is currently considered rare
+    #[must_use]
+    pub fn is_rare(&self, feature: usize) -> bool {
+        self.rare_features.contains_key(&feature)
+    }
+
+    /// The global hit count of the most abundant rare feature
+    #[must_use]
+    pub fn freq_of_most_abundant_rare_feature(&self) -> u16 {
+        self.freq_of_most_abundant_rare_feature
+    }
+
+    /// The number of rare features kept before abundant ones are dropped
+    #[must_use]
+    pub fn number_of_rare_features(&self) -> usize {
+        self.number_of_rare_features
+    }
+
+    /// The frequency a rare feature needs to reach before it can be dropped
+    #[must_use]
+    pub fn feature_frequency_threshold(&self) -> u16 {
+        self.feature_frequency_threshold
+    }
+
+    /// If the energy of a testcase is scaled by its execution time
+    #[must_use]
+    pub fn scale_per_exec_time(&self) -> bool {
+        self.scale_per_exec_time
+    }
+
+    /// The number of executions of mutants, over all testcases
+    #[must_use]
+    pub fn num_executed_mutations(&self) -> u64 {
+        self.num_executed_mutations
+    }
+
+    /// Records a hit of a feature, returns `true` if the hit should be attributed to the current testcase.
+    fn hit_feature(&mut self, feature: usize) -> bool {
+        let most_abundant = self.freq_of_most_abundant_rare_feature;
+        let Some(freq) = self.rare_features.get_mut(&feature) else {
+            return false;
+        };
+        // Saturated increment.
+        if *freq == u16::MAX {
+            return false;
+        }
+        let prev = *freq;
+        *freq += 1;
+        // Skip if abundant.
+        if prev > most_abundant {
+            return false;
+        }
+        if prev == most_abundant {
+            self.freq_of_most_abundant_rare_feature += 1;
+        }
+        true
+    }
+
+    /// Drops the most abundant rare feature, returns it (if any)
+    fn drop_most_abundant(&mut self) -> Option<usize> {
+        let mut most: Option<(usize, u16)> = None;
+        let mut second: u16 = 0;
+        for (&feature, &freq) in &self.rare_features {
+            match most {
+                Some((_, most_freq)) if freq < most_freq => second = second.max(freq),
+                Some((_, most_freq)) => {
+                    second = most_freq;
+                    most = Some((feature, freq));
+                }
+                None => most = Some((feature, freq)),
+            }
+        }
+        let (feature, _) = most?;
+        self.rare_features.remove(&feature);
+        self.freq_of_most_abundant_rare_feature = second;
+        Some(feature)
+    }
+}
+
+/// The per-testcase state of the Entropic power schedule
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    expect(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EntropicTestcaseMetadata {
+    /// How often the mutants of this testcase hit each rare feature, sorted by feature
+    feature_freqs: Vec<(usize, u16)>,
+    /// The number of features covered by this testcase
+    num_features: usize,
+    /// The number of executions of mutants of this testcase
+    num_executed_mutations: u64,
+    /// The energy, as last computed
+    energy: f64,
+    /// The sum of the (smoothed) incidences, as last computed
+    sum_incidence: f64,
+    /// If the energy needs to be recomputed
+    needs_energy_update: bool,
+}
+
+libafl_bolts::impl_serdeany!(EntropicTestcaseMetadata);
+
+impl EntropicTestcaseMetadata {
+    /// Creates a new [`struct@EntropicTestcaseMetadata`], given the current number of rare features
+    #[must_use]
+    #[expect(clippy::cast_precision_loss)]
+    pub fn new(num_features: usize, num_rare_features: usize) -> Self {
+        Self {
+            feature_freqs: Vec::new(),
+            num_features,
+            num_executed_mutations: 0,
+            energy: if num_rare_features == 0 {
+                1.0
+            } else {
+                libm::log(num_rare_features as f64)
+            },
+            sum_incidence: num_rare_features as f64,
+            needs_energy_update: false,
+        }
+    }
+
+    /// How often the mutants of this testcase hit each rare feature
+    #[must_use]
+    pub fn feature_freqs(&self) -> &[(usize, u16)] {
+        &self.feature_freqs
+    }
+
+    /// The number of features covered by this testcase
+    #[must_use]
+    pub fn num_features(&self) -> usize {
+        self.num_features
+    }
+
+    /// The number of executions of mutants of this testcase
+    #[must_use]
+    pub fn num_executed_mutations(&self) -> u64 {
+        self.num_executed_mutations
+    }
+
+    /// The energy, as last computed
+    #[must_use]
+    pub fn energy(&self) -> f64 {
+        self.energy
+    }
+
+    /// If the energy needs to be recomputed
+    #[must_use]
+    pub fn needs_energy_update(&self) -> bool {
+        self.needs_energy_update
+    }
+
+    /// Records a hit of a rare feature by a mutant of this testcase
+    pub fn hit_feature(&mut self, feature: usize) {
+        match self
+            .feature_freqs
+            .binary_search_by_key(&feature, |(f, _)| *f)
+        {
+            Ok(pos) => {
+                let freq = &mut self.feature_freqs[pos].1;
+                *freq = freq.saturating_add(1);
+            }
+            Err(pos) => self.feature_freqs.insert(pos, (feature, 1)),
+        }
+        self.needs_energy_update = true;
+    }
+
+    /// Forgets about a feature, returns `true` if it was tracked
+    pub fn remove_feature(&mut self, feature: usize) -> bool {
+        if let Ok(pos) = self
+            .feature_freqs
+            .binary_search_by_key(&feature, |(f, _)| *f)
+        {
+            self.feature_freqs.remove(pos);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Applies add-one smoothing for a feature that just became rare, without a full recomputation.
+    /// Testcases with zero energy keep zero energy.
+    fn add_undiscovered_feature(&mut self) {
+        if self.energy > 0.0 {
+            self.sum_incidence += 1.0;
+            self.energy += libm::log(self.sum_incidence) / self.sum_incidence;
+        }
+    }
+
+    /// Recomputes the energy, i.e., the entropy over the rare features hit by mutants of this testcase
+    #[expect(clippy::cast_precision_loss)]
+    pub fn update_energy(&mut self, num_rare_features: usize) {
+        let mut energy = 0.0;
+        let mut sum_incidence = 0.0;
+
+        // Apply add-one smoothing to locally discovered features.
+        for (_, freq) in &self.feature_freqs {
+            let local_incidence = f64::from(*freq) + 1.0;
+            energy -= local_incidence * libm::log(local_incidence);
+            sum_incidence += local_incidence;
+        }
+
+        // Apply add-one smoothing to locally undiscovered features (log(1) == 0).
+        sum_incidence += num_rare_features.saturating_sub(self.feature_freqs.len()) as f64;
+
+        // Add a single locally abundant feature, with add-one smoothing.
+        let abundant_incidence = (self.num_executed_mutations + 1) as f64;
+        energy -= abundant_incidence * libm::log(abundant_incidence);
+        sum_incidence += abundant_incidence;
+
+        if sum_incidence != 0.0 {
+            energy = energy / sum_incidence + libm::log(sum_incidence);
+        }
+
+        self.energy = energy;
+        self.sum_incidence = sum_incidence;
+        self.needs_energy_update = false;
+    }
+}
+
+/// The energy of each corpus entry, according to the Entropic power schedule.
+///
+/// Entries without features, or that were fuzzed a lot more than the average, get zero energy.
+#[derive(Debug, Clone)]
+pub struct EntropicTestcaseScore {}
+
+impl<I, S> TestcaseScore<I, S> for EntropicTestcaseScore
+where
+    S: HasCorpus<I> + HasMetadata,
+{
+    #[expect(clippy::cast_precision_loss)]
+    fn compute(state: &S, entry: &mut Testcase<I>) -> Result<f64, Error> {
+        let emeta = state.metadata::<EntropicMetadata>()?;
+        let num_rare_features = emeta.rare_features().len();
+        let scale_per_exec_time = emeta.scale_per_exec_time();
+        let avg_executed_mutations =
+            emeta.num_executed_mutations() / (state.corpus().count().max(1) as u64);
+
+        let exec_time = *entry.exec_time();
+        let tcmeta = entry.metadata_mut::<EntropicTestcaseMetadata>()?;
+        if tcmeta.needs_energy_update && tcmeta.energy != 0.0 {
+            tcmeta.update_energy(num_rare_features);
+        }
+
+        if tcmeta.num_features == 0
+            || tcmeta.num_executed_mutations / MAX_MUTATION_FACTOR > avg_executed_mutations
+        {
+            return Ok(0.0);
+        }
+        let mut energy = tcmeta.energy;
+
+        if scale_per_exec_time {
+            // Favor testcases that execute faster than the average.
+            if let (Some(exec_time), Ok(psmeta)) =
+                (exec_time, state.metadata::<SchedulerMetadata>())
+            {
+                if psmeta.cycles() > 0 {
+                    let avg = psmeta.exec_time().as_nanos() as f64 / psmeta.cycles() as f64;
+                    let t = exec_time.as_nanos() as f64;
+                    let perf_score = if t > avg * 10.0 {
+                        10.0
+                    } else if t > avg * 4.0 {
+                        25.0
+                    } else if t > avg * 2.0 {
+                        50.0
+                    } else if t * 3.0 > avg * 4.0 {
+                        75.0
+                    } else if t * 4.0 < avg {
+                        300.0
+                    } else if t * 3.0 < avg {
+                        200.0
+                    } else if t * 2.0 < avg {
+                        150.0
+                    } else {
+                        100.0
+                    };
+                    energy *= perf_score;
+                }
+            }
+        }
+
+        Ok(energy)
+    }
+}
+
+/// A corpus scheduler selecting entries proportionally to their Entropic energy.
+///
+/// Like the other AFL-style schedulers, it also maintains [`SchedulerMetadata`], so it can be
+/// combined with the [`crate::stages::CalibrationStage`] and power mutational stages.
+#[derive(Debug, Clone)]
+pub struct EntropicScheduler<C, O> {
+    observer_handle: Handle<C>,
+    last_hash: usize,
+    queue_cycles: u64,
+    runs_in_current_cycle: usize,
+    distribution_needs_update: bool,
+    /// The cumulative weights of the corpus entries
+    distribution: Vec<(CorpusId, f64)>,
+    phantom: PhantomData<O>,
+}
+
+impl<C, O> EntropicScheduler<C, O>
+where
+    C: AsRef<O> + Named,
+    O: MapObserver,
+{
+    /// Creates a new [`EntropicScheduler`] with the libFuzzer defaults
+    #[must_use]
+    pub fn new<S>(state: &mut S, observer: &C) -> Self
+    where
+        S: HasMetadata,
+    {
+        Self::with_params(
+            state,
+            observer,
+            DEFAULT_NUMBER_OF_RARE_FEATURES,
+            DEFAULT_FEATURE_FREQUENCY_THRESHOLD,
+            false,
+        )
+    }
+
+    /// Creates a new [`EntropicScheduler`], like libFuzzer's `-entropic_number_of_rarest_features`,
+    /// `-entropic_feature_frequency_threshold` and `-entropic_scale_per_exec_time` flags.
+    #[must_use]
+    pub fn with_params<S>(
+        state: &mut S,
+        observer: &C,
+        number_of_rare_features: usize,
+        feature_frequency_threshold: u16,
+        scale_per_exec_time: bool,
+    ) -> Self
+    where
+        S: HasMetadata,
+    {
+        let _ = state.metadata_or_insert_with(|| SchedulerMetadata::new(None));
+        let _ = state.metadata_or_insert_with(|| {
+            EntropicMetadata::new(
+                number_of_rare_features,
+                feature_frequency_threshold,
+                scale_per_exec_time,
+            )
+        });
+
+        Self {
+            observer_handle: observer.handle(),
+            last_hash: 0,
+            queue_cycles: 0,
+            runs_in_current_cycle: 0,
+            distribution_needs_update: true,
+            distribution: Vec::new(),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Adds a newly discovered feature as rare feature, dropping abundant rare features if there are too many.
+    pub fn add_rare_feature<I, S>(&mut self, state: &mut S, feature: usize) -> Result<(), Error>
+    where
+        S: HasCorpus<I> + HasMetadata,
+    {
+        let emeta = state.metadata_mut::<EntropicMetadata>()?;
+        let mut dropped = Vec::new();
+        while emeta.rare_features.len() > emeta.number_of_rare_features
+            && emeta.freq_of_most_abundant_rare_feature > emeta.feature_frequency_threshold
+        {
+            match emeta.drop_most_abundant() {
+                Some(f) => dropped.push(f),
+                None => break,
+            }
+        }
+        emeta.rare_features.insert(feature, 0);
+
+        for id in state.corpus().ids() {
+            let mut testcase = state.corpus().get(id)?.borrow_mut();
+            if let Ok(tcmeta) = testcase.metadata_mut::<EntropicTestcaseMetadata>() {
+                for f in &dropped {
+                    if tcmeta.remove_feature(*f) {
+                        tcmeta.needs_energy_update = true;
+                    }
+                }
+                tcmeta.remove_feature(feature);
+                tcmeta.add_undiscovered_feature();
+            }
+        }
+
+        self.distribution_needs_update = true;
+        Ok(())
+    }
+
+    /// Recomputes the cumulative weights of all corpus entries
+    #[expect(clippy::cast_precision_loss)]
+    fn update_distribution<I, S>(&mut self, state: &S) -> Result<(), Error>
+    where
+        S: HasCorpus<I> + HasMetadata,
+    {
+        let mut weights = Vec::with_capacity(state.corpus().count());
+        let mut vanishing = true;
+        for id in state.corpus().ids() {
+            let mut testcase = state.corpus().get(id)?.borrow_mut();
+            let weight = EntropicTestcaseScore::compute(state, &mut *testcase)?;
+            if weight > 0.0 {
+                vanishing = false;
+            }
+            let has_features = testcase
+                .metadata::<EntropicTestcaseMetadata>()
+                .is_ok_and(|m| m.num_features() > 0);
+            weights.push((id, weight, has_features));
+        }
+
+        // If the energy of all entries is zero, fall back to prioritizing newer entries.
+        if vanishing {
+            for (i, (_, weight, has_features)) in weights.iter_mut().enumerate() {
+                *weight = if *has_features { (i + 1) as f64 } else { 0.0 };
+            }
+        }
+
+        let mut sum = 0.0;
+        self.distribution.clear();
+        for (id, weight, _) in weights {
+            sum += weight;
+            self.distribution.push((id, sum));
+        }
+        self.distribution_needs_update = false;
+        Ok(())
+    }
+}
+
+impl<C, I, O, S> RemovableScheduler<I, S> for EntropicScheduler<C, O> {
+    /// This will *NOT* neutralize the effect of this removed testcase from the global data such as `EntropicMetadata`
+    fn on_remove(
+        &mut self,
+        _state: &mut S,
+        _id: CorpusId,
+        _prev: &Option<Testcase<I>>,
+    ) -> Result<(), Error> {
+        self.distribution_needs_update = true;
+        Ok(())
+    }
+
+    /// This will *NOT* neutralize the effect of this removed testcase from the global data such as `EntropicMetadata`
+    fn on_replace(
+        &mut self,
+        _state: &mut S,
+        _id: CorpusId,
+        _prev: &Testcase<I>,
+    ) -> Result<(), Error> {
+        self.distribution_needs_update = true;
+        Ok(())
+    }
+}
+
+impl<C, O> AflScheduler for EntropicScheduler<C, O> {
+    type ObserverRef = C;
+
+    fn last_hash(&self) -> usize {
+        self.last_hash
+    }
+
+    fn set_last_hash(&mut self, hash: usize) {
+        self.last_hash = hash;
+    }
+
+    fn observer_handle(&self) -> &Handle<C> {
+        &self.observer_handle
+    }
+}
+
+impl<C, O> HasQueueCycles for EntropicScheduler<C, O> {
+    fn queue_cycles(&self) -> u64 {
+        self.queue_cycles
+    }
+}
+
+impl<C, I, O, S> Scheduler<I, S> for EntropicScheduler<C, O>
+where
+    C: AsRef<O> + Named,
+    O: MapObserver + Hash,
+    S: HasCorpus<I> + HasMetadata + HasRand + HasTestcase<I>,
+{
+    /// Called when a [`Testcase`] is added to the corpus
+    fn on_add(&mut self, state: &mut S, id: CorpusId) -> Result<(), Error> {
+        on_add_metadata_default(self, state, id)?;
+
+        let (novelties, num_features) = {
+            let testcase = state.testcase(id)?;
+            let novelties = testcase
+                .metadata::<MapNoveltiesMetadata>()
+                .map_err(|_| {
+                    Error::key_not_found(
+                        "MapNoveltiesMetadata not found, the map feedback needs to track novelties for the EntropicScheduler",
+                    )
+                })?
+                .list
+                .clone();
+            let num_features = testcase
+                .metadata_map()
+                .get::<MapIndexesMetadata>()
+                .map_or(novelties.len(), |m| m.list.len());
+            (novelties, num_features)
+        };
+
+        for feature in novelties {
+            self.add_rare_feature(state, feature)?;
+        }
+
+        let num_rare_features = state.metadata::<EntropicMetadata>()?.rare_features().len();
+        state
+            .testcase_mut(id)?
+            .add_metadata(EntropicTestcaseMetadata::new(
+                num_features,
+                num_rare_features,
+            ));
+
+        self.distribution_needs_update = true;
+        Ok(())
+    }
+
+    fn on_evaluation<OT>(&mut self, state: &mut S, _input: &I, observers: &OT) -> Result<(), Error>
+    where
+        OT: MatchName,
+    {
+        on_evaluation_metadata_default(self, state, observers)?;
+
+        let Some(current_id) = *state.corpus().current() else {
+            return Ok(());
+        };
+
+        let observer = observers
+            .get(&self.observer_handle)
+            .ok_or_else(|| Error::key_not_found("Observer not found"))?
+            .as_ref();
+        let initial = observer.initial();
+        let usable_count = observer.usable_count();
+
+        let emeta = state.metadata_mut::<EntropicMetadata>()?;
+        emeta.num_executed_mutations += 1;
+        let hit: Vec<usize> = emeta
+            .rare_features
+            .keys()
+            .copied()
+            .filter(|&feature| feature < usable_count && observer.get(feature) != initial)
+            .collect();
+        let attributed: Vec<usize> = hit
+            .into_iter()
+            .filter(|&feature| emeta.hit_feature(feature))
+            .collect();
+
+        let mut testcase = state.testcase_mut(current_id)?;
+        if let Ok(tcmeta) = testcase.metadata_mut::<EntropicTestcaseMetadata>() {
+            tcmeta.num_executed_mutations += 1;
+            for feature in attributed {
+                tcmeta.hit_feature(feature);
+            }
+        }
+        Ok(())
+    }
+
+    fn next(&mut self, state: &mut S) -> Result<CorpusId, Error> {
+        let corpus_counts = state.corpus().count();
+        if corpus_counts == 0 {
+            return Err(Error::empty(
+                "No entries in corpus. This often implies the target is not properly instrumented.",
+            ));
+        }
+
+        // Sparse updates for local changes of feature frequencies.
+        if self.distribution_needs_update
+            || self.distribution.len() != corpus_counts
+            || state.rand_mut().below(nonzero!(SPARSE_ENERGY_UPDATES)) == 0
+        {
+            self.update_distribution(state)?;
+        }
+
+        let total = self.distribution.last().map_or(0.0, |(_, sum)| *sum);
+        let id = if total > 0.0 {
+            let target = state.rand_mut().next_float() * total;
+            let pos = self
+                .distribution
+                .partition_point(|(_, sum)| *sum <= target)
+                .min(self.distribution.len() - 1);
+            self.distribution[pos].0
+        } else {
+            // No entry has features: pick uniformly.
+            let len = NonZero::new(self.distribution.len())
+                .ok_or_else(|| Error::empty("No entries in the distribution of the scheduler"))?;
+            let pos = state.rand_mut().below(len);
+            self.distribution[pos].0
+        };
+
+        self.runs_in_current_cycle += 1;
+        if self.runs_in_current_cycle >= corpus_counts {
+            self.runs_in_current_cycle = 0;
+            self.queue_cycles += 1;
+            let psmeta = state.metadata_mut::<SchedulerMetadata>()?;
+            psmeta.set_queue_cycles(self.queue_cycles());
+        }
+
+        self.set_current_scheduled(state, Some(id))?;
+        Ok(id)
+    }
+
+    /// Set current fuzzed corpus id and `scheduled_count`
+    fn set_current_scheduled(
+        &mut self,
+        state: &mut S,
+        next_id: Option<CorpusId>,
+    ) -> Result<(), Error> {
+        on_next_metadata_default(state)?;
+
+        *state.corpus_mut().current_mut() = next_id;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EntropicMetadata, EntropicTestcaseMetadata};
+
+    #[test]
+    fn test_entropic_energy() {
+        let mut fresh = EntropicTestcaseMetadata::new(10, 4);
+        let mut fuzzed = EntropicTestcaseMetadata::new(10, 4);
+
+        // One testcase discovers all rare features evenly, the other hits a single one over and over.
+        for feature in 0..4 {
+            fresh.hit_feature(feature);
+            fresh.num_executed_mutations += 1;
+        }
+        for _ in 0..4 {
+            fuzzed.hit_feature(0);
+            fuzzed.num_executed_mutations += 1;
+        }
+        fresh.update_energy(4);
+        fuzzed.update_energy(4);
+
+        assert!(!fresh.needs_energy_update());
+        assert!(fresh.energy() > fuzzed.energy());
+        assert!(fuzzed.remove_feature(0));
+        assert!(!fuzzed.remove_feature(0));
+    }
+
+    #[test]
+    fn test_entropic_rare_features() {
+        let mut meta = EntropicMetadata::new(2, 1, false);
+        for feature in 0..3 {
+            meta.rare_features.insert(feature, 0);
+        }
+        assert!(meta.hit_feature(1));
+        assert!(meta.hit_feature(1));
+        assert!(meta.hit_feature(2));
+        assert!(!meta.hit_feature(3));
+        assert_eq!(meta.freq_of_most_abundant_rare_feature(), 2);
+
+        assert_eq!(meta.drop_most_abundant(), Some(1));
+        assert_eq!(meta.freq_of_most_abundant_rare_feature(), 1);
+        assert!(!meta.is_rare(1));
+        assert!(meta.is_rare(2));
+    }
+}
@@ -26,6 +26,12 @@ pub use accounting::CoverageAccountingScheduler;
 pub mod weighted;
 pub use weighted::{StdWeightedScheduler, WeightedScheduler};
 
+pub mod entropic;
+pub use entropic::{EntropicScheduler, EntropicTestcaseScore};
+
+pub mod protocol_state;
+pub use protocol_state::{ProtocolStateScheduler, ProtocolStateSchedulerMetadata};
+
 pub mod tuneable;
 use libafl_bolts::{
     generic_hash_std,
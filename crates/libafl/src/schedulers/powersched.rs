@@ -4,7 +4,7 @@ use alloc::vec::Vec;
 use core::{hash::Hash, marker::PhantomData, time::Duration};
 
 use libafl_bolts::{
-    Named,
+    Named, current_time,
     tuples::{Handle, Handled, MatchName},
 };
 use serde::{Deserialize, Serialize};
@@ -156,11 +156,123 @@ impl SchedulerMetadata {
     }
 }
 
+libafl_bolts::impl_serdeany!(DistanceMetadata);
+
+/// The distance of a testcase to the targets of directed fuzzing,
+/// i.e., the mean distance of all basic blocks it executed.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    expect(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+pub struct DistanceMetadata {
+    distance: f64,
+}
+
+impl DistanceMetadata {
+    /// Creates a new [`struct@DistanceMetadata`]
+    #[must_use]
+    pub fn new(distance: f64) -> Self {
+        Self { distance }
+    }
+
+    /// The distance to the targets
+    #[must_use]
+    pub fn distance(&self) -> f64 {
+        self.distance
+    }
+}
+
+libafl_bolts::impl_serdeany!(DirectedMetadata);
+
+/// The global metadata for directed fuzzing, used by the simulated annealing in power schedules
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    expect(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+pub struct DirectedMetadata {
+    /// The smallest distance of any testcase seen so far
+    min_distance: f64,
+    /// The largest distance of any testcase seen so far
+    max_distance: f64,
+    /// The time the directed campaign started, the annealing temperature depends on it
+    start_time: Duration,
+}
+
+impl Default for DirectedMetadata {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DirectedMetadata {
+    /// Creates a new [`struct@DirectedMetadata`], starting the annealing now
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            min_distance: f64::MAX,
+            max_distance: 0.0,
+            start_time: current_time(),
+        }
+    }
+
+    /// The smallest distance of any testcase seen so far
+    #[must_use]
+    pub fn min_distance(&self) -> f64 {
+        self.min_distance
+    }
+
+    /// The largest distance of any testcase seen so far
+    #[must_use]
+    pub fn max_distance(&self) -> f64 {
+        self.max_distance
+    }
+
+    /// The time the directed campaign started
+    #[must_use]
+    pub fn start_time(&self) -> Duration {
+        self.start_time
+    }
+
+    /// Takes the distance of a new testcase into account
+    pub fn update(&mut self, distance: f64) {
+        if distance < self.min_distance {
+            self.min_distance = distance;
+        }
+        if distance > self.max_distance {
+            self.max_distance = distance;
+        }
+    }
+
+    /// The factor applied to the power of a testcase with the given distance, following `AFLGo`'s
+    /// exponential cooling schedule: early on, all testcases get about the same power (exploration),
+    /// after `time_to_exploit` testcases closer to the targets get up to 32 times more (exploitation).
+    #[must_use]
+    pub fn power_factor(&self, distance: f64, time_to_exploit: Duration) -> f64 {
+        let elapsed = current_time().saturating_sub(self.start_time);
+        let progress = elapsed.as_secs_f64() / time_to_exploit.as_secs_f64().max(f64::EPSILON);
+        let temperature = 1.0 / libm::pow(20.0, progress);
+
+        let normalized_distance = if self.max_distance > self.min_distance {
+            (distance - self.min_distance) / (self.max_distance - self.min_distance)
+        } else {
+            0.0
+        };
+        let p =
+            (1.0 - normalized_distance.clamp(0.0, 1.0)) * (1.0 - temperature) + 0.5 * temperature;
+
+        libm::pow(2.0, 2.0 * libm::log2(32.0) * (p - 0.5))
+    }
+}
+
 /// The struct for the powerschedule algorithm
 #[derive(Debug, Clone, Serialize, Deserialize, Copy)]
 pub struct PowerSchedule {
     base: BaseSchedule,
     avoid_crash: bool,
+    /// Time to exploitation of the simulated annealing towards directed fuzzing targets, if enabled
+    annealing: Option<Duration>,
 }
 
 impl PowerSchedule {
@@ -170,6 +282,18 @@ impl PowerSchedule {
         Self {
             base,
             avoid_crash: false,
+            annealing: None,
+        }
+    }
+
+    /// Use the given power schedule, with simulated annealing towards the targets of directed fuzzing.
+    /// After `time_to_exploit`, testcases closer to the targets (see [`struct@DistanceMetadata`]) are strongly favored.
+    #[must_use]
+    pub fn directed(base: BaseSchedule, time_to_exploit: Duration) -> Self {
+        Self {
+            base,
+            avoid_crash: false,
+            annealing: Some(time_to_exploit),
         }
     }
 
@@ -179,6 +303,7 @@ impl PowerSchedule {
         Self {
             base: BaseSchedule::EXPLORE,
             avoid_crash: false,
+            annealing: None,
         }
     }
 
@@ -188,6 +313,7 @@ impl PowerSchedule {
         Self {
             base: BaseSchedule::EXPLOIT,
             avoid_crash: false,
+            annealing: None,
         }
     }
 
@@ -197,6 +323,7 @@ impl PowerSchedule {
         Self {
             base: BaseSchedule::FAST,
             avoid_crash: false,
+            annealing: None,
         }
     }
 
@@ -206,6 +333,7 @@ impl PowerSchedule {
         Self {
             base: BaseSchedule::COE,
             avoid_crash: false,
+            annealing: None,
         }
     }
 
@@ -215,6 +343,7 @@ impl PowerSchedule {
         Self {
             base: BaseSchedule::LIN,
             avoid_crash: false,
+            annealing: None,
         }
     }
 
@@ -224,6 +353,7 @@ impl PowerSchedule {
         Self {
             base: BaseSchedule::QUAD,
             avoid_crash: false,
+            annealing: None,
         }
     }
 
@@ -238,6 +368,17 @@ impl PowerSchedule {
         self.avoid_crash = true;
     }
 
+    /// Getter to the time to exploitation of the simulated annealing, if enabled
+    #[must_use]
+    pub fn annealing(&self) -> Option<Duration> {
+        self.annealing
+    }
+
+    /// Enable simulated annealing towards the targets of directed fuzzing
+    pub fn set_annealing(&mut self, time_to_exploit: Duration) {
+        self.annealing = Some(time_to_exploit);
+    }
+
     /// Getter to the base scheduler
     #[must_use]
     pub fn base(&self) -> &BaseSchedule {
@@ -409,3 +550,38 @@ where
         &self.strat
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use libafl_bolts::current_time;
+
+    use super::DirectedMetadata;
+
+    #[test]
+    fn test_directed_annealing() {
+        let time_to_exploit = Duration::from_secs(60);
+        let mut meta = DirectedMetadata::new();
+        meta.update(1.0);
+        meta.update(5.0);
+        meta.update(3.0);
+        assert!((meta.min_distance() - 1.0).abs() < f64::EPSILON);
+        assert!((meta.max_distance() - 5.0).abs() < f64::EPSILON);
+
+        // Exploration: every testcase gets about the same power
+        let close = meta.power_factor(1.0, time_to_exploit);
+        let far = meta.power_factor(5.0, time_to_exploit);
+        assert!((close - 1.0).abs() < 0.1, "{close}");
+        assert!((far - 1.0).abs() < 0.1, "{far}");
+
+        // Exploitation: the closest testcase gets (almost) 32 times the power, the farthest 1/32
+        meta.start_time = current_time().saturating_sub(time_to_exploit * 10);
+        let close = meta.power_factor(1.0, time_to_exploit);
+        let middle = meta.power_factor(3.0, time_to_exploit);
+        let far = meta.power_factor(5.0, time_to_exploit);
+        assert!(close > 31.0 && close <= 32.0, "{close}");
+        assert!((middle - 1.0).abs() < 0.01, "{middle}");
+        assert!(far < 1.0 / 31.0, "{far}");
+    }
+}
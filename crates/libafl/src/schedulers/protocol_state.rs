@@ -0,0 +1,369 @@
+//! The [`ProtocolStateScheduler`] targets the states of a stateful network protocol, as `AFLNet` does.
+//!
+//! It first selects a protocol state to target, preferring states which were rarely fuzzed and led to new
+//! corpus entries, then a corpus entry reaching this state. The messages needed to reach the state are
+//! recorded in the [`ProtocolStateSchedulerMetadata`], so that mutators like the
+//! `ProtocolSuffixMutator` replay this prefix unchanged and only mutate the messages after it.
+//!
+//! The reached states of corpus entries come from the [`ProtocolStateTestcaseMetadata`] of the
+//! [`ProtocolStateFeedback`](crate::feedbacks::ProtocolStateFeedback).
+
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::num::NonZero;
+
+use libafl_bolts::{rands::Rand, tuples::MatchName};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Error, HasMetadata,
+    corpus::{Corpus, CorpusId, Testcase},
+    feedbacks::ProtocolStateTestcaseMetadata,
+    observers::INITIAL_PROTOCOL_STATE,
+    schedulers::{RemovableScheduler, Scheduler},
+    state::{HasCorpus, HasRand},
+};
+
+/// The statistics of a protocol state, used to decide which state to target
+#[derive(Default, Serialize, Deserialize, Debug, Clone)]
+pub struct ProtocolStateStats {
+    /// The corpus entries reaching this state
+    pub testcases: Vec<CorpusId>,
+    /// How often this state was selected as target
+    pub selected_times: u64,
+    /// How many inputs were evaluated while targeting this state
+    pub fuzzs: u64,
+    /// How many new corpus entries were found while targeting this state
+    pub paths_discovered: u64,
+}
+
+impl ProtocolStateStats {
+    /// The score of this state, as in `AFLNet`: states fuzzed less often and more productive ones score higher
+    #[must_use]
+    #[expect(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+    #[expect(clippy::cast_sign_loss)]
+    pub fn score(&self) -> u64 {
+        let fuzzed = libm::log10((self.fuzzs + 1) as f64) * self.selected_times as f64;
+        let score = 1000.0
+            * libm::pow(2.0, -libm::log10(fuzzed + 1.0))
+            * libm::pow(2.0, libm::log((self.paths_discovered + 1) as f64));
+        (libm::ceil(score) as u64).max(1)
+    }
+}
+
+/// The state currently targeted by the [`ProtocolStateScheduler`]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProtocolStateTarget {
+    /// The targeted protocol state
+    pub state: u32,
+    /// The number of messages of the scheduled input needed to reach the state
+    pub prefix_len: usize,
+}
+
+/// The metadata of the [`ProtocolStateScheduler`]
+#[derive(Default, Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    expect(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+pub struct ProtocolStateSchedulerMetadata {
+    /// The statistics of each known state
+    pub states: BTreeMap<u32, ProtocolStateStats>,
+    /// The currently targeted state
+    pub target: Option<ProtocolStateTarget>,
+}
+
+libafl_bolts::impl_serdeany!(ProtocolStateSchedulerMetadata);
+
+impl ProtocolStateSchedulerMetadata {
+    /// Adds a corpus entry to the states it reaches
+    fn add_testcase(&mut self, id: CorpusId, reached: &[u32]) {
+        for state in reached {
+            let testcases = &mut self.states.entry(*state).or_default().testcases;
+            if !testcases.contains(&id) {
+                testcases.push(id);
+            }
+        }
+    }
+
+    /// Removes a corpus entry from all states
+    fn remove_testcase(&mut self, id: CorpusId) {
+        for stats in self.states.values_mut() {
+            stats.testcases.retain(|tc| *tc != id);
+        }
+    }
+}
+
+/// The states reached by a testcase, starting with the initial state
+fn reached_states<I>(testcase: &Testcase<I>) -> Vec<u32> {
+    let mut reached = vec![INITIAL_PROTOCOL_STATE];
+    if let Ok(meta) = testcase.metadata::<ProtocolStateTestcaseMetadata>() {
+        reached.extend(meta.reached_states().map(|(state, _)| state));
+    }
+    reached
+}
+
+/// Schedules corpus entries by the protocol state they reach, as `AFLNet` does
+#[derive(Debug, Clone, Default)]
+pub struct ProtocolStateScheduler {}
+
+impl ProtocolStateScheduler {
+    /// Creates a new [`ProtocolStateScheduler`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl<I, S> RemovableScheduler<I, S> for ProtocolStateScheduler
+where
+    S: HasCorpus<I> + HasMetadata,
+{
+    fn on_remove(
+        &mut self,
+        state: &mut S,
+        id: CorpusId,
+        _testcase: &Option<Testcase<I>>,
+    ) -> Result<(), Error> {
+        if let Ok(meta) = state.metadata_mut::<ProtocolStateSchedulerMetadata>() {
+            meta.remove_testcase(id);
+        }
+        Ok(())
+    }
+
+    fn on_replace(
+        &mut self,
+        state: &mut S,
+        id: CorpusId,
+        _prev: &Testcase<I>,
+    ) -> Result<(), Error> {
+        let reached = reached_states(&*state.corpus().get(id)?.borrow());
+        let meta = state.metadata_or_insert_with(ProtocolStateSchedulerMetadata::default);
+        meta.remove_testcase(id);
+        meta.add_testcase(id, &reached);
+        Ok(())
+    }
+}
+
+impl<I, S> Scheduler<I, S> for ProtocolStateScheduler
+where
+    S: HasCorpus<I> + HasMetadata + HasRand,
+{
+    fn on_add(&mut self, state: &mut S, id: CorpusId) -> Result<(), Error> {
+        let current_id = *state.corpus().current();
+        let reached = {
+            let mut testcase = state.corpus().get(id)?.borrow_mut();
+            testcase.set_parent_id_optional(current_id);
+            reached_states(&testcase)
+        };
+
+        let meta = state.metadata_or_insert_with(ProtocolStateSchedulerMetadata::default);
+        meta.add_testcase(id, &reached);
+        if let Some(target) = meta.target {
+            meta.states
+                .entry(target.state)
+                .or_default()
+                .paths_discovered += 1;
+        }
+        Ok(())
+    }
+
+    fn on_evaluation<OT>(&mut self, state: &mut S, _input: &I, _observers: &OT) -> Result<(), Error>
+    where
+        OT: MatchName,
+    {
+        if let Ok(meta) = state.metadata_mut::<ProtocolStateSchedulerMetadata>()
+            && let Some(target) = meta.target
+        {
+            meta.states.entry(target.state).or_default().fuzzs += 1;
+        }
+        Ok(())
+    }
+
+    fn next(&mut self, state: &mut S) -> Result<CorpusId, Error> {
+        if state.corpus().count() == 0 {
+            return Err(Error::empty(
+                "No entries in corpus. This often implies the target is not properly instrumented.",
+            ));
+        }
+
+        let candidates: Vec<(u32, u64)> = state
+            .metadata_or_insert_with(ProtocolStateSchedulerMetadata::default)
+            .states
+            .iter()
+            .filter(|(_, stats)| !stats.testcases.is_empty())
+            .map(|(state, stats)| (*state, stats.score()))
+            .collect();
+        let total: u64 = candidates.iter().map(|(_, score)| score).sum();
+        let Some(total) = NonZero::new(usize::try_from(total)?) else {
+            // No states known (yet), fall back to a random entry, without a state to credit
+            let count = NonZero::new(state.corpus().count()).unwrap();
+            let idx = state.rand_mut().below(count);
+            let id = state.corpus().nth(idx);
+            state
+                .metadata_mut::<ProtocolStateSchedulerMetadata>()?
+                .target = None;
+            <Self as Scheduler<I, S>>::set_current_scheduled(self, state, Some(id))?;
+            return Ok(id);
+        };
+
+        let mut pick = state.rand_mut().below(total) as u64;
+        let target_state = candidates
+            .iter()
+            .find(|(_, score)| {
+                if pick < *score {
+                    true
+                } else {
+                    pick -= score;
+                    false
+                }
+            })
+            .map_or(candidates[candidates.len() - 1].0, |(state, _)| *state);
+
+        let count = state.metadata::<ProtocolStateSchedulerMetadata>()?.states[&target_state]
+            .testcases
+            .len();
+        let idx = state.rand_mut().below(NonZero::new(count).unwrap());
+        let id = state.metadata::<ProtocolStateSchedulerMetadata>()?.states[&target_state]
+            .testcases[idx];
+
+        let prefix_len = state
+            .corpus()
+            .get(id)?
+            .borrow()
+            .metadata::<ProtocolStateTestcaseMetadata>()
+            .ok()
+            .and_then(|meta| meta.prefix_len(target_state))
+            .unwrap_or(0);
+
+        let meta = state.metadata_mut::<ProtocolStateSchedulerMetadata>()?;
+        meta.states.get_mut(&target_state).unwrap().selected_times += 1;
+        meta.target = Some(ProtocolStateTarget {
+            state: target_state,
+            prefix_len,
+        });
+
+        <Self as Scheduler<I, S>>::set_current_scheduled(self, state, Some(id))?;
+        Ok(id)
+    }
+
+    fn set_current_scheduled(
+        &mut self,
+        state: &mut S,
+        next_id: Option<CorpusId>,
+    ) -> Result<(), Error> {
+        *state.corpus_mut().current_mut() = next_id;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use libafl_bolts::rands::StdRand;
+
+    use crate::{
+        HasMetadata,
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        feedbacks::{ConstFeedback, ProtocolStateTestcaseMetadata},
+        inputs::BytesInput,
+        schedulers::{
+            ProtocolStateScheduler, ProtocolStateSchedulerMetadata, Scheduler,
+            protocol_state::ProtocolStateTarget,
+        },
+        state::{HasCorpus, StdState},
+    };
+
+    #[test]
+    fn test_protocol_state_scheduler() {
+        let mut feedback = ConstFeedback::new(false);
+        let mut objective = ConstFeedback::new(false);
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )
+        .unwrap();
+        let mut scheduler = ProtocolStateScheduler::new();
+
+        let mut deep = Testcase::new(BytesInput::new(vec![0]));
+        deep.add_metadata(ProtocolStateTestcaseMetadata {
+            codes: vec![Some(220), None, Some(331)],
+        });
+        let deep = state.corpus_mut().add(deep).unwrap();
+        scheduler.on_add(&mut state, deep).unwrap();
+        let shallow = state
+            .corpus_mut()
+            .add(Testcase::new(BytesInput::new(vec![1])))
+            .unwrap();
+        scheduler.on_add(&mut state, shallow).unwrap();
+
+        let meta = state.metadata::<ProtocolStateSchedulerMetadata>().unwrap();
+        assert_eq!(meta.states.len(), 3);
+        assert_eq!(meta.states[&0].testcases, [deep, shallow]);
+        assert_eq!(meta.states[&331].testcases, [deep]);
+
+        for _ in 0..16 {
+            let id = scheduler.next(&mut state).unwrap();
+            let target = state
+                .metadata::<ProtocolStateSchedulerMetadata>()
+                .unwrap()
+                .target
+                .unwrap();
+            match target.state {
+                0 => assert_eq!(target.prefix_len, 0),
+                220 => assert_eq!(
+                    target,
+                    ProtocolStateTarget {
+                        state: 220,
+                        prefix_len: 1
+                    }
+                ),
+                331 => assert_eq!(
+                    target,
+                    ProtocolStateTarget {
+                        state: 331,
+                        prefix_len: 3
+                    }
+                ),
+                _ => unreachable!(),
+            }
+            if target.state != 0 {
+                assert_eq!(id, deep);
+            }
+            scheduler
+                .on_evaluation(&mut state, &BytesInput::new(vec![]), &())
+                .unwrap();
+        }
+
+        let meta = state.metadata::<ProtocolStateSchedulerMetadata>().unwrap();
+        assert_eq!(
+            meta.states.values().map(|stats| stats.fuzzs).sum::<u64>(),
+            16
+        );
+        assert_eq!(
+            meta.states
+                .values()
+                .map(|stats| stats.selected_times)
+                .sum::<u64>(),
+            16
+        );
+
+        // Without known states, the random fallback does not credit the previous target
+        state
+            .metadata_mut::<ProtocolStateSchedulerMetadata>()
+            .unwrap()
+            .states
+            .clear();
+        scheduler.next(&mut state).unwrap();
+        assert_eq!(
+            state
+                .metadata::<ProtocolStateSchedulerMetadata>()
+                .unwrap()
+                .target,
+            None
+        );
+    }
+}
@@ -10,7 +10,7 @@ use crate::{
     feedbacks::MapIndexesMetadata,
     schedulers::{
         minimizer::{IsFavoredMetadata, TopRatedsMetadata},
-        powersched::{BaseSchedule, SchedulerMetadata},
+        powersched::{BaseSchedule, DirectedMetadata, DistanceMetadata, SchedulerMetadata},
     },
     state::HasCorpus,
 };
@@ -245,6 +245,16 @@ where
             }
         }
 
+        // Simulated annealing towards the targets of directed fuzzing
+        if let Some(time_to_exploit) = psmeta.strat().and_then(|s| s.annealing()) {
+            if let (Ok(dmeta), Some(distance)) = (
+                state.metadata::<DirectedMetadata>(),
+                entry.metadata_map().get::<DistanceMetadata>(),
+            ) {
+                perf_score *= dmeta.power_factor(distance.distance(), time_to_exploit);
+            }
+        }
+
         // Upper bound
         if perf_score > HAVOC_MAX_MULT * 100.0 {
             perf_score = HAVOC_MAX_MULT * 100.0;
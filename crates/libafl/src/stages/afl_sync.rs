@@ -0,0 +1,643 @@
+//! The [`AflSyncStage`] syncs testcases with AFL++ instances, in both directions, using AFL++'s sync directory layout.
+//!
+//! Every instance owns a directory `<sync_dir>/<name>` in a shared sync directory.
+//! Instances write their finds to `queue/`, `crashes/`, and `hangs/` in their own directory,
+//! naming each `id:NNNNNN,...` with a per-directory counter, and import the `queue/` of all others.
+//! An instance remembers the next id to import from each peer in `.synced/<peer>` in its own directory.
+
+use alloc::{
+    borrow::{Cow, ToOwned},
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{fmt::Write, marker::PhantomData, time::Duration};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use libafl_bolts::{Named, current_time};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Error, HasMetadata, HasNamedMetadata,
+    corpus::{Corpus, CorpusId, HasCurrentCorpusId, Testcase},
+    executors::ExitKind,
+    fuzzer::Evaluator,
+    inputs::Input,
+    mutators::LogMutationMetadata,
+    stages::{Restartable, RetryCountRestartHelper, Stage},
+    state::{HasCorpus, HasImported, HasSolutions},
+};
+
+/// Default name for [`AflSyncStage`]
+pub const AFL_SYNC_STAGE_NAME: &str = "afl_sync";
+
+/// The subdirectory of an instance AFL++ syncs testcases from
+const QUEUE_DIR: &str = "queue";
+/// The subdirectory of an instance for crashing testcases
+const CRASHES_DIR: &str = "crashes";
+/// The subdirectory of an instance for testcases that time out
+const HANGS_DIR: &str = "hangs";
+/// The subdirectory of an instance for the sync cursors
+const SYNCED_DIR: &str = ".synced";
+/// AFL++'s stats file in the directory of an instance
+const FUZZER_STATS_FILE: &str = "fuzzer_stats";
+
+/// Metadata placed in a [`Testcase`] imported from another instance by an [`AflSyncStage`]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    expect(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AflSyncedMetadata {
+    /// The name of the instance the testcase came from
+    pub peer: String,
+    /// The id of the testcase in the queue of the instance
+    pub id: u32,
+}
+
+libafl_bolts::impl_serdeany!(AflSyncedMetadata);
+
+/// The progress of an [`AflSyncStage`] writing its own testcases
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    expect(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct AflSyncMetadata {
+    /// The last time the sync was done
+    pub last_time: Option<Duration>,
+    /// The last solution written to `crashes/` or `hangs/`
+    pub last_solution: Option<CorpusId>,
+    /// The corpus entries written to `queue/`, the index being their queue id
+    pub queue: Vec<CorpusId>,
+    /// The number of testcases written to `crashes/`
+    pub crashes: u32,
+    /// The number of testcases written to `hangs/`
+    pub hangs: u32,
+}
+
+libafl_bolts::impl_serdeany!(AflSyncMetadata);
+
+impl AflSyncMetadata {
+    /// The queue id of a corpus entry, if it was written to `queue/`
+    #[must_use]
+    pub fn queue_id(&self, id: CorpusId) -> Option<usize> {
+        // Corpus ids only grow, and entries are written in order
+        self.queue.binary_search(&id).ok()
+    }
+}
+
+/// A stage that syncs testcases with AFL++ instances, or other [`AflSyncStage`]s, in both directions.
+///
+/// It writes the corpus and the solutions of this fuzzer to its instance directory, named with AFL++'s
+/// provenance fields: `src:` for the queue id of the parent, `execs:` for the executions at discovery,
+/// `op:` and `rep:` for the mutations (recorded by a [`crate::mutators::LoggerScheduledMutator`]),
+/// `sync:` for imported testcases, and `orig:` for seeds.
+/// Solutions go to `hangs/` if they timed out, and to `crashes/` otherwise, with `sig:00` as the signal is unknown.
+///
+/// It then evaluates all new testcases in the `queue/` of every other instance, and, if enabled,
+/// their `crashes/` and `hangs/`. Testcases that fail to load with [`Error::InvalidInput`] are skipped.
+///
+/// Point the [`crate::stages::AflStatsStage`] at [`AflSyncStage::fuzzer_stats_file`] for tools like
+/// `afl-whatsup` to see this instance. The directories written here should not be the on-disk corpus itself.
+#[derive(Debug)]
+pub struct AflSyncStage<E, EM, I, S, Z> {
+    name: Cow<'static, str>,
+    sync_dir: PathBuf,
+    fuzzer_name: String,
+    interval: Duration,
+    import_objectives: bool,
+    phantom: PhantomData<(E, EM, I, S, Z)>,
+}
+
+impl<E, EM, I, S, Z> Named for AflSyncStage<E, EM, I, S, Z> {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<E, EM, I, S, Z> Stage<E, EM, S, Z> for AflSyncStage<E, EM, I, S, Z>
+where
+    I: Input,
+    Z: Evaluator<E, EM, I, S>,
+    S: HasCorpus<I>
+        + HasSolutions<I>
+        + HasImported
+        + HasMetadata
+        + HasNamedMetadata
+        + HasCurrentCorpusId,
+{
+    #[inline]
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        let mut meta = state
+            .remove_named_metadata::<AflSyncMetadata>(&self.name)
+            .map(|meta| *meta)
+            .unwrap_or_default();
+
+        if let Some(last) = meta.last_time {
+            if current_time().saturating_sub(last) < self.interval {
+                state.add_named_metadata(&self.name, meta);
+                return Ok(());
+            }
+        }
+        meta.last_time = Some(current_time());
+
+        let exported = self.export(state, &mut meta);
+        state.add_named_metadata(&self.name, meta);
+        exported?;
+
+        self.import(fuzzer, executor, state, manager)
+    }
+}
+
+impl<E, EM, I, S, Z> Restartable<S> for AflSyncStage<E, EM, I, S, Z>
+where
+    S: HasMetadata + HasNamedMetadata + HasCurrentCorpusId,
+{
+    #[inline]
+    fn should_restart(&mut self, state: &mut S) -> Result<bool, Error> {
+        // The cursors are advanced before each import, so a crashing testcase is not imported again
+        RetryCountRestartHelper::no_retry(state, &self.name)
+    }
+
+    #[inline]
+    fn clear_progress(&mut self, state: &mut S) -> Result<(), Error> {
+        RetryCountRestartHelper::clear_progress(state, &self.name)
+    }
+}
+
+impl<E, EM, I, S, Z> AflSyncStage<E, EM, I, S, Z> {
+    /// Creates a new [`AflSyncStage`] for the instance `fuzzer_name` in `sync_dir`,
+    /// syncing at most once per `interval`.
+    ///
+    /// The name has to be unique among the instances syncing through `sync_dir`, like AFL++'s `-M`/`-S`.
+    pub fn new<P>(sync_dir: P, fuzzer_name: &str, interval: Duration) -> Result<Self, Error>
+    where
+        P: Into<PathBuf>,
+    {
+        if fuzzer_name.is_empty()
+            || fuzzer_name.starts_with('.')
+            || fuzzer_name.contains(['/', '\\'])
+        {
+            return Err(Error::illegal_argument(format!(
+                "Invalid instance name for AFL++ sync: {fuzzer_name:?}"
+            )));
+        }
+        let stage = Self {
+            name: Cow::Owned(AFL_SYNC_STAGE_NAME.to_owned() + ":" + fuzzer_name),
+            sync_dir: sync_dir.into(),
+            fuzzer_name: fuzzer_name.to_owned(),
+            interval,
+            import_objectives: false,
+            phantom: PhantomData,
+        };
+        for dir in [QUEUE_DIR, CRASHES_DIR, HANGS_DIR, SYNCED_DIR] {
+            let dir = stage.instance_dir().join(dir);
+            fs::create_dir_all(&dir).map_err(|e| {
+                Error::os_error(e, format!("Error creating directory {}", dir.display()))
+            })?;
+        }
+        Ok(stage)
+    }
+
+    /// Also evaluate the `crashes/` and `hangs/` of the other instances.
+    ///
+    /// Their objectives become ours only if our objective feedbacks agree.
+    #[must_use]
+    pub fn with_objective_import(mut self) -> Self {
+        self.import_objectives = true;
+        self
+    }
+
+    /// The directory of this instance
+    #[must_use]
+    pub fn instance_dir(&self) -> PathBuf {
+        self.sync_dir.join(&self.fuzzer_name)
+    }
+
+    /// Where AFL++ expects the `fuzzer_stats` of this instance, to pass to [`crate::stages::AflStatsStage`]
+    #[must_use]
+    pub fn fuzzer_stats_file(&self) -> PathBuf {
+        self.instance_dir().join(FUZZER_STATS_FILE)
+    }
+
+    /// Write the new corpus entries and solutions to the directory of this instance
+    fn export(&self, state: &S, meta: &mut AflSyncMetadata) -> Result<(), Error>
+    where
+        I: Input,
+        S: HasCorpus<I> + HasSolutions<I>,
+    {
+        let instance_dir = self.instance_dir();
+
+        let mut corpus_id = next_after(state.corpus(), meta.queue.last().copied());
+        while let Some(id) = corpus_id {
+            let queue_id = meta.queue.len();
+            let name = {
+                let testcase = state.corpus().get(id)?.borrow();
+                format!("id:{queue_id:06}{}", provenance(&testcase, meta))
+            };
+            let input = state.corpus().cloned_input_for_id(id)?;
+            input.to_file(instance_dir.join(QUEUE_DIR).join(name))?;
+            meta.queue.push(id);
+
+            corpus_id = state.corpus().next(id);
+        }
+
+        let mut solution_id = next_after(state.solutions(), meta.last_solution);
+        while let Some(id) = solution_id {
+            let mut testcase = state.solutions().get(id)?.borrow_mut();
+            state.solutions().load_input_into(&mut testcase)?;
+
+            let is_hang = testcase.metadata::<ExitKind>().ok() == Some(&ExitKind::Timeout);
+            let (dir, count) = if is_hang {
+                (HANGS_DIR, meta.hangs)
+            } else {
+                (CRASHES_DIR, meta.crashes)
+            };
+            // AFL++ names crashes after their signal, which LibAFL does not record
+            let sig = if is_hang { "" } else { ",sig:00" };
+            let name = format!("id:{count:06}{sig}{}", provenance(&testcase, meta));
+            testcase
+                .input()
+                .as_ref()
+                .ok_or_else(|| Error::empty("The solution has no input"))?
+                .to_file(instance_dir.join(dir).join(name))?;
+            if is_hang {
+                meta.hangs += 1;
+            } else {
+                meta.crashes += 1;
+            }
+            meta.last_solution = Some(id);
+
+            drop(testcase);
+            solution_id = state.solutions().next(id);
+        }
+
+        Ok(())
+    }
+
+    /// Evaluate the new testcases of all other instances
+    fn import(
+        &self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+    ) -> Result<(), Error>
+    where
+        I: Input,
+        Z: Evaluator<E, EM, I, S>,
+        S: HasCorpus<I> + HasImported,
+    {
+        let mut peers = vec![];
+        for entry in fs::read_dir(&self.sync_dir)? {
+            let entry = entry?;
+            let Ok(peer) = entry.file_name().into_string() else {
+                continue;
+            };
+            if peer == self.fuzzer_name || peer.starts_with('.') || !entry.file_type()?.is_dir() {
+                continue;
+            }
+            peers.push(peer);
+        }
+        peers.sort();
+
+        let synced_dir = self.instance_dir().join(SYNCED_DIR);
+        for peer in peers {
+            let peer_dir = self.sync_dir.join(&peer);
+            // AFL++ keeps the cursor of the queue in `.synced/<peer>`
+            Self::import_dir(
+                fuzzer,
+                executor,
+                state,
+                manager,
+                &peer,
+                &peer_dir.join(QUEUE_DIR),
+                &synced_dir.join(&peer),
+            )?;
+            if self.import_objectives {
+                for dir in [CRASHES_DIR, HANGS_DIR] {
+                    Self::import_dir(
+                        fuzzer,
+                        executor,
+                        state,
+                        manager,
+                        &peer,
+                        &peer_dir.join(dir),
+                        &synced_dir.join(format!("{peer}.{dir}")),
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn import_dir(
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+        peer: &str,
+        dir: &Path,
+        cursor_file: &Path,
+    ) -> Result<(), Error>
+    where
+        I: Input,
+        Z: Evaluator<E, EM, I, S>,
+        S: HasCorpus<I> + HasImported,
+    {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return Ok(());
+        };
+        let min_id = read_cursor(cursor_file)?;
+
+        let mut new_files = vec![];
+        for entry in entries {
+            let entry = entry?;
+            if let Some(id) = entry.file_name().to_str().and_then(parse_id)
+                && id >= min_id
+                && entry.file_type()?.is_file()
+            {
+                new_files.push((id, entry.path()));
+            }
+        }
+        new_files.sort();
+        log::debug!(
+            "Syncing {} new testcases from {}",
+            new_files.len(),
+            dir.display()
+        );
+
+        for (id, path) in new_files {
+            // Move on before evaluating, so that a testcase crashing us is not imported again
+            write_cursor(cursor_file, id + 1)?;
+
+            let input = match I::from_file(&path) {
+                Ok(input) => input,
+                Err(Error::InvalidInput(reason, _)) => {
+                    log::warn!(
+                        "Invalid input found in {} when syncing; reason {reason}; skipping;",
+                        path.display()
+                    );
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            log::debug!("Syncing and evaluating {}", path.display());
+            let (_, corpus_id) = fuzzer.evaluate_input(state, executor, manager, &input)?;
+            if let Some(corpus_id) = corpus_id {
+                state
+                    .corpus()
+                    .get(corpus_id)?
+                    .borrow_mut()
+                    .add_metadata(AflSyncedMetadata {
+                        peer: peer.to_string(),
+                        id,
+                    });
+                *state.imported_mut() += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The first entry of `corpus` after `last`, also if `last` was removed from the corpus since
+fn next_after<C, I>(corpus: &C, last: Option<CorpusId>) -> Option<CorpusId>
+where
+    C: Corpus<I>,
+{
+    match last {
+        None => corpus.first(),
+        Some(last) if corpus.get(last).is_ok() => corpus.next(last),
+        // Corpus ids only grow
+        Some(last) => corpus.ids().find(|id| *id > last),
+    }
+}
+
+/// The AFL++ name fields after the id, describing where a testcase came from
+fn provenance<I>(testcase: &Testcase<I>, meta: &AflSyncMetadata) -> String {
+    if let Ok(synced) = testcase.metadata::<AflSyncedMetadata>() {
+        return format!(",sync:{},src:{:06}", synced.peer, synced.id);
+    }
+
+    let Some(parent_id) = testcase.parent_id() else {
+        let orig = testcase
+            .filename()
+            .as_ref()
+            .map(|name| format!(",orig:{}", name.replace([',', '/'], "_")))
+            .unwrap_or_default();
+        return format!(",time:0,execs:0{orig}");
+    };
+
+    let mut name = String::new();
+    if let Some(src) = meta.queue_id(parent_id) {
+        write!(name, ",src:{src:06}").unwrap();
+    }
+    // The executions of the fuzzer when the testcase was found, like AFL++
+    write!(name, ",execs:{}", testcase.executions()).unwrap();
+    if let Ok(log) = testcase.metadata::<LogMutationMetadata>() {
+        let op = if log
+            .iter()
+            .any(|mutation| mutation.contains("Splice") || mutation.contains("Crossover"))
+        {
+            "splice"
+        } else {
+            "havoc"
+        };
+        write!(name, ",op:{op},rep:{}", log.len()).unwrap();
+    }
+    name
+}
+
+/// Parse the id of a testcase named `id:NNNNNN[,...]`
+fn parse_id(name: &str) -> Option<u32> {
+    let id = name.strip_prefix("id:")?;
+    id.split(',').next()?.parse().ok()
+}
+
+/// Read the next id to import, stored like AFL++ as a native endian `u32`
+fn read_cursor(cursor_file: &Path) -> Result<u32, Error> {
+    match fs::read(cursor_file) {
+        Ok(bytes) => Ok(bytes
+            .get(..4)
+            .map_or(0, |bytes| u32::from_ne_bytes(bytes.try_into().unwrap()))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(0),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn write_cursor(cursor_file: &Path, next_id: u32) -> Result<(), Error> {
+    fs::write(cursor_file, next_id.to_ne_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{borrow::Cow, string::String, vec::Vec};
+    use core::time::Duration;
+    use std::{env, fs, path::Path, process};
+
+    use libafl_bolts::rands::StdRand;
+
+    use super::{AflSyncMetadata, AflSyncStage, SYNCED_DIR, parse_id, read_cursor, write_cursor};
+    use crate::{
+        HasMetadata, HasNamedMetadata, StdFuzzer,
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        events::NopEventManager,
+        executors::{ExitKind, InProcessExecutor},
+        feedbacks::{ConstFeedback, CrashFeedback},
+        inputs::BytesInput,
+        mutators::LogMutationMetadata,
+        schedulers::QueueScheduler,
+        stages::Stage,
+        state::{HasCorpus, HasImported, HasSolutions, StdState},
+    };
+
+    /// The sorted names of the files in `dir`
+    fn file_names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<_> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_afl_sync_names() {
+        assert_eq!(parse_id("id:000042,src:000001,op:havoc,rep:2"), Some(42));
+        assert_eq!(parse_id("id:000007"), Some(7));
+        assert_eq!(parse_id("README.txt"), None);
+        assert_eq!(parse_id("id:abc,src:000001"), None);
+
+        let cursor = env::temp_dir().join(format!("libafl_afl_sync_{}", process::id()));
+        drop(fs::remove_file(&cursor));
+        assert_eq!(read_cursor(&cursor).unwrap(), 0);
+        write_cursor(&cursor, 1337).unwrap();
+        assert_eq!(read_cursor(&cursor).unwrap(), 1337);
+        fs::remove_file(&cursor).unwrap();
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_afl_sync_stage() {
+        let sync_dir = env::temp_dir().join(format!("libafl_afl_sync_dir_{}", process::id()));
+        drop(fs::remove_dir_all(&sync_dir));
+
+        let mut harness = |_input: &BytesInput| ExitKind::Ok;
+        let mut feedback = ConstFeedback::new(true);
+        let mut objective = CrashFeedback::new();
+        let mut states: Vec<_> = (0..2)
+            .map(|_| {
+                StdState::new(
+                    StdRand::with_seed(0),
+                    InMemoryCorpus::<BytesInput>::new(),
+                    InMemoryCorpus::new(),
+                    &mut feedback,
+                    &mut objective,
+                )
+                .unwrap()
+            })
+            .collect();
+        let mut state_b = states.pop().unwrap();
+        let mut state_a = states.pop().unwrap();
+        let mut mgr = NopEventManager::new();
+        let mut fuzzer = StdFuzzer::new(QueueScheduler::new(), feedback, objective);
+        let mut executor =
+            InProcessExecutor::new(&mut harness, (), &mut fuzzer, &mut state_a, &mut mgr).unwrap();
+
+        // Instance a has a seed, a mutated child of it, and a timeout and a crash
+        let mut seed = Testcase::new(BytesInput::new(b"seed".to_vec()));
+        *seed.filename_mut() = Some("seed,1".into());
+        let seed_id = state_a.corpus_mut().add(seed).unwrap();
+        let mut child = Testcase::new(BytesInput::new(b"child".to_vec()));
+        child.set_parent_id(seed_id);
+        child.set_executions(42);
+        child.add_metadata(LogMutationMetadata::new(vec![
+            Cow::Borrowed("BitFlipMutator"),
+            Cow::Borrowed("ByteAddMutator"),
+        ]));
+        state_a.corpus_mut().add(child).unwrap();
+        let mut hang = Testcase::new(BytesInput::new(b"hang".to_vec()));
+        hang.add_metadata(ExitKind::Timeout);
+        state_a.solutions_mut().add(hang).unwrap();
+        let mut crash = Testcase::new(BytesInput::new(b"crash".to_vec()));
+        crash.add_metadata(ExitKind::Crash);
+        state_a.solutions_mut().add(crash).unwrap();
+
+        let mut stage_a = AflSyncStage::new(&sync_dir, "a", Duration::ZERO).unwrap();
+        let mut stage_b = AflSyncStage::new(&sync_dir, "b", Duration::ZERO).unwrap();
+        let dir_a = stage_a.instance_dir();
+        let dir_b = stage_b.instance_dir();
+
+        stage_a
+            .perform(&mut fuzzer, &mut executor, &mut state_a, &mut mgr)
+            .unwrap();
+        assert_eq!(
+            file_names(&dir_a.join("queue")),
+            [
+                "id:000000,time:0,execs:0,orig:seed_1",
+                "id:000001,src:000000,execs:42,op:havoc,rep:2"
+            ]
+        );
+        assert_eq!(
+            file_names(&dir_a.join("hangs")),
+            ["id:000000,time:0,execs:0"]
+        );
+        assert_eq!(
+            file_names(&dir_a.join("crashes")),
+            ["id:000000,sig:00,time:0,execs:0"]
+        );
+
+        // Instance b imports the queue of a, and advances its cursor for a past it
+        stage_b
+            .perform(&mut fuzzer, &mut executor, &mut state_b, &mut mgr)
+            .unwrap();
+        assert_eq!(state_b.corpus().count(), 2);
+        assert_eq!(*state_b.imported(), 2);
+        let cursor = dir_b.join(SYNCED_DIR).join("a");
+        assert_eq!(read_cursor(&cursor).unwrap(), 2);
+
+        // The next sync exports the imported testcases, and does not import them again
+        stage_b
+            .perform(&mut fuzzer, &mut executor, &mut state_b, &mut mgr)
+            .unwrap();
+        assert_eq!(state_b.corpus().count(), 2);
+        assert_eq!(read_cursor(&cursor).unwrap(), 2);
+        assert_eq!(
+            file_names(&dir_b.join("queue")),
+            ["id:000000,sync:a,src:000000", "id:000001,sync:a,src:000001"]
+        );
+
+        // Exporting resumes after the last written entry, also if it was removed from the corpus
+        let last = state_b.corpus().last().unwrap();
+        state_b.corpus_mut().remove(last).unwrap();
+        state_b
+            .corpus_mut()
+            .add(Testcase::new(BytesInput::new(b"new".to_vec())))
+            .unwrap();
+        let mut meta = state_b
+            .named_metadata::<AflSyncMetadata>(&stage_b.name)
+            .unwrap()
+            .clone();
+        stage_b.export(&state_b, &mut meta).unwrap();
+        assert_eq!(
+            file_names(&dir_b.join("queue")),
+            [
+                "id:000000,sync:a,src:000000",
+                "id:000001,sync:a,src:000001",
+                "id:000002,time:0,execs:0"
+            ]
+        );
+
+        fs::remove_dir_all(&sync_dir).unwrap();
+    }
+}
@@ -0,0 +1,80 @@
+//! The [`CheckpointStage`] periodically writes a checkpoint of the whole state to disk,
+//! so that the campaign can be resumed later, see [`crate::state::checkpoint`].
+
+use core::time::Duration;
+use std::path::PathBuf;
+
+use libafl_bolts::current_time;
+
+use crate::{
+    Error,
+    stages::{Restartable, Stage},
+    state::HasCheckpoint,
+};
+
+/// A stage that writes a checkpoint of the state to disk, at most once every `interval`
+#[derive(Debug, Clone)]
+pub struct CheckpointStage {
+    path: PathBuf,
+    interval: Duration,
+    last_checkpoint: Duration,
+}
+
+impl<E, EM, S, Z> Stage<E, EM, S, Z> for CheckpointStage
+where
+    S: HasCheckpoint,
+{
+    #[inline]
+    fn perform(
+        &mut self,
+        _fuzzer: &mut Z,
+        _executor: &mut E,
+        state: &mut S,
+        _manager: &mut EM,
+    ) -> Result<(), Error> {
+        let now = current_time();
+        if now.saturating_sub(self.last_checkpoint) >= self.interval {
+            state.write_checkpoint(&self.path)?;
+            self.last_checkpoint = now;
+            log::info!("Wrote checkpoint to {}", self.path.display());
+        }
+        Ok(())
+    }
+}
+
+impl<S> Restartable<S> for CheckpointStage {
+    #[inline]
+    fn should_restart(&mut self, _state: &mut S) -> Result<bool, Error> {
+        // Not executing the target, so restart safety is not needed
+        Ok(true)
+    }
+
+    #[inline]
+    fn clear_progress(&mut self, _state: &mut S) -> Result<(), Error> {
+        // Not executing the target, so restart safety is not needed
+        Ok(())
+    }
+}
+
+impl CheckpointStage {
+    /// Creates a new [`CheckpointStage`], writing a checkpoint to `path` every `interval`.
+    ///
+    /// The first checkpoint is written once `interval` has passed.
+    #[must_use]
+    pub fn new<P>(path: P, interval: Duration) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self {
+            path: path.into(),
+            interval,
+            last_checkpoint: current_time(),
+        }
+    }
+
+    /// The path the checkpoints are written to
+    #[must_use]
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+}
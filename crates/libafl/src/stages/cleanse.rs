@@ -0,0 +1,371 @@
+//! The [`CleanseStage`] is a stage which replaces as many bytes of corpus entries as possible with neutral values,
+//! while preserving their behaviour (for example, a crash signature).
+//!
+//! This is the equivalent of libFuzzer's `-cleanse_crash`: where the [`crate::stages::StdTMinMutationalStage`]
+//! shortens inputs, this stage blanks out the bytes that are irrelevant, which makes triaging crashes much easier.
+
+use alloc::{
+    borrow::{Cow, ToOwned},
+    string::ToString,
+    vec::Vec,
+};
+use core::{hash::Hash, marker::PhantomData};
+
+use ahash::RandomState;
+use libafl_bolts::Named;
+
+use crate::{
+    Error, ExecutesInput, HasFeedback, HasMetadata, HasNamedMetadata, HasScheduler,
+    corpus::{Corpus, HasCurrentCorpusId, Testcase},
+    executors::HasObservers,
+    feedbacks::{Feedback, FeedbackFactory},
+    inputs::{HasMutatorBytes, Input},
+    observers::ObserversTuple,
+    schedulers::RemovableScheduler,
+    stages::{Restartable, RetryCountRestartHelper, Stage},
+    state::{HasCorpus, HasCurrentTestcase, HasExecutions},
+};
+
+/// The neutral values tried for every byte by default, as in libFuzzer
+pub const DEFAULT_CLEANSE_REPLACEMENTS: [u8; 2] = [b' ', 0xff];
+
+/// The default maximum number of passes over an input
+pub const DEFAULT_CLEANSE_PASSES: usize = 5;
+
+/// The counter for giving this stage unique id
+static mut CLEANSE_STAGE_ID: usize = 0;
+/// The name for cleanse stage
+pub static CLEANSE_STAGE_NAME: &str = "cleanse";
+
+/// A stage which replaces as many bytes as possible of the current corpus entry with neutral values.
+///
+/// A replacement is kept if the target exits with the same [`crate::executors::ExitKind`] as for the original input,
+/// and the feedback created by the factory deems the new input interesting. To preserve the crash signature, use an
+/// [`crate::stages::ObserverHashEqualityFactory`] on a [`crate::observers::BacktraceObserver`], or an
+/// [`crate::stages::ObserverEqualityFactory`]. A [`crate::feedbacks::ConstFeedback`] only requires the same exit kind.
+///
+/// The candidates are only executed, not evaluated by the fuzzer, so they never add corpus entries or solutions.
+#[derive(Debug, Clone)]
+pub struct CleanseStage<E, EM, F, FF, I, S, Z> {
+    /// The name
+    name: Cow<'static, str>,
+    /// The factory
+    factory: FF,
+    /// The neutral values tried for every byte
+    replacements: Vec<u8>,
+    /// The maximum number of passes over the input
+    passes: usize,
+    phantom: PhantomData<(E, EM, F, I, S, Z)>,
+}
+
+impl<E, EM, F, FF, I, S, Z> Named for CleanseStage<E, EM, F, FF, I, S, Z> {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<E, EM, F, FF, I, S, Z> Restartable<S> for CleanseStage<E, EM, F, FF, I, S, Z>
+where
+    S: HasNamedMetadata + HasCurrentCorpusId,
+{
+    fn should_restart(&mut self, state: &mut S) -> Result<bool, Error> {
+        // Do not get stuck on inputs crashing the fuzzer itself
+        RetryCountRestartHelper::should_restart(state, &self.name, 3)
+    }
+
+    fn clear_progress(&mut self, state: &mut S) -> Result<(), Error> {
+        RetryCountRestartHelper::clear_progress(state, &self.name)
+    }
+}
+
+impl<E, EM, F, FF, I, S, Z> Stage<E, EM, S, Z> for CleanseStage<E, EM, F, FF, I, S, Z>
+where
+    Z: HasScheduler<I, S> + ExecutesInput<E, EM, I, S> + HasFeedback,
+    Z::Scheduler: RemovableScheduler<I, S>,
+    E: HasObservers,
+    E::Observers: ObserversTuple<I, S>,
+    FF: FeedbackFactory<F, E::Observers>,
+    F: Feedback<EM, I, E::Observers, S>,
+    S: HasMetadata
+        + HasCorpus<I>
+        + HasExecutions
+        + HasNamedMetadata
+        + HasCurrentTestcase<I>
+        + HasCurrentCorpusId,
+    Z::Feedback: Feedback<EM, I, E::Observers, S>,
+    I: Input + Hash + HasMutatorBytes,
+{
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        let Some(base_corpus_id) = state.current_corpus_id()? else {
+            return Err(Error::illegal_state(
+                "state is not currently processing a corpus index",
+            ));
+        };
+
+        let mut base = state.current_input_cloned()?;
+        let base_hash = RandomState::with_seeds(0, 0, 0, 0).hash_one(&base);
+
+        let base_exit_kind = fuzzer.execute_input(state, executor, manager, &base)?;
+        let observers = executor.observers();
+        let mut feedback = self.factory.create_feedback(&*observers);
+
+        for _ in 0..self.passes {
+            let mut changed = false;
+            for idx in 0..base.mutator_bytes().len() {
+                let original = base.mutator_bytes()[idx];
+                if self.replacements.contains(&original) {
+                    continue;
+                }
+
+                for &replacement in &self.replacements {
+                    let mut input = base.clone();
+                    input.mutator_bytes_mut()[idx] = replacement;
+
+                    // only run the candidate: it is expected to crash, and must not be reported again
+                    let exit_kind = fuzzer.execute_input(state, executor, manager, &input)?;
+                    let observers = executor.observers();
+
+                    if exit_kind == base_exit_kind
+                        && feedback.is_interesting(
+                            state,
+                            manager,
+                            &input,
+                            &*observers,
+                            &exit_kind,
+                        )?
+                    {
+                        // the behaviour is preserved, keep the neutral byte
+                        base = input;
+                        changed = true;
+                        break;
+                    }
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        let new_hash = RandomState::with_seeds(0, 0, 0, 0).hash_one(&base);
+        if base_hash != new_hash {
+            let exit_kind = fuzzer.execute_input(state, executor, manager, &base)?;
+            let observers = executor.observers();
+            // let the feedbacks update their state for the testcase replacing the original one
+            fuzzer
+                .feedback_mut()
+                .is_interesting(state, manager, &base, &*observers, &exit_kind)?;
+            let mut testcase = Testcase::from(base);
+            testcase.set_executions(*state.executions());
+            testcase.set_parent_id(base_corpus_id);
+
+            fuzzer
+                .feedback_mut()
+                .append_metadata(state, manager, &*observers, &mut testcase)?;
+            let prev = state.corpus_mut().replace(base_corpus_id, testcase)?;
+            fuzzer
+                .scheduler_mut()
+                .on_replace(state, base_corpus_id, &prev)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<E, EM, F, FF, I, S, Z> CleanseStage<E, EM, F, FF, I, S, Z> {
+    /// Creates a new cleansing stage, trying the [`DEFAULT_CLEANSE_REPLACEMENTS`] for every byte
+    pub fn new(factory: FF) -> Self {
+        Self::with_params(
+            factory,
+            DEFAULT_CLEANSE_REPLACEMENTS.to_vec(),
+            DEFAULT_CLEANSE_PASSES,
+        )
+    }
+
+    /// Creates a new cleansing stage with the given neutral values, and the maximum number of passes over an input
+    pub fn with_params(factory: FF, replacements: Vec<u8>, passes: usize) -> Self {
+        // unsafe but impossible that you create two threads both instantiating this instance
+        let stage_id = unsafe {
+            let ret = CLEANSE_STAGE_ID;
+            CLEANSE_STAGE_ID += 1;
+            ret
+        };
+        Self {
+            name: Cow::Owned(CLEANSE_STAGE_NAME.to_owned() + ":" + stage_id.to_string().as_str()),
+            factory,
+            replacements,
+            passes,
+            phantom: PhantomData,
+        }
+    }
+
+    /// The neutral values tried for every byte
+    #[must_use]
+    pub fn replacements(&self) -> &[u8] {
+        &self.replacements
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::borrow::Cow;
+
+    use libafl_bolts::{Named, rands::StdRand, tuples::tuple_list};
+    use serde::{Deserialize, Serialize};
+
+    use super::CleanseStage;
+    use crate::{
+        Error, ExecutesInput, StdFuzzer,
+        corpus::{Corpus, HasCurrentCorpusId, InMemoryCorpus, Testcase},
+        events::NopEventManager,
+        executors::{ExitKind, InProcessExecutor},
+        feedbacks::CrashFeedback,
+        inputs::{BytesInput, HasMutatorBytes},
+        observers::{Observer, ObserverWithHashField},
+        schedulers::QueueScheduler,
+        stages::{ObserverHashEqualityFactory, Stage},
+        state::{HasCorpus, HasSolutions, StdState},
+    };
+
+    /// Crashes for inputs starting with `B?G`, in a different place depending on the second byte
+    fn crash_site(bytes: &[u8]) -> Option<u64> {
+        (bytes.len() > 2 && bytes[0] == b'B' && bytes[2] == b'G')
+            .then(|| u64::from(bytes[1] == b'U'))
+    }
+
+    /// Reports the crash site as crash signature, like a [`crate::observers::BacktraceObserver`] would
+    #[derive(Debug, Serialize, Deserialize)]
+    struct CrashSiteObserver {
+        name: Cow<'static, str>,
+        hash: Option<u64>,
+    }
+
+    impl Named for CrashSiteObserver {
+        fn name(&self) -> &Cow<'static, str> {
+            &self.name
+        }
+    }
+
+    impl ObserverWithHashField for CrashSiteObserver {
+        fn hash(&self) -> Option<u64> {
+            self.hash
+        }
+    }
+
+    impl<S> Observer<BytesInput, S> for CrashSiteObserver {
+        fn post_exec(
+            &mut self,
+            _state: &mut S,
+            input: &BytesInput,
+            exit_kind: &ExitKind,
+        ) -> Result<(), Error> {
+            self.hash = if *exit_kind == ExitKind::Crash {
+                crash_site(input.mutator_bytes())
+            } else {
+                None
+            };
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_cleanse_crash_signature() {
+        let mut harness = |input: &BytesInput| {
+            if crash_site(input.mutator_bytes()).is_some() {
+                ExitKind::Crash
+            } else {
+                ExitKind::Ok
+            }
+        };
+
+        let mut objective = CrashFeedback::new();
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut objective,
+        )
+        .unwrap();
+        let mut mgr = NopEventManager::new();
+        let mut fuzzer = StdFuzzer::new(QueueScheduler::new(), (), objective);
+        let observer = CrashSiteObserver {
+            name: Cow::Borrowed("crash_site"),
+            hash: None,
+        };
+        let factory = ObserverHashEqualityFactory::new(&observer);
+        let mut executor = InProcessExecutor::new(
+            &mut harness,
+            tuple_list!(observer),
+            &mut fuzzer,
+            &mut state,
+            &mut mgr,
+        )
+        .unwrap();
+
+        let id = state
+            .corpus_mut()
+            .add(Testcase::new(BytesInput::new(b"BUG\x00!".to_vec())))
+            .unwrap();
+        state.set_corpus_id(id).unwrap();
+
+        let mut stage = CleanseStage::new(factory);
+        stage
+            .perform(&mut fuzzer, &mut executor, &mut state, &mut mgr)
+            .unwrap();
+
+        // The second byte decides the crash site, so it is kept, although any value crashes
+        let cleansed = state.corpus().cloned_input_for_id(id).unwrap();
+        assert_eq!(cleansed.mutator_bytes(), b"BUG  ");
+        // The crashing candidates are not reported as new solutions
+        assert_eq!(state.solutions().count(), 0);
+    }
+
+    #[test]
+    fn test_cleanse_stage() {
+        let mut harness = |input: &BytesInput| {
+            let bytes = input.mutator_bytes();
+            if bytes.len() > 2 && bytes[0] == b'B' && bytes[2] == b'G' {
+                ExitKind::Crash
+            } else {
+                ExitKind::Ok
+            }
+        };
+
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut (),
+        )
+        .unwrap();
+        let mut mgr = NopEventManager::new();
+        let mut fuzzer = StdFuzzer::new(QueueScheduler::new(), (), ());
+        let mut executor =
+            InProcessExecutor::new(&mut harness, (), &mut fuzzer, &mut state, &mut mgr).unwrap();
+
+        let input = BytesInput::new(b"BUG\x00!".to_vec());
+        assert_eq!(
+            fuzzer
+                .execute_input(&mut state, &mut executor, &mut mgr, &input)
+                .unwrap(),
+            ExitKind::Crash
+        );
+        let id = state.corpus_mut().add(Testcase::new(input)).unwrap();
+        state.set_corpus_id(id).unwrap();
+
+        let mut stage = CleanseStage::new(CrashFeedback::new());
+        stage
+            .perform(&mut fuzzer, &mut executor, &mut state, &mut mgr)
+            .unwrap();
+
+        let cleansed = state.corpus().cloned_input_for_id(id).unwrap();
+        assert_eq!(cleansed.mutator_bytes(), b"B G  ");
+    }
+}
@@ -0,0 +1,590 @@
+//! The [`CoverageReportStage`] replays the corpus and writes `lcov` and static HTML coverage reports.
+//!
+//! The edges of a [`MapObserver`] are mapped back to the source using an [`EdgeSourceMap`], which can be built
+//! from the sancov PC tables (see `libafl_targets::sanitizer_cov_pc_source_map`), or from the CFG dumped by
+//! `libafl_cc` (see `ControlFlowGraph::edge_functions`), which only knows the function of each edge.
+
+use alloc::{
+    borrow::{Cow, ToOwned},
+    collections::BTreeMap,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{fmt::Write as _, marker::PhantomData};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use libafl_bolts::{
+    Named, impl_serdeany,
+    tuples::{Handle, Handled},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Error, ExecutesInput, HasMetadata,
+    corpus::{Corpus, CorpusId},
+    executors::HasObservers,
+    observers::{MapObserver, ObserversTuple},
+    stages::{Restartable, Stage},
+    state::HasCorpus,
+};
+
+/// The name of the `lcov` report written by the [`CoverageReportStage`]
+pub const LCOV_REPORT_FILENAME: &str = "coverage.lcov";
+/// The directory of the HTML report written by the [`CoverageReportStage`]
+pub const HTML_REPORT_DIRNAME: &str = "html";
+
+/// The default name for the coverage report stage
+pub static COVERAGE_REPORT_STAGE_NAME: &str = "coverage_report";
+
+/// The location in the source of an edge of the coverage map
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceLocation {
+    /// The function containing the edge
+    pub function: String,
+    /// The source file, if known
+    pub file: Option<PathBuf>,
+    /// The line in the source file, if known
+    pub line: Option<u32>,
+}
+
+impl SourceLocation {
+    /// Creates a location only knowing the function of the edge, as in the CFG dumped by `libafl_cc`
+    #[must_use]
+    pub fn function<F: Into<String>>(function: F) -> Self {
+        Self {
+            function: function.into(),
+            file: None,
+            line: None,
+        }
+    }
+}
+
+/// Maps the indices of a coverage map to the [`SourceLocation`]s of the edges
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EdgeSourceMap {
+    locations: Vec<Option<SourceLocation>>,
+}
+
+impl EdgeSourceMap {
+    /// Creates an empty [`EdgeSourceMap`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the location of the edge at the given index of the coverage map
+    pub fn insert(&mut self, edge: usize, location: SourceLocation) {
+        if self.locations.len() <= edge {
+            self.locations.resize(edge + 1, None);
+        }
+        self.locations[edge] = Some(location);
+    }
+
+    /// The location of the edge at the given index of the coverage map, if known
+    #[must_use]
+    pub fn get(&self, edge: usize) -> Option<&SourceLocation> {
+        self.locations.get(edge).and_then(Option::as_ref)
+    }
+
+    /// The number of edges this map may know the location of
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.locations.len()
+    }
+
+    /// Returns `true` if no location is known
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.locations.iter().all(Option::is_none)
+    }
+}
+
+impl FromIterator<(usize, SourceLocation)> for EdgeSourceMap {
+    fn from_iter<T: IntoIterator<Item = (usize, SourceLocation)>>(iter: T) -> Self {
+        let mut map = Self::new();
+        for (edge, location) in iter {
+            map.insert(edge, location);
+        }
+        map
+    }
+}
+
+/// The coverage of a function
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FunctionCoverage {
+    /// The first line of the function, if known
+    pub line: Option<u32>,
+    /// The number of edges of the function
+    pub edges: usize,
+    /// The number of edges of the function covered by the corpus
+    pub covered_edges: usize,
+    /// The highest number of corpus entries covering an edge of the function
+    pub hits: u64,
+}
+
+/// The coverage of a line
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LineCoverage {
+    /// The number of edges on the line
+    pub edges: usize,
+    /// The number of edges on the line covered by the corpus
+    pub covered_edges: usize,
+    /// The highest number of corpus entries covering an edge on the line
+    pub hits: u64,
+}
+
+/// The coverage of a source file
+#[derive(Debug, Clone, Default)]
+pub struct FileCoverage {
+    /// The functions in this file, by name
+    pub functions: BTreeMap<String, FunctionCoverage>,
+    /// The lines in this file with edges on them
+    pub lines: BTreeMap<u32, LineCoverage>,
+}
+
+impl FileCoverage {
+    /// The number of functions with at least one covered edge
+    #[must_use]
+    pub fn covered_functions(&self) -> usize {
+        self.functions
+            .values()
+            .filter(|function| function.covered_edges > 0)
+            .count()
+    }
+
+    /// The number of lines with at least one covered edge
+    #[must_use]
+    pub fn covered_lines(&self) -> usize {
+        self.lines
+            .values()
+            .filter(|line| line.covered_edges > 0)
+            .count()
+    }
+}
+
+/// A per-file, per-function and per-line coverage report
+#[derive(Debug, Clone, Default)]
+pub struct CoverageReport {
+    /// The coverage per source file; edges of which the file is not known are reported with `None`
+    pub files: BTreeMap<Option<PathBuf>, FileCoverage>,
+}
+
+impl CoverageReport {
+    /// Builds a report from the number of corpus entries covering each edge of the coverage map
+    #[must_use]
+    pub fn from_hits(source_map: &EdgeSourceMap, hits: &[u64]) -> Self {
+        let mut report = Self::default();
+        for (edge, location) in source_map.locations.iter().enumerate() {
+            let Some(location) = location else {
+                continue;
+            };
+            let edge_hits = hits.get(edge).copied().unwrap_or(0);
+            let covered = usize::from(edge_hits > 0);
+            let file = report.files.entry(location.file.clone()).or_default();
+
+            let function = file.functions.entry(location.function.clone()).or_default();
+            function.line = match (function.line, location.line) {
+                (Some(first), Some(line)) => Some(first.min(line)),
+                (first, line) => first.or(line),
+            };
+            function.edges += 1;
+            function.covered_edges += covered;
+            function.hits = function.hits.max(edge_hits);
+
+            if let Some(line) = location.line {
+                let line = file.lines.entry(line).or_default();
+                line.edges += 1;
+                line.covered_edges += covered;
+                line.hits = line.hits.max(edge_hits);
+            }
+        }
+        report
+    }
+
+    /// Renders the report in the `lcov` tracefile format.
+    ///
+    /// Edges without a known source file cannot be represented in `lcov`, and only appear in the HTML report.
+    #[must_use]
+    pub fn to_lcov(&self) -> String {
+        let mut out = String::new();
+        for (file, coverage) in &self.files {
+            let Some(file) = file else {
+                continue;
+            };
+            out.push_str("TN:\n");
+            writeln!(out, "SF:{}", file.display()).unwrap();
+            for (name, function) in &coverage.functions {
+                writeln!(out, "FN:{},{name}", function.line.unwrap_or(0)).unwrap();
+            }
+            for (name, function) in &coverage.functions {
+                writeln!(out, "FNDA:{},{name}", function.hits).unwrap();
+            }
+            writeln!(out, "FNF:{}", coverage.functions.len()).unwrap();
+            writeln!(out, "FNH:{}", coverage.covered_functions()).unwrap();
+            for (line, line_coverage) in &coverage.lines {
+                writeln!(out, "DA:{line},{}", line_coverage.hits).unwrap();
+            }
+            writeln!(out, "LF:{}", coverage.lines.len()).unwrap();
+            writeln!(out, "LH:{}", coverage.covered_lines()).unwrap();
+            out.push_str("end_of_record\n");
+        }
+        out
+    }
+
+    /// Writes the report in the `lcov` tracefile format to the given file
+    pub fn write_lcov<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        fs::write(path, self.to_lcov())?;
+        Ok(())
+    }
+
+    /// Writes a static HTML report to the given directory.
+    ///
+    /// The `index.html` lists the coverage of all files and functions, and each file with line information gets
+    /// its own page, showing the annotated source if it can be read.
+    pub fn write_html<P: AsRef<Path>>(&self, dir: P) -> Result<(), Error> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+
+        let mut index = html_header("Coverage report");
+        index.push_str(
+            "<h2>Files</h2>\n<table>\n<tr><th>File</th><th>Lines</th><th>Functions</th></tr>\n",
+        );
+        for (idx, (file, coverage)) in self.files.iter().enumerate() {
+            let name = file.as_ref().map_or_else(
+                || "&lt;unknown&gt;".to_string(),
+                |file| html_escape(&file.display().to_string()),
+            );
+            let name = if file.is_some() && !coverage.lines.is_empty() {
+                let page = format!("file_{idx}.html");
+                fs::write(dir.join(&page), file_page(file.as_deref(), coverage))?;
+                format!("<a href=\"{page}\">{name}</a>")
+            } else {
+                name
+            };
+            writeln!(
+                index,
+                "<tr><td>{name}</td>{}{}</tr>",
+                ratio_cell(coverage.covered_lines(), coverage.lines.len()),
+                ratio_cell(coverage.covered_functions(), coverage.functions.len()),
+            )
+            .unwrap();
+        }
+        index.push_str("</table>\n");
+
+        index.push_str(
+            "<h2>Functions</h2>\n<table>\n<tr><th>Function</th><th>Location</th><th>Edges</th></tr>\n",
+        );
+        for (file, coverage) in &self.files {
+            for (name, function) in &coverage.functions {
+                let location = match (file, function.line) {
+                    (Some(file), Some(line)) => format!("{}:{line}", file.display()),
+                    (Some(file), None) => file.display().to_string(),
+                    (None, _) => String::new(),
+                };
+                writeln!(
+                    index,
+                    "<tr><td>{}</td><td>{}</td>{}</tr>",
+                    html_escape(name),
+                    html_escape(&location),
+                    ratio_cell(function.covered_edges, function.edges),
+                )
+                .unwrap();
+            }
+        }
+        index.push_str("</table>\n</body>\n</html>\n");
+        fs::write(dir.join("index.html"), index)?;
+        Ok(())
+    }
+}
+
+fn html_escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn html_header(title: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n\
+         body {{ font-family: sans-serif; }}\n\
+         table {{ border-collapse: collapse; }}\n\
+         td, th {{ padding: 2px 8px; text-align: left; }}\n\
+         pre {{ margin: 0; }}\n\
+         .covered {{ background: #c8f0c8; }}\n\
+         .partial {{ background: #f0f0b0; }}\n\
+         .uncovered {{ background: #f0c8c8; }}\n\
+         </style>\n</head>\n<body>\n<h1>{}</h1>\n",
+        html_escape(title),
+        html_escape(title)
+    )
+}
+
+fn coverage_class(covered: usize, total: usize) -> &'static str {
+    if covered == 0 {
+        "uncovered"
+    } else if covered < total {
+        "partial"
+    } else {
+        "covered"
+    }
+}
+
+fn ratio_cell(covered: usize, total: usize) -> String {
+    #[expect(clippy::cast_precision_loss)]
+    let percent = if total == 0 {
+        0.0
+    } else {
+        (covered as f64) * 100.0 / (total as f64)
+    };
+    format!(
+        "<td class=\"{}\">{covered}/{total} ({percent:.1}%)</td>",
+        coverage_class(covered, total)
+    )
+}
+
+fn file_page(file: Option<&Path>, coverage: &FileCoverage) -> String {
+    let title = file.map_or_else(
+        || "<unknown>".to_string(),
+        |file| file.display().to_string(),
+    );
+    let mut page = html_header(&title);
+    page.push_str("<table>\n<tr><th>Line</th><th>Hits</th><th>Source</th></tr>\n");
+
+    let source = file.and_then(|file| fs::read_to_string(file).ok());
+    if let Some(source) = source {
+        for (idx, text) in source.lines().enumerate() {
+            let line = u32::try_from(idx + 1).unwrap_or(u32::MAX);
+            let (class, hits) =
+                coverage
+                    .lines
+                    .get(&line)
+                    .map_or((String::new(), String::new()), |line| {
+                        (
+                            format!(
+                                " class=\"{}\"",
+                                coverage_class(line.covered_edges, line.edges)
+                            ),
+                            line.hits.to_string(),
+                        )
+                    });
+            writeln!(
+                page,
+                "<tr{class}><td>{line}</td><td>{hits}</td><td><pre>{}</pre></td></tr>",
+                html_escape(text)
+            )
+            .unwrap();
+        }
+    } else {
+        // the source is not available here, only list the instrumented lines
+        for (line, line_coverage) in &coverage.lines {
+            writeln!(
+                page,
+                "<tr class=\"{}\"><td>{line}</td><td>{}</td><td></td></tr>",
+                coverage_class(line_coverage.covered_edges, line_coverage.edges),
+                line_coverage.hits
+            )
+            .unwrap();
+        }
+    }
+    page.push_str("</table>\n</body>\n</html>\n");
+    page
+}
+
+/// The progress of the [`CoverageReportStage`]: the last replayed corpus entry and the accumulated hits
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    expect(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Default, Serialize, Deserialize, Debug, Clone)]
+pub struct CoverageReportMetadata {
+    last_corpus: Option<CorpusId>,
+    hits: Vec<u64>,
+}
+
+impl_serdeany!(CoverageReportMetadata);
+
+impl CoverageReportMetadata {
+    /// The number of corpus entries covering each edge of the coverage map
+    #[must_use]
+    pub fn hits(&self) -> &[u64] {
+        &self.hits
+    }
+}
+
+/// A stage replaying new corpus entries through the executor, writing `lcov` and HTML coverage reports of the
+/// whole corpus to the output directory whenever the corpus grew.
+///
+/// The executor should not be the one used for fuzzing if it uses a different coverage map, for example one of
+/// a target built with `-fsanitize-coverage=pc-table` to build the [`EdgeSourceMap`].
+#[derive(Debug, Clone)]
+pub struct CoverageReportStage<C, E, EM, I, O, S, Z> {
+    name: Cow<'static, str>,
+    map_observer_handle: Handle<C>,
+    source_map: EdgeSourceMap,
+    output_dir: PathBuf,
+    phantom: PhantomData<(E, EM, I, O, S, Z)>,
+}
+
+impl<C, E, EM, I, O, S, Z> Named for CoverageReportStage<C, E, EM, I, O, S, Z> {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<C, E, EM, I, O, S, Z> Restartable<S> for CoverageReportStage<C, E, EM, I, O, S, Z> {
+    #[inline]
+    fn should_restart(&mut self, _state: &mut S) -> Result<bool, Error> {
+        // The corpus entries have all been executed before, and the progress is only stored once all are replayed
+        Ok(true)
+    }
+
+    #[inline]
+    fn clear_progress(&mut self, _state: &mut S) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<C, E, EM, I, O, S, Z> Stage<E, EM, S, Z> for CoverageReportStage<C, E, EM, I, O, S, Z>
+where
+    C: AsRef<O>,
+    E: HasObservers,
+    E::Observers: ObserversTuple<I, S>,
+    O: MapObserver,
+    I: Clone,
+    S: HasCorpus<I> + HasMetadata,
+    Z: ExecutesInput<E, EM, I, S>,
+{
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        let mut meta = state
+            .metadata_map()
+            .get::<CoverageReportMetadata>()
+            .cloned()
+            .unwrap_or_default();
+        let mut corpus_id = match meta.last_corpus {
+            Some(last) => state.corpus().next(last),
+            None => state.corpus().first(),
+        };
+        if corpus_id.is_none() {
+            return Ok(());
+        }
+
+        while let Some(id) = corpus_id {
+            let input = state.corpus().cloned_input_for_id(id)?;
+            fuzzer.execute_input(state, executor, manager, &input)?;
+
+            let observers = executor.observers();
+            let map = observers[&self.map_observer_handle].as_ref();
+            let initial = map.initial();
+            let len = map.usable_count();
+            if meta.hits.len() < len {
+                meta.hits.resize(len, 0);
+            }
+            for (idx, hits) in meta.hits.iter_mut().enumerate().take(len) {
+                if map.get(idx) != initial {
+                    *hits += 1;
+                }
+            }
+
+            meta.last_corpus = Some(id);
+            corpus_id = state.corpus().next(id);
+        }
+
+        let report = CoverageReport::from_hits(&self.source_map, &meta.hits);
+        report.write_lcov(self.output_dir.join(LCOV_REPORT_FILENAME))?;
+        report.write_html(self.output_dir.join(HTML_REPORT_DIRNAME))?;
+
+        state.add_metadata(meta);
+        Ok(())
+    }
+}
+
+impl<C, E, EM, I, O, S, Z> CoverageReportStage<C, E, EM, I, O, S, Z>
+where
+    C: Handled + Named,
+{
+    /// Creates a new [`CoverageReportStage`] for the given map observer, writing the reports to `output_dir`
+    pub fn new<P>(observer: &C, source_map: EdgeSourceMap, output_dir: P) -> Result<Self, Error>
+    where
+        P: Into<PathBuf>,
+    {
+        let output_dir = output_dir.into();
+        fs::create_dir_all(&output_dir).map_err(|e| {
+            Error::os_error(
+                e,
+                format!("Error creating directory {}", output_dir.display()),
+            )
+        })?;
+        Ok(Self {
+            name: Cow::Owned(
+                COVERAGE_REPORT_STAGE_NAME.to_owned() + ":" + observer.name().as_ref(),
+            ),
+            map_observer_handle: observer.handle(),
+            source_map,
+            output_dir,
+            phantom: PhantomData,
+        })
+    }
+
+    /// The map from edges to source locations used for the reports
+    #[must_use]
+    pub fn source_map(&self) -> &EdgeSourceMap {
+        &self.source_map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{CoverageReport, EdgeSourceMap, SourceLocation};
+
+    fn location(function: &str, line: u32) -> SourceLocation {
+        SourceLocation {
+            function: function.into(),
+            file: Some(PathBuf::from("target.c")),
+            line: Some(line),
+        }
+    }
+
+    #[test]
+    fn test_coverage_report_lcov() {
+        let source_map: EdgeSourceMap = [
+            (0, location("main", 3)),
+            (1, location("main", 4)),
+            (2, location("main", 4)),
+            (3, location("check", 10)),
+            (5, SourceLocation::function("anonymous")),
+        ]
+        .into_iter()
+        .collect();
+        let hits = [2, 0, 1, 0, 7, 1];
+
+        let report = CoverageReport::from_hits(&source_map, &hits);
+        assert_eq!(report.files.len(), 2);
+        let unknown = &report.files[&None];
+        assert_eq!(unknown.functions["anonymous"].covered_edges, 1);
+
+        assert_eq!(
+            report.to_lcov(),
+            "TN:\nSF:target.c\nFN:10,check\nFN:3,main\nFNDA:0,check\nFNDA:2,main\nFNF:2\nFNH:1\n\
+             DA:3,2\nDA:4,1\nDA:10,0\nLF:3\nLH:2\nend_of_record\n"
+        );
+    }
+}
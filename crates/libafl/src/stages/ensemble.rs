@@ -0,0 +1,203 @@
+//! The [`EnsembleStage`] reports the finds of a client to its [`crate::events::ensemble::EnsembleLauncher`],
+//! and stops the client once its core is given to another strategy.
+
+use alloc::borrow::Cow;
+use core::marker::PhantomData;
+
+use libafl_bolts::{Named, shmem::ShMem};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Error, HasNamedMetadata,
+    corpus::Corpus,
+    events::ensemble::EnsembleClient,
+    stages::{Restartable, Stage},
+    state::{HasCorpus, HasImported, Stoppable},
+};
+
+/// Default name for [`EnsembleStage`]
+pub const ENSEMBLE_STAGE_NAME: &str = "ensemble";
+
+/// The finds of this client already reported by the [`EnsembleStage`]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    expect(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct EnsembleStageMetadata {
+    /// The number of corpus entries this client found itself when last reported
+    pub reported: usize,
+}
+
+libafl_bolts::impl_serdeany!(EnsembleStageMetadata);
+
+/// A stage for clients of an [`crate::events::ensemble::EnsembleLauncher`].
+///
+/// It credits the strategy of the client with every corpus entry the client found itself, not counting
+/// initial inputs and testcases imported from other clients. Once the launcher gives the core of the client
+/// to another strategy, it requests the client to stop.
+#[derive(Debug)]
+pub struct EnsembleStage<I, SHM> {
+    client: EnsembleClient<SHM>,
+    name: Cow<'static, str>,
+    phantom: PhantomData<I>,
+}
+
+impl<I, SHM> EnsembleStage<I, SHM> {
+    /// Create a new [`EnsembleStage`] for the given client
+    #[must_use]
+    pub fn new(client: EnsembleClient<SHM>) -> Self {
+        Self {
+            client,
+            name: Cow::Borrowed(ENSEMBLE_STAGE_NAME),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<I, SHM> Named for EnsembleStage<I, SHM> {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<E, EM, I, S, SHM, Z> Stage<E, EM, S, Z> for EnsembleStage<I, SHM>
+where
+    S: HasCorpus<I> + HasImported + HasNamedMetadata + Stoppable,
+    SHM: ShMem,
+{
+    fn perform(
+        &mut self,
+        _fuzzer: &mut Z,
+        _executor: &mut E,
+        state: &mut S,
+        _manager: &mut EM,
+    ) -> Result<(), Error> {
+        let found = state.corpus().count().saturating_sub(*state.imported());
+        if let Ok(meta) = state.named_metadata_mut::<EnsembleStageMetadata>(&self.name) {
+            if found > meta.reported {
+                self.client.report_finds((found - meta.reported) as u64);
+                meta.reported = found;
+            }
+        } else {
+            // The initial inputs are not a find
+            state.add_named_metadata(&self.name, EnsembleStageMetadata { reported: found });
+        }
+
+        if !self.client.is_assigned() {
+            log::info!(
+                "The core of this {} client was given to another strategy, stopping",
+                self.client.strategy_name()
+            );
+            state.request_stop();
+        }
+        Ok(())
+    }
+}
+
+impl<I, S, SHM> Restartable<S> for EnsembleStage<I, SHM> {
+    #[inline]
+    fn should_restart(&mut self, _state: &mut S) -> Result<bool, Error> {
+        // Not executing the target, so restart safety is not needed
+        Ok(true)
+    }
+
+    #[inline]
+    fn clear_progress(&mut self, _state: &mut S) -> Result<(), Error> {
+        // Not executing the target, so restart safety is not needed
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::{
+        core_affinity::CoreId,
+        rands::StdRand,
+        shmem::{ShMem, ShMemProvider, StdShMemProvider},
+    };
+
+    use super::EnsembleStage;
+    use crate::{
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        events::{
+            ClientDescription,
+            ensemble::{EnsembleBoard, EnsembleClient},
+        },
+        feedbacks::ConstFeedback,
+        inputs::BytesInput,
+        stages::Stage,
+        state::{HasCorpus, HasImported, StdState, Stoppable},
+    };
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_ensemble_stage() {
+        let mut provider = StdShMemProvider::new().unwrap();
+        let board = EnsembleBoard::new(&mut provider, 2, 1).unwrap();
+        board.assign(0, 1);
+        let shmem = provider
+            .shmem_from_id_and_size(board.shmem().id(), board.shmem().len())
+            .unwrap();
+        let client = EnsembleClient::with_assignment(
+            EnsembleBoard::from_shmem(shmem).unwrap(),
+            &format!(
+                "1;0;{};1337;b",
+                ClientDescription::new(0, 0, CoreId(0)).to_safe_string()
+            ),
+        )
+        .unwrap();
+        let mut stage = EnsembleStage::<BytesInput, _>::new(client);
+
+        let mut feedback = ConstFeedback::new(true);
+        let mut objective = ConstFeedback::new(false);
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )
+        .unwrap();
+        for seed in [b"a", b"b"] {
+            state
+                .corpus_mut()
+                .add(Testcase::new(BytesInput::new(seed.to_vec())))
+                .unwrap();
+        }
+
+        // The seeds are not a find
+        stage
+            .perform(&mut (), &mut (), &mut state, &mut ())
+            .unwrap();
+        assert_eq!(board.finds(1), 0);
+
+        // Two finds of its own, and one import
+        for input in [b"c", b"d", b"e"] {
+            state
+                .corpus_mut()
+                .add(Testcase::new(BytesInput::new(input.to_vec())))
+                .unwrap();
+        }
+        *state.imported_mut() += 1;
+        stage
+            .perform(&mut (), &mut (), &mut state, &mut ())
+            .unwrap();
+        assert_eq!(board.finds(0), 0);
+        assert_eq!(board.finds(1), 2);
+        assert!(!state.stop_requested());
+
+        // Reported once only
+        stage
+            .perform(&mut (), &mut (), &mut state, &mut ())
+            .unwrap();
+        assert_eq!(board.finds(1), 2);
+
+        // The launcher gave the core to the other strategy
+        board.assign(0, 0);
+        stage
+            .perform(&mut (), &mut (), &mut state, &mut ())
+            .unwrap();
+        assert!(state.stop_requested());
+    }
+}
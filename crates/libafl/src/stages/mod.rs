@@ -14,14 +14,23 @@ use core::{fmt, marker::PhantomData};
 
 #[cfg(feature = "std")]
 pub use afl_stats::{AflStatsStage, CalibrationTime, FuzzTime, SyncTime};
+#[cfg(feature = "std")]
+pub use afl_sync::*;
 pub use calibrate::CalibrationStage;
+#[cfg(feature = "std")]
+pub use checkpoint::CheckpointStage;
+pub use cleanse::CleanseStage;
 pub use colorization::*;
 #[cfg(all(feature = "std", unix))]
 pub use concolic::ConcolicTracingStage;
 #[cfg(all(feature = "std", feature = "concolic_mutation", unix))]
 pub use concolic::SimpleConcolicMutationalStage;
 #[cfg(feature = "std")]
+pub use coverage_report::{CoverageReport, CoverageReportStage, EdgeSourceMap, SourceLocation};
+#[cfg(feature = "std")]
 pub use dump::*;
+#[cfg(all(unix, feature = "fork"))]
+pub use ensemble::{EnsembleStage, EnsembleStageMetadata};
 pub use generalization::GeneralizationStage;
 use hashbrown::HashSet;
 use libafl_bolts::{
@@ -29,14 +38,20 @@ use libafl_bolts::{
     tuples::{HasConstLen, IntoVec},
 };
 pub use logics::*;
-pub use mutational::{MutationalStage, StdMutationalStage};
+pub use mutational::{BatchMutationalStage, MutationalStage, StdMutationalStage};
+pub use plateau::{
+    ClosurePlateauAction, MutatorStackingAction, PlateauAction, PlateauStage, plateau_level,
+};
 pub use power::{PowerMutationalStage, StdPowerMutationalStage};
 use serde::{Deserialize, Serialize};
 #[cfg(feature = "std")]
 pub use sync::*;
 #[cfg(feature = "std")]
 pub use time_tracker::TimeTrackingStageWrapper;
-pub use tmin::{ObserverEqualityFactory, ObserverEqualityFeedback, StdTMinMutationalStage};
+pub use tmin::{
+    ObserverEqualityFactory, ObserverEqualityFeedback, ObserverHashEqualityFactory,
+    ObserverHashEqualityFeedback, StdTMinMutationalStage,
+};
 pub use tracing::TracingStage;
 pub use tuneable::*;
 use tuple_list::NonEmptyTuple;
@@ -65,17 +80,27 @@ pub use replay::*;
 
 #[cfg(feature = "std")]
 pub mod afl_stats;
+#[cfg(feature = "std")]
+pub mod afl_sync;
 pub mod calibrate;
+#[cfg(feature = "std")]
+pub mod checkpoint;
+pub mod cleanse;
 pub mod colorization;
 #[cfg(all(feature = "std", unix))]
 pub mod concolic;
 #[cfg(feature = "std")]
+pub mod coverage_report;
+#[cfg(feature = "std")]
 pub mod dump;
 pub mod dynamic;
+#[cfg(all(unix, feature = "fork"))]
+pub mod ensemble;
 pub mod generalization;
 pub mod generation;
 pub mod logics;
 pub mod nop;
+pub mod plateau;
 pub mod power;
 #[cfg(feature = "std")]
 pub mod sync;
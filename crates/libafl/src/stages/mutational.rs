@@ -4,6 +4,7 @@
 use alloc::{
     borrow::{Cow, ToOwned},
     string::ToString,
+    vec::Vec,
 };
 use core::{marker::PhantomData, num::NonZeroUsize};
 
@@ -14,11 +15,14 @@ use crate::monitors::stats::PerfFeature;
 use crate::{
     Error, HasMetadata, HasNamedMetadata,
     corpus::{Corpus, CorpusId, HasCurrentCorpusId, Testcase},
-    fuzzer::Evaluator,
+    executors::BatchExecutor,
+    fuzzer::{Evaluator, ExecutionProcessor, HasScheduler},
     inputs::Input,
     mark_feature_time,
     mutators::{MultiMutator, MutationResult, Mutator},
     nonzero,
+    observers::ObserversTuple,
+    schedulers::Scheduler,
     stages::{Restartable, RetryCountRestartHelper, Stage},
     start_timer,
     state::{HasCorpus, HasCurrentTestcase, HasExecutions, HasRand, MaybeHasClientPerfMonitor},
@@ -384,3 +388,174 @@ impl<E, EM, I, M, S, Z> MultiMutationalStage<E, EM, I, M, S, Z> {
         }
     }
 }
+
+/// A mutational stage running its mutations in batches on a [`BatchExecutor`],
+/// such as the [`InProcessThreadedExecutor`](crate::executors::InProcessThreadedExecutor).
+#[derive(Debug, Clone)]
+pub struct BatchMutationalStage<E, EM, I, M, S, Z> {
+    name: Cow<'static, str>,
+    mutator: M,
+    /// The maximum amount of iterations we should do each round
+    max_iterations: NonZeroUsize,
+    phantom: PhantomData<(E, EM, I, S, Z)>,
+}
+
+/// The unique id for batch mutational stage
+static mut BATCH_MUTATIONAL_STAGE_ID: usize = 0;
+/// The name for batch mutational stage
+pub static BATCH_MUTATIONAL_STAGE_NAME: &str = "batchmutational";
+
+impl<E, EM, I, M, S, Z> Named for BatchMutationalStage<E, EM, I, M, S, Z> {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<E, EM, I, M, S, Z> MutationalStage<S> for BatchMutationalStage<E, EM, I, M, S, Z>
+where
+    S: HasRand,
+{
+    type Mutator = M;
+
+    #[inline]
+    fn mutator(&self) -> &Self::Mutator {
+        &self.mutator
+    }
+
+    #[inline]
+    fn mutator_mut(&mut self) -> &mut Self::Mutator {
+        &mut self.mutator
+    }
+
+    /// Gets the number of iterations as a random number
+    fn iterations(&self, state: &mut S) -> Result<usize, Error> {
+        Ok(1 + state.rand_mut().below(self.max_iterations))
+    }
+}
+
+impl<E, EM, I, M, S, Z> Stage<E, EM, S, Z> for BatchMutationalStage<E, EM, I, M, S, Z>
+where
+    E: BatchExecutor<EM, I, S, Z>,
+    E::Observers: ObserversTuple<I, S>,
+    I: Clone + MutatedTransform<I, S>,
+    M: Mutator<I, S>,
+    S: HasRand + HasCurrentTestcase<I> + MaybeHasClientPerfMonitor,
+    Z: ExecutionProcessor<EM, I, E::Observers, S> + HasScheduler<I, S>,
+{
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        start_timer!(state);
+        let num = self.iterations(state)?;
+        let mut testcase = state.current_testcase_mut()?;
+        let Ok(input) = I::try_transform_from(&mut testcase, state) else {
+            return Ok(());
+        };
+        drop(testcase);
+        mark_feature_time!(state, PerfFeature::GetInputFromCorpus);
+
+        let batch_size = executor.batch_size().max(1);
+        let mut batch = Vec::with_capacity(batch_size);
+        let mut posts = Vec::with_capacity(batch_size);
+        let mut remaining = num;
+        while remaining > 0 {
+            while remaining > 0 && batch.len() < batch_size {
+                remaining -= 1;
+                let mut input = input.clone();
+
+                start_timer!(state);
+                let mutated = self.mutator.mutate(state, &mut input)?;
+                mark_feature_time!(state, PerfFeature::Mutate);
+
+                if mutated == MutationResult::Skipped {
+                    continue;
+                }
+                let (untransformed, post) = input.try_transform_into(state)?;
+                batch.push(untransformed);
+                posts.push(post);
+            }
+            if batch.is_empty() {
+                continue;
+            }
+
+            let exit_kinds = executor.run_batch(fuzzer, state, manager, &batch)?;
+            for (idx, ((input, post), exit_kind)) in batch
+                .drain(..)
+                .zip(posts.drain(..))
+                .zip(exit_kinds)
+                .enumerate()
+            {
+                executor.observers_mut().pre_exec_all(state, &input)?;
+                executor.restore_batch_observation(idx);
+                executor
+                    .observers_mut()
+                    .post_exec_all(state, &input, &exit_kind)?;
+
+                let observers = executor.observers();
+                fuzzer
+                    .scheduler_mut()
+                    .on_evaluation(state, &input, &*observers)?;
+                let (_, corpus_id) = fuzzer.evaluate_execution(
+                    state,
+                    manager,
+                    &input,
+                    &*observers,
+                    &exit_kind,
+                    true,
+                )?;
+
+                start_timer!(state);
+                self.mutator.post_exec(state, corpus_id)?;
+                post.post_exec(state, corpus_id)?;
+                mark_feature_time!(state, PerfFeature::MutatePostExec);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<E, EM, I, M, S, Z> Restartable<S> for BatchMutationalStage<E, EM, I, M, S, Z>
+where
+    S: HasMetadata + HasNamedMetadata + HasCurrentCorpusId,
+{
+    #[inline]
+    fn should_restart(&mut self, state: &mut S) -> Result<bool, Error> {
+        // Make sure we don't get stuck crashing on a single testcase
+        RetryCountRestartHelper::should_restart(state, &self.name, 3)
+    }
+
+    #[inline]
+    fn clear_progress(&mut self, state: &mut S) -> Result<(), Error> {
+        RetryCountRestartHelper::clear_progress(state, &self.name)
+    }
+}
+
+impl<E, EM, I, M, S, Z> BatchMutationalStage<E, EM, I, M, S, Z> {
+    /// Creates a new [`BatchMutationalStage`]
+    pub fn new(mutator: M) -> Self {
+        Self::with_max_iterations(mutator, nonzero!(DEFAULT_MUTATIONAL_MAX_ITERATIONS))
+    }
+
+    /// Creates a new [`BatchMutationalStage`] with the given max iterations
+    pub fn with_max_iterations(mutator: M, max_iterations: NonZeroUsize) -> Self {
+        // unsafe but impossible that you create two threads both instantiating this instance
+        let stage_id = unsafe {
+            let ret = BATCH_MUTATIONAL_STAGE_ID;
+            BATCH_MUTATIONAL_STAGE_ID += 1;
+            ret
+        };
+        Self {
+            name: Cow::Owned(
+                BATCH_MUTATIONAL_STAGE_NAME.to_owned() + ":" + stage_id.to_string().as_str(),
+            ),
+            mutator,
+            max_iterations,
+            phantom: PhantomData,
+        }
+    }
+}
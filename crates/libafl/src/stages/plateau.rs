@@ -0,0 +1,483 @@
+//! The [`PlateauStage`] detects coverage plateaus and switches the fuzzing strategy using [`PlateauAction`]s.
+//!
+//! A plateau is reached when the [`MapFeedback`] has not found any new map entries for a number of executions, or
+//! for some time. Every further window without progress escalates the plateau to the next level, and once new
+//! coverage is found again, the actions are told to return to normal.
+//!
+//! Actions that should only run while in a plateau, for example a `ColorizationStage` and `RedQueen`, can be wrapped
+//! in an [`crate::stages::IfStage`] checking [`plateau_level`].
+
+use alloc::borrow::{Cow, ToOwned};
+use core::{fmt::Debug, marker::PhantomData, time::Duration};
+
+use libafl_bolts::{Named, current_time, impl_serdeany};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::{
+    Error, HasMetadata, HasNamedMetadata,
+    events::{Event, EventFirer, EventWithStats},
+    feedbacks::{MapFeedback, map::MapFeedbackMetadata},
+    monitors::stats::{AggregatorOps, UserStats, UserStatsValue},
+    mutators::TuneableScheduledMutatorMetadata,
+    observers::MapObserver,
+    stages::{Restartable, Stage},
+    state::HasExecutions,
+};
+
+/// The default name for the plateau stage
+pub static PLATEAU_STAGE_NAME: &str = "plateau";
+
+/// The progress tracked by a [`PlateauStage`]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    expect(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+#[derive(Default, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlateauMetadata {
+    /// The number of covered map entries at the last progress
+    pub covered: usize,
+    /// The executions at the last progress or escalation
+    pub executions: u64,
+    /// The time of the last progress or escalation
+    pub time: Duration,
+    /// The current plateau level, `0` if not in a plateau
+    pub level: usize,
+}
+
+impl_serdeany!(PlateauMetadata);
+
+/// Returns the current level of the plateau detected by the [`PlateauStage`] with the given name,
+/// `0` if the fuzzer is making progress.
+pub fn plateau_level<S>(state: &S, name: &str) -> usize
+where
+    S: HasNamedMetadata,
+{
+    state
+        .named_metadata::<PlateauMetadata>(name)
+        .map_or(0, |meta| meta.level)
+}
+
+/// An action switching the behavior of the fuzzer when a plateau is reached
+pub trait PlateauAction<E, EM, S, Z> {
+    /// Called when the plateau reaches the given level, starting at `1`
+    fn on_plateau(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+        level: usize,
+    ) -> Result<(), Error>;
+
+    /// Called when the fuzzer makes progress again after a plateau
+    fn on_progress(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+    ) -> Result<(), Error>;
+}
+
+impl<E, EM, S, Z> PlateauAction<E, EM, S, Z> for () {
+    fn on_plateau(
+        &mut self,
+        _fuzzer: &mut Z,
+        _executor: &mut E,
+        _state: &mut S,
+        _manager: &mut EM,
+        _level: usize,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn on_progress(
+        &mut self,
+        _fuzzer: &mut Z,
+        _executor: &mut E,
+        _state: &mut S,
+        _manager: &mut EM,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<Head, Tail, E, EM, S, Z> PlateauAction<E, EM, S, Z> for (Head, Tail)
+where
+    Head: PlateauAction<E, EM, S, Z>,
+    Tail: PlateauAction<E, EM, S, Z>,
+{
+    fn on_plateau(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+        level: usize,
+    ) -> Result<(), Error> {
+        self.0.on_plateau(fuzzer, executor, state, manager, level)?;
+        self.1.on_plateau(fuzzer, executor, state, manager, level)
+    }
+
+    fn on_progress(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        self.0.on_progress(fuzzer, executor, state, manager)?;
+        self.1.on_progress(fuzzer, executor, state, manager)
+    }
+}
+
+/// A [`PlateauAction`] raising the mutation stack depth of a [`crate::mutators::TuneableScheduledMutator`]
+///
+/// The depth doubles with every plateau level, up to a maximum, and is reset when the fuzzer makes progress again.
+#[derive(Debug, Clone, Copy)]
+pub struct MutatorStackingAction {
+    normal_iters: Option<u64>,
+    base_iters: u64,
+    max_iters: u64,
+}
+
+impl MutatorStackingAction {
+    /// Creates a new [`MutatorStackingAction`].
+    ///
+    /// In a plateau of level `n`, `base_iters * 2^(n-1)` mutations are stacked, at most `max_iters`.
+    /// Once the fuzzer makes progress again, the iterations are set back to `normal_iters`
+    /// (`None` for the randomly chosen default).
+    #[must_use]
+    pub fn new(normal_iters: Option<u64>, base_iters: u64, max_iters: u64) -> Self {
+        Self {
+            normal_iters,
+            base_iters,
+            max_iters,
+        }
+    }
+}
+
+impl<E, EM, S, Z> PlateauAction<E, EM, S, Z> for MutatorStackingAction
+where
+    S: HasMetadata,
+{
+    fn on_plateau(
+        &mut self,
+        _fuzzer: &mut Z,
+        _executor: &mut E,
+        state: &mut S,
+        _manager: &mut EM,
+        level: usize,
+    ) -> Result<(), Error> {
+        let shift = u32::try_from(level.saturating_sub(1)).unwrap_or(u32::MAX);
+        let iters = self
+            .base_iters
+            .checked_shl(shift)
+            .unwrap_or(u64::MAX)
+            .min(self.max_iters);
+        TuneableScheduledMutatorMetadata::get_mut(state)?.iters = Some(iters);
+        Ok(())
+    }
+
+    fn on_progress(
+        &mut self,
+        _fuzzer: &mut Z,
+        _executor: &mut E,
+        state: &mut S,
+        _manager: &mut EM,
+    ) -> Result<(), Error> {
+        TuneableScheduledMutatorMetadata::get_mut(state)?.iters = self.normal_iters;
+        Ok(())
+    }
+}
+
+/// A [`PlateauAction`] calling closures, for example to switch the `TuneableScheduler` or re-weight the corpus
+#[derive(Debug, Clone)]
+pub struct ClosurePlateauAction<CB1, CB2> {
+    on_plateau: CB1,
+    on_progress: CB2,
+}
+
+impl<CB1, CB2> ClosurePlateauAction<CB1, CB2> {
+    /// Creates a new [`ClosurePlateauAction`] from the closures called on a plateau, and on progress after it
+    pub fn new(on_plateau: CB1, on_progress: CB2) -> Self {
+        Self {
+            on_plateau,
+            on_progress,
+        }
+    }
+}
+
+impl<CB1, CB2, E, EM, S, Z> PlateauAction<E, EM, S, Z> for ClosurePlateauAction<CB1, CB2>
+where
+    CB1: FnMut(&mut Z, &mut E, &mut S, &mut EM, usize) -> Result<(), Error>,
+    CB2: FnMut(&mut Z, &mut E, &mut S, &mut EM) -> Result<(), Error>,
+{
+    fn on_plateau(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+        level: usize,
+    ) -> Result<(), Error> {
+        (self.on_plateau)(fuzzer, executor, state, manager, level)
+    }
+
+    fn on_progress(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        (self.on_progress)(fuzzer, executor, state, manager)
+    }
+}
+
+/// A stage detecting coverage plateaus of a [`MapFeedback`], running its [`PlateauAction`]s when a plateau is
+/// reached or escalated, and when the fuzzer makes progress again.
+///
+/// The current level is also reported to the monitors as the `plateau` user stat.
+#[derive(Debug)]
+pub struct PlateauStage<A, E, EM, I, S, T, Z> {
+    name: Cow<'static, str>,
+    map_name: Cow<'static, str>,
+    max_executions: u64,
+    max_time: Duration,
+    actions: A,
+    phantom: PhantomData<(E, EM, I, S, T, Z)>,
+}
+
+impl<A, E, EM, I, S, T, Z> Named for PlateauStage<A, E, EM, I, S, T, Z> {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<A, E, EM, I, S, T, Z> Restartable<S> for PlateauStage<A, E, EM, I, S, T, Z> {
+    #[inline]
+    fn should_restart(&mut self, _state: &mut S) -> Result<bool, Error> {
+        // Not executing the target, so restart safety is not needed
+        Ok(true)
+    }
+
+    #[inline]
+    fn clear_progress(&mut self, _state: &mut S) -> Result<(), Error> {
+        // Not executing the target, so restart safety is not needed
+        Ok(())
+    }
+}
+
+impl<A, E, EM, I, S, T, Z> Stage<E, EM, S, Z> for PlateauStage<A, E, EM, I, S, T, Z>
+where
+    A: PlateauAction<E, EM, S, Z>,
+    EM: EventFirer<I, S>,
+    S: HasNamedMetadata + HasExecutions,
+    T: 'static + Debug + Serialize + DeserializeOwned,
+{
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        let covered = state
+            .named_metadata::<MapFeedbackMetadata<T>>(&self.map_name)?
+            .num_covered_map_indexes;
+        let executions = *state.executions();
+        let now = current_time();
+        let mut meta = *state.named_metadata_or_insert_with(&self.name, || PlateauMetadata {
+            covered,
+            executions,
+            time: now,
+            level: 0,
+        });
+
+        let previous_level = meta.level;
+        if covered != meta.covered {
+            meta = PlateauMetadata {
+                covered,
+                executions,
+                time: now,
+                level: 0,
+            };
+        } else if executions.saturating_sub(meta.executions) >= self.max_executions
+            || now.saturating_sub(meta.time) >= self.max_time
+        {
+            // the next window without progress escalates the plateau further
+            meta.executions = executions;
+            meta.time = now;
+            meta.level += 1;
+        }
+        *state.named_metadata_mut::<PlateauMetadata>(&self.name)? = meta;
+
+        if meta.level == previous_level {
+            return Ok(());
+        }
+        if meta.level == 0 {
+            log::info!("{}: new coverage, leaving the plateau", self.name);
+            self.actions.on_progress(fuzzer, executor, state, manager)?;
+        } else {
+            log::info!(
+                "{}: no new coverage of {} entries, plateau level {}",
+                self.name,
+                covered,
+                meta.level
+            );
+            self.actions
+                .on_plateau(fuzzer, executor, state, manager, meta.level)?;
+        }
+
+        manager.fire(
+            state,
+            EventWithStats::with_current_time(
+                Event::UpdateUserStats {
+                    name: Cow::from("plateau"),
+                    value: UserStats::new(
+                        UserStatsValue::Number(meta.level as u64),
+                        AggregatorOps::Max,
+                    ),
+                    phantom: PhantomData,
+                },
+                executions,
+            ),
+        )
+    }
+}
+
+impl<A, E, EM, I, S, T, Z> PlateauStage<A, E, EM, I, S, T, Z> {
+    /// Creates a new [`PlateauStage`] for the given map feedback.
+    ///
+    /// A plateau is reached after `max_executions` executions or `max_time` without new map entries; pass
+    /// [`u64::MAX`] or [`Duration::MAX`] to only use the other condition.
+    pub fn new<C, N, O, R>(
+        map_feedback: &MapFeedback<C, N, O, R>,
+        max_executions: u64,
+        max_time: Duration,
+        actions: A,
+    ) -> Self
+    where
+        O: MapObserver<Entry = T>,
+    {
+        let map_name = map_feedback.name().clone();
+        Self {
+            name: Cow::Owned(PLATEAU_STAGE_NAME.to_owned() + ":" + map_name.as_ref()),
+            map_name,
+            max_executions,
+            max_time,
+            actions,
+            phantom: PhantomData,
+        }
+    }
+
+    /// The current plateau level, `0` if the fuzzer is making progress
+    pub fn level(&self, state: &S) -> usize
+    where
+        S: HasNamedMetadata,
+    {
+        plateau_level(state, &self.name)
+    }
+
+    /// The actions run on plateaus
+    pub fn actions(&self) -> &A {
+        &self.actions
+    }
+
+    /// The actions run on plateaus, mutable
+    pub fn actions_mut(&mut self) -> &mut A {
+        &mut self.actions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use libafl_bolts::{rands::StdRand, tuples::tuple_list};
+
+    use super::{MutatorStackingAction, PlateauStage, plateau_level};
+    use crate::{
+        HasMetadata, HasNamedMetadata,
+        corpus::InMemoryCorpus,
+        events::NopEventManager,
+        feedbacks::{MaxMapFeedback, map::MapFeedbackMetadata},
+        inputs::BytesInput,
+        mutators::TuneableScheduledMutatorMetadata,
+        observers::StdMapObserver,
+        stages::Stage,
+        state::{HasExecutions, StdState},
+    };
+
+    #[test]
+    fn test_plateau_stage() {
+        let mut map = [0_u8; 16];
+        let observer = unsafe { StdMapObserver::new("edges", &mut map) };
+        let feedback = MaxMapFeedback::new(&observer);
+
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut (),
+        )
+        .unwrap();
+        state.add_named_metadata("edges", MapFeedbackMetadata::<u8>::new(16));
+        state.add_metadata(TuneableScheduledMutatorMetadata::default());
+        let mut mgr = NopEventManager::new();
+
+        let mut stage: PlateauStage<_, (), _, BytesInput, _, _, ()> = PlateauStage::new(
+            &feedback,
+            100,
+            Duration::MAX,
+            tuple_list!(MutatorStackingAction::new(None, 4, 10)),
+        );
+        let mut perform = |state: &mut _, stage: &mut PlateauStage<_, _, _, _, _, _, _>| {
+            stage.perform(&mut (), &mut (), state, &mut mgr).unwrap();
+        };
+
+        perform(&mut state, &mut stage);
+        assert_eq!(stage.level(&state), 0);
+
+        *state.executions_mut() += 100;
+        perform(&mut state, &mut stage);
+        assert_eq!(plateau_level(&state, stage.name.as_ref()), 1);
+        assert_eq!(
+            TuneableScheduledMutatorMetadata::get(&state).unwrap().iters,
+            Some(4)
+        );
+
+        *state.executions_mut() += 50;
+        perform(&mut state, &mut stage);
+        assert_eq!(stage.level(&state), 1);
+
+        *state.executions_mut() += 50;
+        perform(&mut state, &mut stage);
+        assert_eq!(stage.level(&state), 2);
+        assert_eq!(
+            TuneableScheduledMutatorMetadata::get(&state).unwrap().iters,
+            Some(8)
+        );
+
+        *state.executions_mut() += 100;
+        perform(&mut state, &mut stage);
+        assert_eq!(
+            TuneableScheduledMutatorMetadata::get(&state).unwrap().iters,
+            Some(10)
+        );
+
+        state
+            .named_metadata_map_mut()
+            .get_mut::<MapFeedbackMetadata<u8>>("edges")
+            .unwrap()
+            .num_covered_map_indexes = 1;
+        perform(&mut state, &mut stage);
+        assert_eq!(stage.level(&state), 0);
+        assert_eq!(
+            TuneableScheduledMutatorMetadata::get(&state).unwrap().iters,
+            None
+        );
+    }
+}
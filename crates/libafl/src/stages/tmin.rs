@@ -27,7 +27,7 @@ use crate::{
     inputs::Input,
     mark_feature_time,
     mutators::{MutationResult, Mutator},
-    observers::ObserversTuple,
+    observers::{ObserverWithHashField, ObserversTuple},
     schedulers::RemovableScheduler,
     stages::{
         ExecutionCountRestartHelper, Restartable, Stage,
@@ -442,3 +442,107 @@ where
         }
     }
 }
+
+/// A feedback which checks if the hash reported by an [`ObserverWithHashField`], such as the
+/// [`crate::observers::BacktraceObserver`], is equal to the original one
+#[derive(Debug, Clone)]
+pub struct ObserverHashEqualityFeedback<O> {
+    name: Cow<'static, str>,
+    observer_handle: Handle<O>,
+    orig_hash: Option<u64>,
+    #[cfg(feature = "track_hit_feedbacks")]
+    // The previous run's result of `Self::is_interesting`
+    last_result: Option<bool>,
+}
+
+impl<O> Named for ObserverHashEqualityFeedback<O> {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<O> HasObserverHandle for ObserverHashEqualityFeedback<O> {
+    type Observer = O;
+
+    fn observer_handle(&self) -> &Handle<Self::Observer> {
+        &self.observer_handle
+    }
+}
+
+impl<O, S> StateInitializer<S> for ObserverHashEqualityFeedback<O> {}
+
+impl<EM, I, O, OT, S> Feedback<EM, I, OT, S> for ObserverHashEqualityFeedback<O>
+where
+    O: ObserverWithHashField,
+    OT: MatchName,
+{
+    fn is_interesting(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        let obs = observers
+            .get(self.observer_handle())
+            .expect("Should have been provided valid observer name.");
+        // no hash (e.g. no crash) never preserves the original behaviour
+        let res = self.orig_hash.is_some() && obs.hash() == self.orig_hash;
+        #[cfg(feature = "track_hit_feedbacks")]
+        {
+            self.last_result = Some(res);
+        }
+        Ok(res)
+    }
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        self.last_result.ok_or(premature_last_result_err())
+    }
+}
+
+/// A feedback factory for ensuring that the hash of an [`ObserverWithHashField`], such as the crash signature
+/// of a [`crate::observers::BacktraceObserver`], is the same for minimized or cleansed inputs
+#[derive(Debug, Clone)]
+pub struct ObserverHashEqualityFactory<O> {
+    observer_handle: Handle<O>,
+}
+
+impl<O> ObserverHashEqualityFactory<O>
+where
+    O: ObserverWithHashField + Handled,
+{
+    /// Creates a new observer hash equality feedback for the given observer
+    pub fn new(obs: &O) -> Self {
+        Self {
+            observer_handle: obs.handle(),
+        }
+    }
+}
+
+impl<O> HasObserverHandle for ObserverHashEqualityFactory<O> {
+    type Observer = O;
+
+    fn observer_handle(&self) -> &Handle<O> {
+        &self.observer_handle
+    }
+}
+
+impl<O, OT> FeedbackFactory<ObserverHashEqualityFeedback<O>, OT> for ObserverHashEqualityFactory<O>
+where
+    O: ObserverWithHashField,
+    OT: MatchName,
+{
+    fn create_feedback(&self, observers: &OT) -> ObserverHashEqualityFeedback<O> {
+        let obs = observers
+            .get(self.observer_handle())
+            .expect("Should have been provided valid observer name.");
+        ObserverHashEqualityFeedback {
+            name: Cow::from("ObserverHashEq"),
+            observer_handle: self.observer_handle.clone(),
+            orig_hash: obs.hash(),
+            #[cfg(feature = "track_hit_feedbacks")]
+            last_result: None,
+        }
+    }
+}
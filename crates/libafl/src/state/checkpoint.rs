@@ -0,0 +1,143 @@
+//! Campaign checkpoints, to stop a fuzzing campaign and resume it later, with all of its state intact.
+//!
+//! In contrast to restarts through the [`libafl_bolts::staterestore::StateRestorer`], which only survive
+//! within a single restart chain in shared memory, a checkpoint is a plain file.
+//! It contains the whole [`StdState`]: the corpus, including the [`crate::corpus::SchedulerTestcaseMetadata`]
+//! of each [`crate::corpus::Testcase`], the solutions, and all metadata of the state, such as the
+//! [`crate::schedulers::SchedulerMetadata`], [`crate::schedulers::minimizer::TopRatedsMetadata`],
+//! [`crate::schedulers::weighted::WeightedScheduleMetadata`], [`crate::mutators::Tokens`],
+//! or the [`crate::mutators::MOpt`] swarm state.
+//! The inputs of on-disk corpora are not part of the checkpoint, they need to stay where they are.
+//!
+//! Checkpoints are written periodically by the [`crate::stages::CheckpointStage`],
+//! and loaded with [`HasCheckpoint::from_checkpoint`] or [`HasCheckpoint::from_checkpoint_or_else`].
+
+use alloc::{format, vec::Vec};
+use std::{fs, path::Path};
+
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::{Error, state::StdState};
+
+/// The magic bytes at the start of each checkpoint
+const CHECKPOINT_MAGIC: [u8; 8] = *b"LIBAFLCP";
+
+/// The version of the checkpoint format, increased on incompatible changes
+pub const CHECKPOINT_VERSION: u32 = 1;
+
+/// A state that can be written to, and restored from, a checkpoint file
+pub trait HasCheckpoint: Sized {
+    /// Writes a checkpoint of this state to `path`, atomically replacing any previous checkpoint
+    fn write_checkpoint(&self, path: &Path) -> Result<(), Error>;
+
+    /// Restores the state from the checkpoint at `path`
+    fn from_checkpoint(path: &Path) -> Result<Self, Error>;
+
+    /// Restores the state from the checkpoint at `path`, if it exists; else, creates a new state with `create`
+    fn from_checkpoint_or_else<F>(path: &Path, create: F) -> Result<Self, Error>
+    where
+        F: FnOnce() -> Result<Self, Error>,
+    {
+        if path.exists() {
+            log::info!("Resuming from checkpoint {}", path.display());
+            Self::from_checkpoint(path)
+        } else {
+            create()
+        }
+    }
+}
+
+impl<C, I, R, SC> HasCheckpoint for StdState<C, I, R, SC>
+where
+    C: Serialize + DeserializeOwned,
+    R: Serialize + DeserializeOwned,
+    SC: Serialize + DeserializeOwned,
+{
+    fn write_checkpoint(&self, path: &Path) -> Result<(), Error> {
+        let mut serialized = Vec::from(CHECKPOINT_MAGIC);
+        serialized.extend_from_slice(&CHECKPOINT_VERSION.to_le_bytes());
+        serialized.extend(postcard::to_allocvec(self)?);
+
+        // Never leave a half-written checkpoint behind, in case we get killed
+        let Some(file_name) = path.file_name() else {
+            return Err(Error::illegal_argument(format!(
+                "Invalid checkpoint path {}",
+                path.display()
+            )));
+        };
+        let tmpfile_path = path.with_file_name(format!(".{}.tmp", file_name.to_string_lossy()));
+        fs::write(&tmpfile_path, serialized)?;
+        fs::rename(&tmpfile_path, path)?;
+        Ok(())
+    }
+
+    fn from_checkpoint(path: &Path) -> Result<Self, Error> {
+        let serialized = fs::read(path)?;
+        let Some((version, state)) = serialized
+            .strip_prefix(&CHECKPOINT_MAGIC)
+            .and_then(|rest| rest.split_first_chunk::<4>())
+        else {
+            return Err(Error::illegal_argument(format!(
+                "{} is not a checkpoint",
+                path.display()
+            )));
+        };
+        let version = u32::from_le_bytes(*version);
+        if version != CHECKPOINT_VERSION {
+            return Err(Error::illegal_argument(format!(
+                "Checkpoint {} has version {version}, expected {CHECKPOINT_VERSION}",
+                path.display()
+            )));
+        }
+        Ok(postcard::from_bytes(state)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use libafl_bolts::rands::StdRand;
+
+    use super::HasCheckpoint;
+    use crate::{
+        HasMetadata,
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        inputs::NopInput,
+        schedulers::{SchedulerMetadata, powersched::PowerSchedule},
+        state::{HasCorpus, StdState},
+    };
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_checkpoint_roundtrip() {
+        let path = env::temp_dir().join(format!("libafl_test_checkpoint_{}", process::id()));
+        drop(fs::remove_file(&path));
+
+        let mut state = StdState::nop().unwrap();
+        state.add_metadata(SchedulerMetadata::new(Some(PowerSchedule::fast())));
+        state
+            .metadata_mut::<SchedulerMetadata>()
+            .unwrap()
+            .set_bitmap_size(1337);
+        state.corpus_mut().add(Testcase::new(NopInput {})).unwrap();
+        state.write_checkpoint(&path).unwrap();
+
+        let restored: StdState<
+            InMemoryCorpus<NopInput>,
+            NopInput,
+            StdRand,
+            InMemoryCorpus<NopInput>,
+        > = StdState::from_checkpoint(&path).unwrap();
+        assert_eq!(restored.corpus().count(), 1);
+        assert_eq!(
+            restored
+                .metadata::<SchedulerMetadata>()
+                .unwrap()
+                .bitmap_size(),
+            1337
+        );
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -26,6 +26,11 @@ use serde::{Deserialize, Serialize, de::DeserializeOwned};
 mod stack;
 pub use stack::StageStack;
 
+#[cfg(feature = "std")]
+pub mod checkpoint;
+#[cfg(feature = "std")]
+pub use checkpoint::HasCheckpoint;
+
 #[cfg(feature = "std")]
 use crate::fuzzer::ExecuteInputResult;
 #[cfg(feature = "introspection")]
@@ -3,7 +3,7 @@
 use alloc::{borrow::ToOwned, vec::Vec};
 use std::{
     ffi::{OsStr, OsString},
-    path::Path,
+    path::{Path, PathBuf},
 };
 
 use crate::fs::{InputFile, get_unique_std_input_file};
@@ -11,6 +11,7 @@ use crate::fs::{InputFile, get_unique_std_input_file};
 /// How to deliver input to an external program
 /// `StdIn`: The target reads from stdin
 /// `File`: The target reads from the specified [`InputFile`]
+/// `Preload`: A preload library serves the input when the target reads from a fixed path or fd
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum InputLocation {
     /// Mutate a commandline argument to deliver an input
@@ -29,6 +30,29 @@ pub enum InputLocation {
         /// The file to write input to. The target should read input from this location.
         out_file: InputFile,
     },
+    /// Deliver the input in shared memory to the `LD_PRELOAD` input library of `libafl_targets`,
+    /// which hands it to the uninstrumented target whenever it reads from the given [`PreloadSource`].
+    ///
+    /// Useful for third-party binaries that only read from fixed filenames or sockets.
+    Preload {
+        /// Where the target reads its input from
+        source: PreloadSource,
+        /// The path of the preload library, see `libafl_targets::INPUT_PRELOAD_LIBRARY`
+        library: PathBuf,
+    },
+}
+
+/// The reads the [`InputLocation::Preload`] library redirects to the input
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PreloadSource {
+    /// Opening this path (as passed to `open`, `openat` or `fopen`) yields a file holding the input.
+    /// The file does not need to exist.
+    Path(PathBuf),
+    /// Reading from this file descriptor (with `read`, `recv` or `recvfrom`) yields the input.
+    /// If the fd is already open when the target starts, such as stdin, it is replaced by a file holding the input.
+    /// Otherwise, it yields the input once the target creates it with `socket`, `accept` or `accept4`, until it is
+    /// closed again. Other files reusing the fd number, or fds created with `dup2`, read their own contents.
+    Fd(i32),
 }
 
 impl Default for InputLocation {
@@ -128,7 +152,7 @@ pub trait StdTargetArgs: Sized {
                 InputLocation::StdIn { input_file } => input_file
                     .as_ref()
                     .is_none_or(|of| of.path.as_path() == path.as_ref()),
-                InputLocation::Arg { argnum: _ } => false,
+                InputLocation::Arg { argnum: _ } | InputLocation::Preload { .. } => false,
             },
             "Already specified an input file under a different name. This is not supported"
         );
@@ -137,6 +161,16 @@ pub trait StdTargetArgs: Sized {
         moved
     }
 
+    /// Serve the input with the `LD_PRELOAD` input `library` of `libafl_targets`,
+    /// whenever the target reads from `source`. See [`InputLocation::Preload`].
+    #[must_use]
+    fn preload_input<P: AsRef<Path>>(self, library: P, source: PreloadSource) -> Self {
+        self.input(InputLocation::Preload {
+            source,
+            library: library.as_ref().to_path_buf(),
+        })
+    }
+
     /// Place the input at this position and set the default filename for the input.
     #[must_use]
     /// The filename includes the PID of the fuzzer to ensure that no two fuzzers write to the same file
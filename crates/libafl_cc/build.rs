@@ -8,6 +8,7 @@ use core::str;
     feature = "cmplog-instructions",
     feature = "ctx",
     feature = "dump-cfg",
+    feature = "directed",
 ))]
 use std::path::PathBuf;
 use std::{env, fs::File, io::Write, path::Path, process::Command};
@@ -33,6 +34,7 @@ const LLVM_VERSION_MIN: u32 = 15;
     feature = "cmplog-instructions",
     feature = "ctx",
     feature = "dump-cfg",
+    feature = "directed",
 ))]
 fn dll_extension<'a>() -> &'a str {
     if let Ok(vendor) = env::var("CARGO_CFG_TARGET_VENDOR") {
@@ -165,6 +167,7 @@ fn find_llvm_version() -> Option<i32> {
     feature = "cmplog-instructions",
     feature = "ctx",
     feature = "dump-cfg",
+    feature = "directed",
 ))]
 #[expect(clippy::too_many_arguments)]
 fn build_pass(
@@ -548,6 +551,18 @@ pub const LIBAFL_CC_LLVM_VERSION: Option<usize> = None;
         false,
     );
 
+    #[cfg(feature = "directed")]
+    build_pass(
+        bindir_path,
+        out_dir,
+        &cxxflags,
+        &ldflags,
+        src_dir,
+        "directed-distance-pass.cc",
+        None,
+        false,
+    );
+
     cc::Build::new()
         .file(src_dir.join("no-link-rt.c"))
         .compile("no-link-rt");
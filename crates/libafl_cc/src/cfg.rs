@@ -280,6 +280,19 @@ where
         self.edges[xored_loc].as_mut()
     }
 
+    /// Iterate over the indexes of the coverage map of all known edges, with the name of the function containing them.
+    ///
+    /// Use this to attribute the coverage of a map observer to functions, for example in coverage reports.
+    pub fn edge_functions(&self) -> impl Iterator<Item = (usize, &str)> {
+        self.edges
+            .iter()
+            .enumerate()
+            .filter_map(|(xored_loc, edge)| {
+                edge.as_ref()
+                    .map(|edge| (xored_loc, edge.calling_func.as_str()))
+            })
+    }
+
     /// Get entry basic block information of a function.
     #[must_use]
     pub fn get_entry(&self, func_name: &str) -> Option<&EntryBasicBlockInfo> {
@@ -388,6 +401,10 @@ mod tests {
 
         assert!(cfg.get_edge(26911).is_none());
         assert!(cfg.get_edge(41864).is_some());
+
+        let functions = cfg.edge_functions().collect::<Vec<_>>();
+        assert_eq!(functions.len(), 7);
+        assert!(functions.contains(&((50306 >> 1) ^ 19123, "_ZN7MyClass1VEi")));
     }
 
     #[test]
@@ -39,6 +39,9 @@ pub enum LLVMPasses {
     Ctx,
     /// Function logging
     FunctionLogging,
+    /// Instrument basic blocks with their distance to the targets in `LIBAFL_DIRECTED_TARGETS`.
+    /// Only built with the `directed` feature.
+    DirectedDistance,
 }
 
 impl LLVMPasses {
@@ -65,6 +68,8 @@ impl LLVMPasses {
             LLVMPasses::FunctionLogging => {
                 PathBuf::from(env!("OUT_DIR")).join(format!("function-logging.{}", dll_extension()))
             }
+            LLVMPasses::DirectedDistance => PathBuf::from(env!("OUT_DIR"))
+                .join(format!("directed-distance-pass.{}", dll_extension())),
         }
     }
 }
@@ -0,0 +1,276 @@
+/*
+   LibAFL - Directed Distance LLVM pass
+   --------------------------------------------------
+
+   Computes, for every basic block, the distance to a set of target locations
+   in the spirit of AFLGo, and instruments the block to accumulate this
+   distance at runtime (see `libafl_targets::directed`).
+
+   Targets are read from the file given in the LIBAFL_DIRECTED_TARGETS
+   environment variable, one per line, either as `file:line` or as a function
+   name. Empty lines and lines starting with `#` are ignored.
+
+   The call graph is only known within the current module, so the pass should
+   be used with LTO (or on single-module targets) for best results.
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at:
+
+     http://www.apache.org/licenses/LICENSE-2.0
+
+*/
+
+#include <stdio.h>
+#include <stdlib.h>
+#include "common-llvm.h"
+#ifndef _WIN32
+  #include <unistd.h>
+  #include <sys/time.h>
+#else
+  #include <io.h>
+#endif
+#include <string.h>
+#include <sys/types.h>
+#include <sys/stat.h>
+#include <fcntl.h>
+#include <ctype.h>
+
+#include <deque>
+#include <fstream>
+#include <map>
+#include <set>
+#include <string>
+#include <utility>
+#include <vector>
+
+#include "llvm/IR/DebugInfo.h"
+#include "llvm/IR/DebugLoc.h"
+
+using namespace llvm;
+
+/* Distances are stored as fixed point numbers with this scale */
+#define DISTANCE_SCALE 1000
+/* The cost of following a call, relative to a CFG edge (as in AFLGo) */
+#define CALL_COST 10
+
+namespace {
+
+class DirectedDistancePass : public PassInfoMixin<DirectedDistancePass> {
+ public:
+  DirectedDistancePass() {
+  }
+
+  PreservedAnalyses run(Module &M, ModuleAnalysisManager &MAM);
+
+ protected:
+  /* file name (without directories) and line of target locations */
+  std::set<std::pair<std::string, unsigned>> target_lines;
+  /* target function names */
+  std::set<std::string> target_functions;
+
+ private:
+  bool loadTargets() {
+    const char *path = getenv("LIBAFL_DIRECTED_TARGETS");
+    if (!path) { return false; }
+
+    std::ifstream in(path);
+    if (!in.is_open()) {
+      FATAL("Could not open LIBAFL_DIRECTED_TARGETS file %s\n", path);
+    }
+
+    std::string line;
+    while (std::getline(in, line)) {
+      while (!line.empty() && isspace(line.back())) {
+        line.pop_back();
+      }
+      if (line.empty() || line[0] == '#') { continue; }
+
+      auto colon = line.rfind(':');
+      if (colon != std::string::npos && colon + 1 < line.size() &&
+          isdigit(line[colon + 1])) {
+        std::string file = line.substr(0, colon);
+        auto        slash = file.rfind('/');
+        if (slash != std::string::npos) { file = file.substr(slash + 1); }
+        target_lines.insert(
+            {file, (unsigned)strtoul(line.c_str() + colon + 1, NULL, 10)});
+      } else {
+        target_functions.insert(line);
+      }
+    }
+    return true;
+  }
+
+  bool isTargetBlock(BasicBlock &BB) {
+    for (auto &IN : BB) {
+      const DebugLoc &loc = IN.getDebugLoc();
+      if (!loc) { continue; }
+      auto *scope = dyn_cast<DIScope>(loc.getScope());
+      if (!scope) { continue; }
+      std::string file = scope->getFilename().str();
+      auto        slash = file.rfind('/');
+      if (slash != std::string::npos) { file = file.substr(slash + 1); }
+      if (target_lines.count({file, loc.getLine()})) { return true; }
+    }
+    return false;
+  }
+};
+
+}  // namespace
+
+extern "C" ::llvm::PassPluginLibraryInfo LLVM_ATTRIBUTE_WEAK
+llvmGetPassPluginInfo() {
+  return {LLVM_PLUGIN_API_VERSION, "DirectedDistancePass", "v0.1",
+          /* lambda to insert our pass into the pass pipeline. */
+          [](PassBuilder &PB) {
+            PB.registerOptimizerLastEPCallback(
+                [](ModulePassManager &MPM, OptimizationLevel OL
+#if LLVM_VERSION_MAJOR >= 20
+                   ,
+                   ThinOrFullLTOPhase Phase
+#endif
+
+                ) { MPM.addPass(DirectedDistancePass()); });
+          }};
+}
+
+PreservedAnalyses DirectedDistancePass::run(Module &M,
+                                            ModuleAnalysisManager &MAM) {
+  if (!loadTargets()) { return PreservedAnalyses::all(); }
+
+  LLVMContext &C = M.getContext();
+  IntegerType *Int64Ty = IntegerType::getInt64Ty(C);
+
+  /* 1. Target blocks, and the callers of each function */
+  std::set<BasicBlock *>                          target_bbs;
+  std::map<Function *, std::set<Function *>>      callers;
+  std::map<BasicBlock *, std::vector<Function *>> calls_in_bb;
+  std::map<Function *, uint64_t>                  func_distance;
+  std::deque<Function *>                          func_queue;
+
+  for (auto &F : M) {
+    if (F.isDeclaration() || isIgnoreFunction(&F)) { continue; }
+
+    if (target_functions.count(F.getName().str())) {
+      target_bbs.insert(&F.getEntryBlock());
+    }
+
+    for (auto &BB : F) {
+      if (isTargetBlock(BB)) { target_bbs.insert(&BB); }
+
+      for (auto &IN : BB) {
+        if (auto *call = dyn_cast<CallBase>(&IN)) {
+          Function *callee = call->getCalledFunction();
+          if (callee && !callee->isDeclaration()) {
+            callers[callee].insert(&F);
+            calls_in_bb[&BB].push_back(callee);
+          }
+        }
+      }
+    }
+  }
+
+  if (target_bbs.empty()) { return PreservedAnalyses::all(); }
+
+  /* 2. Function-level distances: hops in the reversed call graph */
+  for (auto *BB : target_bbs) {
+    Function *F = BB->getParent();
+    if (!func_distance.count(F)) {
+      func_distance[F] = 0;
+      func_queue.push_back(F);
+    }
+  }
+  while (!func_queue.empty()) {
+    Function *F = func_queue.front();
+    func_queue.pop_front();
+    for (auto *caller : callers[F]) {
+      if (!func_distance.count(caller)) {
+        func_distance[caller] = func_distance[F] + 1;
+        func_queue.push_back(caller);
+      }
+    }
+  }
+
+  /* 3. Block-level distances: backwards Dijkstra within each function,
+        seeded by target blocks and by blocks calling towards a target */
+  GlobalVariable *dist_sum =
+      M.getGlobalVariable("__libafl_directed_distance_sum");
+  if (!dist_sum) {
+    dist_sum = new GlobalVariable(M, Int64Ty, false,
+                                  GlobalValue::ExternalLinkage, nullptr,
+                                  "__libafl_directed_distance_sum");
+  }
+  GlobalVariable *dist_count =
+      M.getGlobalVariable("__libafl_directed_distance_count");
+  if (!dist_count) {
+    dist_count = new GlobalVariable(M, Int64Ty, false,
+                                    GlobalValue::ExternalLinkage, nullptr,
+                                    "__libafl_directed_distance_count");
+  }
+
+  uint32_t instrumented = 0;
+  for (auto &F : M) {
+    if (F.isDeclaration() || isIgnoreFunction(&F)) { continue; }
+    if (!func_distance.count(&F)) { continue; }
+
+    std::map<BasicBlock *, uint64_t>            bb_distance;
+    std::set<std::pair<uint64_t, BasicBlock *>> work;
+
+    for (auto &BB : F) {
+      uint64_t seed = UINT64_MAX;
+      if (target_bbs.count(&BB)) { seed = 0; }
+      for (auto *callee : calls_in_bb[&BB]) {
+        if (func_distance.count(callee)) {
+          uint64_t d = CALL_COST * (func_distance[callee] + 1);
+          if (d < seed) { seed = d; }
+        }
+      }
+      if (seed != UINT64_MAX) {
+        bb_distance[&BB] = seed;
+        work.insert({seed, &BB});
+      }
+    }
+
+    while (!work.empty()) {
+      auto [d, BB] = *work.begin();
+      work.erase(work.begin());
+      for (auto *pred : predecessors(BB)) {
+        auto it = bb_distance.find(pred);
+        if (it == bb_distance.end() || it->second > d + 1) {
+          if (it != bb_distance.end()) { work.erase({it->second, pred}); }
+          bb_distance[pred] = d + 1;
+          work.insert({d + 1, pred});
+        }
+      }
+    }
+
+    /* 4. Instrument: sum += distance; count += 1 */
+    for (auto &[BB, d] : bb_distance) {
+      IRBuilder<> IRB(&*BB->getFirstInsertionPt());
+
+      LoadInst *sum = IRB.CreateLoad(Int64Ty, dist_sum);
+      sum->setMetadata(M.getMDKindID("nosanitize"), MDNode::get(C, None));
+      Value *new_sum =
+          IRB.CreateAdd(sum, ConstantInt::get(Int64Ty, d * DISTANCE_SCALE));
+      IRB.CreateStore(new_sum, dist_sum)
+          ->setMetadata(M.getMDKindID("nosanitize"), MDNode::get(C, None));
+
+      LoadInst *count = IRB.CreateLoad(Int64Ty, dist_count);
+      count->setMetadata(M.getMDKindID("nosanitize"), MDNode::get(C, None));
+      Value *new_count = IRB.CreateAdd(count, ConstantInt::get(Int64Ty, 1));
+      IRB.CreateStore(new_count, dist_count)
+          ->setMetadata(M.getMDKindID("nosanitize"), MDNode::get(C, None));
+
+      instrumented++;
+    }
+  }
+
+  if (getenv("LIBAFL_DEBUG")) {
+    fprintf(stderr,
+            "DirectedDistancePass: %zu target blocks, %u blocks with distance "
+            "in %s\n",
+            target_bbs.size(), instrumented, M.getName().str().c_str());
+  }
+
+  return PreservedAnalyses::none();
+}
@@ -0,0 +1,120 @@
+use core::{ffi::c_void, ops::Range};
+
+use libafl_targets::sanitizer_cov_pc_table;
+
+/// A function of the target, with the range of its edges in the coverage map
+#[derive(Debug)]
+struct PcFunction {
+    name: String,
+    location: String,
+    edges: Range<usize>,
+}
+
+fn symbolize(addr: usize) -> (String, String) {
+    let mut name = None;
+    let mut location = None;
+    backtrace::resolve(addr as *mut c_void, |symbol| {
+        if name.is_none() {
+            name = symbol.name().map(|name| format!("{name:#}"));
+            location = symbol
+                .filename()
+                .zip(symbol.lineno())
+                .map(|(file, line)| format!("{}:{line}", file.display()));
+        }
+    });
+    (
+        name.unwrap_or_else(|| format!("{addr:#x}")),
+        location.unwrap_or_else(|| "<unknown>".to_string()),
+    )
+}
+
+/// The functions in the sanitizer coverage PC tables, in the order of the coverage map
+fn pc_functions() -> Vec<PcFunction> {
+    let mut functions = Vec::new();
+    let mut offset = 0;
+    for table in sanitizer_cov_pc_table() {
+        let entries = table
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.is_function_entry())
+            .map(|(idx, entry)| (idx, entry.addr()))
+            .collect::<Vec<_>>();
+        for (i, &(start, addr)) in entries.iter().enumerate() {
+            let end = entries.get(i + 1).map_or(table.len(), |&(end, _)| end);
+            let (name, location) = symbolize(addr);
+            functions.push(PcFunction {
+                name,
+                location,
+                edges: offset + start..offset + end,
+            });
+        }
+        offset += table.len();
+    }
+    functions
+}
+
+fn matches_function(symbol: &str, function: &str) -> bool {
+    symbol == function || symbol.split('(').next() == Some(function)
+}
+
+/// Finds the edges of the function given with `-focus_function`
+pub fn focus_function_edges(function: &str) -> Option<Range<usize>> {
+    if function == "auto" {
+        eprintln!(
+            "WARNING: -focus_function=auto requires a data flow trace, which is not supported; fuzzing without a focus function"
+        );
+        return None;
+    }
+    let focus = pc_functions()
+        .into_iter()
+        .find(|candidate| matches_function(&candidate.name, function));
+    if let Some(focus) = &focus {
+        eprintln!(
+            "INFO: focus function: {} ({}), {} edges",
+            focus.name,
+            focus.location,
+            focus.edges.len()
+        );
+    } else {
+        eprintln!(
+            "WARNING: could not find the focus function {function} in the PC tables; fuzzing without a focus function"
+        );
+    }
+    focus.map(|focus| focus.edges)
+}
+
+/// Prints the covered and uncovered functions of the target, like libFuzzer's `-print_coverage`
+pub fn print_coverage(history_map: &[u8], print: &dyn Fn(&str)) {
+    let functions = pc_functions();
+    let Some(last) = functions.last() else {
+        print("WARNING: no PC tables available; compile with -fsanitize-coverage=pc-table");
+        return;
+    };
+    if last.edges.end != history_map.len() {
+        print(&format!(
+            "WARNING: the PC tables ({} edges) do not match the coverage map ({} edges); cannot print coverage",
+            last.edges.end,
+            history_map.len()
+        ));
+        return;
+    }
+
+    print("COVERAGE:");
+    for function in &functions {
+        let covered = history_map[function.edges.clone()]
+            .iter()
+            .filter(|&&hits| hits != 0)
+            .count();
+        let kind = if covered == 0 {
+            "UNCOVERED_FUNC"
+        } else {
+            "COVERED_FUNC"
+        };
+        print(&format!(
+            "{kind}: edges: {covered}/{} {} {}",
+            function.edges.len(),
+            function.name,
+            function.location
+        ));
+    }
+}
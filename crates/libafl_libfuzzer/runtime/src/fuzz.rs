@@ -1,4 +1,5 @@
-use core::ffi::c_int;
+use core::{ffi::c_int, time::Duration};
+use std::{collections::VecDeque, path::PathBuf};
 #[cfg(unix)]
 use std::{
     fmt::Debug,
@@ -12,13 +13,17 @@ use std::{
 #[cfg(feature = "tui_monitor")]
 use libafl::monitors::tui::TuiMonitor;
 use libafl::{
-    Error, Fuzzer, HasMetadata,
+    Error, Fuzzer, HasMetadata, HasNamedMetadata,
     corpus::Corpus,
-    events::{EventReceiver, ProgressReporter, SimpleEventManager},
+    events::{EventReceiver, ProgressReporter, SendExiting, SimpleEventManager},
     executors::ExitKind,
+    feedbacks::MapFeedbackMetadata,
     monitors::MultiMonitor,
     stages::StagesTuple,
-    state::{HasCurrentStageId, HasExecutions, HasLastReportTime, HasSolutions, Stoppable},
+    state::{
+        HasCorpus, HasCurrentStageId, HasExecutions, HasLastReportTime, HasMaxSize, HasSolutions,
+        HasStartTime, Stoppable,
+    },
 };
 #[cfg(unix)]
 use libafl::{
@@ -30,8 +35,170 @@ use libafl_bolts::{
     core_affinity::Cores,
     shmem::{ShMemProvider, StdShMemProvider},
 };
+use libafl_bolts::{current_time, impl_serdeany};
+use serde::{Deserialize, Serialize};
 
-use crate::{feedbacks::LibfuzzerCrashCauseMetadata, fuzz_with, options::LibfuzzerOptions};
+use crate::{
+    coverage::print_coverage, feedbacks::LibfuzzerCrashCauseMetadata, fuzz_with,
+    options::LibfuzzerOptions,
+};
+
+/// How often the fuzz loop reports its progress to the monitor
+const STATS_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// The default `-max_len`, if the corpus does not contain larger inputs
+const DEFAULT_MAX_LEN: usize = 4096;
+
+/// The largest `-max_len` guessed from the corpus
+const MAX_SANE_LEN: usize = 1 << 20;
+
+/// The progress of the fuzz loop, kept across restarts for `-len_control` and `-print_final_stats`
+#[derive(Deserialize, Serialize, Debug)]
+struct FuzzLoopMetadata {
+    max_len: usize,
+    tmp_max_len: usize,
+    last_corpus_update_run: u64,
+    last_corpus_count: usize,
+    initial_corpus_count: usize,
+}
+
+impl_serdeany!(FuzzLoopMetadata);
+
+/// The length of the largest input in the given corpus directories
+fn max_input_len(dirs: &[PathBuf]) -> usize {
+    let mut max_len = 0;
+    let mut queue = dirs.iter().cloned().collect::<VecDeque<_>>();
+    while let Some(entry) = queue.pop_front() {
+        if entry.is_dir() {
+            if let Ok(entries) = std::fs::read_dir(entry) {
+                queue.extend(entries.filter_map(Result::ok).map(|entry| entry.path()));
+            }
+        } else if let Ok(metadata) = entry.metadata() {
+            max_len = max_len.max(usize::try_from(metadata.len()).unwrap_or(usize::MAX));
+        }
+    }
+    max_len
+}
+
+/// Integer log2, as used by libFuzzer to grow the maximum length
+fn log2(len: usize) -> usize {
+    len.checked_ilog2().unwrap_or_default() as usize
+}
+
+fn init_fuzz_loop<I, S>(options: &LibfuzzerOptions, state: &mut S)
+where
+    S: HasMetadata + HasMaxSize + HasCorpus<I>,
+{
+    if !state.has_metadata::<FuzzLoopMetadata>() {
+        let corpus_max_len = max_input_len(options.dirs());
+        let max_len = options
+            .max_len()
+            .unwrap_or_else(|| corpus_max_len.clamp(DEFAULT_MAX_LEN, MAX_SANE_LEN));
+        let tmp_max_len = if options.len_control() == 0 {
+            max_len
+        } else {
+            max_len.min(corpus_max_len.max(4))
+        };
+        let corpus_count = state.corpus().count();
+        state.add_metadata(FuzzLoopMetadata {
+            max_len,
+            tmp_max_len,
+            last_corpus_update_run: 0,
+            last_corpus_count: corpus_count,
+            initial_corpus_count: corpus_count,
+        });
+    }
+    let tmp_max_len = state.metadata::<FuzzLoopMetadata>().unwrap().tmp_max_len;
+    state.set_max_size(tmp_max_len);
+}
+
+/// Grows the maximum length of the mutated inputs as in libFuzzer's `-len_control`
+fn update_len_control<I, S>(options: &LibfuzzerOptions, state: &mut S)
+where
+    S: HasMetadata + HasMaxSize + HasCorpus<I> + HasExecutions,
+{
+    let executions = *state.executions();
+    let corpus_count = state.corpus().count();
+    let meta = state.metadata_mut::<FuzzLoopMetadata>().unwrap();
+    if corpus_count != meta.last_corpus_count {
+        meta.last_corpus_count = corpus_count;
+        meta.last_corpus_update_run = executions;
+    }
+    if options.len_control() != 0
+        && meta.tmp_max_len < meta.max_len
+        && executions - meta.last_corpus_update_run
+            > (options.len_control() * log2(meta.tmp_max_len)) as u64
+    {
+        meta.tmp_max_len = meta
+            .max_len
+            .min(meta.tmp_max_len + log2(meta.tmp_max_len).max(1));
+        meta.last_corpus_update_run = executions;
+        let tmp_max_len = meta.tmp_max_len;
+        state.set_max_size(tmp_max_len);
+    }
+}
+
+/// The peak resident set size of this process, in MiB
+#[cfg(unix)]
+fn peak_rss_mb() -> u64 {
+    let mut usage = core::mem::MaybeUninit::<libc::rusage>::zeroed();
+    // # Safety
+    // `getrusage` only writes to the given struct.
+    let usage = unsafe {
+        libc::getrusage(libc::RUSAGE_SELF, usage.as_mut_ptr());
+        usage.assume_init()
+    };
+    let max_rss = u64::try_from(usage.ru_maxrss).unwrap_or_default();
+    // Linux reports KiB, Apple platforms report bytes
+    if cfg!(target_vendor = "apple") {
+        max_rss >> 20
+    } else {
+        max_rss >> 10
+    }
+}
+
+fn print_final_stats<I, S>(state: &S)
+where
+    S: HasMetadata + HasExecutions + HasStartTime + HasCorpus<I>,
+{
+    let print = create_monitor_closure();
+    let executions = *state.executions();
+    let elapsed = current_time()
+        .saturating_sub(*state.start_time())
+        .as_secs()
+        .max(1);
+    let new_units = state.metadata::<FuzzLoopMetadata>().map_or(0, |meta| {
+        state
+            .corpus()
+            .count()
+            .saturating_sub(meta.initial_corpus_count)
+    });
+    print(&format!("stat::number_of_executed_units: {executions}"));
+    print(&format!(
+        "stat::average_exec_per_sec:     {}",
+        executions / elapsed
+    ));
+    print(&format!("stat::new_units_added:          {new_units}"));
+    #[cfg(unix)]
+    print(&format!(
+        "stat::peak_rss_mb:              {}",
+        peak_rss_mb()
+    ));
+}
+
+fn finish_fuzzing<I, S>(options: &LibfuzzerOptions, state: &S)
+where
+    S: HasMetadata + HasNamedMetadata + HasExecutions + HasStartTime + HasCorpus<I>,
+{
+    if options.print_final_stats() {
+        print_final_stats(state);
+    }
+    if options.print_coverage()
+        && let Ok(meta) = state.named_metadata::<MapFeedbackMetadata<u8>>("edges")
+    {
+        print_coverage(&meta.history_map, &create_monitor_closure());
+    }
+}
 
 #[cfg(unix)]
 fn destroy_output_fds(options: &LibfuzzerOptions) {
@@ -83,12 +250,16 @@ fn do_fuzz<F, ST, E, I, S, EM>(
 where
     F: Fuzzer<E, EM, I, S, ST>,
     S: HasMetadata
+        + HasNamedMetadata
         + HasExecutions
         + HasSolutions<I>
+        + HasCorpus<I>
+        + HasMaxSize
+        + HasStartTime
         + HasLastReportTime
         + HasCurrentStageId
         + Stoppable,
-    EM: ProgressReporter<S> + EventReceiver<I, S>,
+    EM: ProgressReporter<S> + EventReceiver<I, S> + SendExiting,
     ST: StagesTuple<E, EM, S, F>,
 {
     if let Some(solution) = state.solutions().last() {
@@ -110,18 +281,30 @@ where
             }
         }
         if halt {
+            finish_fuzzing(options, state);
             log::info!("Halting; the error on the next line is actually okay. :)");
             return Err(Error::shutting_down());
         }
     }
-    if options.runs() == 0 {
-        fuzzer.fuzz_loop(stages, executor, state, mgr)?;
-    } else {
-        for _ in 0..options.runs() {
-            fuzzer.fuzz_one(stages, executor, state, mgr)?;
+
+    init_fuzz_loop(options, state);
+    let mut runs = 0;
+    while options.runs() == 0 || runs < options.runs() {
+        if options.max_total_time().is_some_and(|max_total_time| {
+            current_time().saturating_sub(*state.start_time()) >= max_total_time
+        }) {
+            log::info!("Reached the maximum total time, stopping.");
+            break;
         }
+        update_len_control(options, state);
+        mgr.maybe_report_progress(state, STATS_TIMEOUT)?;
+        fuzzer.fuzz_one(stages, executor, state, mgr)?;
+        runs += 1;
     }
-    Ok(())
+    mgr.report_progress(state)?;
+    finish_fuzzing(options, state);
+    // Make sure restarting managers do not respawn us
+    mgr.send_exiting()
 }
 
 #[cfg(unix)]
@@ -77,9 +77,10 @@ use std::{
 use env_logger::Target;
 use libafl::{
     Error,
+    events::ClientDescription,
     inputs::{BytesInput, HasTargetBytes, Input},
 };
-use libafl_bolts::AsSlice;
+use libafl_bolts::{AsSlice, core_affinity::CoreId};
 use libc::_exit;
 use mimalloc::MiMalloc;
 
@@ -88,10 +89,12 @@ use crate::options::{LibfuzzerMode, LibfuzzerOptions};
 static GLOBAL: MiMalloc = MiMalloc;
 
 mod corpus;
+mod coverage;
 mod feedbacks;
 mod fuzz;
 mod merge;
 mod misc;
+mod mutators;
 mod observers;
 mod options;
 mod report;
@@ -153,10 +156,11 @@ macro_rules! fuzz_with {
         };
         use libafl::{
             corpus::Corpus,
+            events::ClientDescription,
             executors::{ExitKind, InProcessExecutor, ShadowExecutor},
             feedback_and_fast, feedback_not, feedback_or, feedback_or_fast,
             feedbacks::{ConstFeedback, CrashFeedback, MaxMapFeedback, NewHashFeedback, TimeFeedback, TimeoutFeedback},
-            generators::RandBytesGenerator,
+            generators::{RandBytesGenerator, RandPrintablesGenerator},
             inputs::{BytesInput, HasTargetBytes, GeneralizedInputMetadata},
             mutators::{
                 GrimoireExtensionMutator, GrimoireRecursiveReplacementMutator, GrimoireRandomDeleteMutator,
@@ -167,7 +171,7 @@ macro_rules! fuzz_with {
             },
             observers::{stacktrace::BacktraceObserver, TimeObserver, CanTrack, ConstMapObserver},
             schedulers::{
-                IndexesLenTimeMinimizerScheduler, powersched::PowerSchedule, PowerQueueScheduler,
+                EntropicScheduler, IndexesLenTimeMinimizerScheduler, powersched::PowerSchedule, PowerQueueScheduler,
             },
             stages::{
                 CalibrationStage, GeneralizationStage, IfStage, StdMutationalStage,
@@ -183,14 +187,17 @@ macro_rules! fuzz_with {
         use crate::{
             CustomMutationStatus,
             corpus::{ArtifactCorpus, LibfuzzerCorpus},
+            coverage::focus_function_edges,
             feedbacks::{LibfuzzerCrashCauseFeedback, LibfuzzerKeepFeedback, ShrinkMapFeedback},
             misc::should_use_grimoire,
+            mutators::OnlyAsciiMutator,
             observers::{MappedEdgeMapObserver, SizeValueObserver},
+            schedulers::{FocusFunctionScheduler, LibfuzzerScheduler},
         };
 
         let edge_maker = &$edge_maker;
 
-        let closure = |mut state: Option<_>, mut mgr, _cpu_id| {
+        let closure = |mut state: Option<_>, mut mgr, client_description: ClientDescription| {
             let mutator_status = CustomMutationStatus::new();
             let grimoire_metadata = should_use_grimoire(&mut state, &$options, &mutator_status)?;
             let grimoire = grimoire_metadata.should();
@@ -258,7 +265,10 @@ macro_rules! fuzz_with {
 
             // A feedback to choose if an input is a solution or not
             let mut objective = feedback_or_fast!(
-                LibfuzzerCrashCauseFeedback::new($options.artifact_prefix().clone()),
+                LibfuzzerCrashCauseFeedback::new(
+                    $options.artifact_prefix().clone(),
+                    $options.exact_artifact_path().cloned(),
+                ),
                 OomFeedback,
                 feedback_and_fast!(
                     CrashFeedback::new(),
@@ -290,7 +300,7 @@ macro_rules! fuzz_with {
             let mut state = state.unwrap_or_else(|| {
                 StdState::new(
                     // RNG
-                    StdRand::new(),
+                    StdRand::with_seed($options.client_seed(client_description.id())),
                     // Corpus that will be evolved, we keep it in memory for performance
                     LibfuzzerCorpus::new(corpus_dir.clone(), 4096),
                     // Corpus in which we store solutions (crashes in this example),
@@ -306,7 +316,8 @@ macro_rules! fuzz_with {
             state.metadata_map_mut().insert_boxed(grimoire_metadata);
 
             // Set up a string category analysis stage for unicode mutations
-            let unicode_used = $options.unicode();
+            let unicode_used = $options.unicode() && !$options.only_ascii();
+            let only_ascii = $options.only_ascii();
             let unicode_mutator = HavocScheduledMutator::new(
                 tuple_list!(
                     UnicodeCategoryRandMutator,
@@ -358,18 +369,26 @@ macro_rules! fuzz_with {
             }
 
             // Setup a randomic Input2State stage, conditionally within a custom mutator
-            let i2s =
-                StdMutationalStage::new(HavocScheduledMutator::new(tuple_list!(I2SRandReplace::new())));
+            let i2s = StdMutationalStage::new(OnlyAsciiMutator::new(
+                HavocScheduledMutator::new(tuple_list!(I2SRandReplace::new())),
+                only_ascii,
+            ));
             let i2s = IfStage::new(|_, _, _, _| Ok((!mutator_status.custom_mutation).into()), (i2s, ()));
-            let cm_i2s = StdMutationalStage::new(unsafe {
-                LLVMCustomMutator::mutate_unchecked(HavocScheduledMutator::new(tuple_list!(
-                    I2SRandReplace::new()
-                )))
-            });
+            let cm_i2s = StdMutationalStage::new(OnlyAsciiMutator::new(
+                unsafe {
+                    LLVMCustomMutator::mutate_unchecked(HavocScheduledMutator::new(tuple_list!(
+                        I2SRandReplace::new()
+                    )))
+                },
+                only_ascii,
+            ));
             let cm_i2s = IfStage::new(|_, _, _, _| Ok(mutator_status.custom_mutation.into()), (cm_i2s, ()));
 
             // TODO configure with mutation stacking options from libfuzzer
-            let std_mutator = HavocScheduledMutator::new(havoc_mutations().merge(tokens_mutations()));
+            let std_mutator = OnlyAsciiMutator::new(
+                HavocScheduledMutator::new(havoc_mutations().merge(tokens_mutations())),
+                only_ascii,
+            );
 
             let std_power: StdPowerMutationalStage<_, _, BytesInput, _, _, _> = StdPowerMutationalStage::new(std_mutator);
             let std_power = IfStage::new(|_, _, _, _| Ok(mutator_status.std_mutational.into()), (std_power, ()));
@@ -387,11 +406,17 @@ macro_rules! fuzz_with {
             // without performing the custom mutator's preprocessing beforehand
             // we opt not to use crossover in the LLVMFuzzerMutate and instead have a second crossover pass,
             // though it is likely an error for fuzzers to provide custom mutators but not custom crossovers
-            let custom_mutator = unsafe {
-                LLVMCustomMutator::mutate_unchecked(HavocScheduledMutator::new(havoc_mutations_no_crossover().merge(tokens_mutations())))
-            };
+            let custom_mutator = OnlyAsciiMutator::new(
+                unsafe {
+                    LLVMCustomMutator::mutate_unchecked(HavocScheduledMutator::new(havoc_mutations_no_crossover().merge(tokens_mutations())))
+                },
+                only_ascii,
+            );
             // Safe to unwrap: stack pow is not 0.
-            let std_mutator_no_mutate = HavocScheduledMutator::with_max_stack_pow(havoc_crossover(),3);
+            let std_mutator_no_mutate = OnlyAsciiMutator::new(
+                HavocScheduledMutator::with_max_stack_pow(havoc_crossover(), 3),
+                only_ascii,
+            );
 
             let cm_power: StdPowerMutationalStage<_, _, BytesInput, _, _, _> = StdPowerMutationalStage::new(custom_mutator);
             let cm_power = IfStage::new(|_, _, _, _| Ok(mutator_status.custom_mutation.into()), (cm_power, ()));
@@ -403,13 +428,19 @@ macro_rules! fuzz_with {
             // while the scenario that a custom crossover is defined without a custom mutator is unlikely
             // we handle it here explicitly anyways
             // Safe to unwrap: stack pow is not 0.
-            let custom_crossover = unsafe {
-                LLVMCustomMutator::crossover_unchecked(HavocScheduledMutator::with_max_stack_pow(
-                    havoc_mutations_no_crossover().merge(tokens_mutations()),
-                    3,
-                ))
-            };
-            let std_mutator_no_crossover = HavocScheduledMutator::new(havoc_mutations_no_crossover().merge(tokens_mutations()));
+            let custom_crossover = OnlyAsciiMutator::new(
+                unsafe {
+                    LLVMCustomMutator::crossover_unchecked(HavocScheduledMutator::with_max_stack_pow(
+                        havoc_mutations_no_crossover().merge(tokens_mutations()),
+                        3,
+                    ))
+                },
+                only_ascii,
+            );
+            let std_mutator_no_crossover = OnlyAsciiMutator::new(
+                HavocScheduledMutator::new(havoc_mutations_no_crossover().merge(tokens_mutations())),
+                only_ascii,
+            );
 
             let cc_power = StdMutationalStage::new(custom_crossover);
             let cc_power = IfStage::new(|_, _, _, _| Ok(mutator_status.custom_crossover.into()), (cc_power, ()));
@@ -432,7 +463,22 @@ macro_rules! fuzz_with {
             let grimoire = IfStage::new(|_, _, _, _| Ok(grimoire.into()), (StdMutationalStage::<_, _, GeneralizedInputMetadata, BytesInput, _, _, _>::transforming(grimoire_mutator), ()));
 
             // A minimization+queue policy to get testcasess from the corpus
-            let scheduler = IndexesLenTimeMinimizerScheduler::new(&edges_observer, PowerQueueScheduler::new(&mut state, &edges_observer, PowerSchedule::fast()));
+            let scheduler = if $options.entropic() {
+                LibfuzzerScheduler::Entropic(EntropicScheduler::with_params(
+                    &mut state,
+                    &edges_observer,
+                    $options.entropic_number_of_rarest_features(),
+                    $options.entropic_feature_frequency_threshold(),
+                    $options.entropic_scale_per_exec_time(),
+                ))
+            } else {
+                LibfuzzerScheduler::Power(PowerQueueScheduler::new(&mut state, &edges_observer, PowerSchedule::fast()))
+            };
+            let scheduler = IndexesLenTimeMinimizerScheduler::new(&edges_observer, scheduler);
+
+            // Prefer the inputs reaching the focus function, if any
+            let focus_edges = $options.focus_function().and_then(focus_function_edges);
+            let scheduler = FocusFunctionScheduler::new(scheduler, focus_edges);
 
             // A fuzzer with feedbacks and a corpus scheduler
             let mut fuzzer = StdFuzzer::new(scheduler, feedback, objective);
@@ -480,19 +526,30 @@ macro_rules! fuzz_with {
                     println!("We imported {} inputs from disk.", state.corpus().count());
                 }
                 if state.corpus().count() < 1 {
-                    // Generator of bytearrays of max size 64
-                    let mut generator = RandBytesGenerator::from(RandBytesGenerator::new(nonzero!(64)));
-
-                    // Generate 1024 initial inputs
-                    state
-                        .generate_initial_inputs(
-                            &mut fuzzer,
-                            &mut executor,
-                            &mut generator,
-                            &mut mgr,
-                            1 << 10,
-                        )
-                        .expect("Failed to generate the initial corpus");
+                    // Generate 1024 initial inputs of max size 64
+                    if only_ascii {
+                        let mut generator = RandPrintablesGenerator::new(nonzero!(64));
+                        state
+                            .generate_initial_inputs(
+                                &mut fuzzer,
+                                &mut executor,
+                                &mut generator,
+                                &mut mgr,
+                                1 << 10,
+                            )
+                            .expect("Failed to generate the initial corpus");
+                    } else {
+                        let mut generator = RandBytesGenerator::new(nonzero!(64));
+                        state
+                            .generate_initial_inputs(
+                                &mut fuzzer,
+                                &mut executor,
+                                &mut generator,
+                                &mut mgr,
+                                1 << 10,
+                            )
+                            .expect("Failed to generate the initial corpus");
+                    }
                     println!(
                         "We imported {} inputs from the generator.",
                         state.corpus().count()
@@ -579,9 +636,9 @@ pub fn start_fuzzing_single<F, S, EM>(
     mgr: EM,
 ) -> Result<(), Error>
 where
-    F: FnMut(Option<S>, EM, usize) -> Result<(), Error>,
+    F: FnMut(Option<S>, EM, ClientDescription) -> Result<(), Error>,
 {
-    fuzz_single(initial_state, mgr, 0)
+    fuzz_single(initial_state, mgr, ClientDescription::new(0, 0, CoreId(0)))
 }
 
 unsafe extern "C" {
@@ -656,6 +713,12 @@ pub unsafe extern "C" fn LLVMFuzzerRunDriver(
     if !options.unknown().is_empty() {
         eprintln!("Unrecognised options: {:?}", options.unknown());
     }
+    if !options.ignored().is_empty() {
+        eprintln!(
+            "INFO: ignoring libFuzzer options without effect in libafl_libfuzzer: {:?}",
+            options.ignored()
+        );
+    }
 
     for folder in options
         .dirs()
@@ -673,7 +736,7 @@ pub unsafe extern "C" fn LLVMFuzzerRunDriver(
         }
     }
 
-    if *options.mode() != LibfuzzerMode::Tmin
+    if !matches!(options.mode(), LibfuzzerMode::Tmin | LibfuzzerMode::Cleanse)
         && !options.dirs().is_empty()
         && options.dirs().iter().all(|maybe_dir| maybe_dir.is_file())
     {
@@ -690,10 +753,14 @@ pub unsafe extern "C" fn LLVMFuzzerRunDriver(
         }
         return 0;
     }
+    if *options.mode() == LibfuzzerMode::Fuzz {
+        eprintln!("INFO: Seed: {}", options.seed());
+    }
     let res = match options.mode() {
         LibfuzzerMode::Fuzz => fuzz::fuzz(&options, harness),
         LibfuzzerMode::Merge => merge::merge(&options, harness),
         LibfuzzerMode::Tmin => tmin::minimize_crash(&options, *harness),
+        LibfuzzerMode::Cleanse => tmin::cleanse_crash(&options, *harness),
         LibfuzzerMode::Report => report::report(&options, harness),
     };
     match res {
@@ -0,0 +1,61 @@
+use std::borrow::Cow;
+
+use libafl::{
+    Error,
+    corpus::CorpusId,
+    inputs::HasMutatorBytes,
+    mutators::{MutationResult, Mutator},
+};
+use libafl_bolts::Named;
+
+/// Turns a byte into a printable or whitespace ASCII character, like libFuzzer's `ToASCII`
+fn to_ascii(byte: u8) -> u8 {
+    let byte = byte & 0x7f;
+    if byte.is_ascii_graphic() || byte == b' ' || (b'\t'..=b'\r').contains(&byte) {
+        byte
+    } else {
+        b' '
+    }
+}
+
+/// A mutator wrapper which restricts the mutated inputs to ASCII, as requested with `-only_ascii`
+#[derive(Debug)]
+pub struct OnlyAsciiMutator<M> {
+    inner: M,
+    enabled: bool,
+}
+
+impl<M> OnlyAsciiMutator<M> {
+    pub fn new(inner: M, enabled: bool) -> Self {
+        Self { inner, enabled }
+    }
+}
+
+impl<M> Named for OnlyAsciiMutator<M>
+where
+    M: Named,
+{
+    fn name(&self) -> &Cow<'static, str> {
+        self.inner.name()
+    }
+}
+
+impl<I, M, S> Mutator<I, S> for OnlyAsciiMutator<M>
+where
+    I: HasMutatorBytes,
+    M: Mutator<I, S>,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+        let result = self.inner.mutate(state, input)?;
+        if self.enabled && result == MutationResult::Mutated {
+            for byte in input.mutator_bytes_mut() {
+                *byte = to_ascii(*byte);
+            }
+        }
+        Ok(result)
+    }
+
+    fn post_exec(&mut self, state: &mut S, new_corpus_id: Option<CorpusId>) -> Result<(), Error> {
+        self.inner.post_exec(state, new_corpus_id)
+    }
+}
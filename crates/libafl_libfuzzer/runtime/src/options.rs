@@ -2,10 +2,45 @@ use core::fmt::{Display, Formatter};
 use std::{path::PathBuf, time::Duration};
 
 use libafl::mutators::Tokens;
+use libafl_bolts::current_nanos;
 use serde::{Deserialize, Serialize};
 
 use crate::options::RawOption::{Directory, Flag};
 
+/// libFuzzer flags which are accepted for compatibility, but have no effect in `libafl_libfuzzer`
+const IGNORED_FLAGS: &[&str] = &[
+    "verbosity",
+    "reload",
+    "detect_leaks",
+    "use_counters",
+    "use_cmp",
+    "use_memmem",
+    "mutate_depth",
+    "reduce_inputs",
+    "reduce_depth",
+    "prefer_small",
+    "shuffle",
+    "keep_seed",
+    "cross_over",
+    "print_pcs",
+    "print_funcs",
+    "print_new",
+    "print_corpus_stats",
+    "purge_allocations_every_n",
+    "report_slow_units",
+    "handle_segv",
+    "handle_bus",
+    "handle_abrt",
+    "handle_ill",
+    "handle_fpe",
+    "handle_int",
+    "handle_term",
+    "handle_xfsz",
+    "handle_usr1",
+    "handle_usr2",
+    "handle_winexcept",
+];
+
 enum RawOption<'a> {
     Directory(&'a str),
     Flag { name: &'a str, value: &'a str },
@@ -31,6 +66,7 @@ pub enum LibfuzzerMode {
     Fuzz,
     Merge,
     Tmin,
+    Cleanse,
     Report,
 }
 
@@ -123,8 +159,22 @@ pub struct LibfuzzerOptions {
     skip_tracing: bool,
     tui: bool,
     runs: usize,
+    entropic: bool,
+    entropic_feature_frequency_threshold: u16,
+    entropic_number_of_rarest_features: usize,
+    entropic_scale_per_exec_time: bool,
+    max_len: Option<usize>,
+    len_control: usize,
+    max_total_time: Option<Duration>,
+    seed: u64,
+    exact_artifact_path: Option<PathBuf>,
+    print_final_stats: bool,
+    print_coverage: bool,
+    only_ascii: bool,
+    focus_function: Option<String>,
     #[allow(unused)]
     close_fd_mask: u8,
+    ignored: Vec<String>,
     unknown: Vec<String>,
 }
 
@@ -227,11 +277,73 @@ impl LibfuzzerOptions {
         self.runs
     }
 
+    pub fn entropic(&self) -> bool {
+        self.entropic
+    }
+
+    pub fn entropic_feature_frequency_threshold(&self) -> u16 {
+        self.entropic_feature_frequency_threshold
+    }
+
+    pub fn entropic_number_of_rarest_features(&self) -> usize {
+        self.entropic_number_of_rarest_features
+    }
+
+    pub fn entropic_scale_per_exec_time(&self) -> bool {
+        self.entropic_scale_per_exec_time
+    }
+
+    pub fn max_len(&self) -> Option<usize> {
+        self.max_len
+    }
+
+    pub fn len_control(&self) -> usize {
+        self.len_control
+    }
+
+    pub fn max_total_time(&self) -> Option<Duration> {
+        self.max_total_time
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The seed for the given client of `-fork`/`-jobs`, so that clients do not all mutate the same way.
+    /// The first client uses [`Self::seed`] itself.
+    pub fn client_seed(&self, client_id: usize) -> u64 {
+        self.seed.wrapping_add(client_id as u64)
+    }
+
+    pub fn exact_artifact_path(&self) -> Option<&PathBuf> {
+        self.exact_artifact_path.as_ref()
+    }
+
+    pub fn print_final_stats(&self) -> bool {
+        self.print_final_stats
+    }
+
+    pub fn print_coverage(&self) -> bool {
+        self.print_coverage
+    }
+
+    pub fn only_ascii(&self) -> bool {
+        self.only_ascii
+    }
+
+    pub fn focus_function(&self) -> Option<&str> {
+        self.focus_function.as_deref()
+    }
+
     #[cfg(unix)]
     pub fn close_fd_mask(&self) -> u8 {
         self.close_fd_mask
     }
 
+    pub fn ignored(&self) -> &[String] {
+        &self.ignored
+    }
+
     pub fn unknown(&self) -> &[String] {
         &self.unknown
     }
@@ -260,7 +372,21 @@ struct LibfuzzerOptionsBuilder<'a> {
     skip_tracing: bool,
     tui: bool,
     runs: usize,
+    entropic: Option<bool>,
+    entropic_feature_frequency_threshold: Option<u16>,
+    entropic_number_of_rarest_features: Option<usize>,
+    entropic_scale_per_exec_time: bool,
+    max_len: Option<usize>,
+    len_control: Option<usize>,
+    max_total_time: Option<u64>,
+    seed: Option<u64>,
+    exact_artifact_path: Option<&'a str>,
+    print_final_stats: bool,
+    print_coverage: bool,
+    only_ascii: bool,
+    focus_function: Option<&'a str>,
     close_fd_mask: u8,
+    ignored: Vec<&'a str>,
     unknown: Vec<&'a str>,
 }
 
@@ -299,6 +425,14 @@ impl<'a> LibfuzzerOptionsBuilder<'a> {
                                 return Err(OptionsParseError::MultipleModesSelected);
                             }
                         }
+                        "cleanse_crash" => {
+                            if parse_or_bail!(name, value, u64) > 0
+                                && *self.mode.get_or_insert(LibfuzzerMode::Cleanse)
+                                    != LibfuzzerMode::Cleanse
+                            {
+                                return Err(OptionsParseError::MultipleModesSelected);
+                            }
+                        }
                         "report" => {
                             if parse_or_bail!(name, value, u64) > 0
                                 && *self.mode.get_or_insert(LibfuzzerMode::Report)
@@ -362,6 +496,36 @@ impl<'a> LibfuzzerOptionsBuilder<'a> {
                             }
                         }
                         "runs" => self.runs = parse_or_bail!(name, value, usize),
+                        "entropic" => self.entropic = Some(parse_or_bail!(name, value, u64) > 0),
+                        "entropic_feature_frequency_threshold" => {
+                            self.entropic_feature_frequency_threshold =
+                                Some(parse_or_bail!(name, value, u16));
+                        }
+                        "entropic_number_of_rarest_features" => {
+                            self.entropic_number_of_rarest_features =
+                                Some(parse_or_bail!(name, value, usize));
+                        }
+                        "entropic_scale_per_exec_time" => {
+                            self.entropic_scale_per_exec_time =
+                                parse_or_bail!(name, value, u64) > 0;
+                        }
+                        "max_len" => self.max_len = Some(parse_or_bail!(name, value, usize)),
+                        "len_control" => {
+                            self.len_control = Some(parse_or_bail!(name, value, usize));
+                        }
+                        "max_total_time" => {
+                            self.max_total_time = Some(parse_or_bail!(name, value, u64));
+                        }
+                        "seed" => self.seed = Some(parse_or_bail!(name, value, u64)),
+                        "exact_artifact_path" => self.exact_artifact_path = Some(value),
+                        "print_final_stats" => {
+                            self.print_final_stats = parse_or_bail!(name, value, u64) > 0;
+                        }
+                        "print_coverage" => {
+                            self.print_coverage = parse_or_bail!(name, value, u64) > 0;
+                        }
+                        "only_ascii" => self.only_ascii = parse_or_bail!(name, value, u64) > 0,
+                        "focus_function" => self.focus_function = Some(value),
                         "close_fd_mask" => self.close_fd_mask = parse_or_bail!(name, value, u8),
                         "help" => {
                             println!(
@@ -393,9 +557,23 @@ impl<'a> LibfuzzerOptionsBuilder<'a> {
                                 skip_tracing                           0       If 1, skip coverage tracing for faster execution.\n\
                                 tui                                    0       If 1, use the terminal UI interface.\n\
                                 runs                                   0       Number of individual test runs (0 for infinite runs).\n\
+                                entropic                               1       If 1, use the Entropic power schedule instead of the fast power schedule.\n\
+                                entropic_feature_frequency_threshold   255     Frequency above which the rarest features may be dropped by the Entropic schedule.\n\
+                                entropic_number_of_rarest_features     100     Number of rare features considered by the Entropic schedule.\n\
+                                entropic_scale_per_exec_time           0       If 1, the Entropic schedule favors inputs with a lower execution time.\n\
+                                max_len                                0       Maximum length of the test input. If 0, a guess is made based on the corpus.\n\
+                                len_control                            100     Try generating small inputs first, then try larger inputs over time. The higher, the slower the length grows. 0 disables this.\n\
+                                max_total_time                         0       If positive, indicates the maximal total time in seconds to run the fuzzer.\n\
+                                seed                                   0       Random seed. If 0, seed is generated.\n\
+                                exact_artifact_path                    0       Write the single artifact on failure (crash, timeout) as $(exact_artifact_path). This overrides artifact_prefix.\n\
+                                print_final_stats                      0       If 1, print statistics at exit.\n\
+                                print_coverage                         0       If 1, print coverage information as text at exit.\n\
+                                only_ascii                             0       If 1, generate only ASCII (isprint+isspace) inputs.\n\
+                                focus_function                         0       Fuzzing will focus on inputs that trigger calls to this function.\n\
                                 close_fd_mask                          0       If 1, close stdout; if 2, close stderr; if 3, close both.\n\
                                 merge                                  0       If 1, merge multiple corpora into a single one.\n\
                                 minimize_crash                         0       If 1, minimize crashes to their smallest reproducing input.\n\
+                                cleanse_crash                          0       If 1, tries to cleanse the provided crash input to make it contain fewer original bytes. Use with exact_artifact_path.\n\
                                 report                                 0       If 1, report statistics without actually fuzzing.\n\
                                 help                                   0       Print this help message.\n\
                                 \n\
@@ -404,6 +582,9 @@ impl<'a> LibfuzzerOptionsBuilder<'a> {
                             );
                             std::process::exit(0);
                         }
+                        _ if IGNORED_FLAGS.contains(&name) => {
+                            self.ignored.push(arg);
+                        }
                         _ => {
                             self.unknown.push(arg);
                         }
@@ -449,8 +630,51 @@ impl<'a> LibfuzzerOptionsBuilder<'a> {
             skip_tracing: self.skip_tracing,
             tui: self.tui,
             runs: self.runs,
+            entropic: self.entropic.unwrap_or(true),
+            entropic_feature_frequency_threshold: self
+                .entropic_feature_frequency_threshold
+                .unwrap_or(0xff),
+            entropic_number_of_rarest_features: self
+                .entropic_number_of_rarest_features
+                .unwrap_or(100),
+            entropic_scale_per_exec_time: self.entropic_scale_per_exec_time,
+            max_len: self.max_len.filter(|&max_len| max_len != 0),
+            len_control: self.len_control.unwrap_or(100),
+            max_total_time: self
+                .max_total_time
+                .filter(|&secs| secs != 0)
+                .map(Duration::from_secs),
+            seed: match self.seed.unwrap_or_default() {
+                0 => current_nanos(),
+                seed => seed,
+            },
+            exact_artifact_path: self.exact_artifact_path.map(PathBuf::from),
+            print_final_stats: self.print_final_stats,
+            print_coverage: self.print_coverage,
+            only_ascii: self.only_ascii,
+            focus_function: self.focus_function.map(ToString::to_string),
             close_fd_mask: self.close_fd_mask,
+            ignored: self.ignored.into_iter().map(ToString::to_string).collect(),
             unknown: self.unknown.into_iter().map(ToString::to_string).collect(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::LibfuzzerOptions;
+
+    #[test]
+    fn test_client_seed() {
+        let options = LibfuzzerOptions::new(["fuzzer", "-seed=42"].into_iter()).unwrap();
+        assert_eq!(options.seed(), 42);
+        assert_eq!(options.client_seed(0), 42);
+        assert_ne!(options.client_seed(1), options.client_seed(0));
+
+        // Without `-seed`, a random seed is picked, but the clients still differ
+        let options = LibfuzzerOptions::new(["fuzzer"].into_iter()).unwrap();
+        assert_ne!(options.seed(), 0);
+        assert_eq!(options.client_seed(0), options.seed());
+        assert_ne!(options.client_seed(1), options.client_seed(0));
+    }
+}
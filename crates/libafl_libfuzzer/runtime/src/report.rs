@@ -10,11 +10,11 @@ use libafl::{
     state::{HasCurrentStageId, HasExecutions, HasLastReportTime, Stoppable},
 };
 
-use crate::{fuzz_with, options::LibfuzzerOptions};
+use crate::{coverage::print_coverage, fuzz_with, options::LibfuzzerOptions};
 
 #[expect(clippy::unnecessary_wraps, clippy::cast_precision_loss)]
 fn do_report<E, F, I, S, ST, EM>(
-    _options: &LibfuzzerOptions,
+    options: &LibfuzzerOptions,
     _fuzzer: &mut F,
     _stages: &mut ST,
     _executor: &mut E,
@@ -44,6 +44,10 @@ where
         observed as f64 / total as f64
     );
 
+    if options.print_coverage() {
+        print_coverage(&meta.history_map, &|s| println!("{s}"));
+    }
+
     Ok(())
 }
 
@@ -1,14 +1,15 @@
-use std::{collections::BTreeSet, marker::PhantomData};
+use std::{collections::BTreeSet, marker::PhantomData, ops::Range};
 
 use hashbrown::HashMap;
 use libafl::{
     Error, HasMetadata,
     corpus::{Corpus, CorpusId, Testcase},
-    feedbacks::MapNoveltiesMetadata,
+    feedbacks::{MapIndexesMetadata, MapNoveltiesMetadata},
     inputs::Input,
     schedulers::{RemovableScheduler, Scheduler},
-    state::HasCorpus,
+    state::{HasCorpus, HasRand},
 };
+use libafl_bolts::{rands::Rand, tuples::MatchName};
 
 #[derive(Debug, Clone)]
 pub struct MergeScheduler<I, S> {
@@ -81,3 +82,214 @@ impl<I, S> MergeScheduler<I, S> {
         &self.all
     }
 }
+
+/// The base scheduler of the fuzz mode, selected at runtime with `-entropic`
+#[derive(Debug, Clone)]
+pub enum LibfuzzerScheduler<P, E> {
+    /// A power schedule, as used by `LibAFL` by default
+    Power(P),
+    /// The Entropic schedule, as used by libFuzzer by default
+    Entropic(E),
+}
+
+impl<P, E, I, S> RemovableScheduler<I, S> for LibfuzzerScheduler<P, E>
+where
+    P: RemovableScheduler<I, S>,
+    E: RemovableScheduler<I, S>,
+{
+    fn on_remove(
+        &mut self,
+        state: &mut S,
+        id: CorpusId,
+        testcase: &Option<Testcase<I>>,
+    ) -> Result<(), Error> {
+        match self {
+            Self::Power(scheduler) => scheduler.on_remove(state, id, testcase),
+            Self::Entropic(scheduler) => scheduler.on_remove(state, id, testcase),
+        }
+    }
+
+    fn on_replace(&mut self, state: &mut S, id: CorpusId, prev: &Testcase<I>) -> Result<(), Error> {
+        match self {
+            Self::Power(scheduler) => scheduler.on_replace(state, id, prev),
+            Self::Entropic(scheduler) => scheduler.on_replace(state, id, prev),
+        }
+    }
+}
+
+impl<P, E, I, S> Scheduler<I, S> for LibfuzzerScheduler<P, E>
+where
+    P: Scheduler<I, S>,
+    E: Scheduler<I, S>,
+{
+    fn on_add(&mut self, state: &mut S, id: CorpusId) -> Result<(), Error> {
+        match self {
+            Self::Power(scheduler) => scheduler.on_add(state, id),
+            Self::Entropic(scheduler) => scheduler.on_add(state, id),
+        }
+    }
+
+    fn on_evaluation<OT>(&mut self, state: &mut S, input: &I, observers: &OT) -> Result<(), Error>
+    where
+        OT: MatchName,
+    {
+        match self {
+            Self::Power(scheduler) => scheduler.on_evaluation(state, input, observers),
+            Self::Entropic(scheduler) => scheduler.on_evaluation(state, input, observers),
+        }
+    }
+
+    fn next(&mut self, state: &mut S) -> Result<CorpusId, Error> {
+        match self {
+            Self::Power(scheduler) => scheduler.next(state),
+            Self::Entropic(scheduler) => scheduler.next(state),
+        }
+    }
+
+    fn set_current_scheduled(
+        &mut self,
+        state: &mut S,
+        next_id: Option<CorpusId>,
+    ) -> Result<(), Error> {
+        match self {
+            Self::Power(scheduler) => scheduler.set_current_scheduled(state, next_id),
+            Self::Entropic(scheduler) => scheduler.set_current_scheduled(state, next_id),
+        }
+    }
+}
+
+/// A scheduler only picking the inputs which reach the edges of the `-focus_function`, as long as any does.
+/// Until then, it asks the base scheduler.
+#[derive(Debug, Clone)]
+pub struct FocusFunctionScheduler<CS> {
+    base: CS,
+    focus_edges: Option<Range<usize>>,
+    focused: BTreeSet<CorpusId>,
+}
+
+impl<CS> FocusFunctionScheduler<CS> {
+    pub fn new(base: CS, focus_edges: Option<Range<usize>>) -> Self {
+        Self {
+            base,
+            focus_edges,
+            focused: BTreeSet::new(),
+        }
+    }
+}
+
+impl<CS, I, S> RemovableScheduler<I, S> for FocusFunctionScheduler<CS>
+where
+    CS: RemovableScheduler<I, S>,
+{
+    fn on_remove(
+        &mut self,
+        state: &mut S,
+        id: CorpusId,
+        testcase: &Option<Testcase<I>>,
+    ) -> Result<(), Error> {
+        self.focused.remove(&id);
+        self.base.on_remove(state, id, testcase)
+    }
+
+    fn on_replace(&mut self, state: &mut S, id: CorpusId, prev: &Testcase<I>) -> Result<(), Error> {
+        self.base.on_replace(state, id, prev)
+    }
+}
+
+impl<CS, I, S> Scheduler<I, S> for FocusFunctionScheduler<CS>
+where
+    CS: Scheduler<I, S>,
+    S: HasCorpus<I> + HasRand,
+{
+    fn on_add(&mut self, state: &mut S, id: CorpusId) -> Result<(), Error> {
+        if let Some(focus_edges) = &self.focus_edges {
+            let testcase = state.corpus().get(id)?.borrow();
+            if let Ok(meta) = testcase.metadata::<MapIndexesMetadata>()
+                && meta.list.iter().any(|idx| focus_edges.contains(idx))
+            {
+                self.focused.insert(id);
+            }
+        }
+        self.base.on_add(state, id)
+    }
+
+    fn on_evaluation<OT>(&mut self, state: &mut S, input: &I, observers: &OT) -> Result<(), Error>
+    where
+        OT: MatchName,
+    {
+        self.base.on_evaluation(state, input, observers)
+    }
+
+    fn next(&mut self, state: &mut S) -> Result<CorpusId, Error> {
+        let Some(&id) = state.rand_mut().choose(&self.focused) else {
+            return self.base.next(state);
+        };
+        self.set_current_scheduled(state, Some(id))?;
+        Ok(id)
+    }
+
+    fn set_current_scheduled(
+        &mut self,
+        state: &mut S,
+        next_id: Option<CorpusId>,
+    ) -> Result<(), Error> {
+        self.base.set_current_scheduled(state, next_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl::{
+        HasMetadata,
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        feedbacks::{ConstFeedback, MapIndexesMetadata},
+        inputs::BytesInput,
+        schedulers::{QueueScheduler, Scheduler},
+        state::{HasCorpus, StdState},
+    };
+    use libafl_bolts::rands::StdRand;
+
+    use super::FocusFunctionScheduler;
+
+    #[test]
+    fn test_focus_function_scheduler() {
+        let mut feedback = ConstFeedback::new(false);
+        let mut objective = ConstFeedback::new(false);
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )
+        .unwrap();
+        let mut scheduler = FocusFunctionScheduler::new(QueueScheduler::new(), Some(10..20));
+
+        let mut ids = vec![];
+        for (i, edges) in [vec![1, 2], vec![3, 15], vec![25]].into_iter().enumerate() {
+            let mut testcase = Testcase::new(BytesInput::new(vec![i as u8]));
+            testcase.add_metadata(MapIndexesMetadata::new(edges));
+            let id = state.corpus_mut().add(testcase).unwrap();
+            scheduler.on_add(&mut state, id).unwrap();
+            ids.push(id);
+        }
+
+        // Only the input reaching the focus function is picked
+        for _ in 0..8 {
+            let id = scheduler.next(&mut state).unwrap();
+            assert_eq!(id, ids[1]);
+            assert_eq!(*state.corpus().current(), Some(ids[1]));
+        }
+
+        // Without a focus function, the base scheduler walks the whole queue
+        let mut scheduler = FocusFunctionScheduler::new(QueueScheduler::new(), None);
+        for &id in &ids {
+            scheduler.on_add(&mut state, id).unwrap();
+        }
+        let mut picked = (0..3)
+            .map(|_| scheduler.next(&mut state).unwrap())
+            .collect::<Vec<_>>();
+        picked.sort();
+        assert_eq!(picked, ids);
+    }
+}
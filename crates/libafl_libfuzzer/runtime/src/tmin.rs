@@ -12,17 +12,19 @@ use libafl::{
     corpus::{Corpus, HasTestcase, InMemoryCorpus, Testcase},
     events::SimpleEventManager,
     executors::ExitKind,
-    feedbacks::{CrashFeedback, TimeoutFeedback},
+    feedbacks::{ConstFeedback, CrashFeedback, TimeoutFeedback},
     inputs::{BytesInput, HasMutatorBytes, HasTargetBytes},
     mutators::{HavocScheduledMutator, Mutator, havoc_mutations_no_crossover},
+    observers::{BacktraceObserver, HarnessType},
     schedulers::QueueScheduler,
-    stages::StdTMinMutationalStage,
+    stages::{CleanseStage, ObserverHashEqualityFactory, StdTMinMutationalStage},
     state::{HasCorpus, StdState},
 };
 #[cfg(unix)]
 use libafl_bolts::shmem::{ShMemProvider, StdShMemProvider};
 use libafl_bolts::{
     AsSlice, HasLen,
+    ownedref::OwnedRefMut,
     rands::{RomuDuoJrRand, StdRand},
     tuples::tuple_list,
 };
@@ -32,6 +34,18 @@ use crate::{CustomMutationStatus, options::LibfuzzerOptions};
 type TMinState =
     StdState<InMemoryCorpus<BytesInput>, BytesInput, RomuDuoJrRand, InMemoryCorpus<BytesInput>>;
 
+fn run_harness(harness: extern "C" fn(*const u8, usize) -> c_int, input: &BytesInput) -> ExitKind {
+    let target = input.target_bytes();
+    let buf = target.as_slice();
+
+    let result =
+        unsafe { crate::libafl_libfuzzer_test_one_input(Some(harness), buf.as_ptr(), buf.len()) };
+    match result {
+        -2 => ExitKind::Crash,
+        _ => ExitKind::Ok,
+    }
+}
+
 fn minimize_crash_with_mutator<M: Mutator<BytesInput, TMinState>>(
     options: &LibfuzzerOptions,
     harness: extern "C" fn(*const u8, usize) -> c_int,
@@ -50,18 +64,7 @@ fn minimize_crash_with_mutator<M: Mutator<BytesInput, TMinState>>(
 
     let input = BytesInput::new(read(&options.dirs()[0])?);
 
-    let mut harness = |input: &BytesInput| {
-        let target = input.target_bytes();
-        let buf = target.as_slice();
-
-        let result = unsafe {
-            crate::libafl_libfuzzer_test_one_input(Some(harness), buf.as_ptr(), buf.len())
-        };
-        match result {
-            -2 => ExitKind::Crash,
-            _ => ExitKind::Ok,
-        }
-    };
+    let mut harness = |input: &BytesInput| run_harness(harness, input);
 
     let mut fuzzer = StdFuzzer::new(QueueScheduler::new(), (), ());
 
@@ -138,12 +141,17 @@ fn minimize_crash_with_mutator<M: Mutator<BytesInput, TMinState>>(
             options.dirs()[0].as_path().as_os_str().to_str().unwrap()
         );
     } else {
-        let mut dest = options.artifact_prefix().dir().clone();
-        dest.push(format!(
-            "{}minimized-from-{}",
-            options.artifact_prefix().filename_prefix(),
-            options.dirs()[0].file_name().unwrap().to_str().unwrap()
-        ));
+        let dest = if let Some(exact_artifact_path) = options.exact_artifact_path() {
+            exact_artifact_path.clone()
+        } else {
+            let mut dest = options.artifact_prefix().dir().clone();
+            dest.push(format!(
+                "{}minimized-from-{}",
+                options.artifact_prefix().filename_prefix(),
+                options.dirs()[0].file_name().unwrap().to_str().unwrap()
+            ));
+            dest
+        };
         write(&dest, input)?;
         println!(
             "Wrote minimised input to {}",
@@ -172,7 +180,7 @@ pub fn minimize_crash(
     let mutator_status = CustomMutationStatus::new();
 
     let state = StdState::new(
-        StdRand::new(),
+        StdRand::with_seed(options.seed()),
         InMemoryCorpus::<BytesInput>::new(),
         InMemoryCorpus::new(),
         &mut (),
@@ -192,3 +200,109 @@ pub fn minimize_crash(
         minimize_crash_with_mutator(options, harness, std_mutator, state)
     }
 }
+
+/// Replaces as many bytes of the crash as possible with neutral values, as libFuzzer's `-cleanse_crash`
+pub fn cleanse_crash(
+    options: &LibfuzzerOptions,
+    harness: extern "C" fn(*const u8, usize) -> c_int,
+) -> Result<(), Error> {
+    if options.dirs().len() != 1 || !options.dirs()[0].is_file() {
+        return Err(Error::illegal_argument(
+            "-cleanse_crash should be given exactly one input file",
+        ));
+    }
+    let Some(dest) = options.exact_artifact_path() else {
+        return Err(Error::illegal_argument(
+            "-cleanse_crash requires -exact_artifact_path to write the cleansed input to",
+        ));
+    };
+    println!(
+        "Attempting to cleanse a crash: {}",
+        options.dirs()[0].to_string_lossy()
+    );
+
+    let mut state: TMinState = StdState::new(
+        StdRand::with_seed(options.seed()),
+        InMemoryCorpus::new(),
+        InMemoryCorpus::new(),
+        &mut (),
+        &mut (),
+    )?;
+    let mut mgr: SimpleEventManager<BytesInput, _, TMinState> = SimpleEventManager::printing();
+    let mut fuzzer = StdFuzzer::new(QueueScheduler::new(), (), ());
+    let mut harness = |input: &BytesInput| run_harness(harness, input);
+
+    // The crash signature, computed in the child by the crash handler
+    #[cfg(unix)]
+    let mut shmem_provider = StdShMemProvider::new()?;
+    #[cfg(unix)]
+    let mut backtrace = shmem_provider.new_on_shmem::<Option<u64>>(None)?;
+    #[cfg(unix)]
+    let backtrace_observer = BacktraceObserver::new(
+        "BacktraceObserver",
+        unsafe { OwnedRefMut::from_shmem(&mut backtrace) },
+        HarnessType::Child,
+    );
+    #[cfg(windows)]
+    let backtrace_observer = BacktraceObserver::owned("BacktraceObserver", HarnessType::InProcess);
+    let signature = ObserverHashEqualityFactory::new(&backtrace_observer);
+
+    #[cfg(unix)]
+    let mut executor = InProcessForkExecutor::new(
+        &mut harness,
+        tuple_list!(backtrace_observer),
+        &mut fuzzer,
+        &mut state,
+        &mut mgr,
+        options.timeout(),
+        shmem_provider,
+    )?;
+
+    #[cfg(windows)]
+    let mut executor = InProcessExecutor::with_timeout(
+        &mut harness,
+        tuple_list!(backtrace_observer),
+        &mut fuzzer,
+        &mut state,
+        &mut mgr,
+        options.timeout(),
+    )?;
+
+    let input = BytesInput::new(read(&options.dirs()[0])?);
+    let exit_kind = fuzzer.execute_input(&mut state, &mut executor, &mut mgr, &input)?;
+    let original = input.mutator_bytes().to_vec();
+    let id = state.corpus_mut().add(Testcase::new(input))?;
+
+    // keep the same crash signature, or only the timeout, which has no signature
+    match exit_kind {
+        ExitKind::Crash => {
+            let mut stages = tuple_list!(CleanseStage::new(signature));
+            fuzzer.fuzz_one(&mut stages, &mut executor, &mut state, &mut mgr)?;
+        }
+        ExitKind::Timeout => {
+            let mut stages = tuple_list!(CleanseStage::new(ConstFeedback::True));
+            fuzzer.fuzz_one(&mut stages, &mut executor, &mut state, &mut mgr)?;
+        }
+        kind => {
+            return Err(Error::illegal_argument(format!(
+                "The input to cleanse does not crash the target (exit kind: {kind:?})"
+            )));
+        }
+    }
+
+    let mut testcase = state.testcase_mut(id)?;
+    let input = testcase
+        .load_input(state.corpus())?
+        .mutator_bytes()
+        .to_vec();
+    drop(testcase);
+
+    write(dest, &input)?;
+    println!(
+        "Cleansed {}/{} bytes, wrote the cleansed input to {}",
+        input.iter().zip(&original).filter(|(a, b)| a != b).count(),
+        input.len(),
+        dest.to_string_lossy()
+    );
+    Ok(())
+}
@@ -282,6 +282,27 @@ fn main() {
         write!(file, "").unwrap();
     }
 
+    #[cfg(feature = "input_preload")]
+    if env::var("CARGO_CFG_TARGET_OS").unwrap() == "linux" {
+        println!("cargo:rerun-if-changed=src/input_preload.c");
+
+        // A shared object to `LD_PRELOAD` into uninstrumented targets, not linked into the fuzzer
+        let library = Path::new(&out_dir).join("libafl_input_preload.so");
+        let status = cc::Build::new()
+            .get_compiler()
+            .to_command()
+            .args(["-shared", "-fPIC", "-O2", "-Wall", "-o"])
+            .arg(&library)
+            .arg(src_dir.join("input_preload.c"))
+            .arg("-ldl")
+            .status()
+            .expect("Could not run the C compiler for the input preload library");
+        assert!(
+            status.success(),
+            "Could not compile the input preload library"
+        );
+    }
+
     #[cfg(feature = "libfuzzer_interceptors")]
     {
         println!("cargo:rerun-if-changed=src/libfuzzer/FuzzerInterceptors.cpp");
@@ -175,6 +175,56 @@ where
     unsafe { StdMapObserver::from_mut_slice(name, edges_map_mut_slice()) }
 }
 
+#[cfg(all(
+    unix,
+    feature = "sancov_pcguard_threads",
+    any(feature = "sancov_pcguard_edges", feature = "sancov_pcguard_hitcounts")
+))]
+std::thread_local! {
+    /// The edges map of the current thread, used instead of the [`EDGES_MAP`] if not null
+    pub(crate) static THREAD_EDGES_MAP_PTR: core::cell::Cell<*mut u8> =
+        const { core::cell::Cell::new(core::ptr::null_mut()) };
+}
+
+/// Makes the sancov `pc_guard` callbacks of the current thread record edges in `map` instead of the [`EDGES_MAP`].
+///
+/// Pass a null pointer to record in the [`EDGES_MAP`] again.
+///
+/// # Safety
+/// The `map` needs to be at least [`edges_max_num`] long, and valid for as long as this thread runs instrumented code.
+#[cfg(all(
+    unix,
+    feature = "sancov_pcguard_threads",
+    any(feature = "sancov_pcguard_edges", feature = "sancov_pcguard_hitcounts")
+))]
+pub unsafe fn set_thread_edges_map_ptr(map: *mut u8) {
+    THREAD_EDGES_MAP_PTR.set(map);
+}
+
+/// Gets the [`WorkerMaps`](libafl::executors::inprocess::threaded::WorkerMaps) for an
+/// [`InProcessThreadedExecutor`](libafl::executors::InProcessThreadedExecutor) with `workers` workers.
+///
+/// Each worker records its edges in its own map, which is copied into the [`edges_map_mut_slice`] for the observers.
+/// Only supports `sancov_pcguard_edges` and `sancov_pcguard_hitcounts`, not the ngram and ctx variants.
+///
+/// # Safety
+/// Same as for [`edges_map_mut_slice`], the observers need to observe the [`edges_map_mut_slice`] as well.
+#[cfg(all(
+    unix,
+    feature = "sancov_pcguard_threads",
+    any(feature = "sancov_pcguard_edges", feature = "sancov_pcguard_hitcounts")
+))]
+#[must_use]
+pub unsafe fn thread_edges_worker_maps(
+    workers: core::num::NonZeroUsize,
+) -> libafl::executors::inprocess::threaded::WorkerMaps {
+    libafl::executors::inprocess::threaded::WorkerMaps::new(
+        workers,
+        unsafe { edges_map_mut_slice() },
+        set_thread_edges_map_ptr,
+    )
+}
+
 /// Gets the current edges map pt
 /// It will usually take `EDGES_MAP`, but `EDGES_MAP_PTR`,
 /// if built with the `pointer_maps` feature.
@@ -0,0 +1,238 @@
+//! Runtime for directed fuzzing, used with the `DirectedDistance` pass of `libafl_cc`.
+//!
+//! The pass adds the distance of every instrumented basic block to the targets to
+//! [`__libafl_directed_distance_sum`], and counts the executed blocks in [`__libafl_directed_distance_count`].
+//! The [`DistanceObserver`] turns them into the mean distance of an execution, and the [`DistanceFeedback`]
+//! stores it in the testcases, for the simulated annealing of [`libafl::schedulers::powersched::PowerSchedule::directed`].
+//!
+//! The distances are kept in globals of the process running the target, so the [`DistanceObserver`] only works
+//! for targets linked into the fuzzer and run in-process, e.g. by an `InProcessExecutor`.
+//! For targets in a forkserver or a separate command, it never sees a distance.
+
+use alloc::borrow::Cow;
+
+use libafl::{
+    Error, HasMetadata,
+    corpus::Testcase,
+    executors::ExitKind,
+    feedbacks::{Feedback, StateInitializer},
+    observers::Observer,
+    schedulers::powersched::{DirectedMetadata, DistanceMetadata},
+};
+use libafl_bolts::{
+    Named,
+    tuples::{Handle, Handled, MatchName, MatchNameRef},
+};
+use serde::{Deserialize, Serialize};
+
+/// The fixed point scale of the distances emitted by the pass
+pub const DISTANCE_SCALE: f64 = 1000.0;
+
+/// The sum of the (scaled) distances of all basic blocks executed so far
+#[unsafe(no_mangle)]
+pub static mut __libafl_directed_distance_sum: u64 = 0;
+
+/// The number of basic blocks with a distance executed so far
+#[unsafe(no_mangle)]
+pub static mut __libafl_directed_distance_count: u64 = 0;
+
+/// An observer for the mean distance to the targets of the blocks executed by the last run.
+///
+/// It reads the distance globals of the fuzzer process, so it only works with in-process executors.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DistanceObserver {
+    name: Cow<'static, str>,
+    last_distance: Option<f64>,
+}
+
+impl DistanceObserver {
+    /// Creates a new [`DistanceObserver`] with the given name
+    #[must_use]
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name: Cow::from(name),
+            last_distance: None,
+        }
+    }
+
+    /// The mean distance of the last run, or `None` if no block with a distance was executed
+    #[must_use]
+    pub fn last_distance(&self) -> Option<f64> {
+        self.last_distance
+    }
+}
+
+impl Named for DistanceObserver {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<I, S> Observer<I, S> for DistanceObserver {
+    fn pre_exec(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        // # Safety
+        // The target is not running, nothing else touches these.
+        unsafe {
+            __libafl_directed_distance_sum = 0;
+            __libafl_directed_distance_count = 0;
+        }
+        self.last_distance = None;
+        Ok(())
+    }
+
+    #[expect(clippy::cast_precision_loss)]
+    fn post_exec(
+        &mut self,
+        _state: &mut S,
+        _input: &I,
+        _exit_kind: &ExitKind,
+    ) -> Result<(), Error> {
+        // # Safety
+        // The target is not running anymore, nothing else touches these.
+        let (sum, count) = unsafe {
+            (
+                __libafl_directed_distance_sum,
+                __libafl_directed_distance_count,
+            )
+        };
+        self.last_distance = if count == 0 {
+            None
+        } else {
+            Some(sum as f64 / count as f64 / DISTANCE_SCALE)
+        };
+        Ok(())
+    }
+}
+
+/// Nop feedback that annotates the distance to the targets in the new testcase, if any.
+/// For this feedback, the testcase is never interesting (use with an OR).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DistanceFeedback {
+    observer_handle: Handle<DistanceObserver>,
+}
+
+impl DistanceFeedback {
+    /// Creates a new [`DistanceFeedback`] for the given [`DistanceObserver`]
+    #[must_use]
+    pub fn new(observer: &DistanceObserver) -> Self {
+        Self {
+            observer_handle: observer.handle(),
+        }
+    }
+}
+
+impl Named for DistanceFeedback {
+    fn name(&self) -> &Cow<'static, str> {
+        self.observer_handle.name()
+    }
+}
+
+impl<S> StateInitializer<S> for DistanceFeedback
+where
+    S: HasMetadata,
+{
+    fn init_state(&mut self, state: &mut S) -> Result<(), Error> {
+        state.metadata_or_insert_with(DirectedMetadata::new);
+        Ok(())
+    }
+}
+
+impl<EM, I, OT, S> Feedback<EM, I, OT, S> for DistanceFeedback
+where
+    OT: MatchName,
+    S: HasMetadata,
+{
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        Ok(false)
+    }
+
+    fn append_metadata(
+        &mut self,
+        state: &mut S,
+        _manager: &mut EM,
+        observers: &OT,
+        testcase: &mut Testcase<I>,
+    ) -> Result<(), Error> {
+        let Some(observer) = observers.get(&self.observer_handle) else {
+            return Err(Error::illegal_state(
+                "Observer referenced by DistanceFeedback is not found in observers given to the fuzzer",
+            ));
+        };
+
+        if let Some(distance) = observer.last_distance() {
+            state
+                .metadata_or_insert_with(DirectedMetadata::new)
+                .update(distance);
+            testcase.add_metadata(DistanceMetadata::new(distance));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl::{
+        HasMetadata,
+        corpus::Testcase,
+        executors::ExitKind,
+        feedbacks::{Feedback, StateInitializer},
+        inputs::BytesInput,
+        observers::Observer,
+        schedulers::powersched::{DirectedMetadata, DistanceMetadata},
+        state::NopState,
+    };
+    use libafl_bolts::{serdeany::RegistryBuilder, tuples::tuple_list};
+
+    use super::{
+        __libafl_directed_distance_count, __libafl_directed_distance_sum, DistanceFeedback,
+        DistanceObserver,
+    };
+
+    #[test]
+    fn test_distance_observer() {
+        // # Safety
+        // No concurrency per testcase
+        unsafe {
+            RegistryBuilder::register::<DirectedMetadata>();
+            RegistryBuilder::register::<DistanceMetadata>();
+        }
+
+        let mut state = NopState::<BytesInput>::new();
+        let input = BytesInput::new(vec![0; 4]);
+        let mut observer = DistanceObserver::new("distance");
+        let mut feedback = DistanceFeedback::new(&observer);
+        feedback.init_state(&mut state).unwrap();
+
+        // A run that executes no block with a distance
+        observer.pre_exec(&mut state, &input).unwrap();
+        observer
+            .post_exec(&mut state, &input, &ExitKind::Ok)
+            .unwrap();
+        assert_eq!(observer.last_distance(), None);
+
+        // A run executing two blocks, at distance 1 and 3 of the targets
+        observer.pre_exec(&mut state, &input).unwrap();
+        unsafe {
+            __libafl_directed_distance_sum = 4000;
+            __libafl_directed_distance_count = 2;
+        }
+        observer
+            .post_exec(&mut state, &input, &ExitKind::Ok)
+            .unwrap();
+        assert_eq!(observer.last_distance(), Some(2.0));
+
+        let observers = tuple_list!(observer);
+        let mut testcase = Testcase::new(input);
+        feedback
+            .append_metadata(&mut state, &mut (), &observers, &mut testcase)
+            .unwrap();
+        assert!(
+            (testcase.metadata::<DistanceMetadata>().unwrap().distance() - 2.0).abs()
+                < f64::EPSILON
+        );
+        let directed = state.metadata::<DirectedMetadata>().unwrap();
+        assert!((directed.min_distance() - 2.0).abs() < f64::EPSILON);
+        assert!((directed.max_distance() - 2.0).abs() < f64::EPSILON);
+    }
+}
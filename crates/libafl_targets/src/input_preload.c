@@ -0,0 +1,307 @@
+/*
+ * LD_PRELOAD library serving the inputs of LibAFL's `CommandExecutor` with
+ * `InputLocation::Preload` to uninstrumented targets.
+ *
+ * The fuzzer writes each input to a SysV shared memory segment before spawning
+ * the target, prefixed by its length as native-endian u32. This library maps
+ * the segment at load time and then either
+ *  - answers `open`/`openat`/`fopen` of LIBAFL_PRELOAD_PATH with a memfd
+ *    holding the input, or
+ *  - answers `read`/`recv`/`recvfrom` on LIBAFL_PRELOAD_FD with the input. If
+ *    the fd is already open at load time (e.g. stdin), it is replaced by a
+ *    memfd holding the input instead, so buffered stdio reads work as well.
+ *    Otherwise, the fd is only served once the target creates it with
+ *    `socket`/`accept`/`accept4`, until it is closed again, so unrelated files
+ *    reusing the fd number are left alone. Fds created with `dup2`/`dup3` or
+ *    received over unix sockets are not tracked.
+ */
+
+#undef _FORTIFY_SOURCE
+#define _GNU_SOURCE
+#include <dlfcn.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <stdarg.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/mman.h>
+#include <sys/shm.h>
+#include <sys/socket.h>
+#include <sys/types.h>
+#include <unistd.h>
+
+/* Keep in sync with `libafl::executors::command` */
+#define LIBAFL_PRELOAD_SHM_ENV_VAR "__LIBAFL_PRELOAD_SHM_ID"
+#define LIBAFL_PRELOAD_SHM_SIZE_ENV_VAR "__LIBAFL_PRELOAD_SHM_ID_SIZE"
+#define LIBAFL_PRELOAD_PATH_ENV_VAR "__LIBAFL_PRELOAD_PATH"
+#define LIBAFL_PRELOAD_FD_ENV_VAR "__LIBAFL_PRELOAD_FD"
+#define LIBAFL_PRELOAD_HDR_SIZE 4
+
+static const uint8_t *input_buf;
+static size_t         input_len;
+static size_t         input_pos;
+static const char    *input_path;
+static int            input_fd = -1;
+/* `input_fd` was open at load time and got replaced by a memfd */
+static int input_fd_replaced;
+/* `input_fd` is a socket the target created, reads from it are served the input */
+static int input_fd_served;
+
+#define REAL(name) \
+  static __typeof__(name) *real_##name; \
+  if (!real_##name) { real_##name = dlsym(RTLD_NEXT, #name); }
+
+static int input_memfd(int cloexec) {
+  int fd = memfd_create("libafl_input", cloexec ? MFD_CLOEXEC : 0);
+  if (fd < 0) { return -1; }
+  size_t written = 0;
+  while (written < input_len) {
+    ssize_t ret = write(fd, input_buf + written, input_len - written);
+    if (ret < 0) {
+      if (errno == EINTR) { continue; }
+      int err = errno;
+      close(fd);
+      errno = err;
+      return -1;
+    }
+    written += (size_t)ret;
+  }
+  lseek(fd, 0, SEEK_SET);
+  return fd;
+}
+
+static int is_input_path(const char *pathname) {
+  return input_buf && input_path && pathname && !strcmp(pathname, input_path);
+}
+
+static ssize_t serve_input(void *buf, size_t count, int peek) {
+  size_t len = input_len - input_pos;
+  if (len > count) { len = count; }
+  memcpy(buf, input_buf + input_pos, len);
+  if (!peek) { input_pos += len; }
+  return (ssize_t)len;
+}
+
+__attribute__((constructor)) static void libafl_preload_init(void) {
+  const char *id = getenv(LIBAFL_PRELOAD_SHM_ENV_VAR);
+  const char *size = getenv(LIBAFL_PRELOAD_SHM_SIZE_ENV_VAR);
+  if (!id || !size) { return; }
+
+  uint8_t *shm = shmat(atoi(id), NULL, SHM_RDONLY);
+  if (shm == (void *)-1) {
+    perror("[libafl] input preload: shmat");
+    abort();
+  }
+  size_t   shm_size = strtoul(size, NULL, 10);
+  uint32_t len;
+  memcpy(&len, shm, sizeof(len));
+  if (shm_size < LIBAFL_PRELOAD_HDR_SIZE) { shm_size = LIBAFL_PRELOAD_HDR_SIZE; }
+  if (len > shm_size - LIBAFL_PRELOAD_HDR_SIZE) {
+    len = shm_size - LIBAFL_PRELOAD_HDR_SIZE;
+  }
+  input_buf = shm + LIBAFL_PRELOAD_HDR_SIZE;
+  input_len = len;
+
+  input_path = getenv(LIBAFL_PRELOAD_PATH_ENV_VAR);
+  const char *fd = getenv(LIBAFL_PRELOAD_FD_ENV_VAR);
+  if (fd) {
+    input_fd = atoi(fd);
+    if (fcntl(input_fd, F_GETFD) >= 0) {
+      int memfd = input_memfd(0);
+      if (memfd < 0 || dup2(memfd, input_fd) < 0) {
+        perror("[libafl] input preload: replacing the input fd");
+        abort();
+      }
+      close(memfd);
+      input_fd_replaced = 1;
+    }
+  }
+}
+
+/* Opening the input path */
+
+/* `O_TMPFILE` includes the bits of `O_DIRECTORY`, test it like glibc's `__OPEN_NEEDS_MODE` */
+static mode_t open_mode(int flags, va_list ap) {
+  if ((flags & O_CREAT) || (flags & O_TMPFILE) == O_TMPFILE) {
+    return (mode_t)va_arg(ap, int);
+  }
+  return 0;
+}
+
+int open(const char *pathname, int flags, ...) {
+  if (is_input_path(pathname)) { return input_memfd(flags & O_CLOEXEC); }
+  va_list ap;
+  va_start(ap, flags);
+  mode_t mode = open_mode(flags, ap);
+  va_end(ap);
+  REAL(open);
+  return real_open(pathname, flags, mode);
+}
+
+int open64(const char *pathname, int flags, ...) {
+  if (is_input_path(pathname)) { return input_memfd(flags & O_CLOEXEC); }
+  va_list ap;
+  va_start(ap, flags);
+  mode_t mode = open_mode(flags, ap);
+  va_end(ap);
+  REAL(open64);
+  return real_open64(pathname, flags, mode);
+}
+
+int openat(int dirfd, const char *pathname, int flags, ...) {
+  if (is_input_path(pathname)) { return input_memfd(flags & O_CLOEXEC); }
+  va_list ap;
+  va_start(ap, flags);
+  mode_t mode = open_mode(flags, ap);
+  va_end(ap);
+  REAL(openat);
+  return real_openat(dirfd, pathname, flags, mode);
+}
+
+int openat64(int dirfd, const char *pathname, int flags, ...) {
+  if (is_input_path(pathname)) { return input_memfd(flags & O_CLOEXEC); }
+  va_list ap;
+  va_start(ap, flags);
+  mode_t mode = open_mode(flags, ap);
+  va_end(ap);
+  REAL(openat64);
+  return real_openat64(dirfd, pathname, flags, mode);
+}
+
+/* Used instead of `open` by targets built with `_FORTIFY_SOURCE` */
+int __open_2(const char *pathname, int flags);
+int __open64_2(const char *pathname, int flags);
+
+int __open_2(const char *pathname, int flags) {
+  if (is_input_path(pathname)) { return input_memfd(flags & O_CLOEXEC); }
+  REAL(__open_2);
+  return real___open_2(pathname, flags);
+}
+
+int __open64_2(const char *pathname, int flags) {
+  if (is_input_path(pathname)) { return input_memfd(flags & O_CLOEXEC); }
+  REAL(__open64_2);
+  return real___open64_2(pathname, flags);
+}
+
+FILE *fopen(const char *pathname, const char *mode) {
+  if (is_input_path(pathname)) {
+    int fd = input_memfd(strchr(mode, 'e') != NULL);
+    return fd < 0 ? NULL : fdopen(fd, mode);
+  }
+  REAL(fopen);
+  return real_fopen(pathname, mode);
+}
+
+FILE *fopen64(const char *pathname, const char *mode) {
+  if (is_input_path(pathname)) {
+    int fd = input_memfd(strchr(mode, 'e') != NULL);
+    return fd < 0 ? NULL : fdopen(fd, mode);
+  }
+  REAL(fopen64);
+  return real_fopen64(pathname, mode);
+}
+
+/* Tracking the lifetime of the input fd */
+
+static int is_input_fd(int fd) {
+  return input_buf && fd == input_fd && (input_fd_replaced || input_fd_served);
+}
+
+static int track_input_fd(int fd) {
+  if (input_buf && fd >= 0 && fd == input_fd) {
+    input_fd_replaced = 0;
+    input_fd_served = 1;
+  }
+  return fd;
+}
+
+int socket(int domain, int type, int protocol) {
+  REAL(socket);
+  return track_input_fd(real_socket(domain, type, protocol));
+}
+
+int accept(int sockfd, struct sockaddr *addr, socklen_t *addrlen) {
+  REAL(accept);
+  return track_input_fd(real_accept(sockfd, addr, addrlen));
+}
+
+int accept4(int sockfd, struct sockaddr *addr, socklen_t *addrlen, int flags) {
+  REAL(accept4);
+  return track_input_fd(real_accept4(sockfd, addr, addrlen, flags));
+}
+
+int close(int fd) {
+  if (fd == input_fd) {
+    input_fd_replaced = 0;
+    input_fd_served = 0;
+  }
+  REAL(close);
+  return real_close(fd);
+}
+
+/* Reading from the input fd */
+
+ssize_t read(int fd, void *buf, size_t count) {
+  if (input_buf && fd == input_fd && input_fd_served) {
+    return serve_input(buf, count, 0);
+  }
+  REAL(read);
+  return real_read(fd, buf, count);
+}
+
+/* Used instead of `read` by targets built with `_FORTIFY_SOURCE` */
+ssize_t __read_chk(int fd, void *buf, size_t nbytes, size_t buflen);
+
+ssize_t __read_chk(int fd, void *buf, size_t nbytes, size_t buflen) {
+  if (nbytes > buflen) { abort(); }
+  if (input_buf && fd == input_fd && input_fd_served) {
+    return serve_input(buf, nbytes, 0);
+  }
+  REAL(__read_chk);
+  return real___read_chk(fd, buf, nbytes, buflen);
+}
+
+static ssize_t recv_input(int fd, void *buf, size_t len, int flags) {
+  int peek = flags & MSG_PEEK;
+  if (input_fd_served) { return serve_input(buf, len, peek); }
+  /* The replaced fd is a memfd, not a socket */
+  REAL(read);
+  if (!peek) { return real_read(fd, buf, len); }
+  off_t pos = lseek(fd, 0, SEEK_CUR);
+  return pos < 0 ? -1 : pread(fd, buf, len, pos);
+}
+
+ssize_t recv(int fd, void *buf, size_t len, int flags) {
+  if (is_input_fd(fd)) { return recv_input(fd, buf, len, flags); }
+  REAL(recv);
+  return real_recv(fd, buf, len, flags);
+}
+
+ssize_t recvfrom(int fd, void *buf, size_t len, int flags,
+                 struct sockaddr *src_addr, socklen_t *addrlen) {
+  if (is_input_fd(fd)) {
+    if (src_addr && addrlen) { *addrlen = 0; }
+    return recv_input(fd, buf, len, flags);
+  }
+  REAL(recvfrom);
+  return real_recvfrom(fd, buf, len, flags, src_addr, addrlen);
+}
+
+/* Used instead of `recv`/`recvfrom` by targets built with `_FORTIFY_SOURCE` */
+ssize_t __recv_chk(int fd, void *buf, size_t len, size_t buflen, int flags);
+ssize_t __recvfrom_chk(int fd, void *buf, size_t len, size_t buflen, int flags,
+                       struct sockaddr *src_addr, socklen_t *addrlen);
+
+ssize_t __recv_chk(int fd, void *buf, size_t len, size_t buflen, int flags) {
+  if (len > buflen) { abort(); }
+  return recv(fd, buf, len, flags);
+}
+
+ssize_t __recvfrom_chk(int fd, void *buf, size_t len, size_t buflen, int flags,
+                       struct sockaddr *src_addr, socklen_t *addrlen) {
+  if (len > buflen) { abort(); }
+  return recvfrom(fd, buf, len, flags, src_addr, addrlen);
+}
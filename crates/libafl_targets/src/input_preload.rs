@@ -0,0 +1,129 @@
+//! The `LD_PRELOAD` library serving inputs to uninstrumented targets, for [`InputLocation::Preload`].
+//!
+//! The library is compiled by the build script. It is not linked into the fuzzer, but preloaded into
+//! the targets spawned by the `CommandExecutor`, so it has to stay next to the fuzzer binary when you
+//! move the latter to another machine. Copy it over and point [`InputLocation::Preload`] to the copy then.
+
+use libafl_bolts::{InputLocation, PreloadSource};
+
+/// The path of the `LD_PRELOAD` input library, as built for this crate
+pub const INPUT_PRELOAD_LIBRARY: &str = concat!(env!("OUT_DIR"), "/libafl_input_preload.so");
+
+/// An [`InputLocation`] serving the input from the [`INPUT_PRELOAD_LIBRARY`]
+/// whenever the target reads from `source`
+#[must_use]
+pub fn preload_input_location(source: PreloadSource) -> InputLocation {
+    InputLocation::Preload {
+        source,
+        library: INPUT_PRELOAD_LIBRARY.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+    use std::process::Command;
+
+    use libafl::{
+        events::NopEventManager,
+        executors::{CommandExecutor, Executor, HasObservers, StdChildArgs},
+        fuzzer::NopFuzzer,
+        inputs::BytesInput,
+        observers::StdOutObserver,
+        state::NopState,
+    };
+    use libafl_bolts::{
+        PreloadSource, StdTargetArgs,
+        tuples::{Handled, tuple_list},
+    };
+
+    use super::preload_input_location;
+
+    fn run(program: &str, args: &[&str], source: PreloadSource, input: &[u8]) -> Option<Vec<u8>> {
+        let stdout = StdOutObserver::new_piped("stdout".into()).unwrap();
+        let handle = stdout.handle();
+        let mut executor = CommandExecutor::builder()
+            .program(program)
+            .args(args)
+            .stdout_observer(handle.clone())
+            .input(preload_input_location(source))
+            .build(tuple_list!(stdout))
+            .unwrap();
+        executor
+            .run_target(
+                &mut NopFuzzer::new(),
+                &mut NopState::<BytesInput>::new(),
+                &mut NopEventManager::new(),
+                &BytesInput::new(input.to_vec()),
+            )
+            .unwrap();
+        executor.observers()[&handle].output.clone()
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_input_preload() {
+        let path = "/nonexistent/libafl/input";
+        assert_eq!(
+            run(
+                "cat",
+                &[path],
+                PreloadSource::Path(path.into()),
+                b"from a path"
+            ),
+            Some(b"from a path".to_vec())
+        );
+        assert_eq!(
+            run("cat", &[], PreloadSource::Fd(0), b"from stdin"),
+            Some(b"from stdin".to_vec())
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_input_preload_too_large() {
+        let mut executor = CommandExecutor::builder()
+            .max_input_size(4)
+            .program("cat")
+            .input(preload_input_location(PreloadSource::Fd(0)))
+            .build(())
+            .unwrap();
+        let mut run = |input: &[u8]| {
+            executor.run_target(
+                &mut NopFuzzer::new(),
+                &mut NopState::<BytesInput>::new(),
+                &mut NopEventManager::new(),
+                &BytesInput::new(input.to_vec()),
+            )
+        };
+        assert!(run(b"four").is_ok());
+        assert!(run(b"five!").is_err());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_input_preload_socket() {
+        if Command::new("perl").arg("-v").output().is_err() {
+            log::warn!("Skipping the input preload socket test, perl is not installed");
+            return;
+        }
+        // A file reusing the fd number before the socket is created reads its own contents
+        let script = r#"use Socket;
+            open(my $file, "<", "/dev/zero") or die;
+            sysread($file, my $zeros, 4);
+            print fileno($file), unpack("H*", $zeros), ";";
+            close($file);
+            socket(my $socket, PF_INET, SOCK_DGRAM, 0) or die;
+            recv($socket, my $input, 100, 0);
+            print fileno($socket), $input;"#;
+        assert_eq!(
+            run(
+                "perl",
+                &["-e", script],
+                PreloadSource::Fd(3),
+                b"from a socket"
+            ),
+            Some(b"300000000;3from a socket".to_vec())
+        );
+    }
+}
@@ -115,6 +115,12 @@ pub mod call;
 #[cfg(feature = "function-logging")]
 pub use call::*;
 
+/// The module for directed fuzzing, used with the directed distance pass
+#[cfg(feature = "directed")]
+pub mod directed;
+#[cfg(feature = "directed")]
+pub use directed::*;
+
 /// runtime related to comparisons
 pub mod cmps;
 pub use cmps::*;
@@ -131,3 +137,14 @@ pub use windows_asan::*;
 pub mod forkserver;
 #[cfg(all(unix, feature = "std", feature = "forkserver"))]
 pub use forkserver::*;
+
+#[cfg(all(target_os = "linux", feature = "input_preload"))]
+pub mod input_preload;
+#[cfg(all(target_os = "linux", feature = "input_preload"))]
+pub use input_preload::*;
+
+/// The target side of the persistent protocol of the `PersistentCommandExecutor`
+#[cfg(all(unix, feature = "persistent"))]
+pub mod persistent;
+#[cfg(all(unix, feature = "persistent"))]
+pub use persistent::*;
@@ -0,0 +1,105 @@
+#ifndef __LIBAFL_TARGETS_PERSISTENT__
+#define __LIBAFL_TARGETS_PERSISTENT__
+
+/*
+ * The target side of the persistent protocol of LibAFL's
+ * `PersistentCommandExecutor`. Header-only, so targets which cannot link the
+ * forkserver runtime can simply include it:
+ *
+ *   int main(int argc, char **argv) {
+ *     const uint8_t *data;
+ *     size_t         len;
+ *     if (!libafl_persistent_init()) {
+ *       // Not running under the fuzzer, run once on a file instead
+ *       ...
+ *     }
+ *     while (libafl_persistent_next(&data, &len)) {
+ *       target(data, len);
+ *     }
+ *     return 0;
+ *   }
+ *
+ * Keep looping until `libafl_persistent_next` returns 0: a child which exits
+ * on its own is restarted by the fuzzer, but its last input is lost.
+ */
+
+#include <errno.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+#include <sys/shm.h>
+#include <unistd.h>
+
+/* Keep in sync with `libafl::executors::command` */
+#define LIBAFL_PERSISTENT_SHM_ENV_VAR "__LIBAFL_PERSISTENT_SHM_ID"
+#define LIBAFL_PERSISTENT_SHM_SIZE_ENV_VAR "__LIBAFL_PERSISTENT_SHM_ID_SIZE"
+#define LIBAFL_PERSISTENT_FD 196
+#define LIBAFL_PERSISTENT_HELLO 0x4c414650u
+#define LIBAFL_PERSISTENT_HDR_SIZE 4
+
+static uint8_t *libafl_persistent_shm;
+static size_t   libafl_persistent_shm_size;
+static int      libafl_persistent_started;
+
+static int libafl_persistent_read_u32(uint32_t *val) {
+  size_t done = 0;
+  while (done < sizeof(*val)) {
+    ssize_t ret = read(LIBAFL_PERSISTENT_FD, (uint8_t *)val + done,
+                       sizeof(*val) - done);
+    if (ret < 0 && errno == EINTR) { continue; }
+    if (ret <= 0) { return 0; }
+    done += (size_t)ret;
+  }
+  return 1;
+}
+
+static int libafl_persistent_write_u32(uint32_t val) {
+  size_t done = 0;
+  while (done < sizeof(val)) {
+    ssize_t ret = write(LIBAFL_PERSISTENT_FD + 1, (uint8_t *)&val + done,
+                        sizeof(val) - done);
+    if (ret < 0 && errno == EINTR) { continue; }
+    if (ret <= 0) { return 0; }
+    done += (size_t)ret;
+  }
+  return 1;
+}
+
+/* Maps the input shared memory and tells the fuzzer the target is ready.
+   Returns 0 if the target does not run under the fuzzer. */
+static int libafl_persistent_init(void) {
+  const char *id = getenv(LIBAFL_PERSISTENT_SHM_ENV_VAR);
+  const char *size = getenv(LIBAFL_PERSISTENT_SHM_SIZE_ENV_VAR);
+  if (!id || !size) { return 0; }
+
+  void *shm = shmat(atoi(id), NULL, 0);
+  if (shm == (void *)-1) { return 0; }
+  libafl_persistent_shm = (uint8_t *)shm;
+  libafl_persistent_shm_size = (size_t)strtoull(size, NULL, 10);
+
+  return libafl_persistent_write_u32(LIBAFL_PERSISTENT_HELLO);
+}
+
+/* Waits for the next input, after reporting the previous one as done.
+   Returns 0 once the fuzzer is gone, the target should exit then. */
+static int libafl_persistent_next(const uint8_t **data, size_t *len) {
+  uint32_t msg;
+  uint32_t input_len;
+
+  if (!libafl_persistent_shm) { return 0; }
+  if (libafl_persistent_started && !libafl_persistent_write_u32(0)) {
+    return 0;
+  }
+  libafl_persistent_started = 1;
+  if (!libafl_persistent_read_u32(&msg)) { return 0; }
+
+  input_len = *(uint32_t *)libafl_persistent_shm;
+  if (input_len > libafl_persistent_shm_size - LIBAFL_PERSISTENT_HDR_SIZE) {
+    input_len = libafl_persistent_shm_size - LIBAFL_PERSISTENT_HDR_SIZE;
+  }
+  *data = libafl_persistent_shm + LIBAFL_PERSISTENT_HDR_SIZE;
+  *len = input_len;
+  return 1;
+}
+
+#endif
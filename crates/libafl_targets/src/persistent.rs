@@ -0,0 +1,177 @@
+//! The target side of the persistent protocol of the `PersistentCommandExecutor`.
+//!
+//! A long-lived target loops over the inputs the fuzzer delivers in shared memory, without linking the forkserver
+//! runtime. Targets in C include `libafl_persistent.h` instead, which implements the same protocol.
+
+use core::mem::ManuallyDrop;
+use std::{
+    fs::File,
+    io::{ErrorKind, Read, Write},
+    os::fd::{FromRawFd, RawFd},
+};
+
+use libafl::{
+    Error,
+    executors::{
+        command::{PERSISTENT_FD, PERSISTENT_HELLO, PERSISTENT_SHM_ENV_VAR},
+        forkserver::SHMEM_FUZZ_HDR_SIZE,
+    },
+};
+use libafl_bolts::shmem::{ShMemProvider, UnixShMem, UnixShMemProvider};
+
+/// The pinned fd the fuzzer writes its control messages to
+const CTL_FD: RawFd = PERSISTENT_FD;
+/// The pinned fd the target writes its status messages to
+const ST_FD: RawFd = PERSISTENT_FD + 1;
+
+/// Borrows one of the pinned fds of the protocol as [`File`], without closing it afterwards
+fn pinned_fd(fd: RawFd) -> ManuallyDrop<File> {
+    // # Safety
+    // The fuzzer opened the pinned fds before `exec`, and they are never closed by us.
+    ManuallyDrop::new(unsafe { File::from_raw_fd(fd) })
+}
+
+/// The inputs of a persistent target, delivered by the `PersistentCommandExecutor`
+#[derive(Debug)]
+pub struct PersistentInput {
+    shmem: UnixShMem,
+    started: bool,
+}
+
+impl PersistentInput {
+    /// Maps the input shared memory and tells the fuzzer the target is ready.
+    ///
+    /// Returns `None` if the target does not run under the `PersistentCommandExecutor`.
+    pub fn from_env() -> Result<Option<Self>, Error> {
+        if std::env::var_os(PERSISTENT_SHM_ENV_VAR).is_none() {
+            return Ok(None);
+        }
+        let shmem = UnixShMemProvider::new()?.existing_from_env(PERSISTENT_SHM_ENV_VAR)?;
+        pinned_fd(ST_FD).write_all(&PERSISTENT_HELLO.to_ne_bytes())?;
+        Ok(Some(Self {
+            shmem,
+            started: false,
+        }))
+    }
+
+    /// Waits for the next input, after reporting the previous one as done.
+    ///
+    /// Returns `None` once the fuzzer is gone, the target should exit then.
+    /// A target exiting on its own gets restarted, but its last input is lost.
+    pub fn next_input(&mut self) -> Result<Option<&[u8]>, Error> {
+        if self.started {
+            pinned_fd(ST_FD).write_all(&0_u32.to_ne_bytes())?;
+        }
+        self.started = true;
+
+        let mut msg = [0_u8; 4];
+        match pinned_fd(CTL_FD).read_exact(&mut msg) {
+            Ok(()) => (),
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err.into()),
+        }
+
+        let (hdr, input) = self.shmem.split_at(SHMEM_FUZZ_HDR_SIZE);
+        let len = u32::from_ne_bytes(hdr.try_into().unwrap()) as usize;
+        Ok(Some(&input[..len.min(input.len())]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env,
+        fs::{self, OpenOptions},
+        io::Write,
+        process,
+    };
+
+    use libafl::{
+        Error,
+        events::NopEventManager,
+        executors::{CommandExecutor, Executor, ExitKind, command::PersistentCommandExecutor},
+        fuzzer::NopFuzzer,
+        inputs::BytesInput,
+        state::NopState,
+    };
+    use libafl_bolts::{
+        StdTargetArgs,
+        shmem::{ShMemProvider, UnixShMem, UnixShMemProvider},
+    };
+
+    use super::PersistentInput;
+
+    /// Runs one input in the persistent child
+    fn run(
+        executor: &mut PersistentCommandExecutor<BytesInput, (), NopState<BytesInput>, UnixShMem>,
+        input: &[u8],
+    ) -> Result<ExitKind, Error> {
+        executor.run_target(
+            &mut NopFuzzer::new(),
+            &mut NopState::new(),
+            &mut NopEventManager::new(),
+            &BytesInput::new(input.to_vec()),
+        )
+    }
+
+    /// Makes [`persistent_target`] append its inputs to the file at this path
+    const TARGET_ENV: &str = "LIBAFL_TEST_PERSISTENT_OUT";
+
+    /// The target of [`test_persistent_round_trip`], which runs this test binary again.
+    ///
+    /// Appends each input to a file, and crashes on `crash`.
+    #[test]
+    fn persistent_target() {
+        let Some(out) = env::var_os(TARGET_ENV) else {
+            return;
+        };
+        let mut out = OpenOptions::new().append(true).open(out).unwrap();
+        let mut input = PersistentInput::from_env().unwrap().unwrap();
+        while let Some(data) = input.next_input().unwrap() {
+            out.write_all(data).unwrap();
+            out.write_all(b"\n").unwrap();
+            if data == b"crash" {
+                process::abort();
+            }
+        }
+        process::exit(0);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_persistent_round_trip() {
+        let out = env::temp_dir().join(format!("libafl_persistent_out_{}", process::id()));
+        fs::write(&out, b"").unwrap();
+
+        let mut executor = CommandExecutor::builder()
+            .program(env::current_exe().unwrap())
+            .args([
+                "--exact",
+                "persistent::tests::persistent_target",
+                "--test-threads=1",
+            ])
+            .env(TARGET_ENV, &out)
+            .max_input_size(8)
+            .build_persistent::<BytesInput, (), NopState<BytesInput>, _>(
+                &mut UnixShMemProvider::new().unwrap(),
+                (),
+            )
+            .unwrap();
+        assert_eq!(executor.max_input_size(), 8);
+
+        assert_eq!(run(&mut executor, b"hello").unwrap(), ExitKind::Ok);
+        assert_eq!(run(&mut executor, b"world").unwrap(), ExitKind::Ok);
+        // Too large inputs are not truncated
+        assert!(run(&mut executor, b"too large").is_err());
+        assert_eq!(run(&mut executor, b"crash").unwrap(), ExitKind::Crash);
+        // A new child takes over after the crash
+        assert_eq!(run(&mut executor, b"again").unwrap(), ExitKind::Ok);
+        let first_pid = executor.child_pid();
+        assert_eq!(run(&mut executor, b"").unwrap(), ExitKind::Ok);
+        assert_eq!(executor.child_pid(), first_pid);
+        drop(executor);
+
+        assert_eq!(fs::read(&out).unwrap(), b"hello\nworld\ncrash\nagain\n\n");
+        fs::remove_file(&out).unwrap();
+    }
+}
@@ -26,6 +26,12 @@ use crate::EDGES_MAP_DEFAULT_SIZE;
 ))]
 use crate::coverage::EDGES_MAP;
 use crate::coverage::MAX_EDGES_FOUND;
+#[cfg(all(
+    unix,
+    feature = "sancov_pcguard_threads",
+    any(feature = "sancov_pcguard_edges", feature = "sancov_pcguard_hitcounts")
+))]
+use crate::coverage::THREAD_EDGES_MAP_PTR;
 #[cfg(feature = "pointer_maps")]
 use crate::{EDGES_MAP_ALLOCATED_SIZE, coverage::EDGES_MAP_PTR};
 
@@ -229,6 +235,25 @@ pub unsafe extern "C" fn __sanitizer_cov_trace_pc_guard(guard: *mut u32) {
             // println!("Wrinting to {} {}", pos, EDGES_MAP_DEFAULT_SIZE);
         }
 
+        #[cfg(all(unix, feature = "sancov_pcguard_threads"))]
+        #[cfg(any(feature = "sancov_pcguard_hitcounts", feature = "sancov_pcguard_edges"))]
+        {
+            let thread_map = THREAD_EDGES_MAP_PTR.get();
+            if !thread_map.is_null() {
+                #[cfg(feature = "sancov_pcguard_edges")]
+                {
+                    thread_map.add(pos).write(1);
+                }
+                #[cfg(feature = "sancov_pcguard_hitcounts")]
+                {
+                    let addr = thread_map.add(pos);
+                    let val = addr.read().wrapping_add(1);
+                    addr.write(val);
+                }
+                return;
+            }
+        }
+
         #[cfg(feature = "pointer_maps")]
         {
             #[cfg(feature = "sancov_pcguard_edges")]
@@ -355,3 +380,38 @@ pub fn sanitizer_cov_pc_table<'a>() -> impl Iterator<Item = &'a [PcTableEntry]>
         pc_tables.iter().copied()
     }
 }
+
+/// Maps the edges of the coverage map to their source locations, by symbolizing the PC tables.
+///
+/// The edges are numbered in the order of the tables, as the guards are, so the resulting map can be used for the
+/// coverage reports of [`libafl::stages::CoverageReportStage`] on the edges map.
+/// This requires a target built with `-fsanitize-coverage=pc-table` and debug info.
+#[cfg(feature = "coverage_report")]
+#[must_use]
+pub fn sanitizer_cov_pc_source_map() -> libafl::stages::EdgeSourceMap {
+    let mut source_map = libafl::stages::EdgeSourceMap::new();
+    let mut offset = 0;
+    for table in sanitizer_cov_pc_table() {
+        for (idx, entry) in table.iter().enumerate() {
+            let mut location = None;
+            backtrace::resolve(entry.addr() as *mut core::ffi::c_void, |symbol| {
+                // the innermost frame comes first, which is the one the line belongs to
+                if location.is_none() {
+                    location = Some(libafl::stages::SourceLocation {
+                        function: symbol.name().map_or_else(
+                            || format!("{:#x}", entry.addr()),
+                            |name| format!("{name:#}"),
+                        ),
+                        file: symbol.filename().map(std::path::Path::to_path_buf),
+                        line: symbol.lineno(),
+                    });
+                }
+            });
+            if let Some(location) = location {
+                source_map.insert(offset + idx, location);
+            }
+        }
+        offset += table.len();
+    }
+    source_map
+}
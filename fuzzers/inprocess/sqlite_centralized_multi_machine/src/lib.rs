@@ -9,8 +9,10 @@ use clap::{self, Parser};
 use libafl::{
     corpus::{Corpus, InMemoryCorpus, OnDiskCorpus},
     events::{
-        centralized::CentralizedEventManager, launcher::CentralizedLauncher,
-        multi_machine::NodeDescriptor, ClientDescription, EventConfig,
+        centralized::CentralizedEventManager,
+        launcher::CentralizedLauncher,
+        multi_machine::{NodeDescriptor, PeerDirectory},
+        ClientDescription, EventConfig,
     },
     executors::{inprocess::InProcessExecutor, ExitKind},
     feedback_or, feedback_or_fast,
@@ -131,6 +133,22 @@ struct Opt {
         default_value = None
     )]
     node_listening_port: Option<u16>,
+
+    #[arg(
+        long,
+        help = "A directory shared by all nodes to find a parent in, instead of PARENT_ADDR",
+        name = "PEER_DIR",
+        default_value = None
+    )]
+    peer_dir: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "The host name or IP other nodes reach this node at, to register in PEER_DIR",
+        name = "ADVERTISED_HOST",
+        default_value = None
+    )]
+    advertised_host: Option<String>,
 }
 
 /// The main fn, `no_mangle` as it is a C symbol
@@ -278,6 +296,13 @@ pub extern "C" fn libafl_main() {
         node_description.node_listening_port = opt.node_listening_port;
     }
 
+    node_description.discovery = opt.peer_dir.map(|peer_dir| {
+        PeerDirectory::builder()
+            .path(peer_dir)
+            .advertised_host(opt.advertised_host)
+            .build()
+    });
+
     match CentralizedLauncher::builder()
         .shmem_provider(shmem_provider)
         .configuration(EventConfig::from_name("default"))
@@ -0,0 +1,137 @@
+//! Inspect, export, and replay event logs written by the `EventLogHook` broker hook.
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use clap::{Parser, Subcommand};
+use libafl::{
+    Error,
+    events::{Event, EventLogReader, EventLogRecord},
+    inputs::{BytesInput, Input},
+};
+use libafl_bolts::{
+    llmp::LlmpClient,
+    shmem::{ShMemProvider, StdShMemProvider},
+};
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+#[command(
+    name = "event_log_replay",
+    about,
+    long_about = "Inspect, export, and replay LibAFL broker event logs"
+)]
+pub struct Opt {
+    #[arg(short, long, help = "The event log to read")]
+    pub log: PathBuf,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// List all records of the log
+    Dump,
+    /// Write all logged testcases and objectives to directories, in the order they were found
+    Export {
+        #[arg(short, long, help = "Output folder, testcases and objectives go to subfolders")]
+        out_dir: PathBuf,
+    },
+    /// Send all logged messages to a running broker, in order
+    Replay {
+        #[arg(short, long, help = "The port of the broker", default_value_t = 1337)]
+        port: u16,
+    },
+}
+
+fn describe(record: &EventLogRecord) -> Result<String, Error> {
+    let Some(event) = record.event::<BytesInput>()? else {
+        return Ok(format!("tag {:?}", record.tag));
+    };
+    Ok(match event.event() {
+        Event::NewTestcase {
+            input, corpus_size, ..
+        } => format!(
+            "{} ({} bytes, corpus size {corpus_size})",
+            event.event().name(),
+            input.as_ref().len()
+        ),
+        Event::Objective { objective_size, .. } => format!(
+            "{} (objective size {objective_size})",
+            event.event().name()
+        ),
+        Event::Log { message, .. } => format!("{}: {message}", event.event().name()),
+        event => event.name().to_string(),
+    })
+}
+
+fn dump(reader: EventLogReader<impl io::Read>) -> Result<(), Error> {
+    let mut stdout = io::stdout().lock();
+    for (idx, record) in reader.enumerate() {
+        let record = record?;
+        writeln!(
+            stdout,
+            "{idx:>8} {:>12.3}s {:?} {}",
+            record.time.as_secs_f64(),
+            record.client_id,
+            describe(&record)?
+        )?;
+    }
+    Ok(())
+}
+
+fn export(reader: EventLogReader<impl io::Read>, out_dir: &Path) -> Result<(), Error> {
+    let queue = out_dir.join("queue");
+    let crashes = out_dir.join("crashes");
+    fs::create_dir_all(&queue)?;
+    fs::create_dir_all(&crashes)?;
+
+    let (mut testcases, mut objectives) = (0_usize, 0_usize);
+    for record in reader {
+        let record = record?;
+        let Some(event) = record.event::<BytesInput>()? else {
+            continue;
+        };
+        match event.event() {
+            Event::NewTestcase { input, .. } => {
+                let name = format!("id_{testcases:06}_client_{}", record.client_id.0);
+                input.to_file(queue.join(name))?;
+                testcases += 1;
+            }
+            Event::Objective {
+                input: Some(input), ..
+            } => {
+                let name = format!("id_{objectives:06}_client_{}", record.client_id.0);
+                input.to_file(crashes.join(name))?;
+                objectives += 1;
+            }
+            _ => {}
+        }
+    }
+    println!("Exported {testcases} testcases and {objectives} objectives");
+    Ok(())
+}
+
+fn replay(mut reader: EventLogReader<impl io::Read>, port: u16) -> Result<(), Error> {
+    let mut client = LlmpClient::create_attach_to_tcp(StdShMemProvider::new()?, port)?;
+    let count = reader.replay_to_client(&mut client)?;
+    client.sender_mut().send_exiting()?;
+    client.await_safe_to_unmap_blocking();
+    println!("Replayed {count} messages");
+    Ok(())
+}
+
+fn main() -> Result<(), Error> {
+    env_logger::init();
+    let opts = Opt::parse();
+
+    let reader = EventLogReader::open(&opts.log)?;
+    match opts.command {
+        Command::Dump => dump(reader),
+        Command::Export { out_dir } => export(reader, &out_dir),
+        Command::Replay { port } => replay(reader, port),
+    }
+}